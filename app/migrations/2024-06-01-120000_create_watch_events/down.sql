@@ -0,0 +1 @@
+DROP TABLE watch_events;
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TABLE watch_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    channel_id INTEGER NOT NULL,
+    success BOOLEAN NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (channel_id)
+        REFERENCES streamers (id) 
+)
\ No newline at end of file
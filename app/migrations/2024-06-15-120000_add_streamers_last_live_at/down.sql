@@ -0,0 +1 @@
+ALTER TABLE streamers DROP COLUMN last_live_at;
\ No newline at end of file
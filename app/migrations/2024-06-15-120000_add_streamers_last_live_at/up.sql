@@ -0,0 +1 @@
+ALTER TABLE streamers ADD COLUMN last_live_at TIMESTAMP;
\ No newline at end of file
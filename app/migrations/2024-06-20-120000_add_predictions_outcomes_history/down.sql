@@ -0,0 +1 @@
+ALTER TABLE predictions DROP COLUMN outcomes_history;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE predictions ADD COLUMN outcomes_history TEXT NOT NULL DEFAULT '[]';
\ No newline at end of file
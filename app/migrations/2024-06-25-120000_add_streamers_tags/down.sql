@@ -0,0 +1 @@
+ALTER TABLE streamers DROP COLUMN tags;
\ No newline at end of file
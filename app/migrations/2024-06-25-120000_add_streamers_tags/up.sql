@@ -0,0 +1 @@
+ALTER TABLE streamers ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';
\ No newline at end of file
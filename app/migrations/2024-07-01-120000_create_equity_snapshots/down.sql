@@ -0,0 +1 @@
+DROP TABLE equity_snapshots;
\ No newline at end of file
@@ -0,0 +1,5 @@
+CREATE TABLE equity_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    balance BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+)
\ No newline at end of file
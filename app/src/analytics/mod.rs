@@ -1,32 +1,46 @@
-use std::thread::spawn;
+use std::{collections::HashMap, thread::spawn};
 
-use chrono::{DateTime, Local, NaiveDateTime};
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
 use diesel::{
     deserialize, result::DatabaseErrorKind, row::NamedRow, sqlite::Sqlite, Connection,
-    ConnectionError, ExpressionMethods, QueryDsl, QueryableByName, RunQueryDsl, SqliteConnection,
+    ConnectionError, ExpressionMethods, QueryDsl, QueryableByName, RunQueryDsl, SelectableHelper,
+    SqliteConnection,
 };
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use flume::{Receiver, Sender};
 use serde::Serialize;
 use thiserror::Error;
 use tokio::sync::Mutex;
-use tracing::{error, trace};
+use tracing::{error, trace, warn};
 
 use crate::analytics::model::{PredictionBet, PredictionBetWrapper};
 
-use self::model::{Outcomes, Point, PointsInfo, Prediction, Streamer};
+use self::model::{
+    AnalyticsSummary, DatabaseSize, EquitySnapshot, OptimizeResult, Outcomes, OutcomesHistory,
+    Point, PointsInfo, PointsMover, Prediction, PredictionRecord, ResetResult, SessionChange,
+    Streamer, TopMovers, WatchEvent, WatchSummary,
+};
 
 pub mod model;
 mod schema;
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+/// Minimum seconds between two stored snapshots of a prediction's outcomes
+#[cfg(not(test))]
+pub const OUTCOMES_SAMPLE_SECS: i64 = 10;
+#[cfg(test)]
+pub const OUTCOMES_SAMPLE_SECS: i64 = 1;
+/// Days between scheduled optimizations of the database
+pub const OPTIMIZE_INTERVAL_DAYS: u64 = 30;
+/// Default number of writes waiting for the database before senders have to wait
+pub const QUEUE_CAPACITY: usize = 1000;
 
 pub struct AnalyticsWrapper(pub Mutex<Option<Analytics>>);
 
 #[derive(Debug, Error)]
 pub enum AnalyticsError {
-    #[error("Analytics not initialized")]
-    NotInitialized,
+    #[error("Analytics are disabled, start the miner with --analytics true to record them")]
+    Disabled,
     #[error("Could not connect to database: {0}")]
     ConnectionError(ConnectionError),
     #[error("SQL execute error: {0} at {1}")]
@@ -37,7 +51,20 @@ pub enum AnalyticsError {
 
 impl axum::response::IntoResponse for AnalyticsError {
     fn into_response(self) -> axum::response::Response {
-        format!("{self:#?}").into_response()
+        let (status_code, code) = match self {
+            AnalyticsError::Disabled => {
+                (http::StatusCode::SERVICE_UNAVAILABLE, "analytics_disabled")
+            }
+            _ => (http::StatusCode::INTERNAL_SERVER_ERROR, "analytics_error"),
+        };
+        (
+            status_code,
+            axum::Json(serde_json::json!({
+                "error": self.to_string(),
+                "code": code,
+            })),
+        )
+            .into_response()
     }
 }
 
@@ -52,6 +79,11 @@ impl AnalyticsWrapper {
         AnalyticsWrapper(Mutex::new(Some(analytics)))
     }
 
+    /// Without a database, every query fails with [`AnalyticsError::Disabled`]
+    pub fn empty() -> AnalyticsWrapper {
+        AnalyticsWrapper(Mutex::new(None))
+    }
+
     pub async fn execute<F, R>(&self, func: F) -> Result<R, AnalyticsError>
     where
         F: FnOnce(&mut Analytics) -> Result<R, AnalyticsError>,
@@ -59,7 +91,7 @@ impl AnalyticsWrapper {
         if let Some(analytics) = self.0.lock().await.as_mut() {
             func(analytics)
         } else {
-            Err(AnalyticsError::NotInitialized)
+            Err(AnalyticsError::Disabled)
         }
     }
 }
@@ -70,15 +102,37 @@ pub struct Analytics {
 
 pub type Request = Box<dyn Fn(&mut Analytics) -> Result<(), AnalyticsError> + Send>;
 
+/// Queue a low priority write, like a points update, dropping it with a warning if the queue is full
+/// instead of waiting for the database to catch up. Returns false if the writer has stopped
+pub fn send_droppable(tx: &Sender<Request>, what: &str, request: Request) -> bool {
+    match tx.try_send(request) {
+        Ok(()) => true,
+        Err(flume::TrySendError::Full(_)) => {
+            warn!("Analytics queue is full, dropping {what}");
+            true
+        }
+        Err(flume::TrySendError::Disconnected(_)) => false,
+    }
+}
+
 impl Analytics {
     pub fn new(url: &str) -> Result<(Analytics, Sender<Request>), AnalyticsError> {
+        Analytics::with_capacity(url, QUEUE_CAPACITY)
+    }
+
+    /// Writes sent with `send_async` wait while `capacity` writes are queued, so a slow database
+    /// slows down senders rather than growing the queue without bound
+    pub fn with_capacity(
+        url: &str,
+        capacity: usize,
+    ) -> Result<(Analytics, Sender<Request>), AnalyticsError> {
         let mut conn = SqliteConnection::establish(url)?;
         let conn_thread = SqliteConnection::establish(url)?;
         _ = conn
             .run_pending_migrations(MIGRATIONS)
             .map_err(AnalyticsError::DbInit);
 
-        let (tx, rx) = flume::unbounded();
+        let (tx, rx) = flume::bounded(capacity.max(1));
         spawn(move || {
             Analytics::run(
                 Analytics {
@@ -90,6 +144,37 @@ impl Analytics {
         Ok((Analytics { conn: Some(conn) }, tx))
     }
 
+    /// Check the database can be opened and written to, leaving it unchanged
+    pub fn check_writable(path: &str) -> Result<(), AnalyticsError> {
+        use diesel::connection::SimpleConnection;
+
+        let mut conn = SqliteConnection::establish(path)?;
+        let res = conn.transaction::<(), _, _>(|conn| {
+            conn.batch_execute("CREATE TABLE _write_check (id INTEGER)")?;
+            Err(diesel::result::Error::RollbackTransaction)
+        });
+        match res {
+            Err(diesel::result::Error::RollbackTransaction) | Ok(()) => Ok(()),
+            Err(err) => Err(AnalyticsError::from_diesel_error(
+                err,
+                "Write check".to_owned(),
+            )),
+        }
+    }
+
+    /// Writer queue for disabled analytics, every write sent to it is dropped without touching a database
+    pub fn disabled() -> Sender<Request> {
+        let (tx, rx) = flume::unbounded::<Request>();
+        spawn(move || while rx.recv().is_ok() {});
+        tx
+    }
+
+    /// Open an existing database without write access, no migrations are run
+    pub fn open_read_only(path: &str) -> Result<Analytics, AnalyticsError> {
+        let conn = SqliteConnection::establish(&format!("file:{path}?mode=ro"))?;
+        Ok(Analytics { conn: Some(conn) })
+    }
+
     pub fn run(mut self, rx: Receiver<Request>) {
         while let Ok(data) = rx.recv() {
             trace!("got analytics request");
@@ -99,6 +184,61 @@ impl Analytics {
         }
     }
 
+    /// Rebuild the database file with `VACUUM` and refresh query planner statistics with `ANALYZE`
+    pub fn optimize(&mut self) -> Result<OptimizeResult, AnalyticsError> {
+        use diesel::{connection::SimpleConnection, sql_query};
+
+        let conn = self.conn.as_mut().unwrap();
+        let size = |conn: &mut SqliteConnection| {
+            sql_query(
+                "select page_count * page_size as size from pragma_page_count(), pragma_page_size()",
+            )
+            .get_result::<DatabaseSize>(conn)
+            .map(|x| x.size)
+            .map_err(|err| AnalyticsError::from_diesel_error(err, "Database size".to_owned()))
+        };
+        let size_before = size(conn)?;
+        // VACUUM fails inside a transaction, so it is issued on its own
+        conn.batch_execute("VACUUM")
+            .map_err(|err| AnalyticsError::from_diesel_error(err, "Vacuum".to_owned()))?;
+        conn.batch_execute("ANALYZE")
+            .map_err(|err| AnalyticsError::from_diesel_error(err, "Analyze".to_owned()))?;
+        let size_after = size(conn)?;
+
+        Ok(OptimizeResult {
+            size_before,
+            size_after,
+            reclaimed: size_before - size_after,
+        })
+    }
+
+    /// Remove all points, predictions and viewership pings of a channel, keeping its name
+    pub fn reset_streamer(&mut self, c_id: i32) -> Result<ResetResult, AnalyticsError> {
+        self.conn
+            .as_mut()
+            .unwrap()
+            .transaction(|conn| {
+                let points = diesel::delete(
+                    schema::points::table.filter(schema::points::channel_id.eq(c_id)),
+                )
+                .execute(conn)?;
+                let predictions = diesel::delete(
+                    schema::predictions::table.filter(schema::predictions::channel_id.eq(c_id)),
+                )
+                .execute(conn)?;
+                let watch_events = diesel::delete(
+                    schema::watch_events::table.filter(schema::watch_events::channel_id.eq(c_id)),
+                )
+                .execute(conn)?;
+                Ok(ResetResult {
+                    points,
+                    predictions,
+                    watch_events,
+                })
+            })
+            .map_err(|err| AnalyticsError::from_diesel_error(err, format!("Reset streamer {c_id}")))
+    }
+
     pub fn insert_streamer(&mut self, id: i32, name: String) -> Result<bool, AnalyticsError> {
         let res = diesel::insert_into(schema::streamers::table)
             .values(&Streamer {
@@ -117,6 +257,83 @@ impl Analytics {
         Ok(true)
     }
 
+    pub fn set_last_live_at(&mut self, c_id: i32, at: NaiveDateTime) -> Result<(), AnalyticsError> {
+        use schema::streamers::dsl::*;
+        diesel::update(streamers)
+            .filter(id.eq(c_id))
+            .set(last_live_at.eq(at))
+            .execute(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(err, format!("Set last live time of {c_id}"))
+            })?;
+        Ok(())
+    }
+
+    pub fn last_live_at(&mut self, c_id: i32) -> Result<Option<NaiveDateTime>, AnalyticsError> {
+        use schema::streamers::dsl::*;
+        streamers
+            .filter(id.eq(c_id))
+            .select(last_live_at)
+            .first(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(err, format!("Get last live time of {c_id}"))
+            })
+    }
+
+    pub fn streamer_name(&mut self, c_id: i32) -> Result<Option<String>, AnalyticsError> {
+        use diesel::OptionalExtension;
+        use schema::streamers::dsl::*;
+        streamers
+            .filter(id.eq(c_id))
+            .select(name)
+            .first(self.conn.as_mut().unwrap())
+            .optional()
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(err, format!("Get name of streamer {c_id}"))
+            })
+    }
+
+    pub fn set_tags(&mut self, c_id: i32, new_tags: &[String]) -> Result<(), AnalyticsError> {
+        use schema::streamers::dsl::*;
+        diesel::update(streamers)
+            .filter(id.eq(c_id))
+            .set(tags.eq(serde_json::to_string(new_tags).unwrap()))
+            .execute(self.conn.as_mut().unwrap())
+            .map_err(|err| AnalyticsError::from_diesel_error(err, format!("Set tags of {c_id}")))?;
+        Ok(())
+    }
+
+    pub fn tags(&mut self, c_id: i32) -> Result<Vec<String>, AnalyticsError> {
+        use schema::streamers::dsl::*;
+        let value: String = streamers
+            .filter(id.eq(c_id))
+            .select(tags)
+            .first(self.conn.as_mut().unwrap())
+            .map_err(|err| AnalyticsError::from_diesel_error(err, format!("Get tags of {c_id}")))?;
+        Ok(serde_json::from_str(&value).unwrap_or_default())
+    }
+
+    /// IDs of the channels tagged with `tag`
+    pub fn channels_with_tag(&mut self, tag: &str) -> Result<Vec<i32>, AnalyticsError> {
+        use schema::streamers::dsl::*;
+        let items: Vec<(i32, String)> = streamers
+            .select((id, tags))
+            .load(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(err, format!("Get channels tagged {tag}"))
+            })?;
+        Ok(items
+            .into_iter()
+            .filter(|(_, value)| {
+                serde_json::from_str::<Vec<String>>(value)
+                    .unwrap_or_default()
+                    .iter()
+                    .any(|x| x == tag)
+            })
+            .map(|x| x.0)
+            .collect())
+    }
+
     pub fn insert_points(
         &mut self,
         channel_id: i32,
@@ -176,6 +393,270 @@ impl Analytics {
         }
     }
 
+    pub fn insert_watch_event(
+        &mut self,
+        channel_id: i32,
+        success: bool,
+    ) -> Result<(), AnalyticsError> {
+        diesel::insert_into(schema::watch_events::table)
+            .values(&WatchEvent {
+                channel_id,
+                success,
+                created_at: Local::now().naive_local(),
+            })
+            .execute(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(
+                    err,
+                    format!("Insert watch event for {channel_id} {success}"),
+                )
+            })?;
+        Ok(())
+    }
+
+    pub fn insert_equity_snapshot(&mut self, balance: i64) -> Result<(), AnalyticsError> {
+        diesel::insert_into(schema::equity_snapshots::table)
+            .values(&EquitySnapshot {
+                balance,
+                created_at: Local::now().naive_local(),
+            })
+            .execute(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(err, format!("Insert equity snapshot {balance}"))
+            })?;
+        Ok(())
+    }
+
+    /// Snapshots of the total balance in the range, oldest first
+    pub fn equity(
+        &mut self,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+    ) -> Result<Vec<EquitySnapshot>, AnalyticsError> {
+        use schema::equity_snapshots::dsl::*;
+        equity_snapshots
+            .filter(created_at.ge(from.naive_local()))
+            .filter(created_at.le(to.naive_local()))
+            .order((created_at.asc(), id.asc()))
+            .select(EquitySnapshot::as_select())
+            .load(self.conn.as_mut().unwrap())
+            .map_err(|err| AnalyticsError::from_diesel_error(err, "Equity".to_owned()))
+    }
+
+    pub fn watch_summary(
+        &mut self,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+    ) -> Result<Vec<WatchSummary>, AnalyticsError> {
+        use diesel::sql_query;
+
+        trace!("Watch summary {from} {to}");
+        let query = format!(
+            r#"select a.channel_id, b.name,
+                count(distinct case when a.success then strftime('%Y-%m-%d %H:%M', a.created_at) end) as minutes_watched,
+                sum(case when a.success then 0 else 1 end) as failures
+                from watch_events a left join streamers b on a.channel_id == b.id
+                where a.created_at >= '{}' and a.created_at <= '{}' group by a.channel_id order by a.channel_id asc"#,
+            from.naive_local(),
+            to.naive_local(),
+        );
+
+        let items = sql_query(query)
+            .get_results(self.conn.as_mut().unwrap())
+            .map_err(|err| AnalyticsError::from_diesel_error(err, "Watch summary".to_owned()))?;
+        Ok(items)
+    }
+
+    /// Change in points of each channel since its latest session baseline, recorded when the miner started.
+    /// Baselines written before `since` belong to earlier runs, channels with only those are left out
+    pub fn session(
+        &mut self,
+        since: DateTime<Local>,
+    ) -> Result<Vec<SessionChange>, AnalyticsError> {
+        use diesel::sql_query;
+
+        let baseline = serde_json::to_string(&PointsInfo::SessionBaseline).unwrap();
+        let query = format!(
+            r#"select a.channel_id, c.name, a.points_value as baseline, b.points_value as points,
+                b.points_value - a.points_value as net
+                from points a
+                join points b on b.id == (select max(id) from points where channel_id == a.channel_id)
+                left join streamers c on a.channel_id == c.id
+                where a.id in (select max(id) from points where points_info == '{baseline}' and created_at >= '{}' group by channel_id)
+                order by a.channel_id asc"#,
+            since.naive_local(),
+        );
+
+        let items = sql_query(query)
+            .get_results(self.conn.as_mut().unwrap())
+            .map_err(|err| AnalyticsError::from_diesel_error(err, "Session".to_owned()))?;
+        Ok(items)
+    }
+
+    /// Channels with the largest net gains and losses in the range, at most `limit` of each.
+    /// Channels with equal changes are ordered by name
+    pub fn top_movers(
+        &mut self,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+        limit: usize,
+    ) -> Result<TopMovers, AnalyticsError> {
+        use diesel::sql_query;
+
+        trace!("Top movers {from} {to}");
+        let query = format!(
+            r#"select r.channel_id, c.name, b.points_value - a.points_value as net
+                from (select channel_id, min(id) as first_id, max(id) as last_id from points
+                    where created_at >= '{}' and created_at <= '{}' group by channel_id) r
+                join points a on a.id == r.first_id
+                join points b on b.id == r.last_id
+                left join streamers c on r.channel_id == c.id
+                order by c.name asc, r.channel_id asc"#,
+            from.naive_local(),
+            to.naive_local(),
+        );
+
+        let mut items: Vec<PointsMover> = sql_query(query)
+            .get_results(self.conn.as_mut().unwrap())
+            .map_err(|err| AnalyticsError::from_diesel_error(err, "Top movers".to_owned()))?;
+        // stable sorts, so ties stay in name order
+        items.sort_by_key(|x| std::cmp::Reverse(x.net));
+        let gainers = items
+            .iter()
+            .filter(|x| x.net > 0)
+            .take(limit)
+            .cloned()
+            .collect();
+        items.sort_by_key(|x| x.net);
+        let losers = items
+            .into_iter()
+            .filter(|x| x.net < 0)
+            .take(limit)
+            .collect();
+        Ok(TopMovers { gainers, losers })
+    }
+
+    /// Summary of the range, limited to `only` if channels are given
+    pub fn summary(
+        &mut self,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+        only: Option<&[i32]>,
+    ) -> Result<AnalyticsSummary, AnalyticsError> {
+        let (from, to) = (from.naive_local(), to.naive_local());
+        let mut summary = AnalyticsSummary::default();
+        let included = |channel_id: i32| match only {
+            Some(only) => only.contains(&channel_id),
+            None => true,
+        };
+
+        let items = schema::predictions::table
+            .filter(schema::predictions::created_at.ge(from))
+            .filter(schema::predictions::created_at.le(to))
+            .select(Prediction::as_select())
+            .load(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(err, "Summary predictions".to_owned())
+            })?;
+        for p in items.into_iter().filter(|x| included(x.channel_id)) {
+            summary.predictions += 1;
+            if let PredictionBetWrapper::Some(bet) = p.placed_bet {
+                summary.bets_placed += 1;
+                summary.points_wagered += bet.points as i64;
+                if p.winning_outcome_id.is_some_and(|x| x == bet.outcome_id) {
+                    summary.bets_won += 1;
+                }
+            }
+        }
+
+        let items: Vec<(i32, i32)> = schema::points::table
+            .filter(schema::points::created_at.ge(from))
+            .filter(schema::points::created_at.le(to))
+            .order((schema::points::created_at.asc(), schema::points::id.asc()))
+            .select((schema::points::channel_id, schema::points::points_value))
+            .load(self.conn.as_mut().unwrap())
+            .map_err(|err| AnalyticsError::from_diesel_error(err, "Summary points".to_owned()))?;
+        let mut channels: HashMap<i32, (i32, i32)> = HashMap::new();
+        for (channel_id, value) in items.into_iter().filter(|x| included(x.0)) {
+            channels
+                .entry(channel_id)
+                .and_modify(|x| x.1 = value)
+                .or_insert((value, value));
+        }
+        summary.points_change = channels
+            .values()
+            .map(|(first, last)| (last - first) as i64)
+            .sum();
+        Ok(summary)
+    }
+
+    /// Predictions started in the range as flat records, all channels if none are given
+    pub fn predictions_export(
+        &mut self,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+        channels: &[i32],
+    ) -> Result<Vec<PredictionRecord>, AnalyticsError> {
+        trace!("Predictions export {from} {to} {channels:?}");
+        let mut query = schema::predictions::table
+            .filter(schema::predictions::created_at.ge(from.naive_local()))
+            .filter(schema::predictions::created_at.le(to.naive_local()))
+            .order((
+                schema::predictions::created_at.asc(),
+                schema::predictions::id.asc(),
+            ))
+            .select(Prediction::as_select())
+            .into_boxed();
+        if !channels.is_empty() {
+            query = query.filter(schema::predictions::channel_id.eq_any(channels));
+        }
+        let items = query.load(self.conn.as_mut().unwrap()).map_err(|err| {
+            AnalyticsError::from_diesel_error(err, "Predictions export".to_owned())
+        })?;
+
+        let names: HashMap<i32, String> = schema::streamers::table
+            .select((schema::streamers::id, schema::streamers::name))
+            .load(self.conn.as_mut().unwrap())
+            .map_err(|err| AnalyticsError::from_diesel_error(err, "Streamer names".to_owned()))?
+            .into_iter()
+            .collect();
+
+        let rfc3339 = |at: NaiveDateTime| {
+            at.and_local_timezone(Local)
+                .earliest()
+                .map(|x| x.to_rfc3339())
+                .unwrap_or_else(|| at.and_utc().to_rfc3339())
+        };
+        let records = items
+            .into_iter()
+            .map(|p| {
+                let bet = match &p.placed_bet {
+                    PredictionBetWrapper::Some(bet) => Some(bet),
+                    _ => None,
+                };
+                PredictionRecord {
+                    channel_id: p.channel_id,
+                    channel_name: names.get(&p.channel_id).cloned(),
+                    prediction_id: p.prediction_id,
+                    title: p.title,
+                    prediction_window: p.prediction_window,
+                    created_at: rfc3339(p.created_at),
+                    closed_at: p.closed_at.map(rfc3339),
+                    bet_outcome_id: bet.map(|x| x.outcome_id.clone()),
+                    bet_points: bet.map(|x| x.points as i64).unwrap_or_default(),
+                    bet_simulated: bet.is_some_and(|x| x.simulated),
+                    blacklisted: p.placed_bet == PredictionBetWrapper::Blacklisted,
+                    won: bet
+                        .zip(p.winning_outcome_id.as_ref())
+                        .map(|(bet, winner)| bet.outcome_id.eq(winner)),
+                    winning_outcome_id: p.winning_outcome_id,
+                    outcomes: p.outcomes.0,
+                }
+            })
+            .collect();
+        Ok(records)
+    }
+
     pub fn upsert_prediction(&mut self, prediction: &Prediction) -> Result<(), AnalyticsError> {
         use schema::predictions::dsl::*;
         let last_prediction_id = predictions
@@ -202,7 +683,7 @@ impl Analytics {
                 if last_prediction_id != prediction.prediction_id {
                     insert_prediction(prediction)
                 } else {
-                    Ok(())
+                    self.append_outcomes(prediction)
                 }
             }
             Err(err) => match err {
@@ -217,12 +698,54 @@ impl Analytics {
         }
     }
 
+    /// Add the latest outcomes of a prediction to its history, skipping unchanged outcomes
+    /// and sampling at most once every `OUTCOMES_SAMPLE_SECS`
+    fn append_outcomes(&mut self, prediction: &Prediction) -> Result<(), AnalyticsError> {
+        use schema::predictions::dsl::*;
+        let snapshot = match prediction.outcomes_history.0.last() {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        let (entry_id, mut history) = predictions
+            .filter(channel_id.eq(prediction.channel_id))
+            .filter(prediction_id.eq(&prediction.prediction_id))
+            .order(id.desc())
+            .select((id, outcomes_history))
+            .first::<(i32, OutcomesHistory)>(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(err, "Get outcomes history".to_owned())
+            })?;
+
+        if let Some(last) = history.0.last() {
+            if last.outcomes == snapshot.outcomes
+                || snapshot.at - last.at < Duration::seconds(OUTCOMES_SAMPLE_SECS)
+            {
+                return Ok(());
+            }
+        }
+
+        history.0.push(snapshot.clone());
+        diesel::update(predictions)
+            .filter(id.eq(entry_id))
+            .set(outcomes_history.eq(history))
+            .execute(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(
+                    err,
+                    format!("Append outcomes history {}", prediction.prediction_id),
+                )
+            })?;
+        Ok(())
+    }
+
     pub fn place_bet(
         &mut self,
         p_id: &str,
         c_id: i32,
         o_id: &str,
         p: u32,
+        simulated: bool,
     ) -> Result<(), AnalyticsError> {
         use schema::predictions::dsl::*;
         diesel::update(predictions)
@@ -231,6 +754,8 @@ impl Analytics {
             .set(placed_bet.eq(PredictionBetWrapper::Some(PredictionBet {
                 outcome_id: o_id.to_owned(),
                 points: p,
+                simulated,
+                result: None,
             })))
             .execute(self.conn.as_mut().unwrap())
             .map_err(|err| {
@@ -239,6 +764,45 @@ impl Analytics {
         Ok(())
     }
 
+    /// Store a bet as twitch reported it, replacing the one recorded when it was placed
+    pub fn confirm_bet(
+        &mut self,
+        p_id: &str,
+        c_id: i32,
+        bet: PredictionBet,
+    ) -> Result<(), AnalyticsError> {
+        use schema::predictions::dsl::*;
+        diesel::update(predictions)
+            .filter(channel_id.eq(c_id))
+            .filter(prediction_id.eq(p_id))
+            .set(placed_bet.eq(PredictionBetWrapper::Some(bet)))
+            .execute(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(
+                    err,
+                    format!("Confirm bet on {c_id} event {p_id}"),
+                )
+            })?;
+        Ok(())
+    }
+
+    pub fn mark_blacklisted(&mut self, p_id: &str, c_id: i32) -> Result<(), AnalyticsError> {
+        use schema::predictions::dsl::*;
+        diesel::update(predictions)
+            .filter(channel_id.eq(c_id))
+            .filter(prediction_id.eq(p_id))
+            .filter(placed_bet.eq(PredictionBetWrapper::None))
+            .set(placed_bet.eq(PredictionBetWrapper::Blacklisted))
+            .execute(self.conn.as_mut().unwrap())
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(
+                    err,
+                    format!("Mark {c_id} event {p_id} blacklisted"),
+                )
+            })?;
+        Ok(())
+    }
+
     pub fn end_prediction(
         &mut self,
         p_id: &str,
@@ -332,11 +896,46 @@ impl Analytics {
             },
         }
     }
+
+    /// Latest prediction with this event ID, on any channel
+    pub fn find_prediction(&mut self, p_id: &str) -> Result<Option<Prediction>, AnalyticsError> {
+        use diesel::OptionalExtension;
+        use schema::predictions::dsl::*;
+        predictions
+            .filter(prediction_id.eq(p_id))
+            .order(id.desc())
+            .select(Prediction::as_select())
+            .first(self.conn.as_mut().unwrap())
+            .optional()
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(err, format!("Find prediction {p_id}"))
+            })
+    }
+
+    /// Balance of the channel at a point in time, from the latest points entry before it
+    pub fn balance_at(
+        &mut self,
+        c_id: i32,
+        at: NaiveDateTime,
+    ) -> Result<Option<i32>, AnalyticsError> {
+        use diesel::OptionalExtension;
+        use schema::points::dsl::*;
+        points
+            .filter(channel_id.eq(c_id))
+            .filter(created_at.le(at))
+            .order(created_at.desc())
+            .select(points_value)
+            .first(self.conn.as_mut().unwrap())
+            .optional()
+            .map_err(|err| {
+                AnalyticsError::from_diesel_error(err, format!("Get balance of {c_id} at {at}"))
+            })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
 pub struct TimelineResult {
-    point: Point,
+    pub point: Point,
     difference: Option<i32>,
     prediction: Option<Prediction>,
 }
@@ -371,3 +970,209 @@ impl From<ConnectionError> for AnalyticsError {
         AnalyticsError::ConnectionError(value)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Local, NaiveDate};
+    use diesel::RunQueryDsl;
+
+    use super::{
+        model::{
+            Outcome, Outcomes, OutcomesHistory, OutcomesSnapshot, PointsInfo, Prediction,
+            PredictionBetWrapper, WatchEvent,
+        },
+        schema, send_droppable, Analytics, AnalyticsError, Request, OUTCOMES_SAMPLE_SECS,
+    };
+
+    #[test]
+    fn flooded_queue_keeps_critical_writes() -> Result<(), AnalyticsError> {
+        use diesel::{Connection, QueryDsl, SqliteConnection};
+
+        let path = std::env::temp_dir().join(format!("queue-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let (mut analytics, _) = Analytics::new(path)?;
+        analytics.insert_streamer(1, "a".to_owned())?;
+
+        // nothing drains the queue yet, as if the database had fallen behind
+        let (tx, rx) = flume::bounded::<Request>(4);
+        for _ in 0..10_000 {
+            assert!(send_droppable(
+                &tx,
+                "points update",
+                Box::new(|analytics| analytics.insert_points(1, 10, PointsInfo::FirstEntry)),
+            ));
+        }
+        assert_eq!(tx.len(), 4);
+
+        let writer = Analytics {
+            conn: Some(SqliteConnection::establish(path)?),
+        };
+        let writer = std::thread::spawn(move || writer.run(rx));
+        for id in 2..10 {
+            tx.send(Box::new(move |analytics| {
+                analytics.insert_streamer(id, id.to_string()).map(|_| ())
+            }))
+            .unwrap();
+        }
+        drop(tx);
+        writer.join().unwrap();
+
+        let conn = analytics.conn.as_mut().unwrap();
+        let streamers: i64 = schema::streamers::table.count().get_result(conn).unwrap();
+        let points: i64 = schema::points::table.count().get_result(conn).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(streamers, 9);
+        assert_eq!(points, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn outcome_titles_round_trip() -> Result<(), AnalyticsError> {
+        let (mut analytics, _) = Analytics::new(":memory:")?;
+        analytics.insert_streamer(1, "a".to_owned())?;
+
+        let outcome = |id: &str, title: &str, color: &str| Outcome {
+            id: id.to_owned(),
+            title: title.to_owned(),
+            color: color.to_owned(),
+            total_points: 0,
+            total_users: 0,
+        };
+        let outcomes = Outcomes(vec![
+            outcome("1", "Yes", "BLUE"),
+            outcome("2", "No", "PINK"),
+        ]);
+        analytics.upsert_prediction(&Prediction {
+            channel_id: 1,
+            prediction_id: "pred-1".to_owned(),
+            title: "Win?".to_owned(),
+            prediction_window: 120,
+            outcomes: outcomes.clone(),
+            winning_outcome_id: None,
+            placed_bet: PredictionBetWrapper::None,
+            created_at: Local::now().naive_local(),
+            closed_at: None,
+            outcomes_history: Default::default(),
+        })?;
+
+        let res = analytics.get_live_prediction(1, "pred-1")?.unwrap();
+        assert_eq!(res.outcomes, outcomes);
+
+        let mut ended = outcomes.clone();
+        ended.0[1].total_points = 500;
+        analytics.end_prediction(
+            "pred-1",
+            1,
+            Some("2".to_owned()),
+            ended.clone(),
+            Local::now().naive_local(),
+        )?;
+        let res = analytics.get_live_prediction(1, "pred-1")?.unwrap();
+        assert_eq!(res.outcomes, ended);
+        assert_eq!(res.outcomes.0[1].title, "No");
+
+        // rows written before colors were stored
+        let legacy: Outcomes =
+            serde_json::from_str(r#"[{"id":"1","title":"Yes","total_points":0,"total_users":0}]"#)
+                .unwrap();
+        assert_eq!(legacy.0[0].title, "Yes");
+        assert_eq!(legacy.0[0].color, "");
+        Ok(())
+    }
+
+    #[test]
+    fn outcomes_history_snapshots() -> Result<(), AnalyticsError> {
+        let (mut analytics, _) = Analytics::new(":memory:")?;
+        analytics.insert_streamer(1, "a".to_owned())?;
+
+        let start = Local::now().naive_local();
+        let update = |seconds: i64, points: i64| Prediction {
+            channel_id: 1,
+            prediction_id: "pred-1".to_owned(),
+            title: "Win?".to_owned(),
+            prediction_window: 120,
+            outcomes: Outcomes(Vec::new()),
+            winning_outcome_id: None,
+            placed_bet: PredictionBetWrapper::None,
+            created_at: start,
+            closed_at: None,
+            outcomes_history: OutcomesHistory(vec![OutcomesSnapshot {
+                at: start + Duration::seconds(seconds),
+                outcomes: vec![Outcome {
+                    id: "1".to_owned(),
+                    title: "Yes".to_owned(),
+                    color: "BLUE".to_owned(),
+                    total_points: points,
+                    total_users: 1,
+                }],
+            }]),
+        };
+
+        analytics.upsert_prediction(&update(0, 100))?;
+        // within the sampling interval
+        analytics.upsert_prediction(&update(OUTCOMES_SAMPLE_SECS - 1, 200))?;
+        analytics.upsert_prediction(&update(OUTCOMES_SAMPLE_SECS, 300))?;
+        // unchanged outcomes
+        analytics.upsert_prediction(&update(OUTCOMES_SAMPLE_SECS * 2, 300))?;
+        analytics.upsert_prediction(&update(OUTCOMES_SAMPLE_SECS * 3, 400))?;
+
+        let res = analytics.get_live_prediction(1, "pred-1")?.unwrap();
+        assert_eq!(
+            res.outcomes_history
+                .0
+                .iter()
+                .map(|x| (x.at - start, x.outcomes[0].total_points))
+                .collect::<Vec<_>>(),
+            vec![
+                (Duration::zero(), 100),
+                (Duration::seconds(OUTCOMES_SAMPLE_SECS), 300),
+                (Duration::seconds(OUTCOMES_SAMPLE_SECS * 3), 400),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn watch_events_summary() -> Result<(), AnalyticsError> {
+        let (mut analytics, _) = Analytics::new(":memory:")?;
+        analytics.insert_streamer(1, "a".to_owned())?;
+        analytics.insert_streamer(2, "b".to_owned())?;
+
+        let minute = |m: u32, s: u32| {
+            NaiveDate::from_ymd_opt(2024, 6, 1)
+                .unwrap()
+                .and_hms_opt(10, m, s)
+                .unwrap()
+        };
+        let events = [
+            (1, true, minute(0, 0)),
+            (1, true, minute(0, 10)),
+            (1, true, minute(1, 0)),
+            (1, false, minute(2, 0)),
+            (2, false, minute(0, 0)),
+        ]
+        .map(|(channel_id, success, created_at)| WatchEvent {
+            channel_id,
+            success,
+            created_at,
+        });
+        diesel::insert_into(schema::watch_events::table)
+            .values(&events[..])
+            .execute(analytics.conn.as_mut().unwrap())
+            .unwrap();
+        analytics.insert_watch_event(1, true)?;
+
+        let from = minute(0, 0).and_local_timezone(Local).unwrap();
+        let res = analytics.watch_summary(from, from + Duration::minutes(5))?;
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].name.as_deref(), Some("a"));
+        assert_eq!((res[0].minutes_watched, res[0].failures), (2, 1));
+        assert_eq!(res[1].name.as_deref(), Some("b"));
+        assert_eq!((res[1].minutes_watched, res[1].failures), (0, 1));
+
+        let res = analytics.watch_summary(Local::now() - Duration::minutes(1), Local::now())?;
+        assert_eq!(res.len(), 1);
+        assert_eq!((res[0].minutes_watched, res[0].failures), (1, 0));
+        Ok(())
+    }
+}
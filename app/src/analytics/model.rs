@@ -1,4 +1,5 @@
 use chrono::NaiveDateTime;
+use common::twitch::user_predictions::BetResult;
 use diesel::{
     deserialize::FromSql,
     prelude::*,
@@ -45,10 +46,28 @@ pub struct Point {
 #[diesel(sql_type = Text)]
 pub enum PointsInfo {
     FirstEntry,
+    /// Balance when the miner started tracking the streamer in this run
+    SessionBaseline,
     Watching,
     CommunityPointsClaimed,
     /// prediction event id
     Prediction(String, i32),
+    Raid,
+    WatchStreak,
+    /// Balance set through the API to fix drift from twitch
+    ManualCorrection,
+}
+
+impl PointsInfo {
+    /// Map the reason code of a points-earned event, unknown reasons are counted as watching
+    pub fn from_reason_code(reason: &str) -> PointsInfo {
+        match reason {
+            "CLAIM" => PointsInfo::CommunityPointsClaimed,
+            "RAID" => PointsInfo::Raid,
+            "WATCH_STREAK" => PointsInfo::WatchStreak,
+            _ => PointsInfo::Watching,
+        }
+    }
 }
 
 #[derive(
@@ -64,10 +83,34 @@ pub struct Outcomes(pub Vec<Outcome>);
 pub struct Outcome {
     pub id: String,
     pub title: String,
+    /// Display color of the outcome, empty for entries stored before it was recorded
+    #[serde(default)]
+    pub color: String,
     pub total_points: i64,
     pub total_users: i64,
 }
 
+/// Outcomes observed over the prediction window, oldest first
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    Deserialize,
+    Serialize,
+    PartialEq,
+    FromSqlRow,
+    AsExpression,
+    utoipa::ToSchema,
+)]
+#[diesel(sql_type = Text)]
+pub struct OutcomesHistory(pub Vec<OutcomesSnapshot>);
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, utoipa::ToSchema)]
+pub struct OutcomesSnapshot {
+    pub at: NaiveDateTime,
+    pub outcomes: Vec<Outcome>,
+}
+
 #[derive(
     Debug, Clone, Deserialize, Serialize, PartialEq, FromSqlRow, AsExpression, utoipa::ToSchema,
 )]
@@ -75,6 +118,8 @@ pub struct Outcome {
 pub enum PredictionBetWrapper {
     None,
     Some(PredictionBet),
+    /// Not bet on because the prediction matched the blacklist
+    Blacklisted,
 }
 
 #[derive(
@@ -84,6 +129,12 @@ pub enum PredictionBetWrapper {
 pub struct PredictionBet {
     pub outcome_id: String,
     pub points: u32,
+    /// Bet was only simulated, no points were wagered
+    #[serde(default)]
+    pub simulated: bool,
+    /// Win or loss as reported by twitch once the prediction ended
+    #[serde(default)]
+    pub result: Option<BetResult>,
 }
 
 #[derive(
@@ -111,6 +162,144 @@ pub struct Prediction {
     pub placed_bet: PredictionBetWrapper,
     pub created_at: NaiveDateTime,
     pub closed_at: Option<NaiveDateTime>,
+    #[diesel(sql_type = Text)]
+    pub outcomes_history: OutcomesHistory,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[diesel(table_name = super::schema::watch_events)]
+pub struct WatchEvent {
+    pub channel_id: i32,
+    /// If the viewership ping was accepted
+    pub success: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(
+    Queryable, Selectable, Insertable, Debug, PartialEq, Clone, Serialize, utoipa::ToSchema,
+)]
+#[diesel(table_name = super::schema::equity_snapshots)]
+pub struct EquitySnapshot {
+    /// Total balance across all streamers
+    pub balance: i64,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, QueryableByName, utoipa::ToSchema)]
+pub struct WatchSummary {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub channel_id: i32,
+    #[diesel(sql_type = diesel::sql_types::Nullable<Text>)]
+    pub name: Option<String>,
+    /// Distinct minutes with at least one successful viewership ping
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub minutes_watched: i64,
+    /// Number of viewership pings that failed
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub failures: i64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, QueryableByName, utoipa::ToSchema)]
+pub struct PointsMover {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub channel_id: i32,
+    #[diesel(sql_type = diesel::sql_types::Nullable<Text>)]
+    pub name: Option<String>,
+    /// Last balance in the range minus the first
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub net: i32,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Serialize, utoipa::ToSchema)]
+pub struct TopMovers {
+    /// Largest gains first
+    pub gainers: Vec<PointsMover>,
+    /// Largest losses first
+    pub losers: Vec<PointsMover>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, QueryableByName, utoipa::ToSchema)]
+pub struct SessionChange {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub channel_id: i32,
+    #[diesel(sql_type = diesel::sql_types::Nullable<Text>)]
+    pub name: Option<String>,
+    /// Balance when the miner started
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub baseline: i32,
+    /// Latest known balance
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub points: i32,
+    /// Change in points since the miner started
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub net: i32,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
+pub struct OptimizeResult {
+    /// Database size in bytes before optimizing
+    pub size_before: i64,
+    /// Database size in bytes after optimizing
+    pub size_after: i64,
+    /// Bytes reclaimed by the vacuum
+    pub reclaimed: i64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
+pub struct ResetResult {
+    /// Points entries removed
+    pub points: usize,
+    /// Predictions removed
+    pub predictions: usize,
+    /// Viewership pings removed
+    pub watch_events: usize,
+}
+
+#[derive(QueryableByName)]
+pub(super) struct DatabaseSize {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub size: i64,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Serialize, utoipa::ToSchema)]
+pub struct AnalyticsSummary {
+    /// Predictions started in the range
+    pub predictions: i64,
+    /// Predictions a bet was placed on
+    pub bets_placed: i64,
+    /// Bets placed on the winning outcome
+    pub bets_won: i64,
+    pub points_wagered: i64,
+    /// Change in points across all channels, between the first and last entry of each channel
+    pub points_change: i64,
+}
+
+/// Flat record of a single prediction, for exports
+#[derive(Debug, PartialEq, Clone, Serialize, utoipa::ToSchema)]
+pub struct PredictionRecord {
+    pub channel_id: i32,
+    pub channel_name: Option<String>,
+    pub prediction_id: String,
+    pub title: String,
+    /// Seconds bets were accepted for
+    pub prediction_window: i64,
+    /// RFC3339 timestamp
+    pub created_at: String,
+    /// RFC3339 timestamp, null while the prediction is open
+    pub closed_at: Option<String>,
+    pub winning_outcome_id: Option<String>,
+    /// Outcome bet on, null if no bet was placed
+    pub bet_outcome_id: Option<String>,
+    /// Points bet, 0 if no bet was placed
+    pub bet_points: i64,
+    /// Bet was only simulated, no points were wagered
+    pub bet_simulated: bool,
+    /// Not bet on because the prediction matched the blacklist
+    pub blacklisted: bool,
+    /// If the bet won, null if no bet was placed or the prediction has not been resolved
+    pub won: Option<bool>,
+    /// Final outcomes of the prediction
+    pub outcomes: Vec<Outcome>,
 }
 
 impl From<Vec<twitch_api::pubsub::predictions::Outcome>> for Outcomes {
@@ -121,6 +310,7 @@ impl From<Vec<twitch_api::pubsub::predictions::Outcome>> for Outcomes {
                 .map(|x| Outcome {
                     id: x.id,
                     title: x.title,
+                    color: x.color,
                     total_points: x.total_points,
                     total_users: x.total_users,
                 })
@@ -129,6 +319,19 @@ impl From<Vec<twitch_api::pubsub::predictions::Outcome>> for Outcomes {
     }
 }
 
+impl From<Outcome> for twitch_api::pubsub::predictions::Outcome {
+    fn from(value: Outcome) -> Self {
+        Self {
+            id: value.id,
+            color: value.color,
+            title: value.title,
+            total_points: value.total_points,
+            total_users: value.total_users,
+            top_predictors: Vec::new(),
+        }
+    }
+}
+
 pub fn from_sql<T: DeserializeOwned>(
     bytes: SqliteValue<'_, '_, '_>,
 ) -> diesel::deserialize::Result<T> {
@@ -174,6 +377,21 @@ impl ToSql<Text, Sqlite> for Outcomes {
     }
 }
 
+impl FromSql<Text, Sqlite> for OutcomesHistory {
+    fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
+        from_sql(bytes)
+    }
+}
+
+impl ToSql<Text, Sqlite> for OutcomesHistory {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Sqlite>,
+    ) -> diesel::serialize::Result {
+        to_sql(self, out)
+    }
+}
+
 impl FromSql<Text, Sqlite> for PredictionBetWrapper {
     fn from_sql(bytes: SqliteValue<'_, '_, '_>) -> diesel::deserialize::Result<Self> {
         from_sql(bytes)
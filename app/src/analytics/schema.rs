@@ -1,5 +1,13 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    equity_snapshots (id) {
+        id -> Integer,
+        balance -> BigInt,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     points (id) {
         id -> Integer,
@@ -22,6 +30,7 @@ diesel::table! {
         placed_bet -> Text,
         created_at -> Timestamp,
         closed_at -> Nullable<Timestamp>,
+        outcomes_history -> Text,
     }
 }
 
@@ -29,10 +38,28 @@ diesel::table! {
     streamers (id) {
         id -> Integer,
         name -> Text,
+        last_live_at -> Nullable<Timestamp>,
+        tags -> Text,
+    }
+}
+
+diesel::table! {
+    watch_events (id) {
+        id -> Integer,
+        channel_id -> Integer,
+        success -> Bool,
+        created_at -> Timestamp,
     }
 }
 
 diesel::joinable!(points -> streamers (channel_id));
 diesel::joinable!(predictions -> streamers (channel_id));
+diesel::joinable!(watch_events -> streamers (channel_id));
 
-diesel::allow_tables_to_appear_in_same_query!(points, predictions, streamers,);
+diesel::allow_tables_to_appear_in_same_query!(
+    equity_snapshots,
+    points,
+    predictions,
+    streamers,
+    watch_events,
+);
@@ -0,0 +1,317 @@
+use common::twitch::{api, gql, proxy::Proxy, ws};
+use eyre::{eyre, Result};
+
+use crate::analytics::Analytics;
+
+/// Outcome of one health check
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    /// What was found if the check passed, the error if it failed
+    pub result: Result<String, String>,
+    /// How to fix a failure
+    pub hint: &'static str,
+}
+
+impl Check {
+    fn new(name: &'static str, hint: &'static str, result: Result<String>) -> Check {
+        Check {
+            name,
+            result: result.map_err(|err| format!("{err:#}")),
+            hint,
+        }
+    }
+
+    fn skipped(name: &'static str, hint: &'static str, reason: &str) -> Check {
+        Check {
+            name,
+            result: Err(format!("skipped, {reason}")),
+            hint,
+        }
+    }
+}
+
+/// Check everything the miner needs before it can run, print a checklist and fail if any check failed
+pub async fn run(
+    config: &str,
+    token: &str,
+    analytics_db: &str,
+    analytics: Option<bool>,
+    proxy: Option<&str>,
+) -> Result<()> {
+    let mut checks = Vec::new();
+
+    let config = match crate::parse_config(config).await {
+        Ok(mut c) => match c.parse_and_validate() {
+            Ok(()) => Some(c),
+            Err(err) => {
+                checks.push(Check::new(CONFIG, CONFIG_HINT, Err(err)));
+                None
+            }
+        },
+        Err(err) => {
+            checks.push(Check::new(CONFIG, CONFIG_HINT, Err(err)));
+            None
+        }
+    };
+    let proxy = match proxy
+        .or(config.as_ref().and_then(|c| c.proxy.as_deref()))
+        .map(|x| x.parse::<Proxy>())
+        .transpose()
+    {
+        Ok(proxy) => proxy,
+        Err(err) => {
+            checks.push(Check::new(CONFIG, CONFIG_HINT, Err(err)));
+            None
+        }
+    };
+    if let Some(c) = &config {
+        checks.push(Check::new(
+            CONFIG,
+            CONFIG_HINT,
+            Ok(format!("{} streamers", c.streamers.len())),
+        ));
+    }
+
+    let access_token = crate::read_token(token, false)
+        .await
+        .map(|x| x.access_token);
+    let client = gql::Client::new(
+        access_token.as_ref().cloned().unwrap_or_default(),
+        "https://gql.twitch.tv/gql".to_owned(),
+    )
+    .with_query_hashes(
+        config
+            .as_ref()
+            .and_then(|c| c.gql_query_hashes.clone())
+            .unwrap_or_default(),
+    );
+    let client = match &proxy {
+        Some(proxy) => client.with_proxy(proxy).map(Some),
+        None => Ok(Some(client)),
+    };
+    let client = match client {
+        Ok(client) => client,
+        Err(err) => {
+            checks.push(Check::new(
+                CONFIG,
+                CONFIG_HINT,
+                Err(err.wrap_err("Creating a client with the proxy")),
+            ));
+            None
+        }
+    };
+
+    let gql = match &client {
+        Some(client) => check_gql(client).await,
+        None => Check::skipped(GQL, GQL_HINT, "the proxy is unusable"),
+    };
+    // the remaining GQL checks need a reachable server
+    let client = client.filter(|_| gql.result.is_ok());
+    checks.push(gql);
+    checks.push(match (&access_token, &client) {
+        (Err(err), _) => Check::new(TOKEN, TOKEN_HINT, Err(eyre!("{err:#}"))),
+        (Ok(_), None) => Check::skipped(TOKEN, TOKEN_HINT, "GQL is not reachable"),
+        (Ok(_), Some(client)) => check_token(client).await,
+    });
+
+    let names = config
+        .as_ref()
+        .map(|c| c.streamers.keys().map(|x| x.as_str()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    checks.push(match (config.is_some(), &client) {
+        (false, _) => Check::skipped(STREAMERS, STREAMERS_HINT, "the config is invalid"),
+        (true, None) => Check::skipped(STREAMERS, STREAMERS_HINT, "GQL is not reachable"),
+        (true, Some(client)) => check_streamers(client, &names).await,
+    });
+
+    checks.push(match names.first() {
+        Some(name) => {
+            let options = api::RequestOptions {
+                proxy: proxy.clone(),
+                ..Default::default()
+            };
+            check_spade(name, crate::BASE_URL, &options).await
+        }
+        None => Check::skipped(SPADE, SPADE_HINT, "no streamer to look it up on"),
+    });
+    checks.push(check_pubsub(ws::PUBSUB_URL, proxy.as_ref()).await);
+
+    let analytics = analytics
+        .or(config.as_ref().and_then(|c| c.analytics))
+        .unwrap_or(true);
+    checks.push(check_analytics(analytics, analytics_db));
+
+    print!("{}", format_checks(&checks));
+    let failed = checks.iter().filter(|x| x.result.is_err()).count();
+    if failed > 0 {
+        return Err(eyre!("{failed} of {} checks failed", checks.len()));
+    }
+    Ok(())
+}
+
+const CONFIG: &str = "Config";
+const CONFIG_HINT: &str =
+    "Fix the config file named in the error, or pass the right one with --config";
+const GQL: &str = "GQL";
+const GQL_HINT: &str = "Check the internet connection, firewall and proxy settings";
+const TOKEN: &str = "Token";
+const TOKEN_HINT: &str =
+    "Start the miner to log in again, deleting the tokens file first if it exists, or pass the right one with --token";
+const STREAMERS: &str = "Streamers";
+const STREAMERS_HINT: &str = "Check the spelling of the channel logins in the config, renamed or banned channels cannot be found";
+const SPADE: &str = "Spade URL";
+const SPADE_HINT: &str =
+    "Twitch may have changed its page layout, check for a newer release of the miner";
+const PUBSUB: &str = "Pubsub";
+const PUBSUB_HINT: &str =
+    "Check that websocket connections to pubsub-edge.twitch.tv are not blocked by a firewall or proxy";
+const ANALYTICS: &str = "Analytics";
+const ANALYTICS_HINT: &str = "Make sure the directory of --analytics-db exists and is writable, or run with --analytics false";
+
+pub async fn check_gql(client: &gql::Client) -> Check {
+    Check::new(
+        GQL,
+        GQL_HINT,
+        client.ping().await.map(|_| "reachable".to_owned()),
+    )
+}
+
+pub async fn check_token(client: &gql::Client) -> Check {
+    Check::new(
+        TOKEN,
+        TOKEN_HINT,
+        client
+            .get_user_id()
+            .await
+            .map(|(id, login)| format!("logged in as {login} ({id})")),
+    )
+}
+
+async fn check_streamers(client: &gql::Client, names: &[&str]) -> Check {
+    let res = client.streamer_metadata(names).await.and_then(|found| {
+        let missing = names
+            .iter()
+            .zip(found)
+            .filter(|x| x.1.is_none())
+            .map(|x| *x.0)
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            Ok(format!("all {} found", names.len()))
+        } else {
+            Err(eyre!("not found: {}", missing.join(", ")))
+        }
+    });
+    Check::new(STREAMERS, STREAMERS_HINT, res)
+}
+
+async fn check_spade(streamer: &str, base_url: &str, options: &api::RequestOptions) -> Check {
+    Check::new(
+        SPADE,
+        SPADE_HINT,
+        api::get_spade_url(streamer, base_url, options)
+            .await
+            .map(|_| "discovered".to_owned()),
+    )
+}
+
+async fn check_pubsub(url: &str, proxy: Option<&Proxy>) -> Check {
+    Check::new(
+        PUBSUB,
+        PUBSUB_HINT,
+        ws::probe(url, proxy).await.map(|_| "connected".to_owned()),
+    )
+}
+
+fn check_analytics(enabled: bool, path: &str) -> Check {
+    if !enabled {
+        return Check::new(ANALYTICS, ANALYTICS_HINT, Ok("disabled".to_owned()));
+    }
+    Check::new(
+        ANALYTICS,
+        ANALYTICS_HINT,
+        Analytics::check_writable(path)
+            .map(|_| format!("{path} is writable"))
+            .map_err(Into::into),
+    )
+}
+
+pub fn format_checks(checks: &[Check]) -> String {
+    let width = checks
+        .iter()
+        .map(|x| x.name.len())
+        .max()
+        .unwrap_or_default();
+    let mut out = String::new();
+    for check in checks {
+        match &check.result {
+            Ok(details) => out.push_str(&format!("[ok]   {:width$}  {details}\n", check.name)),
+            Err(err) => out.push_str(&format!(
+                "[fail] {:width$}  {err}\n       {:width$}  hint: {}\n",
+                check.name, "", check.hint
+            )),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use common::{
+        testing::{container, TestContainer},
+        twitch::gql::Client,
+    };
+    use eyre::Result;
+    use rstest::rstest;
+    use std::time::Duration;
+
+    use super::{check_gql, check_token, format_checks};
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn gql_check(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let client = Client::new(
+            String::new(),
+            format!("http://localhost:{}/gql", container.port),
+        );
+        let check = check_gql(&client).await;
+        assert_eq!(check.result, Ok("reachable".to_owned()));
+
+        // nothing listens on a port that was just released
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/gql", listener.local_addr()?);
+        drop(listener);
+        let check = check_gql(&Client::new(String::new(), url)).await;
+        assert!(check.result.is_err());
+        let text = format_checks(&[check]);
+        assert!(text.starts_with("[fail] GQL"), "{text}");
+        assert!(
+            text.contains("hint: Check the internet connection"),
+            "{text}"
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn token_check(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let url = format!("http://localhost:{}/gql", container.port);
+        reqwest::Client::new()
+            .post(format!("http://localhost:{}/current_user", container.port))
+            .json(&("valid-token", "1", "miner"))
+            .send()
+            .await?;
+
+        let check = check_token(&Client::new("valid-token".to_owned(), url.clone())).await;
+        assert_eq!(check.result, Ok("logged in as miner (1)".to_owned()));
+
+        let check = check_token(&Client::new("expired-token".to_owned(), url)).await;
+        assert!(check.result.is_err());
+        assert!(format_checks(&[check]).contains("hint: Start the miner to log in again"));
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::{broadcast, broadcast::error::RecvError, RwLock};
+use tracing::warn;
+
+use crate::pubsub::PubSub;
+
+/// Number of events kept for slow subscribers before they start lagging
+pub const EVENTS_CAPACITY: usize = 128;
+
+pub type EventsTx = broadcast::Sender<AppEvent>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, utoipa::ToSchema)]
+#[serde(untagged)]
+pub enum AppEvent {
+    BetPlaced {
+        channel_name: String,
+        event_id: String,
+        outcome_id: String,
+        points: u32,
+    },
+    PredictionEnded {
+        channel_name: String,
+        event_id: String,
+        winning_outcome_id: Option<String>,
+    },
+    PointsClaimed {
+        channel_name: String,
+        points: u32,
+    },
+    LiveChanged {
+        channel_name: String,
+        live: bool,
+    },
+    PubSubStatus {
+        degraded: bool,
+        failed_reconnects: u32,
+    },
+    /// A bet above `alert_on_bet_above` is about to be placed
+    LargeBet {
+        channel_name: String,
+        event_id: String,
+        outcome_id: String,
+        points: u32,
+        threshold: u32,
+    },
+    /// The balance crossed the points goal of the streamer
+    PointsGoalReached {
+        channel_name: String,
+        points: u32,
+        goal: u32,
+    },
+    DropClaimed {
+        drop_instance_id: String,
+        name: String,
+    },
+    /// The total balance dropped by more than `panic_stop` allows, all bets are stopped until resumed
+    PanicStop {
+        loss: u64,
+        peak: u64,
+        window_seconds: u64,
+    },
+}
+
+impl AppEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppEvent::BetPlaced { .. } => "bet_placed",
+            AppEvent::PredictionEnded { .. } => "prediction_ended",
+            AppEvent::PointsClaimed { .. } => "points_claimed",
+            AppEvent::LiveChanged { .. } => "live_changed",
+            AppEvent::PubSubStatus { .. } => "pubsub_status",
+            AppEvent::LargeBet { .. } => "large_bet",
+            AppEvent::PointsGoalReached { .. } => "points_goal_reached",
+            AppEvent::DropClaimed { .. } => "drop_claimed",
+            AppEvent::PanicStop { .. } => "panic_stop",
+        }
+    }
+
+    /// Streamer the event is about, none for account wide events
+    pub fn channel_name(&self) -> Option<&str> {
+        match self {
+            AppEvent::BetPlaced { channel_name, .. }
+            | AppEvent::PredictionEnded { channel_name, .. }
+            | AppEvent::PointsClaimed { channel_name, .. }
+            | AppEvent::LiveChanged { channel_name, .. }
+            | AppEvent::LargeBet { channel_name, .. }
+            | AppEvent::PointsGoalReached { channel_name, .. } => Some(channel_name),
+            AppEvent::PubSubStatus { .. }
+            | AppEvent::DropClaimed { .. }
+            | AppEvent::PanicStop { .. } => None,
+        }
+    }
+}
+
+/// Post every event with a notify target to its webhook, see [`PubSub::notify_target`]
+pub async fn notify(pubsub: Arc<RwLock<PubSub>>, mut rx: broadcast::Receiver<AppEvent>) {
+    let client = reqwest::Client::new();
+    loop {
+        let event = match rx.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(n)) => {
+                warn!("Notifier lagged, {n} events were not posted");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let target = match pubsub.read().await.notify_target(&event) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        // content is what chat webhooks like discord's display
+        let body = json!({
+            "event": event.name(),
+            "content": format!("{}: {}", event.name(), serde_json::to_string(&event).unwrap_or_default()),
+            "data": event,
+        });
+        let res = client
+            .post(&target.webhook_url)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|x| x.error_for_status());
+        if let Err(err) = res {
+            warn!(
+                "Could not post {} to the notify webhook: {err}",
+                event.name()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use axum::{extract::State, routing::post, Json, Router};
+    use common::config::NotifyTarget;
+    use eyre::Result;
+    use flume::unbounded;
+    use tokio::{
+        net::TcpListener,
+        sync::{Mutex, RwLock},
+    };
+    use twitch_api::types::UserId;
+
+    use super::AppEvent;
+    use crate::pubsub::PubSub;
+
+    type Posted = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
+
+    #[tokio::test]
+    async fn streamer_target_overrides_global() -> Result<()> {
+        let posted = Posted::default();
+        let router = Router::new()
+            .route(
+                "/:target",
+                post(
+                    |State(posted): State<Posted>,
+                     axum::extract::Path(target): axum::extract::Path<String>,
+                     Json(body): Json<serde_json::Value>| async move {
+                        posted.lock().await.push((target, body));
+                    },
+                ),
+            )
+            .with_state(posted.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+        let target = |name: &str| NotifyTarget {
+            webhook_url: format!("http://{address}/{name}"),
+            events: None,
+        };
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.config.notify = Some(target("global"));
+        for (id, name) in [("1", "favorite"), ("2", "other")] {
+            pubsub.streamers.insert(
+                UserId::from_static(id),
+                common::types::StreamerState::new(true, name.to_owned()),
+            );
+        }
+        pubsub.streamers[&UserId::from_static("1")]
+            .config
+            .0
+            .write()
+            .unwrap()
+            .config
+            .notify = Some(Box::new(target("favorite")));
+        let rx = pubsub.events_tx.subscribe();
+        let pubsub = Arc::new(RwLock::new(pubsub));
+        let notifier = tokio::spawn(super::notify(pubsub.clone(), rx));
+
+        for channel_name in ["favorite", "other"] {
+            pubsub.read().await.emit(AppEvent::PointsClaimed {
+                channel_name: channel_name.to_owned(),
+                points: 50,
+            });
+        }
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while posted.lock().await.len() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+
+        let posted = posted.lock().await;
+        let routed = posted
+            .iter()
+            .map(|(target, body)| {
+                (
+                    target.as_str(),
+                    body["data"]["channel_name"].as_str().unwrap_or_default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(routed, vec![("favorite", "favorite"), ("global", "other")]);
+        assert_eq!(posted[0].1["event"], "points_claimed");
+
+        notifier.abort();
+        server.abort();
+        Ok(())
+    }
+}
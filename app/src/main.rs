@@ -1,12 +1,18 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
-use clap::Parser;
-use common::twitch::ws::{Request, WsPool};
+use chrono::Local;
+use clap::{Parser, Subcommand};
+use common::twitch::auth::Token;
+use common::twitch::proxy::Proxy;
+use common::twitch::ws::{RawMessages, Request, WsPool, WsPoolOptions, WsPoolStatus};
 use eyre::{eyre, Context, Result};
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
 use tokio::{fs, spawn};
-use tracing::info;
+use tracing::{error, info, warn};
 use tracing_subscriber::fmt::format::{Compact, DefaultFields};
 use tracing_subscriber::fmt::time::ChronoLocal;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
@@ -15,10 +21,14 @@ use twitch_api::pubsub::video_playback::{VideoPlaybackById, VideoPlaybackReply};
 use twitch_api::pubsub::{TopicData, Topics};
 
 use crate::analytics::{Analytics, AnalyticsWrapper};
+use crate::events::AppEvent;
 
 mod analytics;
+mod doctor;
+mod events;
 // mod live;
 mod pubsub;
+mod replay;
 mod web_api;
 
 #[derive(Parser, Debug)]
@@ -27,12 +37,16 @@ struct Args {
     /// Config file
     #[arg(short, long, default_value_t = String::from("config.yaml"))]
     config: String,
-    /// API address to bind
-    #[arg(short, long, default_value_t = String::from("0.0.0.0:3000"))]
-    address: String,
+    /// API address to bind. Defaults to the TPM_ADDRESS environment variable, then 0.0.0.0 on the PORT environment variable,
+    /// then 0.0.0.0:3000
+    #[arg(short, long)]
+    address: Option<String>,
     /// Simulate predictions, don't actually make them
     #[arg(short, long, default_value_t = false)]
     simulate: bool,
+    /// Only watch streams and claim points, never place bets
+    #[arg(long, default_value_t = false)]
+    watch_only: bool,
     /// Token file
     #[arg(short, long, default_value_t = String::from("tokens.json"))]
     token: String,
@@ -42,9 +56,62 @@ struct Args {
     /// Analytics database path
     #[arg(long, default_value_t = String::from("analytics.db"))]
     analytics_db: String,
+    /// Record analytics, overrides the analytics setting in the config. If disabled no database is created
+    #[arg(long)]
+    analytics: Option<bool>,
+    /// Analytics database of another account, opened read-only to compare against
+    #[arg(long)]
+    compare_analytics_db: Option<String>,
+    /// Only serve the API, without the dashboard and swagger UI
+    #[arg(long, default_value_t = false)]
+    no_dashboard: bool,
+    /// Exit when the pubsub connection is degraded, so a supervisor can restart the miner
+    #[arg(long, default_value_t = false)]
+    exit_on_disconnect: bool,
+    /// Start the login sequence again if the token file cannot be parsed
+    #[arg(long, default_value_t = false)]
+    relogin_on_bad_token: bool,
+    /// Proxy for all traffic to twitch, http://, https:// or socks5://. Overrides the proxy in the config
+    #[arg(long)]
+    proxy: Option<String>,
+    /// Write the OpenAPI spec of the web API to this file and exit
+    #[arg(long)]
+    dump_openapi: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Replay the outcomes history of a recorded prediction against the config's strategies, showing when in the
+    /// window each would have bet and how much
+    Replay {
+        /// Event ID of the prediction
+        prediction_id: String,
+        /// Balance to size bets against, defaults to the channel's balance when the prediction started
+        #[arg(long)]
+        balance: Option<u32>,
+    },
+    /// Check the token, the connections to twitch, every configured streamer and the analytics database, printing
+    /// what failed and how to fix it
+    Doctor,
 }
 
 const BASE_URL: &str = "https://twitch.tv";
+const DEFAULT_ADDRESS: &str = "0.0.0.0:3000";
+
+/// The address to serve the API on, from the --address argument, or the TPM_ADDRESS or PORT environment variables
+fn bind_address(arg: Option<&str>, var: impl Fn(&str) -> Option<String>) -> Result<SocketAddr> {
+    let address = match (arg, var("TPM_ADDRESS"), var("PORT")) {
+        (Some(x), _, _) => x.to_owned(),
+        (None, Some(x), _) => x,
+        (None, None, Some(port)) => format!("0.0.0.0:{port}"),
+        (None, None, None) => DEFAULT_ADDRESS.to_owned(),
+    };
+    address.parse().map_err(|_| {
+        eyre!("Invalid API address {address}, expected an address like {DEFAULT_ADDRESS}")
+    })
+}
 
 fn get_layer<S>(
     layer: tracing_subscriber::fmt::Layer<S>,
@@ -86,25 +153,39 @@ async fn main() -> Result<()> {
 
     tracing::trace!("{args:#?}");
 
-    if !Path::new(&args.token).exists() {
-        info!("Starting login sequence");
-        common::twitch::auth::login(&args.token).await?;
+    if let Some(path) = &args.dump_openapi {
+        web_api::dump_openapi(path).await?;
+        info!("Wrote OpenAPI spec to {path}");
+        return Ok(());
     }
 
-    let mut c: common::config::Config = serde_yaml::from_str(
-        &fs::read_to_string(&args.config)
-            .await
-            .context("Reading config file")?,
-    )
-    .context("Parsing config file")?;
-    info!("Parsed config file");
+    if let Some(Command::Replay {
+        prediction_id,
+        balance,
+    }) = &args.command
+    {
+        return replay::run(&args.config, &args.analytics_db, prediction_id, *balance).await;
+    }
+    if let Some(Command::Doctor) = &args.command {
+        return doctor::run(
+            &args.config,
+            &args.token,
+            &args.analytics_db,
+            args.analytics,
+            args.proxy.as_deref(),
+        )
+        .await;
+    }
 
-    if c.streamers.is_empty() {
-        return Err(eyre!("No streamers in config file"));
+    if !Path::new(&args.token).exists() {
+        info!("Starting login sequence");
+        common::twitch::auth::login(&args.token).await?;
     }
 
-    let c_original = c.clone();
+    let c_original = parse_config(&args.config).await?;
+    let mut c = c_original.clone();
     c.parse_and_validate()?;
+    info!("Parsed config file");
 
     for item in c.watch_priority.clone().unwrap_or_default() {
         if !c.streamers.contains_key(&item) {
@@ -114,21 +195,39 @@ async fn main() -> Result<()> {
         }
     }
 
-    let token: common::twitch::auth::Token = serde_json::from_str(
-        &fs::read_to_string(args.token)
-            .await
-            .context("Reading tokens file")?,
-    )
-    .context("Parsing tokens file")?;
+    let token = read_token(&args.token, args.relogin_on_bad_token).await?;
     info!("Parsed tokens file");
 
-    let gql = common::twitch::gql::Client::new(
+    let proxy = args
+        .proxy
+        .as_ref()
+        .or(c.proxy.as_ref())
+        .map(|x| x.parse::<Proxy>())
+        .transpose()?;
+
+    let mut gql = common::twitch::gql::Client::new(
         token.access_token.clone(),
         "https://gql.twitch.tv/gql".to_owned(),
-    );
+    )
+    .with_chunk_size(c.gql_chunk_size.unwrap_or(common::twitch::gql::CHUNK_SIZE))
+    .with_concurrency(
+        c.gql_concurrency
+            .unwrap_or(common::twitch::gql::CONCURRENCY),
+    )
+    .with_query_hashes(c.gql_query_hashes.clone().unwrap_or_default());
+    if let Some(proxy) = &proxy {
+        info!("Sending twitch traffic through proxy");
+        gql = gql.with_proxy(proxy)?;
+    }
     let user_info = gql.get_user_id().await?;
+    // only startup reports progress, the periodic refreshes would flood the log
+    let startup_gql = gql.clone().with_progress(
+        c.startup_progress_every
+            .unwrap_or(common::twitch::gql::PROGRESS_EVERY),
+        Arc::new(|what, done, total| info!("Resolved {done}/{total} {what}")),
+    );
     let streamer_names = c.streamers.keys().map(|s| s.as_str()).collect::<Vec<_>>();
-    let channels = gql
+    let channels = startup_gql
         .streamer_metadata(&streamer_names)
         .await
         .wrap_err_with(|| "Could not get streamer list. Is your token valid?")?;
@@ -140,10 +239,18 @@ async fn main() -> Result<()> {
         }
     }
 
-    let (mut analytics, analytics_tx) = Analytics::new(&args.analytics_db)?;
+    // session baselines are written from here on, so the run starts before them
+    let launched_at = Local::now();
+    let analytics_enabled = args.analytics.or(c.analytics).unwrap_or(true);
+    let (mut analytics, analytics_tx) = open_analytics(
+        analytics_enabled,
+        &args.analytics_db,
+        c.analytics_queue_capacity
+            .unwrap_or(analytics::QUEUE_CAPACITY),
+    )?;
 
     let channels = channels.into_iter().flatten().collect::<Vec<_>>();
-    let points = gql
+    let points = startup_gql
         .get_channel_points(
             &channels
                 .iter()
@@ -152,15 +259,29 @@ async fn main() -> Result<()> {
         )
         .await?;
 
+    let mut last_live_at = HashMap::new();
+    let mut tags = HashMap::new();
     for (c, p) in channels.iter().zip(&points) {
+        let analytics = match analytics.as_mut() {
+            Some(x) => x,
+            None => break,
+        };
         let id = c.0.as_str().parse::<i32>()?;
         let inserted = analytics.insert_streamer(id, c.1.channel_name.clone())?;
         if inserted {
             analytics.insert_points(id, p.0 as i32, analytics::model::PointsInfo::FirstEntry)?;
+        } else if let Some(at) = analytics.last_live_at(id)? {
+            last_live_at.insert(c.0.clone(), at);
         }
+        analytics.insert_points(
+            id,
+            p.0 as i32,
+            analytics::model::PointsInfo::SessionBaseline,
+        )?;
+        tags.insert(c.0.clone(), analytics.tags(id)?);
     }
 
-    let active_predictions = gql
+    let active_predictions = startup_gql
         .channel_points_context(
             &channels
                 .iter()
@@ -170,8 +291,28 @@ async fn main() -> Result<()> {
         .await?;
 
     info!("Config OK!");
-    let (ws_pool, ws_tx, (ws_data_tx, ws_rx)) = WsPool::start(
+    let raw_messages = RawMessages::new(
+        c.pubsub_raw_messages
+            .unwrap_or(common::twitch::ws::RAW_MESSAGES),
+    );
+    let (ws_pool, ws_tx, (ws_data_tx, ws_rx), ws_status) = WsPool::start(
         &token.access_token,
+        WsPoolOptions {
+            silence_timeout: Duration::from_secs(
+                c.pubsub_silence_timeout
+                    .unwrap_or(common::twitch::ws::SILENCE_TIMEOUT_SECS),
+            ),
+            degraded_after: c
+                .pubsub_degraded_after
+                .unwrap_or(common::twitch::ws::DEGRADED_AFTER),
+            max_connections: c.pubsub_max_connections,
+            topics_per_connection: c
+                .pubsub_topics_per_connection
+                .unwrap_or(common::twitch::ws::TOPICS_PER_CONNECTION)
+                .max(1),
+            raw_messages: raw_messages.clone(),
+            proxy: proxy.clone(),
+        },
         #[cfg(test)]
         String::new(),
     )
@@ -208,7 +349,36 @@ async fn main() -> Result<()> {
     // we definitely do not want to keep this in scope
     drop(ws_data_tx);
 
-    let pubsub_data = Arc::new(RwLock::new(pubsub::PubSub::new(
+    // the topic connections of their own pick up a reloaded token when reconnecting
+    let (token_tx, token_rx) = watch::channel(token.access_token.clone());
+    let onsite_rx = if c.onsite_notifications.unwrap_or(false) {
+        let (onsite_tx, onsite_rx) = flume::unbounded();
+        spawn(common::twitch::onsite::listen(
+            common::twitch::ws::PUBSUB_URL.to_owned(),
+            token_rx.clone(),
+            user_info.0.clone(),
+            proxy.clone(),
+            onsite_tx,
+        ));
+        Some(onsite_rx)
+    } else {
+        None
+    };
+
+    let (user_predictions_tx, user_predictions_rx) = flume::unbounded();
+    spawn(common::twitch::user_predictions::listen(
+        common::twitch::ws::PUBSUB_URL.to_owned(),
+        token_rx,
+        user_info.0.clone(),
+        proxy.clone(),
+        user_predictions_tx,
+    ));
+
+    if analytics_enabled && c.optimize_analytics.unwrap_or(false) {
+        spawn(optimize_analytics(analytics_tx.clone()));
+    }
+
+    let mut state = pubsub::PubSub::new(
         c_original,
         args.config,
         channels
@@ -220,24 +390,61 @@ async fn main() -> Result<()> {
         active_predictions,
         c.presets.unwrap_or_default(),
         args.simulate,
+        args.watch_only,
         user_info,
         gql.clone(),
         BASE_URL,
         ws_tx,
-        Arc::new(AnalyticsWrapper::new(analytics)),
+        Arc::new(match analytics {
+            Some(analytics) => AnalyticsWrapper::new(analytics),
+            None => AnalyticsWrapper::empty(),
+        }),
         analytics_tx,
-    )?));
-
-    let pubsub = spawn(pubsub::PubSub::run(ws_rx, pubsub_data.clone(), gql));
+    )?;
+    state.proxy = proxy;
+    state.token_path.clone_from(&args.token);
+    state.token_tx = token_tx;
+    state.launched_at = launched_at;
+    for (id, at) in last_live_at {
+        if let Some(s) = state.streamers.get_mut(&id) {
+            s.last_live_at = at.and_local_timezone(Local).earliest();
+        }
+    }
+    for (id, tags) in tags {
+        if let Some(s) = state.streamers.get_mut(&id) {
+            s.tags = tags;
+        }
+    }
+    let events_rx = state.events_tx.subscribe();
+    let pubsub_data = Arc::new(RwLock::new(state));
+    spawn(events::notify(pubsub_data.clone(), events_rx));
+
+    let pubsub = spawn(pubsub::PubSub::run(
+        ws_rx,
+        onsite_rx,
+        user_predictions_rx,
+        pubsub_data.clone(),
+        gql,
+    ));
+    spawn(watch_ws_status(
+        ws_status.clone(),
+        pubsub_data.clone(),
+        args.exit_on_disconnect,
+    ));
 
     info!("Starting web api!");
 
+    let address = bind_address(args.address.as_deref(), |x| std::env::var(x).ok())?;
     let axum_server = web_api::get_api_server(
-        args.address,
+        address,
         pubsub_data,
         Arc::new(token),
-        &args.analytics_db,
+        analytics_enabled.then_some(args.analytics_db.as_str()),
+        args.compare_analytics_db.as_deref(),
         args.log_file,
+        ws_status,
+        raw_messages,
+        !args.no_dashboard,
     )
     .await?;
 
@@ -247,3 +454,209 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// The analytics database and the queue writing to it. Disabled analytics have no database, and drop every write
+fn open_analytics(
+    enabled: bool,
+    path: &str,
+    capacity: usize,
+) -> Result<(Option<Analytics>, flume::Sender<analytics::Request>)> {
+    if !enabled {
+        info!("Analytics disabled, not opening {path}");
+        return Ok((None, Analytics::disabled()));
+    }
+    let (analytics, tx) = Analytics::with_capacity(path, capacity)?;
+    Ok((Some(analytics), tx))
+}
+
+/// Read the config file, it still has to be validated
+async fn parse_config(path: &str) -> Result<common::config::Config> {
+    let c: common::config::Config = serde_yaml::from_str(
+        &fs::read_to_string(path)
+            .await
+            .context("Reading config file")?,
+    )
+    .context("Parsing config file")?;
+    if c.streamers.is_empty() {
+        return Err(eyre!("No streamers in config file"));
+    }
+    Ok(c)
+}
+
+/// Read the token file, a corrupt or empty file is replaced by logging in again if `relogin` is set
+async fn read_token(path: &str, relogin: bool) -> Result<Token> {
+    let text = fs::read_to_string(path)
+        .await
+        .context("Reading tokens file")?;
+    match serde_json::from_str(&text) {
+        Ok(token) => Ok(token),
+        Err(err) if relogin => {
+            warn!("Tokens file {path} could not be parsed ({err}), starting login sequence");
+            common::twitch::auth::login(path).await?;
+            let text = fs::read_to_string(path)
+                .await
+                .context("Reading tokens file")?;
+            serde_json::from_str(&text).context("Parsing tokens file")
+        }
+        Err(err) => Err(eyre!(
+            "Tokens file {path} could not be parsed ({err}). \
+            Delete it and restart to log in again, or run with --relogin-on-bad-token"
+        )),
+    }
+}
+
+async fn optimize_analytics(tx: flume::Sender<analytics::Request>) {
+    let period = Duration::from_secs(analytics::OPTIMIZE_INTERVAL_DAYS * 24 * 60 * 60);
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    loop {
+        interval.tick().await;
+        let res = tx
+            .send_async(Box::new(|analytics| {
+                let res = analytics.optimize()?;
+                info!(
+                    "Optimized analytics database, reclaimed {} bytes",
+                    res.reclaimed
+                );
+                Ok(())
+            }))
+            .await;
+        if res.is_err() {
+            break;
+        }
+    }
+}
+
+async fn watch_ws_status(
+    mut status: watch::Receiver<WsPoolStatus>,
+    pubsub: Arc<RwLock<pubsub::PubSub>>,
+    exit_on_disconnect: bool,
+) {
+    let mut degraded = false;
+    while status.changed().await.is_ok() {
+        let current = status.borrow_and_update().clone();
+        if current.degraded == degraded {
+            continue;
+        }
+        degraded = current.degraded;
+
+        if degraded {
+            error!(
+                "Lost connection to twitch pubsub, {} reconnects failed",
+                current.failed_reconnects
+            );
+        } else {
+            info!("Connection to twitch pubsub restored");
+        }
+        pubsub.read().await.emit(AppEvent::PubSubStatus {
+            degraded,
+            failed_reconnects: current.failed_reconnects,
+        });
+
+        if degraded && exit_on_disconnect {
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eyre::Result;
+
+    use super::{bind_address, open_analytics, read_token};
+
+    #[tokio::test]
+    async fn garbage_token_file() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("tokens-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        for contents in ["", "{\"access_tok"] {
+            tokio::fs::write(path, contents).await?;
+            let err = read_token(path, false).await.unwrap_err().to_string();
+            assert!(
+                err.contains("Delete it and restart to log in again"),
+                "{err}"
+            );
+        }
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn disabled_analytics() -> Result<()> {
+        use std::sync::Arc;
+
+        use common::twitch::{auth::Token, ws::RawMessages};
+        use tokio::{
+            net::TcpListener,
+            sync::{watch, RwLock},
+        };
+
+        use crate::pubsub::PubSub;
+
+        let path = std::env::temp_dir().join(format!("disabled-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let (analytics, tx) = open_analytics(false, path, 10)?;
+        assert!(analytics.is_none());
+        // writes are accepted, and dropped
+        assert!(tx
+            .send_async(Box::new(|_| panic!("Written to disabled analytics")))
+            .await
+            .is_ok());
+
+        let pubsub = Arc::new(RwLock::new(PubSub::empty(flume::unbounded().0)));
+        let address = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+        let server = crate::web_api::get_api_server(
+            address,
+            pubsub,
+            Arc::new(Token::default()),
+            None,
+            None,
+            None,
+            watch::channel(Default::default()).1,
+            RawMessages::new(0),
+            false,
+        )
+        .await?;
+        let server = tokio::spawn(async move { server.await });
+
+        let res = reqwest::get(format!(
+            "http://{address}/api/analytics/summary?from=2024-06-01T00:00:00Z&to=2024-06-02T00:00:00Z"
+        ))
+        .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = res.json().await?;
+        assert_eq!(body["code"], "analytics_disabled");
+
+        server.abort();
+        assert!(!std::path::Path::new(path).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn address_from_env() -> Result<()> {
+        let env = |address: Option<&'static str>, port: Option<&'static str>| {
+            move |x: &str| match x {
+                "TPM_ADDRESS" => address.map(str::to_owned),
+                "PORT" => port.map(str::to_owned),
+                _ => None,
+            }
+        };
+        assert_eq!(
+            bind_address(None, env(None, None))?.to_string(),
+            "0.0.0.0:3000"
+        );
+        assert_eq!(
+            bind_address(None, env(None, Some("8080")))?.to_string(),
+            "0.0.0.0:8080"
+        );
+        assert_eq!(
+            bind_address(None, env(Some("127.0.0.1:4000"), Some("8080")))?.to_string(),
+            "127.0.0.1:4000"
+        );
+        assert_eq!(
+            bind_address(Some("127.0.0.1:5000"), env(Some("127.0.0.1:4000"), None))?.to_string(),
+            "127.0.0.1:5000"
+        );
+        assert!(bind_address(None, env(None, Some("http"))).is_err());
+        Ok(())
+    }
+}
@@ -1,24 +1,41 @@
 use std::{
-    collections::HashMap,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
     ops::Deref,
     str::FromStr,
     sync::Arc,
     time::{Duration, Instant},
 };
 
+use chrono::Local;
 use common::{
-    config::{filters::filter_matches, *},
+    config::{
+        filters::{filter_matches, outcome_allowed, Filter},
+        *,
+    },
     remove_duplicates_in_place,
-    twitch::{api, gql, ws::Request},
+    time::{self, Tz},
+    twitch::{
+        api, gql,
+        onsite::Notification,
+        proxy::Proxy,
+        user_predictions::{PredictionsUserReply, UserPrediction},
+        ws::Request,
+    },
     types::*,
 };
 use eyre::{eyre, Context, ContextCompat, Result};
 use flume::{unbounded, Receiver, Sender};
 use indexmap::IndexMap;
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use serde::Serialize;
-use tokio::{spawn, sync::RwLock, time::sleep};
-use tracing::{debug, error, info, trace, warn};
+use tokio::{
+    spawn,
+    sync::{watch, RwLock},
+    task::AbortHandle,
+    time::sleep,
+};
+use tracing::{debug, error, info, instrument, trace, warn};
 use twitch_api::{
     pubsub::{
         community_points::CommunityPointsUserV1Reply,
@@ -30,20 +47,39 @@ use twitch_api::{
     types::UserId,
 };
 
-use crate::analytics::{
-    self,
-    model::{PointsInfo, Prediction, PredictionBetWrapper},
-    AnalyticsWrapper,
+use crate::{
+    analytics::{
+        self,
+        model::{
+            Outcomes, OutcomesHistory, OutcomesSnapshot, PointsInfo, Prediction, PredictionBet,
+            PredictionBetWrapper,
+        },
+        AnalyticsWrapper,
+    },
+    events::{AppEvent, EventsTx, EVENTS_CAPACITY},
 };
 
+/// Bets are not sent this close to the end of the prediction window, they would arrive after it locks
+const LOCK_MARGIN_SECS: i64 = 2;
+
+/// Seconds bets are paused for when twitch reports a prediction cooldown without saying how long it lasts
+const PREDICTION_COOLDOWN_SECS: u64 = 60;
+
 #[derive(Debug, Serialize, Clone, utoipa::ToSchema)]
 pub struct PubSub {
     #[serde(skip)]
     pub config: Config,
     #[serde(skip)]
     pub config_path: String,
+    /// Token file the access token is reloaded from
+    #[serde(skip)]
+    pub token_path: String,
+    /// Access token of the topic connections that are not part of the pool
+    #[serde(skip)]
+    pub token_tx: watch::Sender<String>,
     pub streamers: HashMap<UserId, StreamerState>,
     pub simulate: bool,
+    pub watch_only: bool,
     #[serde(skip)]
     spade_url: Option<String>,
     pub user_id: String,
@@ -60,6 +96,146 @@ pub struct PubSub {
     #[serde(skip)]
     pub analytics_tx: Sender<analytics::Request>,
     pub watching: Vec<StreamerState>,
+    /// Streamers sent viewership pings in the last watch round, and why they were picked
+    #[serde(skip)]
+    pub watched: Vec<WatchedStreamer>,
+    #[serde(skip)]
+    pub events_tx: EventsTx,
+    #[serde(skip)]
+    pub launched_at: chrono::DateTime<Local>,
+    /// Bets are paused as the total balance is below `global_min_balance`
+    pub below_min_balance: bool,
+    /// All bets are stopped as `panic_stop` was triggered, until resumed through the API
+    pub panic_stopped: bool,
+    /// Total balance across streamers at every update within the `panic_stop` window
+    #[serde(skip)]
+    balance_history: Vec<(Instant, u64)>,
+    #[serde(skip)]
+    pub proxy: Option<Proxy>,
+    pub claims: ClaimStats,
+    /// Delayed unlistens of streams that went offline, by channel ID
+    #[serde(skip)]
+    pending_unlisten: HashMap<u32, Arc<AbortHandle>>,
+    /// Streamers whose watch streak bonus was earned since the watch loop last ran
+    #[serde(skip)]
+    watch_streak_earned: Vec<UserId>,
+    /// Start of the day and points bet since then, for `daily_bet_cap`
+    #[serde(skip)]
+    wagered_today: (chrono::DateTime<chrono::Utc>, u32),
+    /// Live streamers with an active drop campaign, watched before the others
+    #[serde(skip)]
+    drop_channels: Vec<UserId>,
+    /// Bets are paused on every streamer until then, after twitch reported a prediction cooldown
+    #[serde(skip)]
+    bet_cooldown_until: Option<Instant>,
+    /// Last decision on a prediction per streamer
+    #[serde(skip)]
+    last_decisions: HashMap<UserId, DecisionTrace>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct WatchedStreamer {
+    pub channel_name: String,
+    pub reason: WatchReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchReason {
+    /// Went live recently, watched for its watch streak bonus
+    Streak,
+    /// Has an active drop campaign
+    Drops,
+    /// Listed in `watch_priority`
+    Priority,
+    /// Fills a free slot in config order
+    Fill,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct ClaimStats {
+    /// Bonuses claimed
+    pub succeeded: u64,
+    /// Failed claim attempts, including ones that succeeded when retried
+    pub failed: u64,
+}
+
+/// Streamers that differ between the config and the streamers being mined
+#[derive(Debug, Default, Clone, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct ConfigDrift {
+    /// Mined streamers without an entry in the config
+    pub orphaned: Vec<String>,
+    /// Streamers in the config that are not mined
+    pub missing: Vec<String>,
+}
+
+impl ConfigDrift {
+    pub fn is_empty(&self) -> bool {
+        self.orphaned.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Why the last prediction considered on a streamer was bet on or skipped
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct DecisionTrace {
+    pub event_id: String,
+    pub decided_at: chrono::DateTime<Local>,
+    /// Filters in config order, up to the first one the prediction did not pass
+    pub filters: Vec<FilterCheck>,
+    /// Odds of every outcome, as computed by the strategy
+    pub odds: Vec<OutcomeOdds>,
+    /// Strategy that picked the bet, `primary` or `fallback`
+    pub strategy: Option<String>,
+    pub outcome_id: Option<String>,
+    /// Final stake, after jitter and the daily bet cap
+    pub points: Option<u32>,
+    /// Why nothing was bet
+    pub skip_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct FilterCheck {
+    pub filter: Filter,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct OutcomeOdds {
+    pub outcome_id: String,
+    pub title: String,
+    /// Between 0 and 1
+    pub odds: f64,
+}
+
+impl DecisionTrace {
+    fn new(event_id: &str) -> Self {
+        DecisionTrace {
+            event_id: event_id.to_owned(),
+            decided_at: Local::now(),
+            filters: Vec::new(),
+            odds: Vec::new(),
+            strategy: None,
+            outcome_id: None,
+            points: None,
+            skip_reason: None,
+        }
+    }
+
+    fn decided(mut self, decision: Decision) -> Self {
+        match decision {
+            Decision::Bet {
+                outcome_id,
+                points,
+                fallback,
+            } => {
+                self.strategy = Some(if fallback { "fallback" } else { "primary" }.to_owned());
+                self.outcome_id = Some(outcome_id);
+                self.points = Some(points);
+            }
+            Decision::Skip { reason, .. } => self.skip_reason = Some(reason.to_owned()),
+        }
+        self
+    }
 }
 
 impl PubSub {
@@ -71,6 +247,7 @@ impl PubSub {
         active_predictions: Vec<Vec<(Event, bool)>>,
         presets: IndexMap<String, StreamerConfig>,
         simulate: bool,
+        watch_only: bool,
         user_info: (String, String),
         gql: gql::Client,
         base_url: &str,
@@ -78,6 +255,9 @@ impl PubSub {
         analytics: Arc<crate::analytics::AnalyticsWrapper>,
         analytics_tx: Sender<crate::analytics::Request>,
     ) -> Result<PubSub> {
+        let max_predictions = config
+            .max_predictions_per_streamer
+            .unwrap_or(common::types::MAX_PREDICTIONS);
         let mut configs = channels
             .iter()
             .filter_map(|x| match x.1 {
@@ -119,10 +299,17 @@ impl PubSub {
                         info,
                         predictions: ap
                             .into_iter()
+                            .take(max_predictions)
                             .map(|x| (x.0.id.to_string(), x))
                             .collect::<HashMap<_, _>>(),
                         points: p,
                         last_points_refresh: Instant::now(),
+                        wagered: Default::default(),
+                        live_since: None,
+                        last_live_at: None,
+                        circuit: Default::default(),
+                        points_goal_reached: false,
+                        tags: Vec::new(),
                     },
                 )
             })
@@ -130,8 +317,11 @@ impl PubSub {
         Ok(PubSub {
             config,
             config_path,
+            token_path: Default::default(),
+            token_tx: watch::channel(String::new()).0,
             streamers,
             simulate,
+            watch_only,
             spade_url: None,
             user_id: user_info.0,
             user_name: user_info.1,
@@ -142,10 +332,24 @@ impl PubSub {
             gql,
             base_url: base_url.to_string(),
             watching: Vec::new(),
+            watched: Vec::new(),
+            events_tx: tokio::sync::broadcast::channel(EVENTS_CAPACITY).0,
+            launched_at: Local::now(),
+            below_min_balance: false,
+            panic_stopped: false,
+            balance_history: Vec::new(),
+            proxy: None,
+            claims: Default::default(),
+            pending_unlisten: Default::default(),
+            watch_streak_earned: Vec::new(),
+            wagered_today: Default::default(),
+            drop_channels: Vec::new(),
+            bet_cooldown_until: None,
+            last_decisions: Default::default(),
         })
     }
 
-    #[cfg(test)]
+    /// State without streamers or a user, for tests and building the API docs
     pub fn empty(ws_tx: Sender<Request>) -> Self {
         use crate::analytics::Analytics;
 
@@ -155,8 +359,11 @@ impl PubSub {
             analytics_tx: tx,
             config: Default::default(),
             config_path: Default::default(),
+            token_path: Default::default(),
+            token_tx: watch::channel(String::new()).0,
             streamers: Default::default(),
             simulate: Default::default(),
+            watch_only: Default::default(),
             spade_url: Default::default(),
             user_id: Default::default(),
             user_name: Default::default(),
@@ -165,40 +372,283 @@ impl PubSub {
             base_url: Default::default(),
             ws_tx,
             watching: Default::default(),
+            watched: Default::default(),
+            events_tx: tokio::sync::broadcast::channel(EVENTS_CAPACITY).0,
+            launched_at: Local::now(),
+            below_min_balance: false,
+            panic_stopped: false,
+            balance_history: Vec::new(),
+            proxy: None,
+            claims: Default::default(),
+            pending_unlisten: Default::default(),
+            watch_streak_earned: Vec::new(),
+            wagered_today: Default::default(),
+            drop_channels: Vec::new(),
+            bet_cooldown_until: None,
+            last_decisions: Default::default(),
         }
     }
 
+    /// Twitch logins are case-insensitive, so are name lookups
     pub fn get_by_name(&self, name: &str) -> Option<&StreamerState> {
         self.streamers
             .values()
-            .find(|s| s.info.channel_name == name)
+            .find(|s| s.info.channel_name.eq_ignore_ascii_case(name))
     }
 
     pub fn get_by_name_mut(&mut self, name: &str) -> Option<&mut StreamerState> {
         self.streamers
             .values_mut()
-            .find(|s| s.info.channel_name == name)
+            .find(|s| s.info.channel_name.eq_ignore_ascii_case(name))
     }
 
     pub fn get_id_by_name(&self, name: &str) -> Option<&str> {
         for (k, v) in self.streamers.iter() {
-            if v.info.channel_name == name {
+            if v.info.channel_name.eq_ignore_ascii_case(name) {
                 return Some(k.as_str());
             }
         }
         None
     }
 
+    /// Key of a streamer in the config file
+    pub fn config_key(&self, name: &str) -> Option<String> {
+        self.config
+            .streamers
+            .keys()
+            .find(|k| k.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Compare the streamers in the config with the ones being mined
+    pub fn config_drift(&self) -> ConfigDrift {
+        let mut orphaned = self
+            .streamers
+            .values()
+            .filter(|s| self.config_key(&s.info.channel_name).is_none())
+            .map(|s| s.info.channel_name.clone())
+            .collect::<Vec<_>>();
+        orphaned.sort();
+        let missing = self
+            .config
+            .streamers
+            .keys()
+            .filter(|k| self.get_by_name(k).is_none())
+            .cloned()
+            .collect();
+        ConfigDrift { orphaned, missing }
+    }
+
+    pub fn is_below_min_balance(&self) -> bool {
+        self.config.global_min_balance.is_some_and(|min| {
+            self.streamers
+                .values()
+                .map(|s| s.points as u64)
+                .sum::<u64>()
+                < min as u64
+        })
+    }
+
+    /// Points bet across all streamers since the start of the day
+    pub fn wagered_today(&self) -> u32 {
+        let today = time::day_start(chrono::Utc::now(), self.config.timezone());
+        if self.wagered_today.0 == today {
+            self.wagered_today.1
+        } else {
+            0
+        }
+    }
+
+    pub fn add_wagered_today(&mut self, points: u32) {
+        let today = time::day_start(chrono::Utc::now(), self.config.timezone());
+        if self.wagered_today.0 != today {
+            self.wagered_today = (today, 0);
+        }
+        self.wagered_today.1 += points;
+    }
+
+    /// Points that can still be bet today, `None` without a `daily_bet_cap`
+    pub fn daily_budget(&self) -> Option<u32> {
+        self.config
+            .daily_bet_cap
+            .map(|cap| cap.saturating_sub(self.wagered_today()))
+    }
+
+    /// Re-evaluate `below_min_balance`, logging when bets are paused or resumed
+    fn update_below_min_balance(&mut self) -> bool {
+        let below = self.is_below_min_balance();
+        if below != self.below_min_balance {
+            if below {
+                warn!(
+                    "Total balance below {}, pausing bets",
+                    self.config.global_min_balance.unwrap_or_default()
+                );
+            } else {
+                info!("Total balance recovered, resuming bets");
+            }
+            self.below_min_balance = below;
+        }
+        below
+    }
+
+    /// Record the total balance for `panic_stop`, stopping all bets once it lost too much from its highest value
+    /// within the window. Points riding on unresolved predictions still count once twitch took them out of the
+    /// balance, simulated bets never leave it
+    fn track_drawdown(&mut self) {
+        let panic_stop = match &self.config.panic_stop {
+            Some(x) => x.clone(),
+            None => return,
+        };
+        let now = Instant::now();
+        let total = self
+            .streamers
+            .values()
+            .map(|s| s.points as u64 + s.wagered.values().map(|w| w.debited as u64).sum::<u64>())
+            .sum::<u64>();
+        let window = Duration::from_secs(panic_stop.window_seconds);
+        self.balance_history
+            .retain(|x| now.duration_since(x.0) <= window);
+        self.balance_history.push((now, total));
+        if self.panic_stopped {
+            return;
+        }
+
+        let peak = self
+            .balance_history
+            .iter()
+            .map(|x| x.1)
+            .max()
+            .unwrap_or(total);
+        let loss = peak - total;
+        if panic_stop.exceeded(peak, loss) {
+            error!(
+                "Total balance lost {loss} of {peak} points within {}s, stopping all bets until resumed",
+                panic_stop.window_seconds
+            );
+            self.panic_stopped = true;
+            self.emit(AppEvent::PanicStop {
+                loss,
+                peak,
+                window_seconds: panic_stop.window_seconds,
+            });
+        }
+    }
+
+    /// Log a decision on a prediction and keep it as the streamer's last one
+    fn record_decision(&mut self, streamer: &UserId, trace: DecisionTrace, decision: Decision) {
+        if let Some(s) = self.streamers.get(streamer) {
+            decision.log(&s.info.channel_name, &trace.event_id);
+        }
+        self.last_decisions
+            .insert(streamer.clone(), trace.decided(decision));
+    }
+
+    pub fn last_decision(&self, streamer: &UserId) -> Option<&DecisionTrace> {
+        self.last_decisions.get(streamer)
+    }
+
+    /// Resume bets after `panic_stop` was triggered, returns whether they were stopped
+    pub fn resume_betting(&mut self) -> bool {
+        let stopped = self.panic_stopped;
+        if stopped {
+            info!("Resuming bets after panic stop");
+        }
+        self.panic_stopped = false;
+        self.balance_history.clear();
+        stopped
+    }
+
+    pub fn spade_url(&self) -> Option<&str> {
+        self.spade_url.as_deref()
+    }
+
+    pub fn request_options(&self) -> api::RequestOptions {
+        let default = api::RequestOptions::default();
+        api::RequestOptions {
+            connect_timeout: self
+                .config
+                .http_connect_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(default.connect_timeout),
+            timeout: self
+                .config
+                .http_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(default.timeout),
+            retries: self.config.http_retries.unwrap_or(default.retries),
+            proxy: self.proxy.clone(),
+        }
+    }
+
+    /// Publish an event to API subscribers, dropped if nobody is listening
+    pub fn emit(&self, event: AppEvent) {
+        _ = self.events_tx.send(event);
+    }
+
+    /// Where an event is posted, the streamer's own notify target if it has one and the global one otherwise
+    pub fn notify_target(&self, event: &AppEvent) -> Option<NotifyTarget> {
+        let streamer = event.channel_name().and_then(|name| {
+            self.streamers
+                .values()
+                .find(|x| x.info.channel_name.eq_ignore_ascii_case(name))
+        });
+        let own = match streamer {
+            Some(s) => s
+                .config
+                .0
+                .read()
+                .ok()
+                .and_then(|c| c.config.notify.as_deref().cloned()),
+            None => None,
+        };
+        own.or_else(|| self.config.notify.clone())
+            .filter(|x| x.wants(event.name()))
+    }
+
+    /// Mark an active prediction as placed so no bet is attempted on it, returns false if the event is unknown
+    pub fn cancel_prediction(&mut self, event_id: &str) -> bool {
+        for s in self.streamers.values_mut() {
+            if let Some((_, placed)) = s.predictions.get_mut(event_id) {
+                *placed = true;
+                return true;
+            }
+        }
+        false
+    }
+
     pub async fn run(
         ws_rx: Receiver<TopicData>,
+        onsite_rx: Option<Receiver<Notification>>,
+        user_predictions_rx: Receiver<PredictionsUserReply>,
         pubsub: Arc<RwLock<PubSub>>,
         gql: gql::Client,
     ) -> Result<()> {
         let (tx_watch_streams, rx_watch_streams) = unbounded();
 
+        if let Some(onsite_rx) = onsite_rx {
+            spawn(onsite_notifications::run(
+                pubsub.clone(),
+                gql.clone(),
+                onsite_rx,
+            ));
+        }
+
+        spawn(user_predictions::run(pubsub.clone(), user_predictions_rx));
         spawn(watch_stream::run(pubsub.clone(), rx_watch_streams));
         spawn(update_and_claim_points::run(pubsub.clone(), gql.clone()));
         spawn(update_spade_url::run(pubsub.clone()));
+        if pubsub.read().await.config.drops.unwrap_or(false) {
+            spawn(drops::run(pubsub.clone(), gql.clone()));
+        }
+        if let Some(secs) = pubsub.read().await.config.game_refresh_seconds {
+            spawn(game_refresh::run(pubsub.clone(), gql.clone(), secs));
+        }
+        if let Some(secs) = pubsub.read().await.config.equity_snapshot_seconds {
+            spawn(equity_snapshot::run(
+                pubsub.clone(),
+                Duration::from_secs(secs),
+            ));
+        }
 
         let mut deferred_updates = Vec::new();
         while let Ok(data) = ws_rx.recv_async().await {
@@ -254,12 +704,25 @@ impl PubSub {
                     .context("Streamer does not exist")?;
                 match *reply {
                     VideoPlaybackReply::StreamUp {
-                        server_time: _,
+                        server_time,
                         play_delay: _,
                     } => {
                         info!("{} is live", streamer.info.channel_name);
                         streamer.info.live = true;
+                        streamer.last_live_at = Some(Local::now());
+                        // the initial live messages sent at startup have no server time
+                        streamer.live_since = (server_time > 0.0).then(Instant::now);
+                        let event = AppEvent::LiveChanged {
+                            channel_name: streamer.info.channel_name.clone(),
+                            live: true,
+                        };
+                        self.emit(event);
+                        self.record_last_live(channel_id).await?;
 
+                        if let Some(pending) = self.pending_unlisten.remove(&channel_id) {
+                            debug!("Canceled unlisten of {channel_id}, back online");
+                            pending.abort();
+                        }
                         for item in topics.into_iter().map(Request::Listen) {
                             self.ws_tx
                                 .send_async(item)
@@ -271,13 +734,16 @@ impl PubSub {
                     }
                     VideoPlaybackReply::StreamDown { server_time: _ } => {
                         streamer.info.live = false;
+                        streamer.live_since = None;
+                        streamer.last_live_at = Some(Local::now());
                         info!("{} is not live", streamer.info.channel_name);
-                        for item in topics.into_iter().map(Request::UnListen) {
-                            self.ws_tx
-                                .send_async(item)
-                                .await
-                                .context("Send ws command")?;
-                        }
+                        let event = AppEvent::LiveChanged {
+                            channel_name: streamer.info.channel_name.clone(),
+                            live: false,
+                        };
+                        self.emit(event);
+                        self.record_last_live(channel_id).await?;
+                        self.unlisten_after_grace(channel_id, topics).await?;
                     }
                     _ => {}
                 }
@@ -301,21 +767,44 @@ impl PubSub {
             TopicData::CommunityPointsUserV1 { topic, reply } => {
                 debug!("Got CommunityPointsUserV1 {:#?}", topic);
 
-                if let CommunityPointsUserV1Reply::ClaimClaimed {
-                    timestamp: _,
-                    claim,
-                } = *reply
-                {
-                    if claim.user_id.as_str().ne(&self.user_id) {
-                        return Ok(None);
-                    };
+                match *reply {
+                    CommunityPointsUserV1Reply::ClaimClaimed {
+                        timestamp: _,
+                        claim,
+                    } => {
+                        if claim.user_id.as_str().ne(&self.user_id) {
+                            return Ok(None);
+                        };
+
+                        if self.streamers.contains_key(&claim.channel_id) {
+                            debug!("Channel points updated for {}", claim.channel_id);
+                            let s = self.streamers.get_mut(&claim.channel_id).unwrap();
+                            s.points = claim.point_gain.total_points as u32;
+                            s.last_points_refresh = Instant::now();
+                        }
+                    }
+                    CommunityPointsUserV1Reply::PointsEarned {
+                        timestamp: _,
+                        channel_id,
+                        point_gain,
+                        balance,
+                    } => {
+                        if balance.user_id.as_str().ne(&self.user_id) {
+                            return Ok(None);
+                        };
 
-                    if self.streamers.contains_key(&claim.channel_id) {
-                        debug!("Channel points updated for {}", claim.channel_id);
-                        let s = self.streamers.get_mut(&claim.channel_id).unwrap();
-                        s.points = claim.point_gain.total_points as u32;
-                        s.last_points_refresh = Instant::now();
+                        debug!(
+                            "Earned {} points on {} for {}",
+                            point_gain.total_points, channel_id, point_gain.reason_code
+                        );
+                        self.points_earned(
+                            &channel_id,
+                            balance.balance as u32,
+                            PointsInfo::from_reason_code(&point_gain.reason_code),
+                        )
+                        .await?;
                     }
+                    _ => {}
                 }
             }
             TopicData::Raid { topic, reply } => {
@@ -338,6 +827,48 @@ impl PubSub {
         Ok(None)
     }
 
+    async fn record_last_live(&self, channel_id: u32) -> Result<()> {
+        let at = Local::now().naive_local();
+        self.analytics_tx
+            .send_async(Box::new(move |analytics| {
+                analytics.set_last_live_at(channel_id as i32, at)
+            }))
+            .await
+            .map_err(|_| eyre!("Failed to send last live time to analytics"))
+    }
+
+    /// Record a points balance pushed by twitch, attributed to the reason it was earned for
+    async fn points_earned(
+        &mut self,
+        channel_id: &UserId,
+        balance: u32,
+        points_info: PointsInfo,
+    ) -> Result<()> {
+        let s = match self.streamers.get_mut(channel_id) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+        s.points = balance;
+        s.last_points_refresh = Instant::now();
+        if points_info == PointsInfo::WatchStreak {
+            self.watch_streak_earned.push(channel_id.clone());
+        }
+        self.track_drawdown();
+
+        let channel_id = channel_id.as_str().parse::<i32>()?;
+        let sent = analytics::send_droppable(
+            &self.analytics_tx,
+            "points update",
+            Box::new(move |analytics| {
+                analytics.insert_points(channel_id, balance as i32, points_info.clone())
+            }),
+        );
+        if !sent {
+            return Err(eyre!("Failed to send points to analytics"));
+        }
+        Ok(())
+    }
+
     async fn update_stream_metadata(&mut self, channel_id: u32) -> Result<()> {
         let streamer = self
             .streamers
@@ -366,16 +897,21 @@ impl PubSub {
             None
         };
 
+        let outcomes: Outcomes = event.outcomes.clone().into();
         let prediction = Prediction {
             channel_id,
             prediction_id: event.id.clone(),
             title: event.title.clone(),
             prediction_window: event.prediction_window_seconds,
-            outcomes: event.outcomes.clone().into(),
+            outcomes: outcomes.clone(),
             winning_outcome_id: None,
             placed_bet: PredictionBetWrapper::None,
             created_at,
             closed_at,
+            outcomes_history: OutcomesHistory(vec![OutcomesSnapshot {
+                at: Local::now().naive_local(),
+                outcomes: outcomes.0,
+            }]),
         };
 
         self.analytics_tx
@@ -387,7 +923,8 @@ impl PubSub {
         Ok(())
     }
 
-    async fn handle_prediction_event(&mut self, event: Event, streamer: UserId) -> Result<()> {
+    #[instrument(skip_all, fields(event_id = %event.id, channel_id = %streamer))]
+    pub async fn handle_prediction_event(&mut self, event: Event, streamer: UserId) -> Result<()> {
         if event.locked_at.is_some() && event.ended_at.is_none() {
             debug!("Event {} locked, but not yet ended", event.id);
             return Ok(());
@@ -399,15 +936,29 @@ impl PubSub {
                 .predictions
                 .contains_key(event.id.as_str())
         {
+            let cap = self.max_predictions();
             let s = self.streamers.get_mut(&streamer).unwrap();
+            if !s.track_prediction(event.clone(), false, cap) {
+                warn!(
+                    dropped_prediction = event.id,
+                    "{} already has {cap} predictions tracked, dropping the new one",
+                    s.info.channel_name
+                );
+                return Ok(());
+            }
             info!("Prediction {} started", event.id);
             let event_id = event.id.clone();
-            s.predictions
-                .insert(event.id.clone(), (event.clone(), false));
 
             self.upsert_prediction(&streamer, &event).await?;
 
-            self.try_prediction(&streamer, &event_id).await?;
+            if self.started_before_launch(&event) {
+                debug!(
+                    "Prediction {} started before launch, not predicting",
+                    event_id
+                );
+            } else {
+                self.try_prediction(&streamer, &event_id).await?;
+            }
         } else if event.ended_at.is_some() {
             info!("Prediction {} ended", event.id);
             if !self
@@ -454,20 +1005,33 @@ impl PubSub {
                 .await
                 .map_err(|_| eyre!("Failed to send prediction to analytics"))?;
 
-            self.streamers
-                .get_mut(&streamer)
-                .unwrap()
-                .predictions
-                .remove(event.id.as_str());
+            let s = self.streamers.get_mut(&streamer).unwrap();
+            s.predictions.remove(event.id.as_str());
+            s.wagered.remove(event.id.as_str());
+            s.points = points_value;
+            s.last_points_refresh = Instant::now();
+            let event = AppEvent::PredictionEnded {
+                channel_name: s.info.channel_name.clone(),
+                event_id: event.id,
+                winning_outcome_id: event.winning_outcome_id,
+            };
+            self.emit(event);
+            self.track_drawdown();
         } else if self.streamers.contains_key(&streamer)
             && self.streamers[&streamer]
                 .predictions
                 .contains_key(event.id.as_str())
         {
+            // twitch sometimes sends the same update more than once
+            if self.streamers[&streamer].predictions[event.id.as_str()].0 == event {
+                trace!("Prediction {} unchanged, skipping", event.id);
+                return Ok(());
+            }
             let event_id = event.id.clone();
             debug!("Prediction {} updated", event.id);
 
             self.upsert_prediction(&streamer, &event).await?;
+            let before_launch = self.started_before_launch(&event);
             if let Some((e, _)) = self
                 .streamers
                 .get_mut(&streamer)
@@ -477,750 +1041,5220 @@ impl PubSub {
             {
                 *e = event;
             }
-            self.try_prediction(&streamer, &event_id).await?;
+            if !before_launch {
+                self.try_prediction(&streamer, &event_id).await?;
+            }
         }
         Ok(())
     }
 
-    async fn try_prediction(&mut self, streamer: &UserId, event_id: &str) -> Result<()> {
-        let s = self.streamers.get(streamer).unwrap().clone();
+    /// Record a bet as twitch reported it on the user's predictions topic. This is authoritative, unlike the balance
+    /// check of `confirm_bets`, and also covers bets placed outside the miner
+    pub async fn handle_user_prediction(&mut self, reply: PredictionsUserReply) -> Result<()> {
+        let (prediction, ended) = match reply {
+            PredictionsUserReply::PredictionMade { prediction } => (prediction, false),
+            PredictionsUserReply::PredictionResult { prediction } => (prediction, true),
+            PredictionsUserReply::Other => return Ok(()),
+        };
+        let UserPrediction {
+            event_id,
+            outcome_id,
+            channel_id,
+            points,
+            result,
+        } = prediction;
 
-        if s.predictions[event_id].1 {
-            return Ok(());
+        if let Some(s) = self.streamers.get_mut(&UserId::from_str(&channel_id)?) {
+            if let Some((_, placed)) = s.predictions.get_mut(&event_id) {
+                *placed = true;
+            }
+            if !ended {
+                s.wagered.insert(
+                    event_id.clone(),
+                    Wager {
+                        outcome_id: outcome_id.clone(),
+                        points,
+                        confirmed: true,
+                        debited: points,
+                    },
+                );
+            }
         }
-        if s.last_points_refresh.elapsed() > Duration::from_secs(30) {
-            let points = self
-                .gql
-                .get_channel_points(&[&s.info.channel_name])
-                .await
-                .context("Get channel points")?;
-            let s = self.streamers.get_mut(streamer).unwrap();
-            s.points = points[0].0;
-            s.last_points_refresh = Instant::now();
+        match &result {
+            Some(result) => info!(
+                "Bet of {points} on {event_id} ended, {:?}",
+                result.result_type
+            ),
+            None => debug!("Twitch confirmed a bet of {points} on {event_id}"),
         }
 
-        if let Some((outcome_id, points_to_bet)) =
-            prediction_logic(&s, event_id).context("Prediction logic")?
-        {
-            info!(
-                "{}: predicting {}, with points {}",
-                s.info.channel_name, event_id, points_to_bet
-            );
-            self.gql
-                .make_prediction(points_to_bet, event_id, &outcome_id, self.simulate)
-                .await
-                .context("Make prediction")?;
-            let s = self.streamers.get_mut(streamer).unwrap();
-            s.predictions.get_mut(event_id).unwrap().1 = true;
-
-            let channel_id = streamer.as_str().parse::<i32>()?;
-            let points = self
-                .gql
-                .get_channel_points(&[s.info.channel_name.as_str()])
-                .await?;
-
-            let event_id = event_id.to_owned();
-            self.analytics_tx
-                .send_async(Box::new(move |analytics| {
-                    let entry_id = analytics.last_prediction_id(channel_id, &event_id)?;
-                    analytics.insert_points(
-                        channel_id,
-                        points[0].0 as i32,
-                        PointsInfo::Prediction(event_id.to_owned(), entry_id),
-                    )?;
-
-                    analytics.place_bet(&event_id, channel_id, &outcome_id, points_to_bet)
-                }))
-                .await
-                .map_err(|_| eyre!("Failed to send prediction to analytics"))?;
-        }
+        let channel_id = channel_id.parse()?;
+        let bet = PredictionBet {
+            outcome_id,
+            points,
+            simulated: false,
+            result,
+        };
+        self.analytics_tx
+            .send_async(Box::new(move |analytics| {
+                analytics.confirm_bet(&event_id, channel_id, bet.clone())
+            }))
+            .await
+            .map_err(|_| eyre!("Failed to send bet confirmation to analytics"))?;
         Ok(())
     }
-}
 
-pub fn prediction_logic(streamer: &StreamerState, event_id: &str) -> Result<Option<(String, u32)>> {
-    let prediction = streamer.predictions.get(event_id);
-    if prediction.is_none() {
-        return Ok(None);
+    /// Predictions tracked per streamer before new ones are dropped
+    pub fn max_predictions(&self) -> usize {
+        self.config
+            .max_predictions_per_streamer
+            .unwrap_or(common::types::MAX_PREDICTIONS)
     }
 
-    let c = streamer
-        .config
-        .0
-        .read()
-        .map_err(|_| eyre!("Streamer config poison error"))?;
+    /// If betting is limited to predictions started after launch, and this one started before
+    fn started_before_launch(&self, event: &Event) -> bool {
+        self.config.skip_predictions_at_startup.unwrap_or(false)
+            && chrono::DateTime::parse_from_rfc3339(event.created_at.as_str())
+                .is_ok_and(|created_at| created_at < self.launched_at)
+    }
 
-    let prediction = prediction.unwrap();
-    for filter in &c.config.prediction.filters {
-        if !filter_matches(&prediction.0, filter, streamer).context("Checking filter")? {
-            debug!("Filter matches {:#?}", filter);
-            return Ok(None);
+    /// Unlisten the topics once the stream has been offline for `pubsub_unlisten_grace`,
+    /// a stream up within the grace period cancels it
+    async fn unlisten_after_grace<const N: usize>(
+        &mut self,
+        channel_id: u32,
+        topics: [Topics; N],
+    ) -> Result<()> {
+        let grace = Duration::from_secs(
+            self.config
+                .pubsub_unlisten_grace
+                .unwrap_or(common::twitch::ws::UNLISTEN_GRACE_SECS),
+        );
+        if grace.is_zero() {
+            for item in topics.into_iter().map(Request::UnListen) {
+                self.ws_tx
+                    .send_async(item)
+                    .await
+                    .context("Send ws command")?;
+            }
+            return Ok(());
         }
-    }
 
-    match &c.config.prediction.strategy {
-        strategy::Strategy::Detailed(s) => {
-            if prediction.0.outcomes.len() < 2 {
-                return Ok(None);
+        let ws_tx = self.ws_tx.clone();
+        let handle = spawn(async move {
+            sleep(grace).await;
+            debug!("Unlistening {channel_id}, offline for {grace:?}");
+            for item in topics.into_iter().map(Request::UnListen) {
+                if let Err(err) = ws_tx.send_async(item).await {
+                    error!("Send ws command: {err}");
+                }
             }
+        });
+        if let Some(previous) = self
+            .pending_unlisten
+            .insert(channel_id, Arc::new(handle.abort_handle()))
+        {
+            previous.abort();
+        }
+        Ok(())
+    }
 
-            let total_points = prediction
-                .0
-                .outcomes
-                .iter()
-                .fold(0, |a, b| a + b.total_points);
+    /// Record the outcome of a GQL request for a streamer, pausing its GQL interactions
+    /// after `circuit_breaker_failures` consecutive failures
+    pub fn record_gql_result(&mut self, streamer: &UserId, ok: bool) {
+        let threshold = self
+            .config
+            .circuit_breaker_failures
+            .unwrap_or(CIRCUIT_FAILURES);
+        let cooldown = self
+            .config
+            .circuit_breaker_cooldown
+            .unwrap_or(CIRCUIT_COOLDOWN_SECS);
+        let s = match self.streamers.get_mut(streamer) {
+            Some(s) => s,
+            None => return,
+        };
 
-            let mut odds_percentage = Vec::new();
-            odds_percentage.reserve_exact(prediction.0.outcomes.len());
-            for o in &prediction.0.outcomes {
-                let odds = if o.total_points == 0 {
-                    0.0
-                } else {
-                    total_points as f64 / o.total_points as f64
-                };
-                odds_percentage.push(if odds == 0.0 { 0.0 } else { 1.0 / odds });
+        if ok {
+            if s.circuit.failures >= threshold {
+                info!("GQL requests for {} resumed", s.info.channel_name);
             }
+            s.circuit.success();
+        } else if s
+            .circuit
+            .failure(threshold, chrono::Duration::seconds(cooldown as i64))
+        {
+            warn!(
+                "GQL requests for {} failed {} times in a row, pausing them for {cooldown}s",
+                s.info.channel_name, s.circuit.failures
+            );
+        }
+    }
 
-            let mut rng = rand::thread_rng();
-            for (idx, p) in odds_percentage.into_iter().enumerate() {
-                debug!("Odds for {}: {}", prediction.0.outcomes[idx].id, p);
-
-                let empty_vec = Vec::new();
-                let points = s.detailed.as_ref().unwrap_or(&empty_vec).iter().find(|x| {
-                    debug!("Checking config {x:#?}");
-                    let does_match = match x._type {
-                        strategy::OddsComparisonType::Le => p <= x.threshold,
-                        strategy::OddsComparisonType::Ge => p >= x.threshold,
-                    };
-                    if does_match && rng.gen_bool(x.attempt_rate) {
-                        return true;
-                    }
-                    false
-                });
-
-                match points {
-                    Some(s) => {
-                        debug!("Using high odds config {s:#?}");
-                        return Ok(Some((
-                            prediction.0.outcomes[idx].id.clone(),
-                            s.points.value(streamer.points),
-                        )));
-                    }
-                    None => {
-                        if p >= s.default.min_percentage && p <= s.default.max_percentage {
-                            debug!("Using default odds config {:#?} {}", s.default, p);
-                            return Ok(Some((
-                                prediction.0.outcomes[idx].id.clone(),
-                                s.default.points.value(streamer.points),
-                            )));
-                        }
-                    }
-                }
+    /// Track the balance against the points goal of the streamer, publishing an event only when
+    /// the balance crosses it upwards
+    pub fn track_points_goal(&mut self, streamer: &UserId, points: u32) -> Result<()> {
+        let s = match self.streamers.get_mut(streamer) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+        let goal = match s
+            .config
+            .0
+            .read()
+            .map_err(|_| eyre!("Streamer config poison error"))?
+            .config
+            .points_goal
+            .or(self.config.points_goal)
+        {
+            Some(goal) => goal,
+            None => {
+                s.points_goal_reached = false;
+                return Ok(());
             }
+        };
+
+        let crossed = s.points < goal && points >= goal;
+        s.points_goal_reached = points >= goal;
+        if crossed {
+            let channel_name = s.info.channel_name.clone();
+            info!("{channel_name}: points goal of {goal} reached with {points} points");
+            self.emit(AppEvent::PointsGoalReached {
+                channel_name,
+                points,
+                goal,
+            });
         }
+        Ok(())
     }
-    Ok(None)
-}
 
-mod watch_stream {
-    use super::*;
+    /// Warn about a single bet above `alert_on_bet_above`, and publish it to API subscribers
+    fn alert_large_bet(&self, channel_name: &str, event_id: &str, outcome_id: &str, points: u32) {
+        let threshold = match self.config.alert_on_bet_above {
+            Some(threshold) if points > threshold => threshold,
+            _ => return,
+        };
+        warn!(
+            "{channel_name}: large bet of {points} points on {event_id}, above the alert threshold of {threshold}"
+        );
+        self.emit(AppEvent::LargeBet {
+            channel_name: channel_name.to_owned(),
+            event_id: event_id.to_owned(),
+            outcome_id: outcome_id.to_owned(),
+            points,
+            threshold,
+        });
+    }
 
-    pub async fn inner(
-        pubsub: &Arc<RwLock<PubSub>>,
-        watch_streak: &mut Vec<(UserId, i32)>,
-        use_watch_streak: bool,
-        live_event: &Receiver<UserId>,
-    ) -> Result<()> {
-        if use_watch_streak {
-            let live = live_event
-                .drain()
-                .map(|x| (x, 0))
-                .filter(|x| !watch_streak.iter().any(|y| y.0 == x.0))
-                .collect::<Vec<_>>();
-
-            watch_streak.extend(live);
+    /// If bets on the streamer are only simulated, by its own setting, the --simulate argument, or the bet allowlist
+    pub fn is_simulated(&self, streamer: &StreamerState) -> Result<bool> {
+        let simulate = streamer
+            .config
+            .0
+            .read()
+            .map_err(|_| eyre!("Streamer config poison error"))?
+            .config
+            .simulate
+            .unwrap_or(self.simulate);
+        let allowed = match &self.config.bet_allowlist {
+            Some(x) => x
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&streamer.info.channel_name)),
+            None => true,
+        };
+        if !simulate && !allowed {
+            debug!(
+                "{}: not on the bet allowlist, simulating",
+                streamer.info.channel_name
+            );
         }
+        Ok(simulate || !allowed)
+    }
 
-        let (streamers, user_id, user_name, spade_url, config) = {
-            let reader = pubsub.read().await;
-            let streamers = reader
-                .streamers
-                .iter()
-                .filter(|x| x.1.info.live)
-                .map(|x| (x.0.clone(), x.1.clone()))
-                .collect::<Vec<_>>();
+    #[instrument(skip_all, fields(event_id = %event_id, streamer = tracing::field::Empty))]
+    async fn try_prediction(&mut self, streamer: &UserId, event_id: &str) -> Result<()> {
+        let s = self.streamers.get(streamer).unwrap().clone();
+        tracing::Span::current().record("streamer", s.info.channel_name.as_str());
 
+        let (betting, chase_odds) = {
+            let c = s
+                .config
+                .0
+                .read()
+                .map_err(|_| eyre!("Streamer config poison error"))?;
             (
-                streamers,
-                reader.user_id.parse()?,
-                reader.user_name.clone(),
-                reader.spade_url.clone().ok_or(eyre!("Spade URL not set"))?,
-                reader.config.clone(),
+                c.config.betting.unwrap_or(true),
+                c.config.prediction.strategy.chase_odds(),
             )
         };
-
-        if streamers.is_empty() {
-            trace!("No streamer found");
+        if self.watch_only || !betting {
+            self.record_decision(
+                streamer,
+                DecisionTrace::new(event_id),
+                Decision::skip("betting_disabled"),
+            );
             return Ok(());
         }
 
-        let watch_priority = config.watch_priority.unwrap_or_default();
-        let mut watch_items = Vec::new();
-        for item in &watch_priority {
-            if let Some(s) = streamers.iter().find(|x| x.1.info.channel_name.eq(item)) {
-                watch_items.push(s);
+        if s.predictions[event_id].1 {
+            // without a known wager we cannot tell which outcome to add to
+            if !chase_odds || !s.wagered.contains_key(event_id) {
+                return Ok(());
             }
         }
-
-        // streamers not given in a priority order
-        for item in config
-            .streamers
-            .iter()
-            .filter(|x| streamers.iter().any(|y| y.1.info.channel_name.eq(x.0)))
+        if self
+            .config
+            .prediction_blacklist
+            .as_ref()
+            .is_some_and(|b| b.matches(event_id, &s.predictions[event_id].0.title))
         {
-            if !watch_priority.contains(&item.0) {
-                watch_items.push(
-                    streamers
-                        .iter()
-                        .find(|x| x.1.info.channel_name.eq(item.0))
-                        .unwrap(),
-                );
+            if s.predictions[event_id].1 {
+                return Ok(());
             }
+            self.record_decision(
+                streamer,
+                DecisionTrace::new(event_id),
+                Decision::skip("blacklisted"),
+            );
+            self.streamers
+                .get_mut(streamer)
+                .unwrap()
+                .predictions
+                .get_mut(event_id)
+                .unwrap()
+                .1 = true;
+            let channel_id = streamer.as_str().parse::<i32>()?;
+            let event_id = event_id.to_owned();
+            self.analytics_tx
+                .send_async(Box::new(move |analytics| {
+                    analytics.mark_blacklisted(&event_id, channel_id)
+                }))
+                .await
+                .map_err(|_| eyre!("Failed to send blacklisted prediction to analytics"))?;
+            return Ok(());
         }
-
-        // Just to allow the reference to live
-        #[allow(unused_assignments)]
-        let mut streak_entry = None;
-        let mut entry = watch_streak.iter_mut().take(1);
-        if let Some(entry) = entry.next() {
-            entry.1 += 1;
-            let s = pubsub.read().await.streamers.get(&entry.0).unwrap().clone();
-            streak_entry = Some((entry.0.clone(), s));
-            watch_items.insert(0, streak_entry.as_ref().unwrap());
-        }
-
-        watch_items = remove_duplicates_in_place(watch_items, |a, b| a.0.eq(&b.0));
-        {
-            pubsub.write().await.watching = watch_items.iter().map(|x| x.1.clone()).collect();
+        if self.panic_stopped {
+            self.record_decision(
+                streamer,
+                DecisionTrace::new(event_id),
+                Decision::skip("panic_stop"),
+            );
+            return Ok(());
         }
-        for (id, streamer) in watch_items.into_iter().take(2) {
-            debug!("Watching {}", streamer.info.channel_name);
-            api::set_viewership(
-                user_name.clone(),
-                user_id,
-                id.clone(),
-                streamer.info.clone(),
-                &spade_url,
-            )
-            .await
-            .context(format!(
-                "Could not set viewership {}",
-                streamer.info.channel_name
-            ))?;
+        if s.circuit.is_open() {
+            self.record_decision(
+                streamer,
+                DecisionTrace::new(event_id),
+                Decision::skip("circuit_open"),
+            );
+            return Ok(());
         }
-
-        *watch_streak = watch_streak.drain(..).filter(|x| x.1 < 31).collect();
-        Ok(())
-    }
-
-    pub async fn run(pubsub: Arc<RwLock<PubSub>>, live_event: Receiver<UserId>) {
-        let use_watch_streak = {
-            let reader = pubsub.read().await;
-            reader.config.watch_streak.unwrap_or(true)
-        };
-
-        let mut watch_streak = Vec::new();
-
-        loop {
-            if let Err(err) = inner(&pubsub, &mut watch_streak, use_watch_streak, &live_event).await
-            {
-                if err.to_string() != "Spade URL not set" {
-                    error!("watch_streams {err}");
+        let simulate = self.is_simulated(&s)?;
+        if !simulate {
+            if let Some(until) = self.bet_cooldown_until {
+                if until > Instant::now() {
+                    self.record_decision(
+                        streamer,
+                        DecisionTrace::new(event_id),
+                        Decision::skip("prediction_cooldown"),
+                    );
+                    return Ok(());
                 }
+                info!("Prediction cooldown over, resuming bets");
+                self.bet_cooldown_until = None;
             }
-
-            #[cfg(test)]
-            let time = 1;
-            #[cfg(not(test))]
-            let time = 10 * 1000;
-            sleep(Duration::from_millis(time)).await;
         }
-    }
-}
-
-mod update_and_claim_points {
-    use super::*;
+        let confirm_bets = self.config.confirm_bets.unwrap_or(false) && !simulate;
+        if confirm_bets || s.last_points_refresh.elapsed() > Duration::from_secs(30) {
+            let points = self.gql.get_channel_points(&[&s.info.channel_name]).await;
+            self.record_gql_result(streamer, points.is_ok());
+            let points = points.context("Get channel points")?;
+            let s = self.streamers.get_mut(streamer).unwrap();
+            s.points = points[0].0;
+            s.last_points_refresh = Instant::now();
+        }
+        let s = self.streamers.get(streamer).unwrap().clone();
+        let balance_before = s.points;
+        if self.update_below_min_balance() {
+            self.record_decision(
+                streamer,
+                DecisionTrace::new(event_id),
+                Decision::skip("below_min_balance"),
+            );
+            return Ok(());
+        }
 
-    async fn inner(pubsub: &Arc<RwLock<PubSub>>, gql: &gql::Client) -> Result<()> {
-        let streamer = {
-            let reader = pubsub.read().await;
-            reader
-                .streamers
-                .iter()
-                .filter(|x| x.1.info.live)
-                .map(|x| (x.0.clone(), x.1.clone()))
-                .collect::<Vec<_>>()
+        let (decision, trace) = prediction_decision(&s, event_id, self.config.timezone())
+            .context("Prediction logic")?;
+        let (outcome_id, points_to_bet, fallback) = match decision {
+            Decision::Bet {
+                outcome_id,
+                points,
+                fallback,
+            } => (outcome_id, points, fallback),
+            skip => {
+                self.record_decision(streamer, trace, skip);
+                return Ok(());
+            }
         };
-
-        let channel_names = streamer
-            .iter()
-            .map(|x| x.1.info.channel_name.as_str())
-            .collect::<Vec<_>>();
-
-        if channel_names.is_empty() {
-            sleep(Duration::from_secs(60)).await;
+        if locks_too_soon(&s.predictions[event_id].0, Local::now()) {
+            self.record_decision(streamer, trace, Decision::skip("locks_too_soon"));
             return Ok(());
         }
-
-        let points = gql
-            .get_channel_points(&channel_names)
+        // the max_bet and bet_rounding of the strategy that picked the bet still apply after jitter and the cap
+        let strategy = {
+            let c = s
+                .config
+                .0
+                .read()
+                .map_err(|_| eyre!("Streamer config poison error"))?;
+            if fallback {
+                c.config.prediction.fallback.as_deref().cloned()
+            } else {
+                Some(c.config.prediction.strategy.clone())
+            }
+        };
+        let round_bet = |value: u32, limit: u32| {
+            strategy
+                .as_ref()
+                .map_or(value, |x| x.round_bet(value, limit))
+        };
+        let points_to_bet = match self.config.points_jitter_percent {
+            Some(percent) if percent > 0.0 => {
+                let jittered = jitter_points(
+                    points_to_bet,
+                    percent,
+                    s.points,
+                    strategy.as_ref().and_then(|x| x.max_bet()),
+                    &mut rand::thread_rng(),
+                );
+                // never rounded above what the jitter allowed
+                let jittered = round_bet(jittered, jittered.max(points_to_bet));
+                trace!(
+                    "{}: jittered bet on {} from {} to {}",
+                    s.info.channel_name,
+                    event_id,
+                    points_to_bet,
+                    jittered
+                );
+                jittered
+            }
+            _ => points_to_bet,
+        };
+        let points_to_bet = match self.daily_budget() {
+            Some(0) => {
+                self.record_decision(streamer, trace, Decision::skip("daily_bet_cap"));
+                return Ok(());
+            }
+            Some(budget) if budget < points_to_bet => {
+                let budget = round_bet(budget, budget);
+                debug!(
+                    "{}: lowering bet on {} from {} to the {} left for today",
+                    s.info.channel_name, event_id, points_to_bet, budget
+                );
+                budget
+            }
+            _ => points_to_bet,
+        };
+        if points_to_bet == 0 {
+            self.record_decision(streamer, trace, Decision::skip("rounded_to_zero"));
+            return Ok(());
+        }
+        self.alert_large_bet(&s.info.channel_name, event_id, &outcome_id, points_to_bet);
+        if let Err(err) = self
+            .gql
+            .make_prediction(points_to_bet, event_id, &outcome_id, simulate)
             .await
-            .context("Get channel points")?;
-
-        let mut changes = Vec::new();
-        for ((points, claim), (channel_id, state)) in points.into_iter().zip(streamer) {
-            match claim {
-                Some(claim_id) => {
-                    info!(
-                        "Claiming community points bonus {}",
-                        state.info.channel_name
+        {
+            if err.downcast_ref::<gql::SubscriberOnly>().is_some() {
+                // retrying would only be rejected again
+                self.streamers
+                    .get_mut(streamer)
+                    .unwrap()
+                    .predictions
+                    .get_mut(event_id)
+                    .unwrap()
+                    .1 = true;
+                self.record_decision(streamer, trace, Decision::skip("subscriber_only"));
+                return Ok(());
+            }
+            return match err.downcast_ref::<gql::PredictionCooldown>() {
+                Some(cooldown) => {
+                    let seconds = cooldown.seconds.unwrap_or(PREDICTION_COOLDOWN_SECS);
+                    warn!(
+                        "{}: twitch reported a prediction cooldown on {}, pausing bets for {}s",
+                        s.info.channel_name, event_id, seconds
                     );
-                    let claimed_points = gql.claim_points(channel_id.as_str(), &claim_id).await?;
-                    changes.push((
-                        PointsInfo::CommunityPointsClaimed,
-                        claimed_points,
-                        channel_id,
-                    ));
+                    self.bet_cooldown_until = Some(Instant::now() + Duration::from_secs(seconds));
+                    self.record_decision(streamer, trace, Decision::skip("prediction_cooldown"));
+                    Ok(())
                 }
-                None => changes.push((PointsInfo::Watching, points, channel_id)),
-            }
+                None => {
+                    self.record_gql_result(streamer, false);
+                    self.record_decision(streamer, trace, Decision::skip("bet_failed"));
+                    Err(err.wrap_err("Make prediction"))
+                }
+            };
         }
 
-        {
-            let now = Instant::now();
-            let mut writer = pubsub.write().await;
-            for (_type, points, channel_id) in changes {
-                let edited = writer
-                    .analytics
-                    .execute(|analytics| {
-                        analytics.insert_points_if_updated(
-                            channel_id.as_str().parse().unwrap(),
-                            points as i32,
-                            _type.clone(),
-                        )
-                    })
-                    .await?;
-                if edited {
-                    let s = writer.streamers.get_mut(&channel_id).unwrap();
-                    s.points = points;
-                    s.last_points_refresh = now
-                }
+        let channel_id = streamer.as_str().parse::<i32>()?;
+        let points = self.gql.get_channel_points(&[&s.info.channel_name]).await;
+        self.record_gql_result(streamer, points.is_ok());
+        let points = match points {
+            Ok(points) => points,
+            Err(err) => {
+                self.record_decision(streamer, trace, Decision::skip("bet_unconfirmed"));
+                return Err(err);
             }
+        };
+        let s = self.streamers.get_mut(streamer).unwrap();
+        s.points = points[0].0;
+        s.last_points_refresh = Instant::now();
+        let debited = !simulate && bet_debited(balance_before, points[0].0, points_to_bet);
+        if confirm_bets && !debited {
+            warn!(
+                "{}: balance went from {} to {} after betting {} on {}, will retry",
+                s.info.channel_name, balance_before, points[0].0, points_to_bet, event_id
+            );
+            self.record_decision(streamer, trace, Decision::skip("bet_not_debited"));
+            return Ok(());
         }
+
+        // only a bet that went through is recorded as one
+        self.record_decision(
+            streamer,
+            trace,
+            Decision::Bet {
+                outcome_id: outcome_id.clone(),
+                points: points_to_bet,
+                fallback,
+            },
+        );
+        let s = self.streamers.get_mut(streamer).unwrap();
+        s.predictions.get_mut(event_id).unwrap().1 = true;
+        match s.record_wager(event_id, &outcome_id, points_to_bet, debited) {
+            Some(total) => debug!(
+                "{}: wagered {} in total on {}",
+                s.info.channel_name, total, event_id
+            ),
+            None => warn!(
+                "{}: bet on {} of {} is not on the outcome already bet on",
+                s.info.channel_name, outcome_id, event_id
+            ),
+        }
+        let channel_name = s.info.channel_name.clone();
+        // simulated bets never leave the balance, so they are not taken from the daily budget
+        if !simulate {
+            self.add_wagered_today(points_to_bet);
+        }
+        self.emit(AppEvent::BetPlaced {
+            channel_name: channel_name.clone(),
+            event_id: event_id.to_owned(),
+            outcome_id: outcome_id.clone(),
+            points: points_to_bet,
+        });
+
+        let event_id = event_id.to_owned();
+        self.analytics_tx
+            .send_async(Box::new(move |analytics| {
+                let entry_id = analytics.last_prediction_id(channel_id, &event_id)?;
+                analytics.insert_points(
+                    channel_id,
+                    points[0].0 as i32,
+                    PointsInfo::Prediction(event_id.to_owned(), entry_id),
+                )?;
+
+                analytics.place_bet(&event_id, channel_id, &outcome_id, points_to_bet, simulate)
+            }))
+            .await
+            .map_err(|_| eyre!("Failed to send prediction to analytics"))?;
         Ok(())
     }
+}
 
-    pub async fn run(pubsub: Arc<RwLock<PubSub>>, gql: gql::Client) {
-        loop {
-            if let Err(err) = inner(&pubsub, &gql).await {
-                error!("update_and_claim_points {err}");
-            }
+/// Move `points` by a random amount of up to `percent` of it in either direction. The bet is at least one point, and
+/// is only raised as far as the balance and the strategy's `max_bet` allow
+fn jitter_points(
+    points: u32,
+    percent: f64,
+    balance: u32,
+    max_bet: Option<u32>,
+    rng: &mut impl Rng,
+) -> u32 {
+    let spread = points as f64 * percent / 100.0;
+    let jittered = (points as f64 + rng.gen_range(-spread..=spread)).round() as u32;
+    let max = max_bet.unwrap_or(balance).max(points).min(balance);
+    jittered.min(max).max(1.min(points))
+}
 
-            sleep(Duration::from_secs(60)).await
-        }
-    }
+/// If the balance dropped by at least half of the bet, allowing for points earned in between
+pub fn bet_debited(before: u32, after: u32, bet: u32) -> bool {
+    before.saturating_sub(after) >= bet / 2
 }
 
-mod update_spade_url {
-    use super::*;
+/// If the prediction window closes within `LOCK_MARGIN_SECS`, so a bet would be rejected as locked
+fn locks_too_soon(event: &Event, now: chrono::DateTime<Local>) -> bool {
+    chrono::DateTime::parse_from_rfc3339(event.created_at.as_str()).is_ok_and(|created_at| {
+        now + chrono::Duration::seconds(LOCK_MARGIN_SECS)
+            >= created_at + chrono::Duration::seconds(event.prediction_window_seconds)
+    })
+}
 
-    async fn inner(pubsub: &Arc<RwLock<PubSub>>, base_url: &str) -> Result<()> {
-        let a_live_stream = {
-            let reader = pubsub.read().await;
-            reader
-                .streamers
-                .iter()
-                .find(|x| x.1.info.live)
-                .map(|x| (x.0.clone(), x.1.clone()))
-        };
+/// Outcome of considering a prediction, logged once per attempt as a structured `decision` event
+#[derive(Debug, Clone)]
+enum Decision {
+    Bet {
+        outcome_id: String,
+        points: u32,
+        /// Suggested by the fallback strategy after the strategy declined
+        fallback: bool,
+    },
+    Skip {
+        reason: &'static str,
+        /// The filter the prediction did not pass
+        filter: Option<filters::Filter>,
+    },
+}
 
-        if let Some((_, streamer)) = a_live_stream {
-            let spade_url = api::get_spade_url(&streamer.info.channel_name, base_url).await?;
-            pubsub.write().await.spade_url = Some(spade_url);
-            debug!("Updated spade url");
+impl Decision {
+    fn skip(reason: &'static str) -> Self {
+        Decision::Skip {
+            reason,
+            filter: None,
         }
-        Ok(())
     }
 
-    pub async fn run(pubsub: Arc<RwLock<PubSub>>) {
-        let base_url = { pubsub.read().await.base_url.clone() };
-        loop {
-            if let Err(err) = inner(&pubsub, &base_url).await {
-                error!("update_and_claim_points {err}");
-            }
-
-            sleep(Duration::from_secs(120)).await
+    fn log(&self, channel_name: &str, event_id: &str) {
+        match self {
+            Decision::Bet {
+                outcome_id,
+                points,
+                fallback,
+            } => info!(
+                decision = "bet",
+                streamer = channel_name,
+                event_id,
+                outcome_id = outcome_id.as_str(),
+                points,
+                strategy = if *fallback { "fallback" } else { "primary" },
+                "{channel_name}: predicting {event_id}, with points {points}"
+            ),
+            // skips repeat on every update of an open prediction
+            Decision::Skip { reason, filter } => debug!(
+                decision = "skip",
+                streamer = channel_name,
+                event_id,
+                reason,
+                filter = filter.as_ref().map(tracing::field::debug),
+                "{channel_name}: not predicting {event_id}, {reason}"
+            ),
         }
     }
 }
 
-#[cfg(test)]
-mod test {
-    use std::{
-        collections::HashMap,
-        str::FromStr,
-        sync::Arc,
-        time::{Duration, Instant},
+pub fn prediction_logic(
+    streamer: &StreamerState,
+    event_id: &str,
+    timezone: Option<Tz>,
+) -> Result<Option<(String, u32)>> {
+    Ok(match prediction_decision(streamer, event_id, timezone)?.0 {
+        Decision::Bet {
+            outcome_id, points, ..
+        } => Some((outcome_id, points)),
+        Decision::Skip { .. } => None,
+    })
+}
+
+#[instrument(skip_all, fields(event_id = %event_id, streamer = %streamer.info.channel_name))]
+fn prediction_decision(
+    streamer: &StreamerState,
+    event_id: &str,
+    timezone: Option<Tz>,
+) -> Result<(Decision, DecisionTrace)> {
+    let mut trace = DecisionTrace::new(event_id);
+    let prediction = match streamer.predictions.get(event_id) {
+        Some(x) => x,
+        None => return Ok((Decision::skip("unknown_prediction"), trace)),
     };
 
-    use chrono::Local;
-    use eyre::Result;
-    use flume::unbounded;
-    use rstest::rstest;
-    use tokio::sync::RwLock;
-    use twitch_api::{
-        pubsub::predictions::{Event, Outcome},
-        types::{Timestamp, UserId},
+    let c = streamer
+        .config
+        .0
+        .read()
+        .map_err(|_| eyre!("Streamer config poison error"))?;
+
+    trace.odds = outcome_odds(
+        &prediction.0,
+        c.config.prediction.strategy.probability_source(),
+    )
+    .into_iter()
+    .zip(&prediction.0.outcomes)
+    .map(|(odds, o)| OutcomeOdds {
+        outcome_id: o.id.clone(),
+        title: o.title.clone(),
+        odds,
+    })
+    .collect();
+    for filter in &c.config.prediction.filters {
+        let passed =
+            filter_matches(&prediction.0, filter, streamer, timezone).context("Checking filter")?;
+        trace.filters.push(FilterCheck {
+            filter: filter.clone(),
+            passed,
+        });
+        if !passed {
+            let decision = Decision::Skip {
+                reason: "filter",
+                filter: Some(filter.clone()),
+            };
+            return Ok((decision, trace));
+        }
+    }
+
+    if let (Some(min_live), Some(live_since)) = (c.config.min_live_seconds, streamer.live_since) {
+        if live_since.elapsed() < Duration::from_secs(min_live) {
+            return Ok((Decision::skip("min_live_seconds"), trace));
+        }
+    }
+
+    let filters = &c.config.prediction.filters;
+    let now = Local::now();
+    let bet = |strategy: &strategy::Strategy, fallback| {
+        strategy_logic(strategy, streamer, &prediction.0, event_id, filters, now).map(
+            |(outcome_id, points)| Decision::Bet {
+                outcome_id,
+                points,
+                fallback,
+            },
+        )
     };
+    if let Some(decision) = bet(&c.config.prediction.strategy, false) {
+        return Ok((decision, trace));
+    }
+    if let Some(fallback) = &c.config.prediction.fallback {
+        debug!("Strategy declined {}, trying the fallback", event_id);
+        if let Some(decision) = bet(fallback, true) {
+            return Ok((decision, trace));
+        }
+    }
+    Ok((Decision::skip("strategy_declined"), trace))
+}
 
-    use common::{
-        config::{strategy::*, ConfigType, PredictionConfig, StreamerConfig},
-        testing::{container, TestContainer},
-        types::*,
+fn strategy_logic(
+    strategy: &strategy::Strategy,
+    streamer: &StreamerState,
+    prediction: &Event,
+    event_id: &str,
+    filters: &[filters::Filter],
+    now: chrono::DateTime<Local>,
+) -> Option<(String, u32)> {
+    match strategy {
+        strategy::Strategy::Detailed(s) => {
+            detailed_logic(s, streamer, prediction, event_id, filters)
+        }
+        strategy::Strategy::Composite(s) => {
+            let bets = s
+                .strategies
+                .iter()
+                .map(|x| {
+                    (
+                        x.weight,
+                        detailed_logic(&x.strategy, streamer, prediction, event_id, filters),
+                    )
+                })
+                .collect::<Vec<_>>();
+            combine_bets(s.combine, &bets)
+        }
+        strategy::Strategy::TimeDecay(s) => {
+            let elapsed = window_elapsed(prediction, now)?;
+            let share = s.stake_share(elapsed);
+            // size the full stake against the balance before the earlier bets, and add what is missing of its share
+            let wager = streamer.wagered.get(event_id);
+            let (outcome_id, full) = match wager {
+                Some(w) => {
+                    let mut before = streamer.clone();
+                    before.wagered.remove(event_id);
+                    before.points += w.points;
+                    detailed_logic(&s.strategy, &before, prediction, event_id, filters)?
+                }
+                None => detailed_logic(&s.strategy, streamer, prediction, event_id, filters)?,
+            };
+            let wagered = match wager {
+                Some(w) if w.outcome_id != outcome_id => return None,
+                Some(w) => w.points,
+                None => 0,
+            };
+            let balance = streamer.points + wagered;
+            let points = s
+                .strategy
+                .round_bet((full as f64 * share) as u32, balance)
+                .saturating_sub(wagered);
+            debug!(
+                "{:.0}% of the window of {} passed, betting {} on {}",
+                elapsed * 100.0,
+                event_id,
+                points,
+                outcome_id
+            );
+            (points > 0).then_some((outcome_id, points))
+        }
+    }
+}
+
+/// Share of the prediction window (0 - 1) that has passed at `now`
+fn window_elapsed(prediction: &Event, now: chrono::DateTime<Local>) -> Option<f64> {
+    let created_at = chrono::DateTime::parse_from_rfc3339(prediction.created_at.as_str()).ok()?;
+    if prediction.prediction_window_seconds <= 0 {
+        return Some(1.0);
+    }
+    let elapsed = (now - created_at.with_timezone(&Local)).num_milliseconds() as f64 / 1000.0;
+    Some((elapsed / prediction.prediction_window_seconds as f64).clamp(0.0, 1.0))
+}
+
+/// Roll `rate` once per event and odds config entry, so later updates of the same prediction
+/// get the same outcome instead of another chance to pass
+fn attempt_roll(event_id: &str, entry: usize, rate: f64) -> bool {
+    let mut hasher = DefaultHasher::new();
+    (event_id, entry).hash(&mut hasher);
+    StdRng::seed_from_u64(hasher.finish()).gen_bool(rate)
+}
+
+/// Odds (0 - 1) of every outcome according to `source`
+fn outcome_odds(prediction: &Event, source: strategy::ProbabilitySource) -> Vec<f64> {
+    let total_points = prediction
+        .outcomes
+        .iter()
+        .fold(0, |a, b| a + b.total_points);
+    let total_users = prediction.outcomes.iter().fold(0, |a, b| a + b.total_users);
+    let share = |value: i64, total: i64| {
+        if value == 0 {
+            0.0
+        } else {
+            value as f64 / total as f64
+        }
     };
+    prediction
+        .outcomes
+        .iter()
+        .map(|o| {
+            source.probability(
+                share(o.total_points, total_points),
+                share(o.total_users, total_users),
+            )
+        })
+        .collect()
+}
 
-    use crate::pubsub::prediction_logic;
+fn detailed_logic(
+    s: &strategy::Detailed,
+    streamer: &StreamerState,
+    prediction: &Event,
+    event_id: &str,
+    filters: &[filters::Filter],
+) -> Option<(String, u32)> {
+    if prediction.outcomes.len() < 2 {
+        return None;
+    }
 
-    use super::PubSub;
+    let total_points = prediction
+        .outcomes
+        .iter()
+        .fold(0, |a, b| a + b.total_points);
+    if s.skip_empty_outcomes && total_points == 0 {
+        debug!("No points wagered on {}, skipping", event_id);
+        return None;
+    }
+    let odds_percentage = outcome_odds(prediction, s.probability_source);
 
-    fn outcome_from(id: u32, points: i64, users: i64) -> Outcome {
-        Outcome {
-            id: id.to_string(),
-            color: "".to_owned(),
-            title: id.to_string(),
-            total_points: points,
-            total_users: users,
-            top_predictors: Vec::new(),
+    // an additional bet has to be on the outcome already bet on
+    let wager = streamer.wagered.get(event_id);
+
+    let mut by_pool = (0..prediction.outcomes.len()).collect::<Vec<_>>();
+    by_pool.sort_by_key(|x| std::cmp::Reverse(prediction.outcomes[*x].total_points));
+    let considered = &by_pool[..s.max_outcomes.unwrap_or(by_pool.len()).min(by_pool.len())];
+
+    let mut in_range = Vec::new();
+    // a bet rounded down to nothing is not placed
+    let bet = |idx: usize, value: u32| {
+        let points = s.round_bet(value, streamer.points);
+        if points == 0 {
+            debug!("Bet of {} on {} rounds to 0, skipping", value, event_id);
+        }
+        (points > 0).then(|| (prediction.outcomes[idx].id.clone(), points))
+    };
+    for (idx, p) in odds_percentage.into_iter().enumerate() {
+        debug!("Odds for {}: {}", prediction.outcomes[idx].id, p);
+        if wager.is_some_and(|w| w.outcome_id != prediction.outcomes[idx].id) {
+            continue;
+        }
+        if s.skip_empty_outcomes && prediction.outcomes[idx].total_points == 0 {
+            debug!(
+                "Outcome {} has an empty pool",
+                prediction.outcomes[idx].title
+            );
+            continue;
+        }
+        if !considered.contains(&idx) {
+            debug!(
+                "Outcome {} is not among the {} largest pools",
+                prediction.outcomes[idx].title,
+                considered.len()
+            );
+            continue;
+        }
+        if s.min_outcome_users
+            .is_some_and(|min| (prediction.outcomes[idx].total_users as u64) < min)
+        {
+            debug!(
+                "Outcome {} has only {} users",
+                prediction.outcomes[idx].title, prediction.outcomes[idx].total_users
+            );
+            continue;
+        }
+        if !outcome_allowed(&prediction.outcomes[idx], filters) {
+            debug!("Outcome {} is not allowed", prediction.outcomes[idx].title);
+            continue;
+        }
+
+        let empty_vec = Vec::new();
+        let points = s
+            .detailed
+            .as_ref()
+            .unwrap_or(&empty_vec)
+            .iter()
+            .enumerate()
+            .find(|(entry, x)| {
+                debug!("Checking config {x:#?}");
+                let does_match = match x._type {
+                    strategy::OddsComparisonType::Le => p <= x.threshold,
+                    strategy::OddsComparisonType::Ge => p >= x.threshold,
+                };
+                does_match && attempt_roll(event_id, *entry, x.attempt_rate)
+            })
+            .map(|(_, x)| x);
+
+        match (points, wager) {
+            (Some(x), Some(w)) if s.chase_odds => {
+                // size against the balance before the earlier bets, minus what is already at stake
+                let balance = streamer.points + w.points;
+                let target = s.round_bet(x.points.value(balance), balance);
+                if target <= w.points {
+                    debug!("Already wagered {} of {} on {}", w.points, target, event_id);
+                    return None;
+                }
+                debug!("Chasing odds with high odds config {x:#?}");
+                return Some((prediction.outcomes[idx].id.clone(), target - w.points));
+            }
+            (Some(x), None) => {
+                debug!("Using high odds config {x:#?}");
+                return bet(idx, x.points.value(streamer.points));
+            }
+            (_, Some(_)) => {}
+            (None, None) => {
+                if s.min_confidence.is_some_and(|min| p < min) {
+                    debug!(
+                        "Outcome {} is below the minimum confidence",
+                        prediction.outcomes[idx].title
+                    );
+                    continue;
+                }
+                if p >= s.default.min_percentage && p <= s.default.max_percentage {
+                    if s.prefer.is_some() {
+                        in_range.push((idx, p));
+                        continue;
+                    }
+                    debug!("Using default odds config {:#?} {}", s.default, p);
+                    return bet(idx, s.default.points.value(streamer.points));
+                }
+            }
         }
     }
 
-    fn get_prediction() -> StreamerState {
-        StreamerState {
-            info: StreamerInfo {
-                broadcast_id: None,
-                live: true,
-                channel_name: "a".to_owned(),
-                game: None,
-            },
-            predictions: HashMap::from([(
-                "pred-key-1".to_owned(),
-                (
-                    Event {
-                        id: "pred-key-1".to_owned(),
-                        channel_id: "channel-id-1".to_owned(),
-                        created_at: Timestamp::new(Local::now().to_rfc3339()).unwrap(),
-                        ended_at: None,
-                        locked_at: None,
-                        outcomes: Vec::new(),
-                        prediction_window_seconds: 1500,
-                        status: "".to_owned(),
-                        title: "".to_owned(),
-                        winning_outcome_id: None,
-                    },
-                    false,
-                ),
-            )]),
-            config: StreamerConfigRefWrapper::new(StreamerConfigRef {
-                _type: ConfigTypeRef::Specific,
-                config: StreamerConfig {
-                    follow_raid: true,
-                    prediction: PredictionConfig {
-                        strategy: Strategy::default(),
-                        filters: vec![],
-                    },
-                },
-            }),
-            points: 0,
-            last_points_refresh: Instant::now(),
+    let idx = s.prefer?.pick(&in_range, &s.default)?;
+    debug!(
+        "Using default odds config {:#?} on {} of {} outcomes in range",
+        s.default,
+        prediction.outcomes[idx].title,
+        in_range.len()
+    );
+    bet(idx, s.default.points.value(streamer.points))
+}
+
+/// Combine the bets suggested by the weighted strategies of a composite strategy
+fn combine_bets(
+    rule: strategy::CombineRule,
+    bets: &[(f64, Option<(String, u32)>)],
+) -> Option<(String, u32)> {
+    let weighted_average = |outcome_id: &str| {
+        let backers = bets.iter().filter_map(|(weight, bet)| match bet {
+            Some((id, points)) if id == outcome_id => Some((*weight, *points)),
+            _ => None,
+        });
+        let (weights, points) = backers.fold((0.0, 0.0), |(w, p), (weight, points)| {
+            (w + weight, p + weight * points as f64)
+        });
+        if weights == 0.0 {
+            0
+        } else {
+            (points / weights) as u32
+        }
+    };
+
+    match rule {
+        strategy::CombineRule::Agreement => {
+            let (id, _) = bets.first()?.1.as_ref()?;
+            if !bets
+                .iter()
+                .all(|(_, bet)| bet.as_ref().is_some_and(|(x, _)| x == id))
+            {
+                debug!("Strategies do not agree on an outcome");
+                return None;
+            }
+            Some((id.clone(), weighted_average(id)))
+        }
+        strategy::CombineRule::Average => {
+            let mut outcomes: Vec<(&String, f64)> = Vec::new();
+            for (weight, bet) in bets {
+                if let Some((id, _)) = bet {
+                    match outcomes.iter_mut().find(|(x, _)| *x == id) {
+                        Some((_, w)) => *w += weight,
+                        None => outcomes.push((id, *weight)),
+                    }
+                }
+            }
+            let (id, _) = outcomes
+                .into_iter()
+                .reduce(|a, b| if b.1 > a.1 { b } else { a })?;
+            Some((id.clone(), weighted_average(id)))
         }
+        strategy::CombineRule::HighestWeight => bets
+            .iter()
+            .filter_map(|(weight, bet)| bet.as_ref().map(|x| (*weight, x)))
+            .reduce(|a, b| if b.0 > a.0 { b } else { a })
+            .map(|(_, bet)| bet.clone()),
     }
+}
 
-    #[test]
-    fn detailed_strategy_default() -> Result<()> {
-        use common::config::strategy as s;
-        let mut streamer = get_prediction();
-        {
-            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
-            streamer.points = 50000;
-            pred.0.outcomes = vec![
-                outcome_from(1, 5_000, 2),
-                outcome_from(2, 30_000, 14),
-                outcome_from(3, 45_000, 10),
-                outcome_from(4, 1_000, 1),
-            ];
+mod watch_stream {
+    use super::*;
+
+    /// Rounds a stream that just went live is watched with priority for its watch streak bonus
+    pub const WATCH_STREAK_ROUNDS: u32 = 31;
+    /// Twitch only credits watch time on this many streams at once
+    const WATCHED_STREAMS: usize = 2;
+
+    pub async fn inner(
+        pubsub: &Arc<RwLock<PubSub>>,
+        watch_streak: &mut Vec<(UserId, i32)>,
+        use_watch_streak: bool,
+        live_event: &Receiver<UserId>,
+    ) -> Result<()> {
+        if use_watch_streak {
+            let live = live_event
+                .drain()
+                .map(|x| (x, 0))
+                .filter(|x| !watch_streak.iter().any(|y| y.0 == x.0))
+                .collect::<Vec<_>>();
+
+            watch_streak.extend(live);
         }
 
-        let default_points_percentage = 0.15;
-        let default_max_points = 40000;
+        // the bonus is only earned once per stream, free the slot for other streamers
+        let earned = std::mem::take(&mut pubsub.write().await.watch_streak_earned);
+        watch_streak.retain(|x| {
+            let keep = !earned.contains(&x.0);
+            if !keep {
+                debug!("Watch streak earned on {}, no longer prioritized", x.0);
+            }
+            keep
+        });
 
-        let mut config_ref = streamer.config.0.write().unwrap();
-        #[allow(irrefutable_let_patterns)]
-        if let Strategy::Detailed(d) = &mut config_ref.config.prediction.strategy {
-            d.default = DefaultPrediction {
-                max_percentage: 0.55,
-                min_percentage: 0.45,
-                points: s::Points {
-                    max_value: default_max_points,
-                    percent: default_points_percentage,
-                },
-            };
+        let (
+            streamers,
+            user_id,
+            user_name,
+            spade_url,
+            config,
+            analytics_tx,
+            options,
+            drop_channels,
+        ) = {
+            let reader = pubsub.read().await;
+            let streamers = reader
+                .streamers
+                .iter()
+                .filter(|x| x.1.info.live)
+                .map(|x| (x.0.clone(), x.1.clone()))
+                .collect::<Vec<_>>();
 
-            d.detailed = Some(vec![
-                DetailedOdds {
-                    _type: s::OddsComparisonType::Le,
-                    threshold: 0.10,
-                    attempt_rate: 0.00,
-                    points: s::Points {
-                        max_value: 1000,
-                        percent: 0.001,
-                    },
-                },
-                DetailedOdds {
-                    _type: s::OddsComparisonType::Le,
-                    threshold: 0.30,
-                    attempt_rate: 0.00,
-                    points: s::Points {
-                        max_value: 5000,
-                        percent: 0.01,
-                    },
-                },
-            ]);
+            (
+                streamers,
+                reader.user_id.parse()?,
+                reader.user_name.clone(),
+                reader.spade_url.clone().ok_or(eyre!("Spade URL not set"))?,
+                reader.config.clone(),
+                reader.analytics_tx.clone(),
+                reader.request_options(),
+                reader.drop_channels.clone(),
+            )
+        };
+
+        if streamers.is_empty() {
+            trace!("No streamer found");
+            pubsub.write().await.watched.clear();
+            return Ok(());
+        }
+
+        let streak_rounds = config.watch_streak_rounds.unwrap_or(WATCH_STREAK_ROUNDS) as i32;
+        let watch_priority = config.watch_priority.unwrap_or_default();
+        let mut watch_items = Vec::new();
+        for item in &watch_priority {
+            if let Some(s) = streamers
+                .iter()
+                .find(|x| x.1.info.channel_name.eq_ignore_ascii_case(item))
+            {
+                watch_items.push(s);
+            }
+        }
+
+        // streamers not given in a priority order
+        for item in config.streamers.iter().filter(|x| {
+            streamers
+                .iter()
+                .any(|y| y.1.info.channel_name.eq_ignore_ascii_case(x.0))
+        }) {
+            if !watch_priority
+                .iter()
+                .any(|x| x.eq_ignore_ascii_case(item.0))
+            {
+                watch_items.push(
+                    streamers
+                        .iter()
+                        .find(|x| x.1.info.channel_name.eq_ignore_ascii_case(item.0))
+                        .unwrap(),
+                );
+            }
+        }
+
+        // drops need watch time, the priority order is kept among them
+        watch_items.sort_by_key(|x| !drop_channels.contains(&x.0));
+
+        // Just to allow the reference to live
+        #[allow(unused_assignments)]
+        let mut streak_entry = None;
+        let mut entry = watch_streak.iter_mut().take(1);
+        if let Some(entry) = entry.next() {
+            entry.1 += 1;
+            let s = pubsub.read().await.streamers.get(&entry.0).unwrap().clone();
+            streak_entry = Some((entry.0.clone(), s));
+            watch_items.insert(0, streak_entry.as_ref().unwrap());
         }
 
-        drop(config_ref);
-        let res = prediction_logic(&streamer, "pred-key-1")?;
-        assert_eq!(res, None);
+        watch_items = remove_duplicates_in_place(watch_items, |a, b| a.0.eq(&b.0));
+        {
+            let reason = |id: &UserId, name: &str| {
+                if streak_entry.as_ref().is_some_and(|x| x.0 == *id) {
+                    WatchReason::Streak
+                } else if drop_channels.contains(id) {
+                    WatchReason::Drops
+                } else if watch_priority.iter().any(|x| x.eq_ignore_ascii_case(name)) {
+                    WatchReason::Priority
+                } else {
+                    WatchReason::Fill
+                }
+            };
+            let mut writer = pubsub.write().await;
+            writer.watching = watch_items.iter().map(|x| x.1.clone()).collect();
+            writer.watched = watch_items
+                .iter()
+                .take(WATCHED_STREAMS)
+                .map(|(id, s)| WatchedStreamer {
+                    channel_name: s.info.channel_name.clone(),
+                    reason: reason(id, &s.info.channel_name),
+                })
+                .collect();
+        }
+        for (id, streamer) in watch_items.into_iter().take(WATCHED_STREAMS) {
+            debug!("Watching {}", streamer.info.channel_name);
+            let res = api::set_viewership(
+                user_name.clone(),
+                user_id,
+                id.clone(),
+                streamer.info.clone(),
+                &spade_url,
+                &options,
+            )
+            .await;
+
+            let channel_id = id.as_str().parse::<i32>()?;
+            let success = res.is_ok();
+            let sent = analytics::send_droppable(
+                &analytics_tx,
+                "watch event",
+                Box::new(move |analytics| analytics.insert_watch_event(channel_id, success)),
+            );
+            if !sent {
+                return Err(eyre!("Failed to send watch event to analytics"));
+            }
+
+            res.context(format!(
+                "Could not set viewership {}",
+                streamer.info.channel_name
+            ))?;
+        }
+
+        *watch_streak = watch_streak
+            .drain(..)
+            .filter(|x| x.1 < streak_rounds)
+            .collect();
+        Ok(())
+    }
+
+    pub async fn run(pubsub: Arc<RwLock<PubSub>>, live_event: Receiver<UserId>) {
+        let use_watch_streak = {
+            let reader = pubsub.read().await;
+            reader.config.watch_streak.unwrap_or(true)
+        };
+
+        let mut watch_streak = Vec::new();
+
+        loop {
+            if let Err(err) = inner(&pubsub, &mut watch_streak, use_watch_streak, &live_event).await
+            {
+                if err.to_string() != "Spade URL not set" {
+                    error!("watch_streams {err}");
+                }
+            }
+
+            #[cfg(test)]
+            let time = 1;
+            #[cfg(not(test))]
+            let time = 10 * 1000;
+            sleep(Duration::from_millis(time)).await;
+        }
+    }
+}
+
+mod update_and_claim_points {
+    use std::future::Future;
+
+    use super::*;
+
+    /// Attempts made after a claim fails, bonuses expire if they are not claimed in time
+    const CLAIM_RETRIES: u32 = 2;
+    #[cfg(test)]
+    const CLAIM_RETRY_DELAY: Duration = Duration::from_millis(10);
+    #[cfg(not(test))]
+    const CLAIM_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+    /// Retry a claim with an exponential backoff, counting failed attempts and successful claims
+    async fn claim_with_retry<T, F, Fut>(
+        pubsub: &Arc<RwLock<PubSub>>,
+        claims: u64,
+        mut f: F,
+    ) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f().await {
+                Ok(x) => {
+                    pubsub.write().await.claims.succeeded += claims;
+                    return Ok(x);
+                }
+                Err(err) => {
+                    pubsub.write().await.claims.failed += claims;
+                    if attempt == CLAIM_RETRIES {
+                        return Err(err);
+                    }
+                    warn!(
+                        "Claiming points failed, attempt {} of {}: {err}",
+                        attempt + 1,
+                        CLAIM_RETRIES + 1
+                    );
+                    sleep(CLAIM_RETRY_DELAY * 2u32.pow(attempt)).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Claim bonuses in batched requests, takes (Channel ID, Claim ID, Channel name). Only the claims that failed
+    /// are retried, with the same backoff as `claim_with_retry`
+    async fn claim_batched_with_retry(
+        pubsub: &Arc<RwLock<PubSub>>,
+        gql: &gql::Client,
+        claims: Vec<(UserId, String, String)>,
+    ) -> Vec<(UserId, String, Result<u32>)> {
+        let mut claimed = Vec::new();
+        let mut pending = claims;
+        let mut attempt = 0;
+        loop {
+            let results = {
+                let ids = pending
+                    .iter()
+                    .map(|(channel_id, claim_id, _)| (channel_id.as_str(), claim_id.as_str()))
+                    .collect::<Vec<_>>();
+                gql.claim_points_batched(&ids).await
+            };
+            let attempted = pending.len();
+            let mut failed = Vec::new();
+            for (claim, res) in pending.into_iter().zip(results) {
+                match res {
+                    Ok(points) => claimed.push((claim.0, claim.2, Ok(points))),
+                    Err(err) => failed.push((claim, err)),
+                }
+            }
+            {
+                let mut writer = pubsub.write().await;
+                writer.claims.succeeded += (attempted - failed.len()) as u64;
+                writer.claims.failed += failed.len() as u64;
+            }
+            if failed.is_empty() || attempt == CLAIM_RETRIES {
+                claimed.extend(failed.into_iter().map(|(c, err)| (c.0, c.2, Err(err))));
+                return claimed;
+            }
+            warn!(
+                "Claiming {} bonuses failed, attempt {} of {}",
+                failed.len(),
+                attempt + 1,
+                CLAIM_RETRIES + 1
+            );
+            sleep(CLAIM_RETRY_DELAY * 2u32.pow(attempt)).await;
+            attempt += 1;
+            pending = failed.into_iter().map(|x| x.0).collect();
+        }
+    }
+
+    /// Points and available claims of the streamers. If the batched request fails the channels
+    /// are requested one by one, so only the failing channels count towards their circuit
+    async fn channel_points(
+        pubsub: &Arc<RwLock<PubSub>>,
+        gql: &gql::Client,
+        streamer: Vec<(UserId, StreamerState)>,
+    ) -> Result<Vec<((u32, Option<String>), (UserId, StreamerState))>> {
+        let channel_names = streamer
+            .iter()
+            .map(|x| x.1.info.channel_name.as_str())
+            .collect::<Vec<_>>();
+        let err = match gql.get_channel_points(&channel_names).await {
+            Ok(points) => {
+                let mut writer = pubsub.write().await;
+                for (channel_id, _) in &streamer {
+                    writer.record_gql_result(channel_id, true);
+                }
+                return Ok(points.into_iter().zip(streamer).collect());
+            }
+            Err(err) => err,
+        };
+
+        warn!("Get channel points failed, requesting channels one by one: {err}");
+        let mut results = Vec::new();
+        for s in streamer {
+            let points = gql.get_channel_points(&[&s.1.info.channel_name]).await;
+            results.push((points, s));
+        }
+        let mut writer = pubsub.write().await;
+        for (points, s) in &results {
+            writer.record_gql_result(&s.0, points.is_ok());
+        }
+        if results.iter().all(|x| x.0.is_err()) {
+            return Err(err);
+        }
+
+        let mut items = Vec::new();
+        for (points, s) in results {
+            match points {
+                Ok(mut points) => items.push((points.remove(0), s)),
+                Err(err) => warn!("Get channel points {}: {err}", s.1.info.channel_name),
+            }
+        }
+        Ok(items)
+    }
+
+    pub async fn inner(pubsub: &Arc<RwLock<PubSub>>, gql: &gql::Client) -> Result<()> {
+        let (streamer, claim_mode) = {
+            let reader = pubsub.read().await;
+            let streamer = reader
+                .streamers
+                .iter()
+                .filter(|x| x.1.info.live && !x.1.circuit.is_open())
+                .map(|x| (x.0.clone(), x.1.clone()))
+                .collect::<Vec<_>>();
+            (streamer, reader.config.claim_mode.unwrap_or_default())
+        };
+
+        if streamer.is_empty() {
+            sleep(Duration::from_secs(60)).await;
+            return Ok(());
+        }
+
+        let points = channel_points(pubsub, gql, streamer)
+            .await
+            .context("Get channel points")?;
+
+        let mut changes = Vec::new();
+        let mut claims = Vec::new();
+        for ((points, claim), (channel_id, state)) in points {
+            match claim {
+                Some(claim_id) => {
+                    info!(
+                        "Claiming community points bonus {}",
+                        state.info.channel_name
+                    );
+                    match claim_mode {
+                        ClaimMode::Immediate => {
+                            let claimed_points = claim_with_retry(pubsub, 1, || {
+                                gql.claim_points(channel_id.as_str(), &claim_id)
+                            })
+                            .await;
+                            pubsub
+                                .write()
+                                .await
+                                .record_gql_result(&channel_id, claimed_points.is_ok());
+                            match claimed_points {
+                                Ok(claimed_points) => changes.push((
+                                    PointsInfo::CommunityPointsClaimed,
+                                    claimed_points,
+                                    channel_id,
+                                )),
+                                Err(err) => {
+                                    error!("Claiming points {}: {err}", state.info.channel_name)
+                                }
+                            }
+                        }
+                        ClaimMode::Batched => {
+                            claims.push((channel_id, claim_id, state.info.channel_name))
+                        }
+                    }
+                }
+                None => changes.push((PointsInfo::Watching, points, channel_id)),
+            }
+        }
+
+        if !claims.is_empty() {
+            let claimed = claim_batched_with_retry(pubsub, gql, claims).await;
+            let mut writer = pubsub.write().await;
+            for (channel_id, channel_name, claimed_points) in claimed {
+                writer.record_gql_result(&channel_id, claimed_points.is_ok());
+                match claimed_points {
+                    Ok(claimed_points) => changes.push((
+                        PointsInfo::CommunityPointsClaimed,
+                        claimed_points,
+                        channel_id,
+                    )),
+                    Err(err) => error!("Claiming points {channel_name}: {err}"),
+                }
+            }
+        }
+
+        {
+            let now = Instant::now();
+            let mut writer = pubsub.write().await;
+            for (_type, points, channel_id) in changes {
+                if _type == PointsInfo::CommunityPointsClaimed {
+                    let event = AppEvent::PointsClaimed {
+                        channel_name: writer.streamers[&channel_id].info.channel_name.clone(),
+                        points,
+                    };
+                    writer.emit(event);
+                }
+                // balances pushed by pubsub are already recorded, along with why they changed
+                let s = writer.streamers.get_mut(&channel_id).unwrap();
+                if s.points == points {
+                    s.last_points_refresh = now;
+                    continue;
+                }
+                let edited = writer
+                    .analytics
+                    .execute(|analytics| {
+                        analytics.insert_points_if_updated(
+                            channel_id.as_str().parse().unwrap(),
+                            points as i32,
+                            _type.clone(),
+                        )
+                    })
+                    .await?;
+                if edited {
+                    writer.track_points_goal(&channel_id, points)?;
+                    let s = writer.streamers.get_mut(&channel_id).unwrap();
+                    s.points = points;
+                    s.last_points_refresh = now
+                }
+            }
+            writer.track_drawdown();
+        }
+        Ok(())
+    }
+
+    pub async fn run(pubsub: Arc<RwLock<PubSub>>, gql: gql::Client) {
+        loop {
+            if let Err(err) = inner(&pubsub, &gql).await {
+                error!("update_and_claim_points {err}");
+            }
+
+            sleep(Duration::from_secs(60)).await
+        }
+    }
+}
+
+mod drops {
+    use super::*;
+
+    pub async fn inner(pubsub: &Arc<RwLock<PubSub>>, gql: &gql::Client) -> Result<()> {
+        let live = pubsub
+            .read()
+            .await
+            .streamers
+            .iter()
+            .filter(|x| x.1.info.live)
+            .map(|x| x.0.clone())
+            .collect::<Vec<_>>();
+        let drop_channels = if live.is_empty() {
+            Vec::new()
+        } else {
+            let available = gql
+                .available_drops(&live.iter().map(|x| x.as_str()).collect::<Vec<_>>())
+                .await
+                .context("Get drop campaigns")?;
+            live.into_iter()
+                .zip(available)
+                .filter(|x| x.1)
+                .map(|x| x.0)
+                .collect()
+        };
+        pubsub.write().await.drop_channels = drop_channels;
+
+        for drop in gql.claimable_drops().await.context("Get drops inventory")? {
+            if let Err(err) = gql.claim_drop(&drop.drop_instance_id).await {
+                warn!("Could not claim drop {}: {err:#}", drop.name);
+                continue;
+            }
+            info!("Claimed drop {}", drop.name);
+            pubsub.read().await.emit(AppEvent::DropClaimed {
+                drop_instance_id: drop.drop_instance_id,
+                name: drop.name,
+            });
+        }
+        Ok(())
+    }
+
+    pub async fn run(pubsub: Arc<RwLock<PubSub>>, gql: gql::Client) {
+        loop {
+            if let Err(err) = inner(&pubsub, &gql).await {
+                error!("drops {err}");
+            }
+
+            sleep(Duration::from_secs(5 * 60)).await
+        }
+    }
+}
+
+mod game_refresh {
+    use super::*;
+
+    pub async fn inner(pubsub: &Arc<RwLock<PubSub>>, gql: &gql::Client) -> Result<()> {
+        let live = pubsub
+            .read()
+            .await
+            .streamers
+            .iter()
+            .filter(|x| x.1.info.live)
+            .map(|x| (x.0.clone(), x.1.info.channel_name.clone()))
+            .collect::<Vec<_>>();
+        if live.is_empty() {
+            return Ok(());
+        }
+        let metadata = gql
+            .streamer_metadata(&live.iter().map(|x| x.1.as_str()).collect::<Vec<_>>())
+            .await
+            .context("Get stream metadata")?;
+
+        let mut writer = pubsub.write().await;
+        for ((id, channel_name), metadata) in live.into_iter().zip(metadata) {
+            // going offline is handled by the video playback topic
+            let game = match metadata {
+                Some((_, info)) if info.live => info.game,
+                _ => continue,
+            };
+            let pending = match writer.streamers.get_mut(&id) {
+                Some(streamer) if streamer.info.game != game => {
+                    info!(
+                        "{channel_name} switched to {}",
+                        game.as_ref()
+                            .map(|x| x.name.as_str())
+                            .unwrap_or("no category")
+                    );
+                    streamer.info.game = game;
+                    streamer
+                        .predictions
+                        .iter()
+                        .filter(|x| !x.1 .1 && x.1 .0.locked_at.is_none())
+                        .map(|x| x.1 .0.clone())
+                        .collect::<Vec<_>>()
+                }
+                _ => continue,
+            };
+
+            // filters on the category may now let these through
+            for event in pending {
+                if !writer.started_before_launch(&event) {
+                    writer.try_prediction(&id, &event.id).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn run(pubsub: Arc<RwLock<PubSub>>, gql: gql::Client, secs: u64) {
+        loop {
+            sleep(Duration::from_secs(secs)).await;
+            if let Err(err) = inner(&pubsub, &gql).await {
+                error!("game_refresh {err}");
+            }
+        }
+    }
+}
+
+mod equity_snapshot {
+    use super::*;
+
+    pub fn inner(pubsub: &PubSub) -> Result<()> {
+        let balance = pubsub
+            .streamers
+            .values()
+            .map(|x| x.points as i64)
+            .sum::<i64>();
+        let sent = analytics::send_droppable(
+            &pubsub.analytics_tx,
+            "equity snapshot",
+            Box::new(move |analytics| analytics.insert_equity_snapshot(balance)),
+        );
+        if !sent {
+            return Err(eyre!("Failed to send equity snapshot to analytics"));
+        }
+        Ok(())
+    }
+
+    pub async fn run(pubsub: Arc<RwLock<PubSub>>, period: Duration) {
+        loop {
+            sleep(period).await;
+            if let Err(err) = inner(&*pubsub.read().await) {
+                error!("equity_snapshot {err}");
+            }
+        }
+    }
+}
+
+mod onsite_notifications {
+    use common::twitch::onsite::{Notification, NotificationKind};
+
+    use super::*;
+
+    pub async fn inner(
+        pubsub: &Arc<RwLock<PubSub>>,
+        gql: &gql::Client,
+        notification: Notification,
+    ) -> Result<()> {
+        let kind = notification.kind();
+        let login = match notification.channel_login() {
+            Some(login) if kind != NotificationKind::Other => login,
+            _ => return Ok(()),
+        };
+        let (streamer, channel_name) = {
+            let reader = pubsub.read().await;
+            match reader.get_id_by_name(&login) {
+                Some(id) => (
+                    UserId::from_str(id)?,
+                    reader.streamers[&UserId::from_str(id)?]
+                        .info
+                        .channel_name
+                        .clone(),
+                ),
+                None => return Ok(()),
+            }
+        };
+
+        match kind {
+            NotificationKind::Prediction => {
+                debug!("Prediction notification for {channel_name}");
+                let events = gql
+                    .channel_points_context(&[&channel_name])
+                    .await
+                    .context("Get active predictions")?;
+                let mut writer = pubsub.write().await;
+                for (event, _) in events.into_iter().flatten().filter(|x| !x.1) {
+                    writer
+                        .handle_prediction_event(event, streamer.clone())
+                        .await?;
+                }
+            }
+            NotificationKind::Raid => {
+                // the raid topic is only listened to while the stream is live
+                debug!("Raid notification for {channel_name}");
+                let channel_id = streamer.as_str().parse()?;
+                pubsub
+                    .read()
+                    .await
+                    .ws_tx
+                    .send_async(Request::Listen(Topics::Raid(Raid { channel_id })))
+                    .await
+                    .context("Send ws command")?;
+            }
+            NotificationKind::Other => {}
+        }
+        Ok(())
+    }
+
+    pub async fn run(
+        pubsub: Arc<RwLock<PubSub>>,
+        gql: gql::Client,
+        notifications: Receiver<Notification>,
+    ) {
+        while let Ok(notification) = notifications.recv_async().await {
+            if let Err(err) = inner(&pubsub, &gql, notification).await {
+                warn!("Error handling onsite notification: {err:?}");
+            }
+        }
+    }
+}
+
+mod user_predictions {
+    use super::*;
+
+    pub async fn run(pubsub: Arc<RwLock<PubSub>>, replies: Receiver<PredictionsUserReply>) {
+        while let Ok(reply) = replies.recv_async().await {
+            if let Err(err) = pubsub.write().await.handle_user_prediction(reply).await {
+                warn!("Error handling user prediction: {err:?}");
+            }
+        }
+    }
+}
+
+mod update_spade_url {
+    use super::*;
+
+    async fn inner(pubsub: &Arc<RwLock<PubSub>>, base_url: &str) -> Result<()> {
+        let a_live_stream = {
+            let reader = pubsub.read().await;
+            reader
+                .streamers
+                .iter()
+                .find(|x| x.1.info.live)
+                .map(|x| (x.0.clone(), x.1.clone()))
+        };
+
+        if let Some((_, streamer)) = a_live_stream {
+            let options = pubsub.read().await.request_options();
+            let spade_url =
+                api::get_spade_url(&streamer.info.channel_name, base_url, &options).await?;
+            pubsub.write().await.spade_url = Some(spade_url);
+            debug!("Updated spade url");
+        }
+        Ok(())
+    }
+
+    pub async fn run(pubsub: Arc<RwLock<PubSub>>) {
+        let base_url = { pubsub.read().await.base_url.clone() };
+        loop {
+            if let Err(err) = inner(&pubsub, &base_url).await {
+                error!("update_and_claim_points {err}");
+            }
+
+            sleep(Duration::from_secs(120)).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{HashMap, HashSet},
+        str::FromStr,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use chrono::Local;
+    use eyre::Result;
+    use flume::unbounded;
+    use rand::{rngs::StdRng, SeedableRng};
+    use rstest::rstest;
+    use tokio::sync::RwLock;
+    use twitch_api::{
+        pubsub::predictions::{Event, Outcome},
+        types::{Timestamp, UserId},
+    };
+
+    use common::{
+        config::{strategy::*, ClaimMode, ConfigType, PredictionConfig, StreamerConfig},
+        testing::{container, TestContainer},
+        twitch::gql,
+        types::*,
+    };
+
+    use crate::{events::AppEvent, pubsub::prediction_logic};
+
+    use super::{jitter_points, locks_too_soon, ClaimStats, PubSub, WatchReason, WatchedStreamer};
+
+    fn outcome_from(id: u32, points: i64, users: i64) -> Outcome {
+        Outcome {
+            id: id.to_string(),
+            color: "".to_owned(),
+            title: id.to_string(),
+            total_points: points,
+            total_users: users,
+            top_predictors: Vec::new(),
+        }
+    }
+
+    fn detailed_mut(strategy: &mut Strategy) -> &mut Detailed {
+        match strategy {
+            Strategy::Detailed(d) => d,
+            _ => unreachable!("test streamers use the detailed strategy"),
+        }
+    }
+
+    fn get_prediction() -> StreamerState {
+        StreamerState {
+            info: StreamerInfo {
+                broadcast_id: None,
+                live: true,
+                channel_name: "a".to_owned(),
+                game: None,
+            },
+            predictions: HashMap::from([(
+                "pred-key-1".to_owned(),
+                (
+                    Event {
+                        id: "pred-key-1".to_owned(),
+                        channel_id: "channel-id-1".to_owned(),
+                        created_at: Timestamp::new(Local::now().to_rfc3339()).unwrap(),
+                        ended_at: None,
+                        locked_at: None,
+                        outcomes: Vec::new(),
+                        prediction_window_seconds: 1500,
+                        status: "".to_owned(),
+                        title: "".to_owned(),
+                        winning_outcome_id: None,
+                    },
+                    false,
+                ),
+            )]),
+            config: StreamerConfigRefWrapper::new(StreamerConfigRef {
+                _type: ConfigTypeRef::Specific,
+                config: StreamerConfig {
+                    follow_raid: true,
+                    betting: None,
+                    min_live_seconds: None,
+                    simulate: None,
+                    points_goal: None,
+                    notify: None,
+                    prediction: PredictionConfig {
+                        strategy: Strategy::default(),
+                        filters: vec![],
+                        fallback: None,
+                    },
+                },
+            }),
+            points: 0,
+            last_points_refresh: Instant::now(),
+            wagered: Default::default(),
+            live_since: None,
+            last_live_at: None,
+            circuit: Default::default(),
+            points_goal_reached: false,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn detailed_strategy_default() -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![
+                outcome_from(1, 5_000, 2),
+                outcome_from(2, 30_000, 14),
+                outcome_from(3, 45_000, 10),
+                outcome_from(4, 1_000, 1),
+            ];
+        }
+
+        let default_points_percentage = 0.15;
+        let default_max_points = 40000;
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.default = DefaultPrediction {
+            max_percentage: 0.55,
+            min_percentage: 0.45,
+            points: s::Points {
+                max_value: default_max_points,
+                percent: default_points_percentage,
+            },
+        };
+
+        d.detailed = Some(vec![
+            DetailedOdds {
+                _type: s::OddsComparisonType::Le,
+                threshold: 0.10,
+                attempt_rate: 0.00,
+                points: s::Points {
+                    max_value: 1000,
+                    percent: 0.001,
+                },
+            },
+            DetailedOdds {
+                _type: s::OddsComparisonType::Le,
+                threshold: 0.30,
+                attempt_rate: 0.00,
+                points: s::Points {
+                    max_value: 5000,
+                    percent: 0.01,
+                },
+            },
+        ]);
+
+        drop(config_ref);
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, None);
+
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            pred.0.outcomes[2] = outcome_from(3, 45_000, 10);
+        }
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, None);
+
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            pred.0.outcomes[2] = outcome_from(3, 40_000, 10);
+        }
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(
+            res,
+            Some((
+                "3".to_owned(),
+                (streamer.points as f64 * default_points_percentage) as u32
+            ))
+        );
+
+        streamer.points = 500000;
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("3".to_owned(), default_max_points)));
+
+        Ok(())
+    }
+
+    /// Bets a percentage of the balance on the first outcome with odds in the range
+    fn default_range_strategy(min_percentage: f64, max_percentage: f64, percent: f64) -> Strategy {
+        Strategy::Detailed(Detailed {
+            default: DefaultPrediction {
+                max_percentage,
+                min_percentage,
+                points: Points {
+                    max_value: 0,
+                    percent,
+                },
+            },
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn duplicate_prediction_update_is_skipped() -> Result<()> {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+        pubsub.watch_only = true;
+        let id = UserId::from_static("1");
+        let mut streamer = get_prediction();
+        let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+        pred.0.outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 3_000, 4)];
+        let mut event = pred.0.clone();
+        pubsub.streamers.insert(id.clone(), streamer);
+
+        // the event as it is already known
+        pubsub
+            .handle_prediction_event(event.clone(), id.clone())
+            .await?;
+        assert!(analytics_rx.is_empty());
+
+        event.outcomes[0].total_points += 100;
+        pubsub
+            .handle_prediction_event(event.clone(), id.clone())
+            .await?;
+        assert_eq!(analytics_rx.drain().count(), 1);
+        assert_eq!(
+            pubsub.streamers[&id].predictions["pred-key-1"].0.outcomes[0].total_points,
+            event.outcomes[0].total_points
+        );
+
+        pubsub.handle_prediction_event(event, id.clone()).await?;
+        assert!(analytics_rx.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn fallback_strategy() -> Result<()> {
+        let mut streamer = get_prediction();
+        streamer.points = 50000;
+        streamer
+            .predictions
+            .get_mut("pred-key-1")
+            .unwrap()
+            .0
+            .outcomes = vec![
+            outcome_from(1, 5_000, 2),
+            outcome_from(2, 30_000, 14),
+            outcome_from(3, 45_000, 10),
+            outcome_from(4, 1_000, 1),
+        ];
+        {
+            let mut config_ref = streamer.config.0.write().unwrap();
+            let prediction = &mut config_ref.config.prediction;
+            prediction.strategy = default_range_strategy(0.45, 0.55, 0.15);
+            prediction.fallback = Some(Box::new(default_range_strategy(0.30, 0.40, 0.01)));
+        }
+
+        // no outcome has odds between 45% and 55%, the fallback bets on the one at 37%
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("2".to_owned(), 500)));
+
+        streamer
+            .config
+            .0
+            .write()
+            .unwrap()
+            .config
+            .prediction
+            .fallback = None;
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, None);
+        Ok(())
+    }
+
+    #[test]
+    fn fallback_not_consulted_when_strategy_bets() -> Result<()> {
+        let mut streamer = get_prediction();
+        streamer.points = 50000;
+        streamer
+            .predictions
+            .get_mut("pred-key-1")
+            .unwrap()
+            .0
+            .outcomes = vec![
+            outcome_from(1, 5_000, 2),
+            outcome_from(2, 30_000, 14),
+            outcome_from(3, 40_000, 10),
+            outcome_from(4, 1_000, 1),
+        ];
+        {
+            let mut config_ref = streamer.config.0.write().unwrap();
+            let prediction = &mut config_ref.config.prediction;
+            prediction.strategy = default_range_strategy(0.45, 0.55, 0.15);
+            prediction.fallback = Some(Box::new(default_range_strategy(0.30, 0.40, 0.01)));
+        }
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("3".to_owned(), 7500)));
+        Ok(())
+    }
+
+    #[test]
+    fn detailed_strategy_high_odds() -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![
+                outcome_from(1, 5_000, 2),
+                outcome_from(2, 30_000, 14),
+                outcome_from(3, 45_000, 10),
+                outcome_from(4, 1_000, 1),
+            ];
+        }
+
+        let high_odds_percentage = 0.001;
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.default = DefaultPrediction {
+            max_percentage: 0.55,
+            min_percentage: 0.45,
+            points: s::Points {
+                max_value: 40000,
+                percent: 0.15,
+            },
+        };
+
+        d.detailed = Some(vec![
+            DetailedOdds {
+                _type: s::OddsComparisonType::Le,
+                threshold: 0.10,
+                attempt_rate: 1.0,
+                points: s::Points {
+                    max_value: 1000,
+                    percent: high_odds_percentage,
+                },
+            },
+            DetailedOdds {
+                _type: s::OddsComparisonType::Le,
+                threshold: 0.30,
+                attempt_rate: 0.00,
+                points: s::Points {
+                    max_value: 5000,
+                    percent: 0.01,
+                },
+            },
+        ]);
+
+        drop(config_ref);
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(
+            res,
+            Some((
+                "1".to_owned(),
+                (streamer.points as f64 * high_odds_percentage) as u32
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn detailed_strategy_chase_odds_accounting() -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 30_000, 14)];
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.chase_odds = true;
+        d.detailed = Some(vec![DetailedOdds {
+            _type: s::OddsComparisonType::Le,
+            threshold: 0.10,
+            attempt_rate: 1.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.02,
+            },
+        }]);
+        drop(config_ref);
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("1".to_owned(), 1000)));
+        assert_eq!(
+            streamer.record_wager("pred-key-1", "1", 1000, true),
+            Some(1000)
+        );
+        streamer.points = 49000;
+
+        // the full stake is already placed
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, None);
+
+        // balance grew, only the difference is added
+        streamer.points = 99000;
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("1".to_owned(), 1000)));
+        assert_eq!(
+            streamer.record_wager("pred-key-1", "1", 1000, true),
+            Some(2000)
+        );
+        assert_eq!(streamer.wagered["pred-key-1"].points, 2000);
+
+        // never add to a different outcome than the one already bet on
+        streamer.wagered.clear();
+        streamer.record_wager("pred-key-1", "2", 1000, true);
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, None);
+        assert_eq!(streamer.record_wager("pred-key-1", "1", 1000, true), None);
+        assert_eq!(streamer.wagered["pred-key-1"].points, 1000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn attempt_rate_rolled_once_per_event() -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        streamer.points = 50000;
+        let mut event = streamer.predictions.remove("pred-key-1").unwrap().0;
+        event.outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 30_000, 14)];
+        for i in 0..32 {
+            let mut event = event.clone();
+            event.id = format!("pred-{i}");
+            streamer
+                .predictions
+                .insert(event.id.clone(), (event, false));
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.detailed = Some(vec![DetailedOdds {
+            _type: s::OddsComparisonType::Le,
+            threshold: 0.10,
+            attempt_rate: 0.5,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.02,
+            },
+        }]);
+        drop(config_ref);
+
+        let mut results = HashSet::new();
+        for i in 0..32 {
+            let id = format!("pred-{i}");
+            let res = prediction_logic(&streamer, &id, None)?;
+            // updates of the same prediction do not get another roll
+            for _ in 0..8 {
+                assert_eq!(prediction_logic(&streamer, &id, None)?, res);
+            }
+            results.insert(res);
+        }
+        assert!(results.len() > 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn min_live_seconds() -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 30_000, 14)];
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        config_ref.config.min_live_seconds = Some(120);
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.detailed = Some(vec![DetailedOdds {
+            _type: s::OddsComparisonType::Le,
+            threshold: 0.10,
+            attempt_rate: 1.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.02,
+            },
+        }]);
+        drop(config_ref);
+
+        // went live just now
+        streamer.live_since = Some(Instant::now());
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, None);
+
+        streamer.live_since = Some(Instant::now() - Duration::from_secs(121));
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("1".to_owned(), 1000)));
+
+        // live before startup, so the live time is unknown
+        streamer.live_since = None;
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("1".to_owned(), 1000)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn outcome_allow_titles() -> Result<()> {
+        use common::config::{filters::Filter, strategy as s};
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![
+                outcome_from(1, 1_000, 2),
+                outcome_from(2, 2_000, 2),
+                outcome_from(3, 30_000, 14),
+            ];
+            pred.0.outcomes[0].title = "Tie".to_owned();
+            pred.0.outcomes[1].title = "Yes".to_owned();
+            pred.0.outcomes[2].title = "No".to_owned();
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.detailed = Some(vec![DetailedOdds {
+            _type: s::OddsComparisonType::Le,
+            threshold: 0.10,
+            attempt_rate: 1.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.02,
+            },
+        }]);
+        drop(config_ref);
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("1".to_owned(), 1000)));
+
+        streamer.config.0.write().unwrap().config.prediction.filters = vec![
+            Filter::OutcomeAllowTitles(vec!["yes".to_owned(), "NO".to_owned()]),
+        ];
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("2".to_owned(), 1000)));
+
+        streamer.config.0.write().unwrap().config.prediction.filters =
+            vec![Filter::OutcomeAllowTitles(vec!["no".to_owned()])];
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn outcome_exclude_colors() -> Result<()> {
+        use common::config::{filters::Filter, strategy as s};
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![
+                outcome_from(1, 1_000, 2),
+                outcome_from(2, 2_000, 2),
+                outcome_from(3, 30_000, 14),
+            ];
+            pred.0.outcomes[0].color = "PINK".to_owned();
+            pred.0.outcomes[1].color = "BLUE".to_owned();
+            pred.0.outcomes[2].color = "BLUE".to_owned();
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.detailed = Some(vec![DetailedOdds {
+            _type: s::OddsComparisonType::Le,
+            threshold: 0.10,
+            attempt_rate: 1.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.02,
+            },
+        }]);
+        drop(config_ref);
+
+        streamer.config.0.write().unwrap().config.prediction.filters =
+            vec![Filter::OutcomeExcludeColors(vec!["pink".to_owned()])];
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("2".to_owned(), 1000)));
+
+        streamer.config.0.write().unwrap().config.prediction.filters = vec![
+            Filter::OutcomeExcludeColors(vec!["Pink".to_owned(), "blue".to_owned()]),
+        ];
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn skip_if_already_bet() -> Result<()> {
+        use common::config::{filters::Filter, strategy as s};
+        let mut streamer = get_prediction();
+        streamer.points = 50000;
+        streamer
+            .predictions
+            .get_mut("pred-key-1")
+            .unwrap()
+            .0
+            .outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 30_000, 14)];
+        {
+            let mut config_ref = streamer.config.0.write().unwrap();
+            let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+            d.detailed = Some(vec![DetailedOdds {
+                _type: s::OddsComparisonType::Le,
+                threshold: 0.10,
+                attempt_rate: 1.0,
+                points: s::Points {
+                    max_value: 0,
+                    percent: 0.02,
+                },
+            }]);
+            config_ref.config.prediction.filters = vec![Filter::SkipIfAlreadyBet];
+        }
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("1".to_owned(), 1000)));
+
+        // already bet on, e.g. found in recentPredictions at startup
+        streamer.predictions.get_mut("pred-key-1").unwrap().1 = true;
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, None);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(100, 456_700, 0.01, Some(4600))]
+    #[case(1, 456_700, 0.01, Some(4567))]
+    #[case(0, 456_700, 0.01, Some(4567))]
+    // rounding up would exceed the balance
+    #[case(100, 4567, 1.0, Some(4500))]
+    // less than half a step is still one step
+    #[case(100, 4000, 0.01, Some(100))]
+    // not even one step can be afforded
+    #[case(100, 50, 0.8, None)]
+    fn bet_rounding(
+        #[case] rounding: u32,
+        #[case] balance: u32,
+        #[case] percent: f64,
+        #[case] expected: Option<u32>,
+    ) {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        streamer.points = balance;
+        streamer
+            .predictions
+            .get_mut("pred-key-1")
+            .unwrap()
+            .0
+            .outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 30_000, 14)];
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.bet_rounding = rounding;
+        d.detailed = Some(vec![DetailedOdds {
+            _type: s::OddsComparisonType::Le,
+            threshold: 0.10,
+            attempt_rate: 1.0,
+            points: s::Points {
+                max_value: 0,
+                percent,
+            },
+        }]);
+        drop(config_ref);
+
+        let res = prediction_logic(&streamer, "pred-key-1", None).unwrap();
+        assert_eq!(res, expected.map(|x| ("1".to_owned(), x)));
+    }
+
+    #[rstest]
+    #[case(false, [0, 10_000, 10_000], Some("1"))]
+    #[case(true, [0, 10_000, 10_000], Some("2"))]
+    #[case(false, [0, 0, 0], Some("1"))]
+    #[case(true, [0, 0, 0], None)]
+    fn skip_empty_outcomes(
+        #[case] skip: bool,
+        #[case] points: [i64; 3],
+        #[case] expected: Option<&str>,
+    ) -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = points
+                .iter()
+                .enumerate()
+                .map(|(idx, p)| outcome_from(idx as u32 + 1, *p, 0))
+                .collect();
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.skip_empty_outcomes = skip;
+        d.default = DefaultPrediction {
+            max_percentage: 0.5,
+            min_percentage: 0.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        };
+        drop(config_ref);
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, expected.map(|o| (o.to_owned(), 500)));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(None, Some(("1", 1000)))]
+    #[case(Some(1), Some(("1", 1000)))]
+    // the best odds are backed by a single user, fall through to the default on the thicker outcome
+    #[case(Some(5), Some(("2", 500)))]
+    #[case(Some(50), None)]
+    fn min_outcome_users(
+        #[case] min_users: Option<u64>,
+        #[case] expected: Option<(&str, u32)>,
+    ) -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![outcome_from(1, 1_000, 1), outcome_from(2, 3_000, 20)];
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.min_outcome_users = min_users;
+        d.detailed = Some(vec![DetailedOdds {
+            _type: s::OddsComparisonType::Le,
+            threshold: 0.5,
+            attempt_rate: 1.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.02,
+            },
+        }]);
+        d.default = DefaultPrediction {
+            max_percentage: 1.0,
+            min_percentage: 0.5,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        };
+        drop(config_ref);
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, expected.map(|(o, p)| (o.to_owned(), p)));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(3, None, Some(("1", 500)))]
+    #[case(3, Some(3), Some(("1", 500)))]
+    // the smallest pools are left out, so the thinnest outcome considered is bet on
+    #[case(12, None, Some(("1", 500)))]
+    #[case(12, Some(3), Some(("10", 500)))]
+    fn max_outcomes(
+        #[case] outcomes: u32,
+        #[case] max: Option<usize>,
+        #[case] expected: Option<(&str, u32)>,
+    ) -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = (1..=outcomes)
+                .map(|x| outcome_from(x, x as i64 * 100, 1))
+                .collect();
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.max_outcomes = max;
+        d.default = DefaultPrediction {
+            max_percentage: 1.0,
+            min_percentage: 0.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        };
+        drop(config_ref);
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, expected.map(|(o, p)| (o.to_owned(), p)));
+        Ok(())
+    }
+
+    #[rstest]
+    // the band bets on the first outcome inside it
+    #[case((0.2, 0.6), None, Some(("1", 500)))]
+    // the floor skips outcomes inside the band that are below it
+    #[case((0.2, 0.6), Some(0.4), Some(("3", 500)))]
+    #[case((0.2, 0.6), Some(0.5), None)]
+    // a floor on its own has no upper bound
+    #[case((0.0, 1.0), Some(0.4), Some(("3", 500)))]
+    #[case((0.0, 0.4), None, Some(("1", 500)))]
+    fn min_confidence(
+        #[case] band: (f64, f64),
+        #[case] floor: Option<f64>,
+        #[case] expected: Option<(&str, u32)>,
+    ) -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![
+                outcome_from(1, 300, 1),
+                outcome_from(2, 250, 1),
+                outcome_from(3, 450, 1),
+            ];
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.min_confidence = floor;
+        d.default = DefaultPrediction {
+            max_percentage: band.1,
+            min_percentage: band.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        };
+        drop(config_ref);
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, expected.map(|(o, p)| (o.to_owned(), p)));
+        Ok(())
+    }
+
+    #[rstest]
+    // outcome 2 has 55% of the points and outcome 3 has 35%, both inside the band
+    #[case(None, (0.2, 0.6), "2")]
+    #[case(Some(RangePreference::HighestInRange), (0.2, 0.6), "2")]
+    #[case(Some(RangePreference::LowestInRange), (0.2, 0.6), "3")]
+    #[case(Some(RangePreference::ClosestToMidpoint), (0.2, 0.6), "3")]
+    #[case(Some(RangePreference::ClosestToMidpoint), (0.3, 0.8), "2")]
+    fn range_preference(
+        #[case] prefer: Option<RangePreference>,
+        #[case] band: (f64, f64),
+        #[case] expected: &str,
+    ) -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![
+                outcome_from(1, 100, 1),
+                outcome_from(2, 550, 1),
+                outcome_from(3, 350, 1),
+            ];
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.prefer = prefer;
+        d.default = DefaultPrediction {
+            max_percentage: band.1,
+            min_percentage: band.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        };
+        drop(config_ref);
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some((expected.to_owned(), 500)));
+        Ok(())
+    }
+
+    #[rstest]
+    // nothing before 10% of the 1500 second window
+    #[case(0, None)]
+    #[case(100, None)]
+    #[case(750, Some(("1", 250)))]
+    // the full stake from 90% until it locks
+    #[case(1450, Some(("1", 500)))]
+    fn time_decay(#[case] elapsed: i64, #[case] expected: Option<(&str, u32)>) -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![outcome_from(1, 100, 1), outcome_from(2, 200, 1)];
+            pred.0.created_at =
+                Timestamp::new((Local::now() - chrono::Duration::seconds(elapsed)).to_rfc3339())?;
+        }
+
+        streamer
+            .config
+            .0
+            .write()
+            .unwrap()
+            .config
+            .prediction
+            .strategy = Strategy::TimeDecay(TimeDecay {
+            strategy: Detailed {
+                default: DefaultPrediction {
+                    max_percentage: 1.0,
+                    min_percentage: 0.0,
+                    points: s::Points {
+                        max_value: 0,
+                        percent: 0.01,
+                    },
+                },
+                ..Default::default()
+            },
+            start_percentage: 0.1,
+            full_percentage: 0.9,
+        });
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, expected.map(|(o, p)| (o.to_owned(), p)));
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn time_decay_tops_up(#[future] container: TestContainer) -> Result<()> {
+        use common::config::strategy as s;
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("1", ("a", 50_000, None::<String>))]))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.simulate = true;
+        let id = UserId::from_static("1");
+        let mut streamer = get_prediction();
+        streamer.points = 50_000;
+        streamer
+            .predictions
+            .get_mut("pred-key-1")
+            .unwrap()
+            .0
+            .outcomes = vec![outcome_from(1, 100, 1), outcome_from(2, 200, 1)];
+        streamer
+            .config
+            .0
+            .write()
+            .unwrap()
+            .config
+            .prediction
+            .strategy = Strategy::TimeDecay(TimeDecay {
+            strategy: Detailed {
+                default: DefaultPrediction {
+                    max_percentage: 1.0,
+                    min_percentage: 0.0,
+                    points: s::Points {
+                        max_value: 0,
+                        percent: 0.01,
+                    },
+                },
+                ..Default::default()
+            },
+            start_percentage: 0.1,
+            full_percentage: 0.9,
+        });
+        pubsub.streamers.insert(id.clone(), streamer);
+
+        // updates at seconds into the 1500 second window, and the total wagered after each
+        for (elapsed, wagered) in [(100, 0), (760, 254), (1060, 379), (1450, 500), (1450, 500)] {
+            let s = pubsub.streamers.get_mut(&id).unwrap();
+            s.predictions.get_mut("pred-key-1").unwrap().0.created_at =
+                Timestamp::new((Local::now() - chrono::Duration::seconds(elapsed)).to_rfc3339())?;
+            // twitch takes the bets out of the balance
+            s.points = 50_000 - s.wagered.get("pred-key-1").map(|w| w.points).unwrap_or(0);
+
+            pubsub.try_prediction(&id, "pred-key-1").await?;
+            let s = &pubsub.streamers[&id];
+            assert_eq!(
+                s.wagered.get("pred-key-1").map(|w| w.points).unwrap_or(0),
+                wagered,
+                "{elapsed}"
+            );
+            assert_eq!(s.predictions["pred-key-1"].1, wagered > 0);
+        }
+        assert_eq!(pubsub.streamers[&id].wagered["pred-key-1"].outcome_id, "1");
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(CombineRule::Agreement, vec![(0.5, 0.01, false), (0.5, 0.03, false)], Some(("1", 1000)))]
+    #[case(CombineRule::Agreement, vec![(0.5, 0.01, false), (0.5, 0.02, true)], None)]
+    #[case(CombineRule::Average, vec![(0.25, 0.01, false), (0.75, 0.02, true)], Some(("2", 1000)))]
+    #[case(CombineRule::Average, vec![(0.4, 0.01, false), (0.4, 0.03, false), (0.2, 0.02, true)], Some(("1", 1000)))]
+    #[case(CombineRule::HighestWeight, vec![(0.4, 0.01, false), (0.6, 0.02, true)], Some(("2", 1000)))]
+    fn composite_strategy(
+        #[case] combine: CombineRule,
+        #[case] strategies: Vec<(f64, f64, bool)>,
+        #[case] expected: Option<(&str, u32)>,
+    ) -> Result<()> {
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            pred.0.outcomes = vec![outcome_from(1, 3000, 0), outcome_from(2, 7000, 0)];
+        }
+
+        // each strategy bets its percent on either the favourite or the underdog
+        streamer
+            .config
+            .0
+            .write()
+            .unwrap()
+            .config
+            .prediction
+            .strategy = Strategy::Composite(Composite {
+            strategies: strategies
+                .into_iter()
+                .map(|(weight, percent, favourite)| WeightedStrategy {
+                    weight,
+                    strategy: Detailed {
+                        default: DefaultPrediction {
+                            max_percentage: if favourite { 1.0 } else { 0.5 },
+                            min_percentage: if favourite { 0.5 } else { 0.0 },
+                            points: Points {
+                                max_value: 0,
+                                percent,
+                            },
+                        },
+                        ..Default::default()
+                    },
+                })
+                .collect(),
+            combine,
+        });
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, expected.map(|(o, p)| (o.to_owned(), p)));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(false, 1000)]
+    #[case(true, 1500)]
+    fn composite_strategy_chase_odds(
+        #[case] chase_second: bool,
+        #[case] expected: u32,
+    ) -> Result<()> {
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            pred.0.outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 30_000, 14)];
+        }
+        streamer.points = 49000;
+        streamer.record_wager("pred-key-1", "1", 1000, true);
+
+        // only the sub-strategies that chase odds top up the earlier bet
+        let chasing = |chase_odds: bool, percent: f64| WeightedStrategy {
+            weight: 0.5,
+            strategy: Detailed {
+                chase_odds,
+                detailed: Some(vec![DetailedOdds {
+                    _type: OddsComparisonType::Le,
+                    threshold: 0.10,
+                    attempt_rate: 1.0,
+                    points: Points {
+                        max_value: 0,
+                        percent,
+                    },
+                }]),
+                ..Default::default()
+            },
+        };
+        streamer
+            .config
+            .0
+            .write()
+            .unwrap()
+            .config
+            .prediction
+            .strategy = Strategy::Composite(Composite {
+            strategies: vec![chasing(true, 0.04), chasing(chase_second, 0.06)],
+            combine: CombineRule::Average,
+        });
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, Some(("1".to_owned(), expected)));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(ProbabilitySource::Points, Some(("1", 500)))]
+    #[case(ProbabilitySource::Users, Some(("2", 500)))]
+    #[case(ProbabilitySource::Blend(0.5), Some(("1", 1000)))]
+    #[case(ProbabilitySource::Blend(0.75), None)]
+    fn probability_source(
+        #[case] source: ProbabilitySource,
+        #[case] expected: Option<(&str, u32)>,
+    ) -> Result<()> {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        {
+            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
+            streamer.points = 50000;
+            // points favor the first outcome, users favor the second
+            pred.0.outcomes = vec![outcome_from(1, 8_000, 2), outcome_from(2, 2_000, 8)];
+        }
+
+        let mut config_ref = streamer.config.0.write().unwrap();
+        let d = detailed_mut(&mut config_ref.config.prediction.strategy);
+        d.probability_source = source;
+        d.detailed = Some(vec![DetailedOdds {
+            _type: s::OddsComparisonType::Ge,
+            threshold: 0.70,
+            attempt_rate: 1.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        }]);
+        d.default = DefaultPrediction {
+            max_percentage: 0.55,
+            min_percentage: 0.45,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.02,
+            },
+        };
+        drop(config_ref);
+
+        let res = prediction_logic(&streamer, "pred-key-1", None)?;
+        assert_eq!(res, expected.map(|(o, p)| (o.to_owned(), p)));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(true, None)]
+    #[case(false, Some(false))]
+    #[tokio::test]
+    async fn betting_disabled_records_prediction_only(
+        #[case] watch_only: bool,
+        #[case] betting: Option<bool>,
+    ) -> Result<()> {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.simulate = true;
+        pubsub.watch_only = watch_only;
+
+        let mut streamer = get_prediction();
+        streamer.points = 50000;
+        let (mut event, _) = streamer.predictions.remove("pred-key-1").unwrap();
+        event.outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 30_000, 14)];
+        {
+            let mut c = streamer.config.0.write().unwrap();
+            c.config.betting = betting;
+            let d = detailed_mut(&mut c.config.prediction.strategy);
+            d.detailed = Some(vec![DetailedOdds {
+                _type: OddsComparisonType::Le,
+                threshold: 0.10,
+                attempt_rate: 1.0,
+                points: Points {
+                    max_value: 0,
+                    percent: 0.02,
+                },
+            }]);
+        }
+
+        let id = UserId::from_static("1");
+        pubsub.streamers.insert(id.clone(), streamer);
+        pubsub.handle_prediction_event(event, id.clone()).await?;
+
+        let streamer = &pubsub.streamers[&id];
+        assert!(!streamer.predictions["pred-key-1"].1);
+        assert!(streamer.wagered.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn name_lookup_ignores_case() {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.streamers.insert(
+            UserId::from_static("1"),
+            StreamerState::new(true, "somestreamer".to_owned()),
+        );
+        pubsub.config.streamers.insert(
+            "SomeStreamer".to_owned(),
+            ConfigType::Preset("p".to_owned()),
+        );
+
+        for name in ["somestreamer", "SomeStreamer", "SOMESTREAMER"] {
+            assert_eq!(pubsub.get_id_by_name(name), Some("1"));
+            assert!(pubsub.get_by_name(name).is_some());
+            assert!(pubsub.get_by_name_mut(name).is_some());
+            assert_eq!(pubsub.config_key(name).as_deref(), Some("SomeStreamer"));
+        }
+        assert!(pubsub.get_by_name("otherstreamer").is_none());
+    }
+
+    #[test]
+    fn points_goal_crossed_once() -> Result<()> {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.config.points_goal = Some(1_000);
+        let mut events = pubsub.events_tx.subscribe();
+        let id = UserId::from_static("1");
+        let mut streamer = get_prediction();
+        streamer.points = 900;
+        pubsub.streamers.insert(id.clone(), streamer);
+
+        for points in [950, 1_010, 1_100, 1_200] {
+            pubsub.track_points_goal(&id, points)?;
+            pubsub.streamers.get_mut(&id).unwrap().points = points;
+        }
+        assert!(pubsub.streamers[&id].points_goal_reached);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            AppEvent::PointsGoalReached {
+                channel_name: "a".to_owned(),
+                points: 1_010,
+                goal: 1_000,
+            }
+        );
+        assert!(events.try_recv().is_err());
+
+        // the streamer's own goal takes precedence over the global one
+        pubsub.streamers[&id]
+            .config
+            .0
+            .write()
+            .unwrap()
+            .config
+            .points_goal = Some(5_000);
+        pubsub.track_points_goal(&id, 1_300)?;
+        assert!(!pubsub.streamers[&id].points_goal_reached);
+        assert!(events.try_recv().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn circuit_breaker() {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.config.circuit_breaker_failures = Some(3);
+        let id = UserId::from_static("1");
+        pubsub.streamers.insert(id.clone(), get_prediction());
+
+        for _ in 0..2 {
+            pubsub.record_gql_result(&id, false);
+        }
+        assert!(!pubsub.streamers[&id].circuit.is_open());
+        pubsub.record_gql_result(&id, false);
+        assert!(pubsub.streamers[&id].circuit.is_open());
+
+        let state = serde_json::to_value(&pubsub.streamers[&id]).unwrap();
+        assert_eq!(state["circuit"]["failures"], 3);
+        assert!(state["circuit"]["open_until"].is_string());
+
+        // once the cooldown is over a successful request closes the circuit
+        pubsub.streamers.get_mut(&id).unwrap().circuit.open_until =
+            Some(Local::now() - chrono::Duration::seconds(1));
+        assert!(!pubsub.streamers[&id].circuit.is_open());
+        pubsub.record_gql_result(&id, true);
+        assert_eq!(pubsub.streamers[&id].circuit, Default::default());
+    }
+
+    #[tokio::test]
+    async fn blacklisted_prediction_is_skipped() -> Result<()> {
+        use crate::analytics::model::PredictionBetWrapper;
+        use common::config::PredictionBlacklist;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+        pubsub.config.prediction_blacklist = Some(PredictionBlacklist {
+            title_keywords: vec!["Deaths".to_owned()],
+            event_ids: Vec::new(),
+        });
+
+        let id = UserId::from_static("1");
+        let mut streamer = get_prediction();
+        let mut event = streamer.predictions.remove("pred-key-1").unwrap().0;
+        event.title = "Over 3 deaths this round?".to_owned();
+        event.outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 3_000, 4)];
+        pubsub.streamers.insert(id.clone(), streamer);
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(1, "a".to_owned()))
+            .await?;
+
+        // no gql client is configured, so any bet attempt would fail
+        pubsub.handle_prediction_event(event, id.clone()).await?;
+        assert!(pubsub.streamers[&id].predictions["pred-key-1"].1);
+        assert!(pubsub.streamers[&id].wagered.is_empty());
+
+        for req in analytics_rx.drain() {
+            pubsub.analytics.execute(|analytics| req(analytics)).await?;
+        }
+        let stored = pubsub
+            .analytics
+            .execute(|analytics| analytics.get_live_prediction(1, "pred-key-1"))
+            .await?
+            .unwrap();
+        assert_eq!(stored.placed_bet, PredictionBetWrapper::Blacklisted);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn prediction_updates_sample_outcomes() -> Result<()> {
+        use twitch_api::pubsub::{predictions::PredictionsChannelV1, TopicData};
+
+        let (ws_tx, _ws_rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+
+        let mut streamer = get_prediction();
+        let mut event = streamer.predictions.remove("pred-key-1").unwrap().0;
+        event.channel_id = "1".to_owned();
+        pubsub.streamers.insert(UserId::from_static("1"), streamer);
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(1, "a".to_owned()))
+            .await?;
+
+        let message = |points: i64| -> Result<TopicData> {
+            let mut event = event.clone();
+            event.outcomes = vec![outcome_from(1, points, 1), outcome_from(2, 0, 0)];
+            Ok(TopicData::PredictionsChannelV1 {
+                topic: PredictionsChannelV1 { channel_id: 1 },
+                reply: Box::new(serde_json::from_value(serde_json::json!({
+                    "type": "event-updated",
+                    "data": {
+                        "timestamp": Local::now().to_rfc3339(),
+                        "event": event,
+                    },
+                }))?),
+            })
+        };
+
+        pubsub.handle_response(message(100)?).await?;
+        // within the sampling interval
+        pubsub.handle_response(message(200)?).await?;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        pubsub.handle_response(message(300)?).await?;
+
+        for req in analytics_rx.drain() {
+            pubsub.analytics.execute(|analytics| req(analytics)).await?;
+        }
+        let stored = pubsub
+            .analytics
+            .execute(|analytics| analytics.get_live_prediction(1, "pred-key-1"))
+            .await?
+            .unwrap();
+        assert_eq!(
+            stored
+                .outcomes_history
+                .0
+                .iter()
+                .map(|x| x.outcomes[0].total_points)
+                .collect::<Vec<_>>(),
+            vec![100, 300]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_down_sets_last_live_at() -> Result<()> {
+        use twitch_api::pubsub::{
+            video_playback::{VideoPlaybackById, VideoPlaybackReply},
+            TopicData,
+        };
+
+        let (ws_tx, _ws_rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+
+        let id = UserId::from_static("1");
+        pubsub.streamers.insert(id.clone(), get_prediction());
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(1, "a".to_owned()))
+            .await?;
+
+        pubsub
+            .handle_response(TopicData::VideoPlaybackById {
+                topic: VideoPlaybackById { channel_id: 1 },
+                reply: Box::new(VideoPlaybackReply::StreamDown { server_time: 0.0 }),
+            })
+            .await?;
+
+        let last_live_at = pubsub.streamers[&id].last_live_at.unwrap();
+        assert!(Local::now() - last_live_at < chrono::Duration::seconds(5));
+
+        for req in analytics_rx.drain() {
+            pubsub.analytics.execute(|analytics| req(analytics)).await?;
+        }
+        let stored = pubsub
+            .analytics
+            .execute(|analytics| analytics.last_live_at(1))
+            .await?
+            .unwrap();
+        assert!(Local::now().naive_local() - stored < chrono::Duration::seconds(5));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_flap_keeps_topics() -> Result<()> {
+        use common::twitch::ws::Request;
+        use twitch_api::pubsub::{
+            video_playback::{VideoPlaybackById, VideoPlaybackReply},
+            TopicData,
+        };
+
+        let (ws_tx, ws_rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.config.pubsub_unlisten_grace = Some(1);
+        pubsub
+            .streamers
+            .insert(UserId::from_static("1"), get_prediction());
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(1, "a".to_owned()))
+            .await?;
+
+        let playback = |reply| TopicData::VideoPlaybackById {
+            topic: VideoPlaybackById { channel_id: 1 },
+            reply: Box::new(reply),
+        };
+        pubsub
+            .handle_response(playback(VideoPlaybackReply::StreamDown {
+                server_time: 0.0,
+            }))
+            .await?;
+        pubsub
+            .handle_response(playback(VideoPlaybackReply::StreamUp {
+                server_time: 1.0,
+                play_delay: 0,
+            }))
+            .await?;
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        let requests = ws_rx.drain().collect::<Vec<_>>();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|x| matches!(x, Request::Listen(_))));
+
+        // without a stream up the topics are unlistened once the grace period is over
+        pubsub
+            .handle_response(playback(VideoPlaybackReply::StreamDown {
+                server_time: 0.0,
+            }))
+            .await?;
+        assert!(ws_rx.is_empty());
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        let requests = ws_rx.drain().collect::<Vec<_>>();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|x| matches!(x, Request::UnListen(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn raid_notification_listens_to_raids() -> Result<()> {
+        use common::twitch::{
+            onsite::{Notification, NotificationAction},
+            ws::Request,
+        };
+        use twitch_api::pubsub::{raid::Raid, Topics};
+
+        let (ws_tx, ws_rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.streamers.insert(
+            UserId::from_static("1"),
+            StreamerState::new(false, "a".to_owned()),
+        );
+        let pubsub = Arc::new(RwLock::new(pubsub));
+
+        let notification = |notification_type: &str, url: &str| Notification {
+            id: "notif-1".to_owned(),
+            notification_type: notification_type.to_owned(),
+            body: String::new(),
+            actions: vec![NotificationAction {
+                url: url.to_owned(),
+            }],
+        };
+        let gql = gql::Client::default();
+        for n in [
+            notification("raid_incoming", "https://www.twitch.tv/b"),
+            notification("live_stream", "https://www.twitch.tv/a"),
+        ] {
+            super::onsite_notifications::inner(&pubsub, &gql, n).await?;
+        }
+        assert!(ws_rx.is_empty());
+
+        super::onsite_notifications::inner(
+            &pubsub,
+            &gql,
+            notification("raid_incoming", "https://www.twitch.tv/A"),
+        )
+        .await?;
+        assert_eq!(
+            ws_rx.drain().collect::<Vec<_>>(),
+            vec![Request::Listen(Topics::Raid(Raid { channel_id: 1 }))]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn panic_stop_on_rapid_loss() -> Result<()> {
+        use crate::analytics::model::PointsInfo;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, _analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+        pubsub.config.panic_stop = Some(common::config::PanicStop {
+            window_seconds: 600,
+            max_loss_points: None,
+            max_loss_percent: Some(20.0),
+        });
+        let mut events = pubsub.events_tx.subscribe();
+
+        let a = UserId::from_static("1");
+        let b = UserId::from_static("2");
+        let mut streamer = betting_streamer("a", None);
+        streamer.points = 0;
+        pubsub.streamers.insert(a.clone(), streamer);
+        pubsub
+            .streamers
+            .insert(b.clone(), StreamerState::new(false, "b".to_owned()));
+        pubsub
+            .points_earned(&b, 1_000, PointsInfo::Watching)
+            .await?;
+        pubsub
+            .points_earned(&a, 1_000, PointsInfo::Watching)
+            .await?;
+
+        // 10% of the 2000 peak is within the limit
+        pubsub.points_earned(&a, 800, PointsInfo::Watching).await?;
+        assert!(!pubsub.panic_stopped);
+
+        pubsub.points_earned(&a, 550, PointsInfo::Watching).await?;
+        assert!(pubsub.panic_stopped);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            AppEvent::PanicStop {
+                loss: 450,
+                peak: 2_000,
+                window_seconds: 600,
+            }
+        );
+
+        // recovering does not resume bets on its own
+        pubsub
+            .points_earned(&a, 1_000, PointsInfo::Watching)
+            .await?;
+        assert!(pubsub.panic_stopped);
+        pubsub.try_prediction(&a, "pred-key-1").await?;
+        assert!(!pubsub.streamers[&a].predictions["pred-key-1"].1);
+        assert!(events.try_recv().is_err());
+
+        assert!(pubsub.resume_betting());
+        assert!(!pubsub.resume_betting());
+        // the balance before resuming is forgotten
+        pubsub.points_earned(&a, 900, PointsInfo::Watching).await?;
+        pubsub.points_earned(&a, 700, PointsInfo::Watching).await?;
+        assert!(!pubsub.panic_stopped);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn panic_stop_ignores_open_bets() -> Result<()> {
+        use crate::analytics::model::PointsInfo;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, _analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+        pubsub.config.panic_stop = Some(common::config::PanicStop {
+            window_seconds: 600,
+            max_loss_points: Some(100),
+            max_loss_percent: None,
+        });
+
+        let id = UserId::from_static("1");
+        pubsub
+            .streamers
+            .insert(id.clone(), StreamerState::new(false, "a".to_owned()));
+        pubsub
+            .points_earned(&id, 1_000, PointsInfo::Watching)
+            .await?;
+
+        // twitch takes the stake out of the balance until the predictions resolve
+        let s = pubsub.streamers.get_mut(&id).unwrap();
+        s.record_wager("event-1", "1", 300, true);
+        s.record_wager("event-2", "1", 200, true);
+        pubsub.points_earned(&id, 500, PointsInfo::Watching).await?;
+        assert!(!pubsub.panic_stopped);
+
+        // a simulated bet never left the balance, so its end is no loss
+        let s = pubsub.streamers.get_mut(&id).unwrap();
+        s.record_wager("event-3", "1", 400, false);
+        pubsub.points_earned(&id, 500, PointsInfo::Watching).await?;
+        pubsub
+            .streamers
+            .get_mut(&id)
+            .unwrap()
+            .wagered
+            .remove("event-3");
+        pubsub.points_earned(&id, 500, PointsInfo::Watching).await?;
+        assert!(!pubsub.panic_stopped);
+
+        // losing one of them is a loss
+        pubsub
+            .streamers
+            .get_mut(&id)
+            .unwrap()
+            .wagered
+            .remove("event-1");
+        pubsub.points_earned(&id, 500, PointsInfo::Watching).await?;
+        assert!(pubsub.panic_stopped);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn points_earned_reason() -> Result<()> {
+        use crate::analytics::model::PointsInfo;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+
+        let id = UserId::from_static("1");
+        pubsub.streamers.insert(id.clone(), get_prediction());
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(1, "a".to_owned()))
+            .await?;
+
+        let from = Local::now();
+        pubsub
+            .points_earned(&id, 1010, PointsInfo::from_reason_code("WATCH"))
+            .await?;
+        pubsub
+            .points_earned(&id, 1260, PointsInfo::from_reason_code("RAID"))
+            .await?;
+        assert_eq!(pubsub.streamers[&id].points, 1260);
+
+        for req in analytics_rx.drain() {
+            pubsub.analytics.execute(|analytics| req(analytics)).await?;
+        }
+        let timeline = pubsub
+            .analytics
+            .execute(|analytics| analytics.timeline(from, Local::now(), &[1]))
+            .await?;
+        let reasons = timeline
+            .into_iter()
+            .map(|x| x.point.points_info)
+            .collect::<Vec<_>>();
+        assert_eq!(reasons, vec![PointsInfo::Watching, PointsInfo::Raid]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancelled_prediction_is_not_bet_on() -> Result<()> {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.simulate = true;
+
+        let mut streamer = get_prediction();
+        streamer.points = 50000;
+        streamer
+            .predictions
+            .get_mut("pred-key-1")
+            .unwrap()
+            .0
+            .outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 30_000, 14)];
+        detailed_mut(
+            &mut streamer
+                .config
+                .0
+                .write()
+                .unwrap()
+                .config
+                .prediction
+                .strategy,
+        )
+        .detailed = Some(vec![DetailedOdds {
+            _type: OddsComparisonType::Le,
+            threshold: 0.10,
+            attempt_rate: 1.0,
+            points: Points {
+                max_value: 0,
+                percent: 0.02,
+            },
+        }]);
+        assert!(prediction_logic(&streamer, "pred-key-1", None)?.is_some());
+
+        let id = UserId::from_static("1");
+        pubsub.streamers.insert(id.clone(), streamer);
+
+        assert!(!pubsub.cancel_prediction("unknown"));
+        assert!(pubsub.cancel_prediction("pred-key-1"));
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+
+        let streamer = &pubsub.streamers[&id];
+        assert!(streamer.predictions["pred-key-1"].1);
+        assert!(streamer.wagered.is_empty());
+
+        Ok(())
+    }
+
+    macro_rules! watch_stream_eq {
+        ($watching_uri:expr,$eq:expr) => {
+            let res: Vec<UserId> = reqwest::get(&$watching_uri).await?.json().await?;
+            assert_eq!(res, $eq)
+        };
+        ($watching_uri:expr,$eq:expr,$user_ids:tt) => {
+            let res: Vec<UserId> = reqwest::get(&$watching_uri).await?.json().await?;
+            assert_eq!(res.len(), $eq.len());
+            for item in res {
+                assert!($user_ids.contains(&item));
+            }
+        };
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn watch_stream_on_live(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+
+        let (ws_tx, _) = unbounded();
+        let (_, rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.spade_url = Some(format!("http://localhost:{}/spade", container.port));
+        pubsub.user_id = "1".to_string();
+
+        let user_ids = vec![UserId::from_static("1"), UserId::from_static("2")];
+        pubsub.streamers = HashMap::from([
+            (
+                user_ids[0].clone(),
+                StreamerState::new(true, user_ids[0].as_str().to_owned()),
+            ),
+            (
+                user_ids[1].clone(),
+                StreamerState::new(true, user_ids[1].as_str().to_owned()),
+            ),
+        ]);
+        pubsub.config.streamers = user_ids
+            .iter()
+            .map(|x| {
+                (
+                    x.to_string(),
+                    ConfigType::Specific(StreamerConfig::default()),
+                )
+            })
+            .collect();
+
+        let pubsub = Arc::new(RwLock::new(pubsub.clone()));
+        let watching_uri = format!("http://localhost:{}/watching", container.port);
+        let mut watch_streak = Vec::new();
+
+        super::watch_stream::inner(&pubsub, &mut watch_streak, true, &rx).await?;
+        watch_stream_eq!(watching_uri, user_ids, user_ids);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    #[rustfmt::skip]
+    async fn watch_stream_with_watch_streak(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+
+        let (ws_tx, _) = unbounded();
+        let (tx, rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.spade_url = Some(format!("http://localhost:{}/spade", container.port));
+        pubsub.user_id = "1".to_string();
+        pubsub.config.watch_streak = Some(true);
+
+        let user_ids: Vec<UserId> = (1..4).map(|x| UserId::from_str(&x.to_string()).unwrap()).collect();
+        pubsub.streamers = user_ids.iter().enumerate().map(|(idx, x)| (x.clone(), StreamerState::new(idx == 0, x.to_string()))).collect();
+        pubsub.config.streamers = user_ids.iter().map(|x| { (x.to_string(), ConfigType::Specific(StreamerConfig::default()) )}).collect();
+
+        let pubsub = Arc::new(RwLock::new(pubsub.clone()));
+        let watching_uri = format!("http://localhost:{}/watching", container.port);
+
+        let mut watch_streak = Vec::new();
+        let use_watch_streak = true;
+
+        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+        watch_stream_eq!(watching_uri, [user_ids[0].clone()]);
+
+        let client = reqwest::Client::new();
+
+        pubsub.write().await.streamers.get_mut(&user_ids[1]).unwrap().info.live = true;
+        tx.send_async(user_ids[1].clone()).await?;
+        client.delete(&watching_uri).send().await?;
+        for _ in 0..30 {
+            super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+            watch_stream_eq!(watching_uri, user_ids[0..2], user_ids);
+        }
+
+        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+        watch_stream_eq!(watching_uri, user_ids[0..2], user_ids);
+
+        pubsub.write().await.streamers.get_mut(&user_ids[2]).unwrap().info.live = true;
+        tx.send_async(user_ids[2].clone()).await?;
+        client.delete(&watching_uri).send().await?;
+        for _ in 0..30 {
+            super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+            watch_stream_eq!(watching_uri, [user_ids[0].clone(), user_ids[2].clone()], user_ids);
+        }
+
+        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+        watch_stream_eq!(watching_uri, [user_ids[0].clone(), user_ids[2].clone()], user_ids);
+
+        pubsub.write().await.config.watch_priority = Some(vec![user_ids[2].as_str().to_owned()]);
+        client.delete(&watching_uri).send().await?;
+        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+        watch_stream_eq!(watching_uri, [user_ids[0].clone(), user_ids[2].clone()], user_ids);
+
+        pubsub.write().await.streamers.get_mut(&user_ids[2]).unwrap().info.live = false;
+        client.delete(&watching_uri).send().await?;
+        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+        watch_stream_eq!(watching_uri, user_ids[0..2], user_ids);
+
+        pubsub.write().await.streamers.get_mut(&user_ids[0]).unwrap().info.live = false;
+        client.delete(&watching_uri).send().await?;
+        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+        watch_stream_eq!(watching_uri, user_ids[1..2], user_ids);
+
+        pubsub.write().await.streamers.get_mut(&user_ids[1]).unwrap().info.live = false;
+        client.delete(&watching_uri).send().await?;
+        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
+        watch_stream_eq!(watching_uri, Vec::<UserId>::new(), user_ids);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn watched_reasons(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+
+        let (ws_tx, _) = unbounded();
+        let (tx, rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.spade_url = Some(format!("http://localhost:{}/spade", container.port));
+        pubsub.user_id = "1".to_string();
+        let user_ids: Vec<UserId> = (96..99)
+            .map(|x| UserId::from_str(&x.to_string()).unwrap())
+            .collect();
+        pubsub.streamers = user_ids
+            .iter()
+            .map(|x| (x.clone(), StreamerState::new(true, x.to_string())))
+            .collect();
+        pubsub.config.streamers = user_ids
+            .iter()
+            .map(|x| {
+                (
+                    x.to_string(),
+                    ConfigType::Specific(StreamerConfig::default()),
+                )
+            })
+            .collect();
+        pubsub.config.watch_priority = Some(vec![user_ids[1].to_string()]);
+        let pubsub = Arc::new(RwLock::new(pubsub));
+        let watched = |entries: [(&UserId, WatchReason); 2]| {
+            entries
+                .map(|(id, reason)| WatchedStreamer {
+                    channel_name: id.to_string(),
+                    reason,
+                })
+                .to_vec()
+        };
+
+        let mut watch_streak = Vec::new();
+        super::watch_stream::inner(&pubsub, &mut watch_streak, true, &rx).await?;
+        assert_eq!(
+            pubsub.read().await.watched,
+            watched([
+                (&user_ids[1], WatchReason::Priority),
+                (&user_ids[0], WatchReason::Fill)
+            ])
+        );
+
+        tx.send_async(user_ids[2].clone()).await?;
+        super::watch_stream::inner(&pubsub, &mut watch_streak, true, &rx).await?;
+        assert_eq!(
+            pubsub.read().await.watched,
+            watched([
+                (&user_ids[2], WatchReason::Streak),
+                (&user_ids[1], WatchReason::Priority)
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn watch_streak_rotation(#[future] container: TestContainer) -> Result<()> {
+        use crate::analytics::model::PointsInfo;
+        let container = container.await;
+
+        let (ws_tx, _) = unbounded();
+        let (tx, rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.spade_url = Some(format!("http://localhost:{}/spade", container.port));
+        pubsub.user_id = "1".to_string();
+        pubsub.config.watch_streak_rounds = Some(3);
+        let (a, b) = (UserId::from_static("81"), UserId::from_static("82"));
+        for id in [&a, &b] {
+            pubsub
+                .streamers
+                .insert(id.clone(), StreamerState::new(true, id.to_string()));
+        }
+        let pubsub = Arc::new(RwLock::new(pubsub));
+
+        // rotated out once watched for the configured rounds
+        let mut watch_streak = Vec::new();
+        tx.send_async(a.clone()).await?;
+        for _ in 0..2 {
+            super::watch_stream::inner(&pubsub, &mut watch_streak, true, &rx).await?;
+        }
+        assert_eq!(watch_streak, vec![(a.clone(), 2)]);
+        super::watch_stream::inner(&pubsub, &mut watch_streak, true, &rx).await?;
+        assert!(watch_streak.is_empty());
+
+        // and as soon as the bonus is earned
+        tx.send_async(b.clone()).await?;
+        super::watch_stream::inner(&pubsub, &mut watch_streak, true, &rx).await?;
+        assert_eq!(watch_streak, vec![(b.clone(), 1)]);
+        pubsub
+            .write()
+            .await
+            .points_earned(&b, 1450, PointsInfo::from_reason_code("WATCH_STREAK"))
+            .await?;
+        super::watch_stream::inner(&pubsub, &mut watch_streak, true, &rx).await?;
+        assert!(watch_streak.is_empty());
+
+        reqwest::Client::new()
+            .delete(format!("http://localhost:{}/watching", container.port))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn claim_requests(
+        base_url: &str,
+        pubsub: &Arc<RwLock<PubSub>>,
+        claim_mode: ClaimMode,
+        chunk_size: usize,
+    ) -> Result<u32> {
+        let client = reqwest::Client::new();
+        let channel_points = ["1", "2", "3"]
+            .map(|x| (x, (x, 100, Some(format!("claim-{x}")))))
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&channel_points)
+            .send()
+            .await?;
+        client
+            .delete(format!("{base_url}/gql_requests"))
+            .send()
+            .await?;
+
+        pubsub.write().await.config.claim_mode = Some(claim_mode);
+        let gql =
+            gql::Client::new(String::new(), format!("{base_url}/gql")).with_chunk_size(chunk_size);
+        super::update_and_claim_points::inner(pubsub, &gql).await?;
+        for s in pubsub.read().await.streamers.values() {
+            assert_eq!(s.points, 150);
+        }
+
+        Ok(client
+            .get(format!("{base_url}/gql_requests"))
+            .send()
+            .await?
+            .json()
+            .await?)
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn claim_points_batched(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        for id in ["1", "2", "3"] {
+            pubsub.streamers.insert(
+                UserId::from_static(id),
+                StreamerState::new(true, id.to_owned()),
+            );
+            pubsub
+                .analytics
+                .execute(|analytics| analytics.insert_streamer(id.parse().unwrap(), id.to_owned()))
+                .await?;
+        }
+        let pubsub = Arc::new(RwLock::new(pubsub));
+
+        // one request for the balances, then one per claim
+        assert_eq!(
+            claim_requests(&base_url, &pubsub, ClaimMode::Immediate, 30).await?,
+            4
+        );
+        assert_eq!(
+            claim_requests(&base_url, &pubsub, ClaimMode::Batched, 30).await?,
+            2
+        );
+        // claims are chunked like the balances
+        assert_eq!(
+            claim_requests(&base_url, &pubsub, ClaimMode::Batched, 2).await?,
+            4
+        );
+
+        // a request per balance and claim, only the claim of the failed chunk is sent again
+        reqwest::Client::new()
+            .post(format!("{base_url}/claim_failures"))
+            .json(&1)
+            .send()
+            .await?;
+        let before = pubsub.read().await.claims;
+        assert_eq!(
+            claim_requests(&base_url, &pubsub, ClaimMode::Batched, 1).await?,
+            7
+        );
+        let after = pubsub.read().await.claims;
+        assert_eq!(
+            (
+                after.succeeded - before.succeeded,
+                after.failed - before.failed
+            ),
+            (3, 1)
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn polled_points_skip_pushed_balance(#[future] container: TestContainer) -> Result<()> {
+        use crate::analytics::model::PointsInfo;
+
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("11", ("k", 1010, None::<String>))]))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+        let id = UserId::from_static("11");
+        pubsub
+            .streamers
+            .insert(id.clone(), StreamerState::new(true, "k".to_owned()));
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(11, "k".to_owned()))
+            .await?;
+
+        let from = Local::now();
+        pubsub
+            .points_earned(&id, 1010, PointsInfo::from_reason_code("RAID"))
+            .await?;
+        let analytics = pubsub.analytics.clone();
+        let pubsub = Arc::new(RwLock::new(pubsub));
+        let gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        super::update_and_claim_points::inner(&pubsub, &gql).await?;
+
+        for req in analytics_rx.drain() {
+            analytics.execute(|analytics| req(analytics)).await?;
+        }
+        let timeline = analytics
+            .execute(|analytics| analytics.timeline(from, Local::now(), &[11]))
+            .await?;
+        let reasons = timeline
+            .into_iter()
+            .map(|x| x.point.points_info)
+            .collect::<Vec<_>>();
+        assert_eq!(reasons, vec![PointsInfo::Raid]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_for_lone_streamer() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // the channel has no community points
+        let requests = Arc::new(AtomicUsize::new(0));
+        let router = axum::Router::new().route(
+            "/gql",
+            axum::routing::post({
+                let requests = requests.clone();
+                move || async move {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    axum::Json(serde_json::json!({ "data": { "community": null } }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.config.circuit_breaker_failures = Some(1);
+        let gql = gql::Client::new(String::new(), format!("http://{address}/gql"));
+        pubsub.gql = gql.clone();
+        let id = UserId::from_static("1");
+        pubsub
+            .streamers
+            .insert(id.clone(), betting_streamer("a", Some(true)));
+        let pubsub = Arc::new(RwLock::new(pubsub));
+
+        assert!(super::update_and_claim_points::inner(&pubsub, &gql)
+            .await
+            .is_err());
+        let mut writer = pubsub.write().await;
+        assert!(writer.streamers[&id].circuit.is_open());
+
+        // bets are not attempted while the circuit is open
+        let before = requests.load(Ordering::SeqCst);
+        writer.try_prediction(&id, "pred-key-1").await?;
+        assert_eq!(requests.load(Ordering::SeqCst), before);
+        assert_eq!(
+            writer.last_decision(&id).unwrap().skip_reason.as_deref(),
+            Some("circuit_open")
+        );
+
+        server.abort();
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failed_claim_is_retried(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("1", ("a", 100, Some("claim-1")))]))
+            .send()
+            .await?;
+        client
+            .post(format!("{base_url}/claim_failures"))
+            .json(&1)
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let id = UserId::from_static("1");
+        pubsub
+            .streamers
+            .insert(id.clone(), StreamerState::new(true, "a".to_owned()));
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(1, "a".to_owned()))
+            .await?;
+        let pubsub = Arc::new(RwLock::new(pubsub));
+
+        let gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        super::update_and_claim_points::inner(&pubsub, &gql).await?;
+
+        let reader = pubsub.read().await;
+        assert_eq!(reader.streamers[&id].points, 150);
+        assert_eq!(
+            reader.claims,
+            ClaimStats {
+                succeeded: 1,
+                failed: 1
+            }
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn bet_not_debited_is_retried(#[future] container: TestContainer) -> Result<()> {
+        use common::config::strategy as s;
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        // the mock accepts bets without ever debiting the balance
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("1", ("a", 50_000, None::<String>))]))
+            .send()
+            .await?;
+        client
+            .delete(format!("{base_url}/predictions_made"))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.config.confirm_bets = Some(true);
+
+        let mut streamer = get_prediction();
+        streamer
+            .predictions
+            .get_mut("pred-key-1")
+            .unwrap()
+            .0
+            .outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 3_000, 4)];
+        detailed_mut(
+            &mut streamer
+                .config
+                .0
+                .write()
+                .unwrap()
+                .config
+                .prediction
+                .strategy,
+        )
+        .default = DefaultPrediction {
+            max_percentage: 1.0,
+            min_percentage: 0.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        };
+        let id = UserId::from_static("1");
+        pubsub.streamers.insert(id.clone(), streamer);
+
+        for _ in 0..2 {
+            pubsub.try_prediction(&id, "pred-key-1").await?;
+            assert!(!pubsub.streamers[&id].predictions["pred-key-1"].1);
+            assert!(pubsub.streamers[&id].wagered.is_empty());
+            assert_eq!(
+                pubsub.last_decision(&id).unwrap().skip_reason.as_deref(),
+                Some("bet_not_debited")
+            );
+        }
+
+        // without confirm_bets the bet counts as placed, the balance after it is kept but the stake is still in it
+        pubsub.config.confirm_bets = None;
+        pubsub.streamers.get_mut(&id).unwrap().points = 49_000;
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+        let s = &pubsub.streamers[&id];
+        assert!(s.predictions["pred-key-1"].1);
+        assert_eq!(s.points, 50_000);
+        assert_eq!(s.wagered["pred-key-1"].debited, 0);
+        assert_eq!(
+            pubsub.last_decision(&id).unwrap().points,
+            Some(s.wagered["pred-key-1"].points)
+        );
+
+        let made: u32 = client
+            .get(format!("{base_url}/predictions_made"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(made, 3);
+        Ok(())
+    }
+
+    /// Streamer named `name` that always bets on its prediction
+    fn betting_streamer(name: &str, simulate: Option<bool>) -> StreamerState {
+        use common::config::strategy as s;
+        let mut streamer = get_prediction();
+        streamer.info.channel_name = name.to_owned();
+        streamer.points = 10_000;
+        streamer
+            .predictions
+            .get_mut("pred-key-1")
+            .unwrap()
+            .0
+            .outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 3_000, 4)];
+        let mut config = streamer.config.0.write().unwrap();
+        config.config.simulate = simulate;
+        let d = detailed_mut(&mut config.config.prediction.strategy);
+        d.default = DefaultPrediction {
+            max_percentage: 1.0,
+            min_percentage: 0.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        };
+        drop(config);
+        streamer
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn last_decision_trace(#[future] container: TestContainer) -> Result<()> {
+        use common::config::filters::Filter;
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("1", ("a", 50_000, None::<String>))]))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+
+        let id = UserId::from_static("1");
+        let streamer = betting_streamer("a", Some(true));
+        // 6 users bet on the prediction
+        streamer.config.0.write().unwrap().config.prediction.filters =
+            vec![Filter::TotalUsers(5), Filter::TotalUsers(10)];
+        pubsub.streamers.insert(id.clone(), streamer);
+        assert!(pubsub.last_decision(&id).is_none());
+
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+        assert!(!pubsub.streamers[&id].predictions["pred-key-1"].1);
+        let trace = pubsub.last_decision(&id).unwrap();
+        assert_eq!(trace.event_id, "pred-key-1");
+        assert_eq!(trace.skip_reason.as_deref(), Some("filter"));
+        assert_eq!(
+            trace.filters.iter().map(|x| x.passed).collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        assert!(matches!(trace.filters[1].filter, Filter::TotalUsers(10)));
+        assert_eq!(
+            trace.odds.iter().map(|x| x.odds).collect::<Vec<_>>(),
+            vec![0.25, 0.75]
+        );
+        assert!(trace.strategy.is_none() && trace.points.is_none());
+
+        pubsub.streamers[&id]
+            .config
+            .0
+            .write()
+            .unwrap()
+            .config
+            .prediction
+            .filters = vec![Filter::TotalUsers(5)];
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+        let wager = pubsub.streamers[&id].wagered["pred-key-1"].clone();
+        let trace = pubsub.last_decision(&id).unwrap();
+        assert_eq!(trace.skip_reason, None);
+        assert_eq!(trace.filters.len(), 1);
+        assert!(trace.filters[0].passed);
+        assert_eq!(trace.strategy.as_deref(), Some("primary"));
+        assert_eq!(trace.outcome_id, Some(wager.outcome_id));
+        assert_eq!(trace.points, Some(wager.points));
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn simulate_per_streamer(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([
+                ("1", ("a", 50_000, None::<String>)),
+                ("2", ("b", 50_000, None::<String>)),
+            ]))
+            .send()
+            .await?;
+        client
+            .delete(format!("{base_url}/predictions_made"))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+
+        let real = UserId::from_static("1");
+        let simulated = UserId::from_static("2");
+        pubsub
+            .streamers
+            .insert(real.clone(), betting_streamer("a", None));
+        pubsub
+            .streamers
+            .insert(simulated.clone(), betting_streamer("b", Some(true)));
+
+        pubsub.try_prediction(&simulated, "pred-key-1").await?;
+        assert!(pubsub.streamers[&simulated].predictions["pred-key-1"].1);
+        let made: u32 = client
+            .get(format!("{base_url}/predictions_made"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(made, 0);
+
+        pubsub.try_prediction(&real, "pred-key-1").await?;
+        assert!(pubsub.streamers[&real].predictions["pred-key-1"].1);
+        let made: u32 = client
+            .get(format!("{base_url}/predictions_made"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(made, 1);
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn prediction_round_trip(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let mut event = get_prediction().predictions["pred-key-1"].0.clone();
+        event.id = "round-trip-1".to_owned();
+        event.channel_id = "7".to_owned();
+        event.outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 3_000, 4)];
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("7", ("g", 10_000, None::<String>))]))
+            .send()
+            .await?;
+        client
+            .post(format!("{base_url}/active_predictions"))
+            .json(&HashMap::from([("7", ("g", vec![event.clone()]))]))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        let id = UserId::from_static("7");
+        let mut streamer = betting_streamer("g", None);
+        streamer.predictions.clear();
+        streamer.points = 10_000;
+        pubsub.streamers.insert(id.clone(), streamer);
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(7, "g".to_owned()))
+            .await?;
+
+        // the prediction is found on the channel, and bet on
+        let active = pubsub.gql.channel_points_context(&["g"]).await?;
+        assert_eq!(active, vec![vec![(event.clone(), false)]]);
+        let (event, _) = active[0][0].clone();
+        pubsub
+            .handle_prediction_event(event.clone(), id.clone())
+            .await?;
+        assert!(pubsub.streamers[&id].predictions["round-trip-1"].1);
+        let active = pubsub.gql.channel_points_context(&["g"]).await?;
+        assert!(active[0][0].1);
+
+        let mut ended = event;
+        ended.ended_at = Some(Timestamp::new(Local::now().to_rfc3339()).unwrap());
+        ended.winning_outcome_id = Some("2".to_owned());
+        pubsub.handle_prediction_event(ended, id.clone()).await?;
+        assert!(pubsub.streamers[&id].predictions.is_empty());
+
+        for req in analytics_rx.drain() {
+            pubsub.analytics.execute(|analytics| req(analytics)).await?;
+        }
+        let from = Local::now() - chrono::Duration::hours(1);
+        let to = Local::now() + chrono::Duration::hours(1);
+        let stored = pubsub
+            .analytics
+            .execute(|analytics| analytics.predictions_export(from, to, &[7]))
+            .await?;
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].winning_outcome_id.as_deref(), Some("2"));
+        let bet = stored[0].bet_outcome_id.clone().unwrap();
+        assert_eq!(stored[0].won, Some(bet == "2"));
+        assert!(stored[0].bet_points > 0);
+        assert!(stored[0].closed_at.is_some());
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn bet_allowlist(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([
+                ("1", ("a", 50_000, None::<String>)),
+                ("2", ("b", 50_000, None::<String>)),
+            ]))
+            .send()
+            .await?;
+        client
+            .delete(format!("{base_url}/predictions_made"))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.config.bet_allowlist = Some(vec!["A".to_owned()]);
+
+        // explicitly betting for real does not get around the allowlist
+        let allowed = UserId::from_static("1");
+        let other = UserId::from_static("2");
+        pubsub
+            .streamers
+            .insert(allowed.clone(), betting_streamer("a", None));
+        pubsub
+            .streamers
+            .insert(other.clone(), betting_streamer("b", Some(false)));
+        assert!(!pubsub.is_simulated(&pubsub.streamers[&allowed])?);
+        assert!(pubsub.is_simulated(&pubsub.streamers[&other])?);
+
+        pubsub.try_prediction(&other, "pred-key-1").await?;
+        assert!(pubsub.streamers[&other].predictions["pred-key-1"].1);
+        pubsub.try_prediction(&allowed, "pred-key-1").await?;
+        assert!(pubsub.streamers[&allowed].predictions["pred-key-1"].1);
+
+        let made: u32 = client
+            .get(format!("{base_url}/predictions_made"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(made, 1);
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn alert_on_large_bet(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([
+                ("1", ("a", 50_000, None::<String>)),
+                ("2", ("b", 200_000, None::<String>)),
+            ]))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.simulate = true;
+        pubsub.config.alert_on_bet_above = Some(1000);
+        let mut events = pubsub.events_tx.subscribe();
+
+        // both bet 1% of their balance
+        let small = UserId::from_static("1");
+        let large = UserId::from_static("2");
+        for (id, name, points) in [(&small, "a", 50_000), (&large, "b", 200_000)] {
+            let mut streamer = betting_streamer(name, None);
+            streamer.points = points;
+            pubsub.streamers.insert(id.clone(), streamer);
+        }
+
+        pubsub.try_prediction(&small, "pred-key-1").await?;
+        assert!(matches!(
+            events.try_recv()?,
+            AppEvent::BetPlaced { points: 500, .. }
+        ));
+        assert!(events.try_recv().is_err());
+
+        pubsub.try_prediction(&large, "pred-key-1").await?;
+        assert_eq!(
+            events.try_recv()?,
+            AppEvent::LargeBet {
+                channel_name: "b".to_owned(),
+                event_id: "pred-key-1".to_owned(),
+                outcome_id: "1".to_owned(),
+                points: 2000,
+                threshold: 1000,
+            }
+        );
+        assert!(matches!(
+            events.try_recv()?,
+            AppEvent::BetPlaced { points: 2000, .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn points_jitter() {
+        let mut rng = StdRng::seed_from_u64(184);
+        let jittered = (0..1000)
+            .map(|_| jitter_points(1000, 10.0, 100_000, None, &mut rng))
+            .collect::<Vec<_>>();
+        assert!(jittered.iter().all(|x| (900..=1100).contains(x)));
+        assert!(jittered.iter().any(|x| *x < 1000));
+        assert!(jittered.iter().any(|x| *x > 1000));
+
+        // never raised above the strategy's max_value or the balance
+        for _ in 0..1000 {
+            let points = jitter_points(1000, 10.0, 100_000, Some(1050), &mut rng);
+            assert!((900..=1050).contains(&points), "{points}");
+            let points = jitter_points(1000, 10.0, 100_000, Some(1000), &mut rng);
+            assert!((900..=1000).contains(&points), "{points}");
+            let points = jitter_points(100, 50.0, 100, None, &mut rng);
+            assert!((50..=100).contains(&points), "{points}");
+        }
+        assert_eq!(jitter_points(1, 100.0, 1, None, &mut rng), 1);
+    }
+
+    #[test]
+    fn strategy_max_bet() {
+        let points = |max_value| Points {
+            max_value,
+            percent: 0.01,
+        };
+        let mut strategy = Strategy::default();
+        let d = detailed_mut(&mut strategy);
+        d.default.points = points(2000);
+        d.detailed = Some(vec![DetailedOdds {
+            _type: OddsComparisonType::Le,
+            threshold: 0.10,
+            attempt_rate: 1.0,
+            points: points(500),
+        }]);
+        assert_eq!(strategy.max_bet(), Some(2000));
+
+        // one uncapped odds config leaves the strategy bounded only by the balance
+        detailed_mut(&mut strategy).detailed.as_mut().unwrap()[0].points = points(0);
+        assert_eq!(strategy.max_bet(), None);
+    }
+
+    #[tokio::test]
+    async fn prediction_cooldown() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // every bet is rejected with a cooldown
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let router = axum::Router::new().route(
+            "/gql",
+            axum::routing::post({
+                let attempts = attempts.clone();
+                move || async move {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    axum::Json(serde_json::json!({
+                        "data": {
+                            "makePrediction": {
+                                "error": { "code": "USER_PREDICTION_COOLDOWN", "cooldownSeconds": 600 }
+                            }
+                        }
+                    }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("http://{address}/gql"));
+        let (a, b) = (UserId::from_static("1"), UserId::from_static("2"));
+        pubsub
+            .streamers
+            .insert(a.clone(), betting_streamer("a", Some(false)));
+        pubsub
+            .streamers
+            .insert(b.clone(), betting_streamer("b", Some(false)));
+
+        pubsub.try_prediction(&a, "pred-key-1").await?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        let until = pubsub.bet_cooldown_until.unwrap();
+        assert!(until > Instant::now() + Duration::from_secs(590));
+        assert!(!pubsub.streamers[&a].predictions["pred-key-1"].1);
+        assert_eq!(
+            pubsub.last_decision(&a).unwrap().skip_reason.as_deref(),
+            Some("prediction_cooldown")
+        );
+
+        // suppressed on every streamer while the cooldown lasts
+        pubsub.try_prediction(&a, "pred-key-1").await?;
+        pubsub.try_prediction(&b, "pred-key-1").await?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        pubsub.bet_cooldown_until = Some(Instant::now() - Duration::from_secs(1));
+        pubsub.try_prediction(&b, "pred-key-1").await?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn subscriber_only_prediction() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let router = axum::Router::new().route(
+            "/gql",
+            axum::routing::post({
+                let attempts = attempts.clone();
+                move || async move {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    axum::Json(serde_json::json!({
+                        "data": {
+                            "makePrediction": {
+                                "error": { "code": "NOT_SUBSCRIBER" }
+                            }
+                        }
+                    }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("http://{address}/gql"));
+        let id = UserId::from_static("1");
+        pubsub
+            .streamers
+            .insert(id.clone(), betting_streamer("a", Some(false)));
+
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(pubsub.streamers[&id].predictions["pred-key-1"].1);
+        assert!(pubsub.streamers[&id].wagered.is_empty());
+        assert_eq!(
+            pubsub.last_decision(&id).unwrap().skip_reason.as_deref(),
+            Some("subscriber_only")
+        );
+
+        // the event is skipped for good
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn no_bet_close_to_lock() -> Result<()> {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let mut streamer = betting_streamer("a", Some(true));
+        let event = &mut streamer.predictions.get_mut("pred-key-1").unwrap().0;
+        event.created_at = Timestamp::new(
+            (Local::now() - chrono::Duration::seconds(event.prediction_window_seconds - 1))
+                .to_rfc3339(),
+        )?;
+        assert!(locks_too_soon(event, Local::now()));
+        assert!(!locks_too_soon(
+            event,
+            Local::now() - chrono::Duration::seconds(10)
+        ));
+        let id = UserId::from_static("1");
+        pubsub.streamers.insert(id.clone(), streamer);
+
+        // the gql client has no url, so any bet attempt would fail
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+        assert!(!pubsub.streamers[&id].predictions["pred-key-1"].1);
+        assert!(pubsub.streamers[&id].wagered.is_empty());
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn skip_predictions_at_startup(#[future] container: TestContainer) -> Result<()> {
+        use common::config::strategy as s;
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("1", ("a", 50_000, None::<String>))]))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.config.skip_predictions_at_startup = Some(true);
+
+        let mut streamer = get_prediction();
+        streamer.points = 50_000;
+        let mut event = streamer.predictions.remove("pred-key-1").unwrap().0;
+        event.outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 3_000, 4)];
+        detailed_mut(
+            &mut streamer
+                .config
+                .0
+                .write()
+                .unwrap()
+                .config
+                .prediction
+                .strategy,
+        )
+        .default = DefaultPrediction {
+            max_percentage: 1.0,
+            min_percentage: 0.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        };
+        let id = UserId::from_static("1");
+        pubsub.streamers.insert(id.clone(), streamer);
+
+        let mut before = event.clone();
+        before.id = "before-launch".to_owned();
+        before.created_at =
+            Timestamp::new((pubsub.launched_at - chrono::Duration::seconds(60)).to_rfc3339())?;
+        let mut after = event;
+        after.id = "after-launch".to_owned();
+        after.created_at =
+            Timestamp::new((pubsub.launched_at + chrono::Duration::seconds(1)).to_rfc3339())?;
+
+        pubsub.handle_prediction_event(before, id.clone()).await?;
+        pubsub.handle_prediction_event(after, id.clone()).await?;
+
+        let predictions = &pubsub.streamers[&id].predictions;
+        assert!(!predictions["before-launch"].1);
+        assert!(predictions["after-launch"].1);
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn global_min_balance(#[future] container: TestContainer) -> Result<()> {
+        use common::config::strategy as s;
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("1", ("a", 400, None::<String>))]))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.config.global_min_balance = Some(1000);
+
+        let mut streamer = get_prediction();
+        streamer.points = 400;
+        streamer
+            .predictions
+            .get_mut("pred-key-1")
+            .unwrap()
+            .0
+            .outcomes = vec![outcome_from(1, 1_000, 2), outcome_from(2, 3_000, 4)];
+        detailed_mut(
+            &mut streamer
+                .config
+                .0
+                .write()
+                .unwrap()
+                .config
+                .prediction
+                .strategy,
+        )
+        .default = DefaultPrediction {
+            max_percentage: 1.0,
+            min_percentage: 0.0,
+            points: s::Points {
+                max_value: 0,
+                percent: 0.01,
+            },
+        };
+        let id = UserId::from_static("1");
+        let other = UserId::from_static("2");
+        pubsub.streamers.insert(id.clone(), streamer);
+        let mut other_streamer = StreamerState::new(false, "b".to_owned());
+        other_streamer.points = 300;
+        pubsub.streamers.insert(other.clone(), other_streamer);
+
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+        assert!(pubsub.below_min_balance);
+        assert!(!pubsub.streamers[&id].predictions["pred-key-1"].1);
+
+        // points earned on another channel lift the total above the floor
+        pubsub.streamers.get_mut(&other).unwrap().points = 700;
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+        assert!(!pubsub.below_min_balance);
+        assert!(pubsub.streamers[&id].predictions["pred-key-1"].1);
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn daily_bet_cap(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([
+                ("91", ("capa", 50_000, None::<String>)),
+                ("92", ("capb", 50_000, None::<String>)),
+                ("93", ("capc", 50_000, None::<String>)),
+                ("94", ("capd", 50_000, None::<String>)),
+            ]))
+            .send()
+            .await?;
 
-        {
-            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
-            pred.0.outcomes[2] = outcome_from(3, 45_000, 10);
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.config.daily_bet_cap = Some(600);
+        let ids = [
+            UserId::from_static("91"),
+            UserId::from_static("92"),
+            UserId::from_static("93"),
+            UserId::from_static("94"),
+        ];
+        for (id, name) in ids.iter().zip(["capa", "capb", "capc", "capd"]) {
+            let mut streamer = betting_streamer(name, Some(name == "capd"));
+            streamer.points = 50_000;
+            pubsub.streamers.insert(id.clone(), streamer);
         }
-        let res = prediction_logic(&streamer, "pred-key-1")?;
-        assert_eq!(res, None);
 
-        {
-            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
-            pred.0.outcomes[2] = outcome_from(3, 40_000, 10);
-        }
-        let res = prediction_logic(&streamer, "pred-key-1")?;
-        assert_eq!(
-            res,
-            Some((
-                "3".to_owned(),
-                (streamer.points as f64 * default_points_percentage) as u32
-            ))
-        );
+        // simulated bets leave the cap alone
+        pubsub.try_prediction(&ids[3], "pred-key-1").await?;
+        assert_eq!(pubsub.streamers[&ids[3]].wagered["pred-key-1"].debited, 0);
+        assert_eq!(pubsub.wagered_today(), 0);
 
-        streamer.points = 500000;
-        let res = prediction_logic(&streamer, "pred-key-1")?;
-        assert_eq!(res, Some(("3".to_owned(), default_max_points)));
+        // 1% of the balance, the second bet is lowered to what is left of the cap
+        pubsub.try_prediction(&ids[0], "pred-key-1").await?;
+        assert_eq!(pubsub.streamers[&ids[0]].wagered["pred-key-1"].points, 500);
+        pubsub.try_prediction(&ids[1], "pred-key-1").await?;
+        assert_eq!(pubsub.streamers[&ids[1]].wagered["pred-key-1"].points, 100);
+        assert_eq!(pubsub.daily_budget(), Some(0));
 
+        // nothing was bet on this streamer, but the cap is shared
+        pubsub.try_prediction(&ids[2], "pred-key-1").await?;
+        assert!(!pubsub.streamers[&ids[2]].predictions["pred-key-1"].1);
+        assert_eq!(pubsub.wagered_today(), 600);
         Ok(())
     }
 
-    #[test]
-    fn detailed_strategy_high_odds() -> Result<()> {
-        use common::config::strategy as s;
-        let mut streamer = get_prediction();
-        {
-            let pred = streamer.predictions.get_mut("pred-key-1").unwrap();
-            streamer.points = 50000;
-            pred.0.outcomes = vec![
-                outcome_from(1, 5_000, 2),
-                outcome_from(2, 30_000, 14),
-                outcome_from(3, 45_000, 10),
-                outcome_from(4, 1_000, 1),
-            ];
-        }
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn jittered_bet_is_rounded(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
 
-        let high_odds_percentage = 0.001;
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("95", ("rnd", 10_000, None::<String>))]))
+            .send()
+            .await?;
 
-        let mut config_ref = streamer.config.0.write().unwrap();
-        #[allow(irrefutable_let_patterns)]
-        if let Strategy::Detailed(d) = &mut config_ref.config.prediction.strategy {
-            d.default = DefaultPrediction {
-                max_percentage: 0.55,
-                min_percentage: 0.45,
-                points: s::Points {
-                    max_value: 40000,
-                    percent: 0.15,
-                },
-            };
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.config.points_jitter_percent = Some(30.0);
+        let id = UserId::from_static("95");
+        let mut streamer = betting_streamer("rnd", Some(false));
+        detailed_mut(
+            &mut streamer
+                .config
+                .0
+                .write()
+                .unwrap()
+                .config
+                .prediction
+                .strategy,
+        )
+        .bet_rounding = 40;
+        let prediction = streamer.predictions["pred-key-1"].clone();
+        for i in 0..20 {
+            streamer
+                .predictions
+                .insert(format!("pred-{i}"), prediction.clone());
+        }
+        pubsub.streamers.insert(id.clone(), streamer);
 
-            d.detailed = Some(vec![
-                DetailedOdds {
-                    _type: s::OddsComparisonType::Le,
-                    threshold: 0.10,
-                    attempt_rate: 1.0,
-                    points: s::Points {
-                        max_value: 1000,
-                        percent: high_odds_percentage,
-                    },
-                },
-                DetailedOdds {
-                    _type: s::OddsComparisonType::Le,
-                    threshold: 0.30,
-                    attempt_rate: 0.00,
-                    points: s::Points {
-                        max_value: 5000,
-                        percent: 0.01,
-                    },
-                },
-            ]);
+        // 1% of the balance jittered to 70 - 130, and rounded back to a multiple of 40
+        for i in 0..20 {
+            pubsub.try_prediction(&id, &format!("pred-{i}")).await?;
+            let points = pubsub.streamers[&id].wagered[&format!("pred-{i}")].points;
+            assert!(points == 80 || points == 120, "{points}");
         }
 
-        drop(config_ref);
-        let res = prediction_logic(&streamer, "pred-key-1")?;
+        // what is left of the cap is rounded down
+        pubsub.config.daily_bet_cap = Some(pubsub.wagered_today() + 70);
+        pubsub.try_prediction(&id, "pred-key-1").await?;
+        assert_eq!(pubsub.streamers[&id].wagered["pred-key-1"].points, 40);
+
+        pubsub.config.daily_bet_cap = Some(pubsub.wagered_today() + 30);
+        let prediction = pubsub.streamers[&id].predictions["pred-key-1"].0.clone();
+        pubsub
+            .streamers
+            .get_mut(&id)
+            .unwrap()
+            .predictions
+            .insert("pred-last".to_owned(), (prediction, false));
+        pubsub.try_prediction(&id, "pred-last").await?;
+        assert!(!pubsub.streamers[&id].predictions["pred-last"].1);
         assert_eq!(
-            res,
-            Some((
-                "1".to_owned(),
-                (streamer.points as f64 * high_odds_percentage) as u32
-            ))
+            pubsub.last_decision(&id).unwrap().skip_reason.as_deref(),
+            Some("rounded_to_zero")
         );
-
         Ok(())
     }
 
-    macro_rules! watch_stream_eq {
-        ($watching_uri:expr,$eq:expr) => {
-            let res: Vec<UserId> = reqwest::get(&$watching_uri).await?.json().await?;
-            assert_eq!(res, $eq)
-        };
-        ($watching_uri:expr,$eq:expr,$user_ids:tt) => {
-            let res: Vec<UserId> = reqwest::get(&$watching_uri).await?.json().await?;
-            assert_eq!(res.len(), $eq.len());
-            for item in res {
-                assert!($user_ids.contains(&item));
+    /// Fields of the events with a `key` field logged while it is the default subscriber
+    #[derive(Clone)]
+    struct Logged {
+        key: &'static str,
+        events: Arc<std::sync::Mutex<Vec<HashMap<String, String>>>>,
+    }
+
+    impl Logged {
+        fn new(key: &'static str) -> Logged {
+            Logged {
+                key,
+                events: Default::default(),
             }
-        };
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Logged {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            struct Fields<'a>(&'a mut HashMap<String, String>);
+            impl tracing::field::Visit for Fields<'_> {
+                fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                    self.0.insert(field.name().to_owned(), value.to_owned());
+                }
+
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    self.0.insert(field.name().to_owned(), format!("{value:?}"));
+                }
+            }
+
+            let mut fields = HashMap::new();
+            event.record(&mut Fields(&mut fields));
+            if fields.contains_key(self.key) {
+                self.events.lock().unwrap().push(fields);
+            }
+        }
     }
 
     #[rstest]
     #[timeout(Duration::from_secs(5))]
     #[tokio::test(flavor = "multi_thread")]
-    async fn watch_stream_on_live(#[future] container: TestContainer) -> Result<()> {
+    async fn decision_events(#[future] container: TestContainer) -> Result<()> {
+        use common::config::filters::Filter;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([
+                ("94", ("trbet", 50_000, None::<String>)),
+                ("95", ("trskip", 50_000, None::<String>)),
+            ]))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        let bet = UserId::from_static("94");
+        let skip = UserId::from_static("95");
+        let mut streamer = betting_streamer("trbet", Some(true));
+        streamer.points = 50_000;
+        pubsub.streamers.insert(bet.clone(), streamer);
+        let streamer = betting_streamer("trskip", Some(true));
+        streamer.config.0.write().unwrap().config.prediction.filters =
+            vec![Filter::TotalUsers(1_000)];
+        pubsub.streamers.insert(skip.clone(), streamer);
+
+        let decisions = Logged::new("decision");
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(decisions.clone()),
+        );
+        pubsub.try_prediction(&bet, "pred-key-1").await?;
+        pubsub.try_prediction(&skip, "pred-key-1").await?;
+
+        let decisions = decisions.events.lock().unwrap();
+        assert_eq!(decisions.len(), 2);
+        let field = |idx: usize, name: &str| decisions[idx].get(name).map(String::as_str);
+        assert_eq!(field(0, "decision"), Some("bet"));
+        assert_eq!(field(0, "streamer"), Some("trbet"));
+        assert_eq!(field(0, "event_id"), Some("pred-key-1"));
+        assert_eq!(field(0, "outcome_id"), Some("1"));
+        assert_eq!(field(0, "points"), Some("500"));
+        assert_eq!(field(0, "strategy"), Some("primary"));
+        assert_eq!(field(1, "decision"), Some("skip"));
+        assert_eq!(field(1, "streamer"), Some("trskip"));
+        assert_eq!(field(1, "reason"), Some("filter"));
+        assert_eq!(field(1, "filter"), Some("TotalUsers(1000)"));
+        assert_eq!(field(1, "points"), None);
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn drops(#[future] container: TestContainer) -> Result<()> {
         let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{base_url}/drops"))
+            .json(&serde_json::json!({
+                "channels": ["3"],
+                "claimable": [["drop-1", "Badge"]],
+            }))
+            .send()
+            .await?;
+        client.delete(format!("{base_url}/watching")).send().await?;
 
         let (ws_tx, _) = unbounded();
         let (_, rx) = unbounded();
         let mut pubsub = PubSub::empty(ws_tx);
-        pubsub.spade_url = Some(format!("http://localhost:{}/spade", container.port));
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.spade_url = Some(format!("{base_url}/spade"));
         pubsub.user_id = "1".to_string();
+        pubsub.config.drops = Some(true);
+        let user_ids = [
+            UserId::from_static("1"),
+            UserId::from_static("2"),
+            UserId::from_static("3"),
+        ];
+        for id in &user_ids {
+            pubsub
+                .streamers
+                .insert(id.clone(), StreamerState::new(true, id.as_str().to_owned()));
+            pubsub.config.streamers.insert(
+                id.to_string(),
+                ConfigType::Specific(StreamerConfig::default()),
+            );
+        }
+        let mut events = pubsub.events_tx.subscribe();
+        let gql = pubsub.gql.clone();
+        let pubsub = Arc::new(RwLock::new(pubsub));
 
-        let user_ids = vec![UserId::from_static("1"), UserId::from_static("2")];
-        pubsub.streamers = HashMap::from([
-            (
-                user_ids[0].clone(),
-                StreamerState::new(true, user_ids[0].as_str().to_owned()),
-            ),
-            (
-                user_ids[1].clone(),
-                StreamerState::new(true, user_ids[1].as_str().to_owned()),
-            ),
-        ]);
-        pubsub.config.streamers = user_ids
+        super::drops::inner(&pubsub, &gql).await?;
+        assert_eq!(pubsub.read().await.drop_channels, vec![user_ids[2].clone()]);
+        assert_eq!(
+            events.try_recv()?,
+            AppEvent::DropClaimed {
+                drop_instance_id: "drop-1".to_owned(),
+                name: "Badge".to_owned(),
+            }
+        );
+
+        // only two streams are watched, the one with drops goes first
+        super::watch_stream::inner(&pubsub, &mut Vec::new(), false, &rx).await?;
+        let watching = pubsub
+            .read()
+            .await
+            .watching
             .iter()
-            .map(|x| {
-                (
-                    x.to_string(),
-                    ConfigType::Specific(StreamerConfig::default()),
-                )
-            })
-            .collect();
+            .map(|x| x.info.channel_name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(watching, vec!["3", "1", "2"]);
+        let res: Vec<UserId> = reqwest::get(format!("{base_url}/watching"))
+            .await?
+            .json()
+            .await?;
+        assert!(res.contains(&user_ids[2]));
 
-        let pubsub = Arc::new(RwLock::new(pubsub.clone()));
-        let watching_uri = format!("http://localhost:{}/watching", container.port);
-        let mut watch_streak = Vec::new();
+        // claimed drops are not claimed again
+        super::drops::inner(&pubsub, &gql).await?;
+        let claimed: Vec<String> = reqwest::get(format!("{base_url}/drops_claimed"))
+            .await?
+            .json()
+            .await?;
+        assert_eq!(claimed, vec!["drop-1"]);
+        assert!(events.try_recv().is_err());
+        Ok(())
+    }
 
-        super::watch_stream::inner(&pubsub, &mut watch_streak, true, &rx).await?;
-        watch_stream_eq!(watching_uri, user_ids, user_ids);
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failed_drop_claim_skipped(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        reqwest::Client::new()
+            .post(format!("{base_url}/drops"))
+            .json(&serde_json::json!({
+                "channels": [],
+                "claimable": [["drop-1", "Badge"], ["drop-2", "Emote"]],
+                "failing": ["drop-1"],
+            }))
+            .send()
+            .await?;
+
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        let mut events = pubsub.events_tx.subscribe();
+        let gql = pubsub.gql.clone();
+        let pubsub = Arc::new(RwLock::new(pubsub));
 
+        // the failed claim does not keep the other drops from being claimed
+        super::drops::inner(&pubsub, &gql).await?;
+        let claimed: Vec<String> = reqwest::get(format!("{base_url}/drops_claimed"))
+            .await?
+            .json()
+            .await?;
+        assert_eq!(claimed, vec!["drop-2"]);
+        assert_eq!(
+            events.try_recv()?,
+            AppEvent::DropClaimed {
+                drop_instance_id: "drop-2".to_owned(),
+                name: "Emote".to_owned(),
+            }
+        );
+        assert!(events.try_recv().is_err());
         Ok(())
     }
 
     #[rstest]
     #[timeout(Duration::from_secs(5))]
     #[tokio::test(flavor = "multi_thread")]
-    #[rustfmt::skip]
-    async fn watch_stream_with_watch_streak(#[future] container: TestContainer) -> Result<()> {
+    async fn game_change_unblocks_bet(#[future] container: TestContainer) -> Result<()> {
+        use common::config::filters::Filter;
         let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
 
         let (ws_tx, _) = unbounded();
-        let (tx, rx) = unbounded();
         let mut pubsub = PubSub::empty(ws_tx);
-        pubsub.spade_url = Some(format!("http://localhost:{}/spade", container.port));
-        pubsub.user_id = "1".to_string();
-        pubsub.config.watch_streak = Some(true);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        let mut streamer = betting_streamer("games", Some(true));
+        streamer.info.game = Some(Game {
+            id: "509658".to_owned(),
+            name: "Just Chatting".to_owned(),
+        });
+        streamer.config.0.write().unwrap().config.prediction.filters =
+            vec![Filter::Games(vec!["chess".to_owned()])];
+        let id = UserId::from_static("95");
+        pubsub.streamers.insert(id.clone(), streamer);
+        let gql = pubsub.gql.clone();
+        let pubsub = Arc::new(RwLock::new(pubsub));
 
-        let user_ids: Vec<UserId> = (1..4).map(|x| UserId::from_str(&x.to_string()).unwrap()).collect();
-        pubsub.streamers = user_ids.iter().enumerate().map(|(idx, x)| (x.clone(), StreamerState::new(idx == 0, x.to_string()))).collect();
-        pubsub.config.streamers = user_ids.iter().map(|x| { (x.to_string(), ConfigType::Specific(StreamerConfig::default()) )}).collect();
+        pubsub
+            .write()
+            .await
+            .try_prediction(&id, "pred-key-1")
+            .await?;
+        assert!(!pubsub.read().await.streamers[&id].predictions["pred-key-1"].1);
 
-        let pubsub = Arc::new(RwLock::new(pubsub.clone()));
-        let watching_uri = format!("http://localhost:{}/watching", container.port);
+        reqwest::Client::new()
+            .post(format!("{base_url}/streamer_metadata"))
+            .json(&serde_json::json!({
+                "95": ["games", {
+                    "id": "95",
+                    "stream": { "id": "950", "game": { "id": "743", "name": "Chess" } },
+                }],
+            }))
+            .send()
+            .await?;
+        super::game_refresh::inner(&pubsub, &gql).await?;
 
-        let mut watch_streak = Vec::new();
-        let use_watch_streak = true;
+        let reader = pubsub.read().await;
+        assert_eq!(
+            reader.streamers[&id]
+                .info
+                .game
+                .as_ref()
+                .map(|x| x.name.as_str()),
+            Some("Chess")
+        );
+        assert!(reader.streamers[&id].predictions["pred-key-1"].1);
+        Ok(())
+    }
 
-        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-        watch_stream_eq!(watching_uri, [user_ids[0].clone()]);
+    #[tokio::test]
+    async fn predictions_cap() -> Result<()> {
+        use tracing_subscriber::layer::SubscriberExt;
 
-        let client = reqwest::Client::new();
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.watch_only = true;
+        pubsub.config.max_predictions_per_streamer = Some(3);
+        let id = UserId::from_static("1");
+        pubsub
+            .streamers
+            .insert(id.clone(), StreamerState::new(true, "a".to_owned()));
+        let template = get_prediction().predictions.remove("pred-key-1").unwrap().0;
+        let event = |n: u32| Event {
+            id: format!("flood-{n}"),
+            ..template.clone()
+        };
 
-        pubsub.write().await.streamers.get_mut(&user_ids[1]).unwrap().info.live = true;
-        tx.send_async(user_ids[1].clone()).await?;
-        client.delete(&watching_uri).send().await?;
-        for _ in 0..30 {
-            super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-            watch_stream_eq!(watching_uri, user_ids[0..2], user_ids);
+        let dropped = Logged::new("dropped_prediction");
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(dropped.clone()));
+        for n in 0..5 {
+            pubsub.handle_prediction_event(event(n), id.clone()).await?;
         }
+        assert_eq!(pubsub.streamers[&id].predictions.len(), 3);
+        assert!(!pubsub.streamers[&id].predictions.contains_key("flood-3"));
+        let warned = dropped
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|x| x["dropped_prediction"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(warned, vec!["flood-3", "flood-4"]);
 
-        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-        watch_stream_eq!(watching_uri, user_ids[0..2], user_ids);
+        // an ended prediction that was never removed makes room
+        pubsub
+            .streamers
+            .get_mut(&id)
+            .unwrap()
+            .predictions
+            .get_mut("flood-0")
+            .unwrap()
+            .0
+            .ended_at = Some(Timestamp::new(Local::now().to_rfc3339())?);
+        pubsub.handle_prediction_event(event(5), id.clone()).await?;
+        let predictions = &pubsub.streamers[&id].predictions;
+        assert_eq!(predictions.len(), 3);
+        assert!(predictions.contains_key("flood-5"));
+        assert!(!predictions.contains_key("flood-0"));
+        Ok(())
+    }
 
-        pubsub.write().await.streamers.get_mut(&user_ids[2]).unwrap().info.live = true;
-        tx.send_async(user_ids[2].clone()).await?;
-        client.delete(&watching_uri).send().await?;
-        for _ in 0..30 {
-            super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-            watch_stream_eq!(watching_uri, [user_ids[0].clone(), user_ids[2].clone()], user_ids);
+    #[tokio::test(start_paused = true)]
+    async fn equity_snapshots_at_interval() -> Result<()> {
+        use crate::analytics::Analytics;
+
+        let path = std::env::temp_dir().join(format!("equity-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let (mut analytics, analytics_tx) = Analytics::new(path)?;
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.analytics_tx = analytics_tx.clone();
+        for (id, name, points) in [("1", "a", 1000), ("2", "b", 500)] {
+            let mut streamer = StreamerState::new(true, name.to_owned());
+            streamer.points = points;
+            pubsub.streamers.insert(UserId::from_static(id), streamer);
         }
+        let pubsub = Arc::new(RwLock::new(pubsub));
 
-        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-        watch_stream_eq!(watching_uri, [user_ids[0].clone(), user_ids[2].clone()], user_ids);
+        let job = tokio::spawn(super::equity_snapshot::run(
+            pubsub.clone(),
+            Duration::from_secs(60),
+        ));
+        tokio::time::sleep(Duration::from_secs(150)).await;
+        pubsub
+            .write()
+            .await
+            .streamers
+            .get_mut(&UserId::from_static("1"))
+            .unwrap()
+            .points = 1200;
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        job.abort();
 
-        pubsub.write().await.config.watch_priority = Some(vec![user_ids[2].as_str().to_owned()]);
-        client.delete(&watching_uri).send().await?;
-        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-        watch_stream_eq!(watching_uri, [user_ids[0].clone(), user_ids[2].clone()], user_ids);
+        // the writer handles requests in order, so every snapshot is stored once this one ran
+        let (done_tx, done_rx) = flume::bounded(1);
+        analytics_tx
+            .send_async(Box::new(move |_| {
+                _ = done_tx.send(());
+                Ok(())
+            }))
+            .await
+            .map_err(|_| eyre::eyre!("Analytics writer stopped"))?;
+        done_rx.recv_async().await?;
 
-        pubsub.write().await.streamers.get_mut(&user_ids[2]).unwrap().info.live = false;
-        client.delete(&watching_uri).send().await?;
-        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-        watch_stream_eq!(watching_uri, user_ids[0..2], user_ids);
+        let from = Local::now() - chrono::Duration::hours(1);
+        let snapshots = analytics.equity(from, Local::now())?;
+        assert_eq!(
+            snapshots.iter().map(|x| x.balance).collect::<Vec<_>>(),
+            vec![1500, 1500, 1700, 1700]
+        );
+        assert!(snapshots
+            .windows(2)
+            .all(|x| x[0].created_at <= x[1].created_at));
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
 
-        pubsub.write().await.streamers.get_mut(&user_ids[0]).unwrap().info.live = false;
-        client.delete(&watching_uri).send().await?;
-        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-        watch_stream_eq!(watching_uri, user_ids[1..2], user_ids);
+    #[tokio::test]
+    async fn user_prediction_confirms_bet() -> Result<()> {
+        use common::twitch::user_predictions::{
+            BetResult, BetResultType, PredictionsUserReply, UserPrediction,
+        };
 
-        pubsub.write().await.streamers.get_mut(&user_ids[1]).unwrap().info.live = false;
-        client.delete(&watching_uri).send().await?;
-        super::watch_stream::inner(&pubsub, &mut watch_streak, use_watch_streak, &rx).await?;
-        watch_stream_eq!(watching_uri, Vec::<UserId>::new(), user_ids);
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        let (analytics_tx, analytics_rx) = unbounded();
+        pubsub.analytics_tx = analytics_tx;
+        let id = UserId::from_static("1");
+        pubsub.streamers.insert(id.clone(), get_prediction());
+        let prediction = UserPrediction {
+            event_id: "pred-key-1".to_owned(),
+            outcome_id: "1".to_owned(),
+            channel_id: "1".to_owned(),
+            points: 100,
+            result: None,
+        };
+
+        // a bet placed outside the miner
+        pubsub
+            .handle_user_prediction(PredictionsUserReply::PredictionMade {
+                prediction: prediction.clone(),
+            })
+            .await?;
+        let streamer = &pubsub.streamers[&id];
+        assert!(streamer.predictions["pred-key-1"].1);
+        let wager = &streamer.wagered["pred-key-1"];
+        assert!(wager.confirmed);
+        assert_eq!(wager.points, 100);
+        assert_eq!(analytics_rx.drain().count(), 1);
 
+        pubsub
+            .handle_user_prediction(PredictionsUserReply::PredictionResult {
+                prediction: UserPrediction {
+                    result: Some(BetResult {
+                        result_type: BetResultType::Lose,
+                        points_won: None,
+                    }),
+                    ..prediction
+                },
+            })
+            .await?;
+        assert_eq!(pubsub.streamers[&id].wagered["pred-key-1"].points, 100);
+        assert_eq!(analytics_rx.drain().count(), 1);
         Ok(())
     }
 }
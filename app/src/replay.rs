@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use chrono::Local;
+use common::{
+    config::{Config, ConfigType, StreamerConfig},
+    time::Tz,
+    types::{ConfigTypeRef, StreamerConfigRef, StreamerConfigRefWrapper, StreamerState},
+};
+use eyre::{eyre, Result};
+use twitch_api::{pubsub::predictions::Event, types::Timestamp};
+
+use crate::{
+    analytics::{model::Prediction, Analytics},
+    pubsub::prediction_logic,
+};
+
+/// Bets the strategies would have placed on one snapshot of a prediction's outcomes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayStep {
+    /// Seconds since the prediction started
+    pub elapsed: i64,
+    /// Share of the prediction window that had passed, from 0 to 100
+    pub window_percent: f64,
+    /// Outcome ID and points by strategy name, none if the strategy would not bet
+    pub bets: Vec<(String, Option<(String, u32)>)>,
+}
+
+/// Replay a recorded prediction from the analytics database against the strategies in the config, and print when
+/// each of them would have bet
+pub async fn run(
+    config: &str,
+    analytics_db: &str,
+    prediction_id: &str,
+    balance: Option<u32>,
+) -> Result<()> {
+    let mut config = crate::parse_config(config).await?;
+    config.parse_and_validate()?;
+    let mut analytics = Analytics::open_read_only(analytics_db)?;
+    let prediction = analytics
+        .find_prediction(prediction_id)?
+        .ok_or_else(|| eyre!("Prediction {prediction_id} not found in {analytics_db}"))?;
+    let channel_name = analytics.streamer_name(prediction.channel_id)?;
+    let balance = match balance {
+        Some(x) => x,
+        None => analytics
+            .balance_at(prediction.channel_id, prediction.created_at)?
+            .ok_or_else(|| {
+                eyre!("No balance recorded before the prediction started, pass one with --balance")
+            })? as u32,
+    };
+
+    let strategies = strategies(&config, channel_name.as_deref());
+    if strategies.is_empty() {
+        return Err(eyre!(
+            "No config for {} and no presets to replay",
+            channel_name.as_deref().unwrap_or("the channel")
+        ));
+    }
+
+    let steps = replay(&prediction, &strategies, balance, config.timezone())?;
+    println!(
+        "{} on {}, {}s window, {} snapshots, balance {balance}",
+        prediction.title,
+        channel_name.as_deref().unwrap_or("unknown channel"),
+        prediction.prediction_window,
+        steps.len()
+    );
+    print!("{}", format_steps(&prediction, &steps));
+    Ok(())
+}
+
+/// The channel's own config, followed by every preset it does not use
+fn strategies(config: &Config, channel_name: Option<&str>) -> Vec<(String, StreamerConfig)> {
+    let presets = config.presets.clone().unwrap_or_default();
+    let mut res = Vec::new();
+    let mut used_preset = None;
+    if let Some(name) = channel_name {
+        match config.streamers.get(name) {
+            Some(ConfigType::Specific(c)) => res.push((name.to_owned(), c.clone())),
+            Some(ConfigType::Preset(preset)) => {
+                if let Some(c) = presets.get(preset) {
+                    res.push((format!("{name} ({preset})"), c.clone()));
+                    used_preset = Some(preset.as_str());
+                }
+            }
+            None => {}
+        }
+    }
+    for (preset, c) in &presets {
+        if used_preset != Some(preset.as_str()) {
+            res.push((format!("preset {preset}"), c.clone()));
+        }
+    }
+    res
+}
+
+pub fn replay(
+    prediction: &Prediction,
+    strategies: &[(String, StreamerConfig)],
+    balance: u32,
+    timezone: Option<Tz>,
+) -> Result<Vec<ReplayStep>> {
+    let mut steps = Vec::new();
+    for snapshot in &prediction.outcomes_history.0 {
+        let elapsed = (snapshot.at - prediction.created_at).num_seconds().max(0);
+        // filters compare against the current time, so the prediction is made to have started `elapsed` ago
+        let created_at = Local::now() - chrono::Duration::seconds(elapsed);
+        let event = Event {
+            id: prediction.prediction_id.clone(),
+            channel_id: prediction.channel_id.to_string(),
+            created_at: Timestamp::new(created_at.to_rfc3339())?,
+            ended_at: None,
+            locked_at: None,
+            outcomes: snapshot.outcomes.iter().cloned().map(Into::into).collect(),
+            prediction_window_seconds: prediction.prediction_window,
+            status: "ACTIVE".to_owned(),
+            title: prediction.title.clone(),
+            winning_outcome_id: None,
+        };
+
+        let mut bets = Vec::new();
+        for (name, config) in strategies {
+            let streamer = StreamerState {
+                predictions: HashMap::from([(event.id.clone(), (event.clone(), false))]),
+                config: StreamerConfigRefWrapper::new(StreamerConfigRef {
+                    _type: ConfigTypeRef::Specific,
+                    config: config.clone(),
+                }),
+                points: balance,
+                ..Default::default()
+            };
+            bets.push((
+                name.clone(),
+                prediction_logic(&streamer, &event.id, timezone)?,
+            ));
+        }
+
+        let window_percent = if prediction.prediction_window > 0 {
+            elapsed as f64 / prediction.prediction_window as f64 * 100.0
+        } else {
+            100.0
+        };
+        steps.push(ReplayStep {
+            elapsed,
+            window_percent,
+            bets,
+        });
+    }
+    Ok(steps)
+}
+
+/// A table of bets per snapshot, followed by the first bet of each strategy
+pub fn format_steps(prediction: &Prediction, steps: &[ReplayStep]) -> String {
+    let titles: HashMap<&str, &str> = prediction
+        .outcomes
+        .0
+        .iter()
+        .chain(
+            prediction
+                .outcomes_history
+                .0
+                .iter()
+                .flat_map(|x| x.outcomes.iter()),
+        )
+        .map(|x| (x.id.as_str(), x.title.as_str()))
+        .collect();
+    let title = |id: &str| titles.get(id).copied().unwrap_or(id).to_owned();
+    let width = steps
+        .iter()
+        .flat_map(|x| x.bets.iter().map(|(name, _)| name.len()))
+        .max()
+        .unwrap_or_default()
+        .max("Strategy".len());
+
+    let mut out = String::new();
+    for step in steps {
+        out.push_str(&format!(
+            "\n{}s ({:.0}% of the window)\n{:width$}  {:<20}  Points\n",
+            step.elapsed, step.window_percent, "Strategy", "Outcome"
+        ));
+        for (name, bet) in &step.bets {
+            match bet {
+                Some((outcome_id, points)) => out.push_str(&format!(
+                    "{name:width$}  {:<20}  {points}\n",
+                    title(outcome_id)
+                )),
+                None => out.push_str(&format!("{name:width$}  {:<20}  -\n", "-")),
+            }
+        }
+    }
+
+    out.push('\n');
+    // every step has the bets of the same strategies in the same order
+    let names = steps.first().map(|x| &x.bets[..]).unwrap_or_default();
+    for (idx, (name, _)) in names.iter().enumerate() {
+        let first = steps
+            .iter()
+            .find_map(|step| step.bets[idx].1.as_ref().map(|bet| (step, bet)));
+        match first {
+            Some((step, (outcome_id, points))) => out.push_str(&format!(
+                "{name}: first bet at {}s ({:.0}% of the window), {points} on {}\n",
+                step.elapsed,
+                step.window_percent,
+                title(outcome_id)
+            )),
+            None => out.push_str(&format!("{name}: never bet\n")),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, NaiveDateTime};
+    use common::config::{
+        filters::Filter,
+        strategy::{DefaultPrediction, Detailed, Points, Strategy},
+        PredictionConfig, StreamerConfig,
+    };
+    use eyre::Result;
+
+    use crate::analytics::model::{
+        Outcome, Outcomes, OutcomesHistory, OutcomesSnapshot, Prediction, PredictionBetWrapper,
+    };
+
+    use super::{format_steps, replay};
+
+    fn outcome(id: &str, total_points: i64) -> Outcome {
+        Outcome {
+            id: id.to_owned(),
+            title: format!("title {id}"),
+            color: String::new(),
+            total_points,
+            total_users: 1,
+        }
+    }
+
+    fn underdog(filters: Vec<Filter>) -> StreamerConfig {
+        StreamerConfig {
+            prediction: PredictionConfig {
+                strategy: Strategy::Detailed(Detailed {
+                    default: DefaultPrediction {
+                        max_percentage: 0.4,
+                        min_percentage: 0.0,
+                        points: Points {
+                            max_value: 0,
+                            percent: 0.01,
+                        },
+                    },
+                    ..Default::default()
+                }),
+                filters,
+                fallback: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn replay_snapshots() -> Result<()> {
+        let created_at = NaiveDateTime::parse_from_str("2024-06-01 12:00:00", "%Y-%m-%d %H:%M:%S")?;
+        let snapshot = |secs: i64, first: i64, second: i64| OutcomesSnapshot {
+            at: created_at + Duration::seconds(secs),
+            outcomes: vec![outcome("1", first), outcome("2", second)],
+        };
+        let prediction = Prediction {
+            channel_id: 1,
+            prediction_id: "replayed".to_owned(),
+            title: "Replayed".to_owned(),
+            prediction_window: 100,
+            outcomes: Outcomes(vec![outcome("1", 200), outcome("2", 800)]),
+            winning_outcome_id: None,
+            placed_bet: PredictionBetWrapper::None,
+            created_at,
+            closed_at: None,
+            outcomes_history: OutcomesHistory(vec![
+                snapshot(0, 500, 500),
+                snapshot(30, 300, 700),
+                snapshot(60, 200, 800),
+            ]),
+        };
+        let strategies = [
+            ("early".to_owned(), underdog(Vec::new())),
+            (
+                "late".to_owned(),
+                underdog(vec![Filter::DelayPercentage(50.0)]),
+            ),
+        ];
+
+        let steps = replay(&prediction, &strategies, 10_000, None)?;
+        let bets = steps
+            .iter()
+            .map(|x| (x.elapsed, x.bets.iter().map(|x| x.1.clone()).collect()))
+            .collect::<Vec<(i64, Vec<_>)>>();
+        let bet = Some(("1".to_owned(), 100));
+        assert_eq!(
+            bets,
+            vec![
+                (0, vec![None, None]),
+                (30, vec![bet.clone(), None]),
+                (60, vec![bet.clone(), bet]),
+            ]
+        );
+        assert_eq!(steps[1].window_percent, 30.0);
+
+        let table = format_steps(&prediction, &steps);
+        assert!(table.contains("30s (30% of the window)"), "{table}");
+        assert!(
+            table.contains("early: first bet at 30s (30% of the window), 100 on title 1"),
+            "{table}"
+        );
+        assert!(
+            table.contains("late: first bet at 60s (60% of the window), 100 on title 1"),
+            "{table}"
+        );
+        Ok(())
+    }
+}
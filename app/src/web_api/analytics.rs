@@ -1,31 +1,99 @@
 use std::sync::Arc;
 
-use axum::{extract::State, routing::post, Json, Router};
-use chrono::{DateTime, FixedOffset};
-use serde::Deserialize;
+use axum::{
+    extract::{Path, Query, State},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Local};
+use common::time::{self, Tz};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::info;
 use utoipa::ToSchema;
 
 use crate::{
-    analytics::{model::Outcome, AnalyticsWrapper, TimelineResult},
-    make_paths,
+    analytics::{
+        model::{
+            AnalyticsSummary, EquitySnapshot, OptimizeResult, Outcome, PointsMover,
+            PredictionRecord, ResetResult, SessionChange, TopMovers, WatchSummary,
+        },
+        AnalyticsWrapper, TimelineResult,
+    },
+    make_paths, sub_error,
 };
 
-use super::{ApiError, RouterBuild};
+use super::{error_response, ApiError, ApiState, RouterBuild, WebApiError};
 
-pub fn build(analytics: Arc<AnalyticsWrapper>) -> RouterBuild {
+pub fn build(
+    state: ApiState,
+    analytics: Arc<AnalyticsWrapper>,
+    compare: Option<Arc<AnalyticsWrapper>>,
+    timezone: Option<Tz>,
+) -> RouterBuild {
     let routes = Router::new()
         .route("/timeline", post(points_timeline))
-        .with_state(analytics);
+        .route("/watch", get(watch_summary))
+        .route("/summary", get(summary))
+        .route("/top-movers", get(top_movers))
+        .route("/equity", get(equity))
+        .route("/predictions", get(predictions_export))
+        .route("/optimize", post(optimize))
+        .layer(Extension(timezone))
+        .with_state(analytics.clone())
+        .merge(
+            Router::new()
+                .route("/streamers/:name", delete(reset_streamer))
+                .route("/session", get(session))
+                .with_state((state, analytics.clone())),
+        )
+        .merge(
+            Router::new()
+                .route("/compare", get(compare_summary))
+                .layer(Extension(timezone))
+                .with_state((analytics, compare)),
+        );
 
-    let schemas = vec![Outcome::schema(), Timeline::schema()];
+    let schemas = vec![
+        Outcome::schema(),
+        Timeline::schema(),
+        WatchRange::schema(),
+        SummaryRange::schema(),
+        WatchSummary::schema(),
+        AnalyticsSummary::schema(),
+        Comparison::schema(),
+        OptimizeResult::schema(),
+        ResetResult::schema(),
+        ResetQuery::schema(),
+        PredictionsRange::schema(),
+        PredictionRecord::schema(),
+        SessionChange::schema(),
+        TopMoversQuery::schema(),
+        TopMovers::schema(),
+        PointsMover::schema(),
+        EquityRange::schema(),
+        EquitySnapshot::schema(),
+    ];
 
-    let paths = make_paths!(__path_points_timeline);
+    let paths = make_paths!(
+        __path_points_timeline,
+        __path_watch_summary,
+        __path_summary,
+        __path_session,
+        __path_top_movers,
+        __path_equity,
+        __path_predictions_export,
+        __path_compare_summary,
+        __path_optimize,
+        __path_reset_streamer
+    );
 
     (routes, schemas, paths)
 }
 
 #[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
-/// Timeline information, RFC3339 strings
+/// Timeline information, RFC3339 strings. Times without an offset are in the configured timezone
 struct Timeline {
     /// GE time
     from: String,
@@ -33,6 +101,15 @@ struct Timeline {
     to: String,
     /// Channels
     channels: Vec<i32>,
+    /// Only channels with this tag, all channels with the tag if no channels are given
+    tag: Option<String>,
+}
+
+/// Parse a timestamp of a request, times without an offset are in `timezone`
+fn parse_timestamp(value: &str, timezone: Option<Tz>) -> Result<DateTime<Local>, ApiError> {
+    time::parse_in_zone(value, timezone)
+        .map(|x| x.with_timezone(&Local))
+        .map_err(|err| ApiError::ParseTimestamp(err.to_string()))
 }
 
 #[utoipa::path(
@@ -45,13 +122,937 @@ struct Timeline {
 )]
 async fn points_timeline(
     State(analytics): State<Arc<AnalyticsWrapper>>,
+    Extension(timezone): Extension<Option<Tz>>,
     axum::extract::Json(timeline): axum::extract::Json<Timeline>,
 ) -> Result<Json<Vec<TimelineResult>>, ApiError> {
-    let from = DateTime::from(DateTime::<FixedOffset>::parse_from_rfc3339(&timeline.from)?);
-    let to = DateTime::from(DateTime::<FixedOffset>::parse_from_rfc3339(&timeline.to)?);
+    let (from, to) = (
+        parse_timestamp(&timeline.from, timezone)?,
+        parse_timestamp(&timeline.to, timezone)?,
+    );
+
+    let res = analytics
+        .execute(|analytics| {
+            let channels = match &timeline.tag {
+                Some(tag) => {
+                    let tagged = analytics.channels_with_tag(tag)?;
+                    if timeline.channels.is_empty() {
+                        tagged
+                    } else {
+                        timeline
+                            .channels
+                            .iter()
+                            .filter(|x| tagged.contains(x))
+                            .copied()
+                            .collect()
+                    }
+                }
+                None => timeline.channels.clone(),
+            };
+            analytics.timeline(from, to, &channels)
+        })
+        .await?;
+    Ok(Json(res))
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+/// Time range, RFC3339 strings. Times without an offset are in the configured timezone
+struct WatchRange {
+    /// GE time
+    from: String,
+    /// LE time
+    to: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/analytics/watch",
+    responses(
+        (status = 200, description = "Minutes watched and failed viewership pings per channel in the specified range", body = Vec<WatchSummary>),
+    ),
+    params(WatchRange)
+)]
+async fn watch_summary(
+    State(analytics): State<Arc<AnalyticsWrapper>>,
+    Extension(timezone): Extension<Option<Tz>>,
+    Query(range): Query<WatchRange>,
+) -> Result<Json<Vec<WatchSummary>>, ApiError> {
+    let (from, to) = (
+        parse_timestamp(&range.from, timezone)?,
+        parse_timestamp(&range.to, timezone)?,
+    );
+
+    let res = analytics
+        .execute(|analytics| analytics.watch_summary(from, to))
+        .await?;
+    Ok(Json(res))
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+/// Time range, RFC3339 strings. Times without an offset are in the configured timezone
+struct SummaryRange {
+    /// GE time
+    from: String,
+    /// LE time
+    to: String,
+    /// Only channels with this tag
+    tag: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/analytics/summary",
+    responses(
+        (status = 200, description = "Predictions, bets and change in points in the specified range", body = AnalyticsSummary),
+        (status = 400, description = "Invalid timestamp"),
+    ),
+    params(SummaryRange)
+)]
+async fn summary(
+    State(analytics): State<Arc<AnalyticsWrapper>>,
+    Extension(timezone): Extension<Option<Tz>>,
+    Query(range): Query<SummaryRange>,
+) -> Result<Json<AnalyticsSummary>, ApiError> {
+    let (from, to) = (
+        parse_timestamp(&range.from, timezone)?,
+        parse_timestamp(&range.to, timezone)?,
+    );
+
+    let res = analytics
+        .execute(|analytics| match &range.tag {
+            Some(tag) => {
+                let channels = analytics.channels_with_tag(tag)?;
+                analytics.summary(from, to, Some(&channels))
+            }
+            None => analytics.summary(from, to, None),
+        })
+        .await?;
+    Ok(Json(res))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/analytics/session",
+    responses(
+        (status = 200, description = "Change in points per channel since the miner started", body = Vec<SessionChange>),
+    )
+)]
+async fn session(
+    State((state, analytics)): State<(ApiState, Arc<AnalyticsWrapper>)>,
+) -> Result<Json<Vec<SessionChange>>, ApiError> {
+    let launched_at = state.read().await.launched_at;
+    let res = analytics
+        .execute(|analytics| analytics.session(launched_at))
+        .await?;
+    Ok(Json(res))
+}
+
+/// Default number of gainers and losers returned by the top movers endpoint
+const TOP_MOVERS_LIMIT: usize = 5;
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+/// Time range, RFC3339 strings. Times without an offset are in the configured timezone
+struct TopMoversQuery {
+    /// GE time
+    from: String,
+    /// LE time
+    to: String,
+    /// Number of gainers and of losers, 5 by default
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/analytics/top-movers",
+    responses(
+        (status = 200, description = "Channels with the largest net gains and losses in points over the range", body = TopMovers),
+        (status = 400, description = "Invalid timestamp"),
+    ),
+    params(TopMoversQuery)
+)]
+async fn top_movers(
+    State(analytics): State<Arc<AnalyticsWrapper>>,
+    Extension(timezone): Extension<Option<Tz>>,
+    Query(query): Query<TopMoversQuery>,
+) -> Result<Json<TopMovers>, ApiError> {
+    let (from, to) = (
+        parse_timestamp(&query.from, timezone)?,
+        parse_timestamp(&query.to, timezone)?,
+    );
+    let limit = query.limit.unwrap_or(TOP_MOVERS_LIMIT);
+
+    let res = analytics
+        .execute(|analytics| analytics.top_movers(from, to, limit))
+        .await?;
+    Ok(Json(res))
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+/// Time range, RFC3339 strings. Times without an offset are in the configured timezone
+struct EquityRange {
+    /// GE time
+    from: String,
+    /// LE time
+    to: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/analytics/equity",
+    responses(
+        (status = 200, description = "Snapshots of the total balance across streamers in the specified range, oldest first", body = Vec<EquitySnapshot>),
+        (status = 400, description = "Invalid timestamp"),
+    ),
+    params(EquityRange)
+)]
+async fn equity(
+    State(analytics): State<Arc<AnalyticsWrapper>>,
+    Extension(timezone): Extension<Option<Tz>>,
+    Query(range): Query<EquityRange>,
+) -> Result<Json<Vec<EquitySnapshot>>, ApiError> {
+    let (from, to) = (
+        parse_timestamp(&range.from, timezone)?,
+        parse_timestamp(&range.to, timezone)?,
+    );
+
+    let res = analytics
+        .execute(|analytics| analytics.equity(from, to))
+        .await?;
+    Ok(Json(res))
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+/// Time range and channels, RFC3339 strings. Times without an offset are in the configured timezone
+struct PredictionsRange {
+    /// GE time
+    from: String,
+    /// LE time
+    to: String,
+    /// Comma separated channel IDs, all channels if not specified
+    channels: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/analytics/predictions",
+    responses(
+        (status = 200, description = "Flat records of the predictions started in the specified range, oldest first", body = Vec<PredictionRecord>),
+        (status = 400, description = "Invalid timestamp or channel IDs"),
+    ),
+    params(PredictionsRange)
+)]
+async fn predictions_export(
+    State(analytics): State<Arc<AnalyticsWrapper>>,
+    Extension(timezone): Extension<Option<Tz>>,
+    Query(range): Query<PredictionsRange>,
+) -> Result<Json<Vec<PredictionRecord>>, ApiError> {
+    let (from, to) = (
+        parse_timestamp(&range.from, timezone)?,
+        parse_timestamp(&range.to, timezone)?,
+    );
+    let channels = match range
+        .channels
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|x| x.parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(channels) => channels,
+        Err(_) => return sub_error!(ExportError::InvalidChannels),
+    };
+
+    let res = analytics
+        .execute(|analytics| analytics.predictions_export(from, to, &channels))
+        .await?;
+    Ok(Json(res))
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("Channels must be comma separated channel IDs")]
+    InvalidChannels,
+}
+
+impl WebApiError for ExportError {
+    fn make_response(&self) -> axum::response::Response {
+        error_response(StatusCode::BAD_REQUEST, "invalid_channels", self)
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/analytics/optimize",
+    responses(
+        (status = 200, description = "Vacuum and analyze the analytics database, returning the reclaimed size", body = OptimizeResult),
+    )
+)]
+async fn optimize(
+    State(analytics): State<Arc<AnalyticsWrapper>>,
+) -> Result<Json<OptimizeResult>, ApiError> {
+    let res = analytics.execute(|analytics| analytics.optimize()).await?;
+    Ok(Json(res))
+}
+
+#[derive(Debug, Error)]
+pub enum ResetError {
+    #[error("Resetting removes all analytics of the streamer, pass confirm=true to do so")]
+    NotConfirmed,
+}
+
+impl WebApiError for ResetError {
+    fn make_response(&self) -> axum::response::Response {
+        error_response(StatusCode::BAD_REQUEST, "reset_not_confirmed", self)
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct ResetQuery {
+    /// Must be true, guards against accidentally removing data
+    #[serde(default)]
+    confirm: bool,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/analytics/streamers/{name}",
+    responses(
+        (status = 200, description = "Remove all points, predictions and viewership pings of the streamer, keeping its name", body = ResetResult),
+        (status = 400, description = "Streamer does not exist, or the reset was not confirmed"),
+    ),
+    params(
+        ("name" = String, Path, description = "Name of streamer"),
+        ResetQuery
+    )
+)]
+async fn reset_streamer(
+    State((state, analytics)): State<(ApiState, Arc<AnalyticsWrapper>)>,
+    Path(name): Path<String>,
+    Query(query): Query<ResetQuery>,
+) -> Result<Json<ResetResult>, ApiError> {
+    if !query.confirm {
+        return sub_error!(ResetError::NotConfirmed);
+    }
+    let id = match state.read().await.get_id_by_name(&name) {
+        Some(id) => id
+            .parse::<i32>()
+            .map_err(|err| ApiError::internal_error(err.into()))?,
+        None => return Err(ApiError::StreamerDoesNotExist),
+    };
 
     let res = analytics
-        .execute(|analytics| analytics.timeline(from, to, &timeline.channels))
+        .execute(|analytics| analytics.reset_streamer(id))
         .await?;
+    info!(
+        "Reset analytics of {name}, removed {} points and {} predictions",
+        res.points, res.predictions
+    );
     Ok(Json(res))
 }
+
+#[derive(Debug, Error)]
+pub enum CompareError {
+    #[error("No comparison analytics database was loaded")]
+    NotConfigured,
+}
+
+impl WebApiError for CompareError {
+    fn make_response(&self) -> axum::response::Response {
+        error_response(StatusCode::NOT_FOUND, "comparison_not_configured", self)
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct Comparison {
+    /// Summary of the analytics database used by the miner
+    primary: AnalyticsSummary,
+    /// Summary of the read-only comparison database
+    secondary: AnalyticsSummary,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/analytics/compare",
+    responses(
+        (status = 200, description = "Summaries of both analytics databases in the specified range", body = Comparison),
+        (status = 404, description = "No comparison analytics database was loaded"),
+    ),
+    params(WatchRange)
+)]
+async fn compare_summary(
+    State((analytics, compare)): State<(Arc<AnalyticsWrapper>, Option<Arc<AnalyticsWrapper>>)>,
+    Extension(timezone): Extension<Option<Tz>>,
+    Query(range): Query<WatchRange>,
+) -> Result<Json<Comparison>, ApiError> {
+    let compare = match compare {
+        Some(compare) => compare,
+        None => return sub_error!(CompareError::NotConfigured),
+    };
+    let (from, to) = (
+        parse_timestamp(&range.from, timezone)?,
+        parse_timestamp(&range.to, timezone)?,
+    );
+
+    let primary = analytics
+        .execute(|analytics| analytics.summary(from, to, None))
+        .await?;
+    let secondary = compare
+        .execute(|analytics| analytics.summary(from, to, None))
+        .await?;
+    Ok(Json(Comparison { primary, secondary }))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use chrono::{Duration, Local};
+    use eyre::Result;
+    use flume::unbounded;
+    use tokio::{net::TcpListener, sync::RwLock};
+    use twitch_api::types::UserId;
+
+    use crate::{
+        analytics::{
+            model::{AnalyticsSummary, PointsInfo, Prediction, PredictionBetWrapper},
+            Analytics, AnalyticsWrapper,
+        },
+        pubsub::PubSub,
+        web_api::ApiState,
+    };
+
+    fn pubsub() -> ApiState {
+        Arc::new(RwLock::new(PubSub::empty(unbounded().0)))
+    }
+
+    fn fill(path: &str, bet: u32, won: bool, points: [i32; 2]) -> Result<()> {
+        fill_channel(path, 1, "a", bet, won, points)
+    }
+
+    fn fill_channel(
+        path: &str,
+        channel_id: i32,
+        name: &str,
+        bet: u32,
+        won: bool,
+        points: [i32; 2],
+    ) -> Result<()> {
+        let (mut analytics, _) = Analytics::new(path)?;
+        analytics.insert_streamer(channel_id, name.to_owned())?;
+        analytics.upsert_prediction(&Prediction {
+            channel_id,
+            prediction_id: "pred-1".to_owned(),
+            title: "Win?".to_owned(),
+            prediction_window: 120,
+            outcomes: Vec::new().into(),
+            winning_outcome_id: None,
+            placed_bet: PredictionBetWrapper::None,
+            created_at: Local::now().naive_local(),
+            closed_at: None,
+            outcomes_history: Default::default(),
+        })?;
+        analytics.place_bet("pred-1", channel_id, "1", bet, false)?;
+        let winner = if won { "1" } else { "2" };
+        analytics.end_prediction(
+            "pred-1",
+            channel_id,
+            Some(winner.to_owned()),
+            Vec::new().into(),
+            Local::now().naive_local(),
+        )?;
+        for p in points {
+            analytics.insert_points(channel_id, p, PointsInfo::Watching)?;
+        }
+        analytics.insert_watch_event(channel_id, true)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compare_two_databases() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let primary = dir.join(format!("compare-primary-{id}.db"));
+        let secondary = dir.join(format!("compare-secondary-{id}.db"));
+        let (primary, secondary) = (primary.to_str().unwrap(), secondary.to_str().unwrap());
+        fill(primary, 100, true, [1000, 1100])?;
+        fill(secondary, 500, false, [1000, 500])?;
+
+        let mut read_only = Analytics::open_read_only(secondary)?;
+        assert!(read_only.insert_streamer(2, "b".to_owned()).is_err());
+
+        let (primary_db, _) = Analytics::new(primary)?;
+        let router = super::build(
+            pubsub(),
+            Arc::new(AnalyticsWrapper::new(primary_db)),
+            Some(Arc::new(AnalyticsWrapper::new(read_only))),
+            None,
+        )
+        .0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let from = Local::now() - Duration::hours(1);
+        let to = Local::now() + Duration::hours(1);
+        let res: serde_json::Value = reqwest::Client::new()
+            .get(format!("http://{address}/compare"))
+            .query(&[("from", from.to_rfc3339()), ("to", to.to_rfc3339())])
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let summary = |value: &serde_json::Value| AnalyticsSummary {
+            predictions: value["predictions"].as_i64().unwrap(),
+            bets_placed: value["bets_placed"].as_i64().unwrap(),
+            bets_won: value["bets_won"].as_i64().unwrap(),
+            points_wagered: value["points_wagered"].as_i64().unwrap(),
+            points_change: value["points_change"].as_i64().unwrap(),
+        };
+        assert_eq!(
+            summary(&res["primary"]),
+            AnalyticsSummary {
+                predictions: 1,
+                bets_placed: 1,
+                bets_won: 1,
+                points_wagered: 100,
+                points_change: 100,
+            }
+        );
+        assert_eq!(
+            summary(&res["secondary"]),
+            AnalyticsSummary {
+                predictions: 1,
+                bets_placed: 1,
+                bets_won: 0,
+                points_wagered: 500,
+                points_change: -500,
+            }
+        );
+
+        server.abort();
+        _ = std::fs::remove_file(primary);
+        _ = std::fs::remove_file(secondary);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn predictions_flat_export() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("export-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        fill(path, 100, true, [1000, 1100])?;
+
+        let (analytics, _) = Analytics::new(path)?;
+        let router = super::build(
+            pubsub(),
+            Arc::new(AnalyticsWrapper::new(analytics)),
+            None,
+            None,
+        )
+        .0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let export = |from: chrono::DateTime<Local>, to: chrono::DateTime<Local>| {
+            reqwest::Client::new()
+                .get(format!("http://{address}/predictions"))
+                .query(&[
+                    ("from", from.to_rfc3339()),
+                    ("to", to.to_rfc3339()),
+                    ("channels", "1".to_owned()),
+                ])
+                .send()
+        };
+        let res: serde_json::Value = export(
+            Local::now() - Duration::hours(1),
+            Local::now() + Duration::hours(1),
+        )
+        .await?
+        .json()
+        .await?;
+        let records = res.as_array().unwrap();
+        assert_eq!(records.len(), 1);
+        let record = records[0].as_object().unwrap();
+        assert_eq!(record["channel_id"], 1);
+        assert_eq!(record["channel_name"], "a");
+        assert_eq!(record["prediction_id"], "pred-1");
+        assert_eq!(record["bet_outcome_id"], "1");
+        assert_eq!(record["bet_points"], 100);
+        assert_eq!(record["bet_simulated"], false);
+        assert_eq!(record["won"], true);
+        assert!(record["outcomes"].is_array());
+        for key in ["created_at", "closed_at"] {
+            let at = record[key].as_str().unwrap();
+            assert!(chrono::DateTime::parse_from_rfc3339(at).is_ok(), "{at}");
+        }
+        // nothing nested except the outcomes
+        assert!(record
+            .iter()
+            .all(|(key, value)| key == "outcomes" || !(value.is_object() || value.is_array())));
+
+        let res: serde_json::Value = export(
+            Local::now() - Duration::hours(3),
+            Local::now() - Duration::hours(2),
+        )
+        .await?
+        .json()
+        .await?;
+        assert_eq!(res, serde_json::json!([]));
+
+        server.abort();
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn summary_by_tag() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("tags-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        fill_channel(path, 1, "a", 100, true, [1000, 1100])?;
+        fill_channel(path, 2, "b", 500, false, [1000, 500])?;
+        {
+            let (mut analytics, _) = Analytics::new(path)?;
+            analytics.set_tags(1, &["favorite".to_owned(), "high-variance".to_owned()])?;
+            analytics.set_tags(2, &["high-variance".to_owned()])?;
+            assert_eq!(analytics.tags(2)?, vec!["high-variance".to_owned()]);
+        }
+
+        let (analytics, _) = Analytics::new(path)?;
+        let router = super::build(
+            pubsub(),
+            Arc::new(AnalyticsWrapper::new(analytics)),
+            None,
+            None,
+        )
+        .0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let summary = |tag: &'static str| {
+            let mut query = vec![
+                ("from", (Local::now() - Duration::hours(1)).to_rfc3339()),
+                ("to", (Local::now() + Duration::hours(1)).to_rfc3339()),
+            ];
+            if !tag.is_empty() {
+                query.push(("tag", tag.to_owned()));
+            }
+            reqwest::Client::new()
+                .get(format!("http://{address}/summary"))
+                .query(&query)
+                .send()
+        };
+        let res: serde_json::Value = summary("favorite").await?.json().await?;
+        assert_eq!(res["predictions"], 1);
+        assert_eq!(res["points_wagered"], 100);
+        assert_eq!(res["points_change"], 100);
+
+        let res: serde_json::Value = summary("high-variance").await?.json().await?;
+        assert_eq!(res["predictions"], 2);
+        assert_eq!(res["points_change"], -400);
+
+        let res: serde_json::Value = summary("unknown").await?.json().await?;
+        assert_eq!(res["predictions"], 0);
+
+        let res: serde_json::Value = summary("").await?.json().await?;
+        assert_eq!(res["points_wagered"], 600);
+
+        server.abort();
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn session_net_since_baseline() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("session-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        // a previous run, its gains are not part of this session
+        fill(path, 100, true, [1000, 1100])?;
+        fill_channel(path, 3, "c", 100, true, [800, 900])?;
+        {
+            let (mut analytics, _) = Analytics::new(path)?;
+            analytics.insert_points(3, 900, PointsInfo::SessionBaseline)?;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let state = pubsub();
+        state.write().await.launched_at = Local::now();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        {
+            let (mut analytics, _) = Analytics::new(path)?;
+            analytics.insert_points(1, 1100, PointsInfo::SessionBaseline)?;
+            analytics.insert_points(1, 1500, PointsInfo::Watching)?;
+            analytics.insert_points(1, 1300, PointsInfo::Watching)?;
+            analytics.insert_streamer(2, "b".to_owned())?;
+            analytics.insert_points(2, 400, PointsInfo::FirstEntry)?;
+        }
+
+        let (analytics, _) = Analytics::new(path)?;
+        let router = super::build(
+            state,
+            Arc::new(AnalyticsWrapper::new(analytics)),
+            None,
+            None,
+        )
+        .0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let res: serde_json::Value = reqwest::get(format!("http://{address}/session"))
+            .await?
+            .json()
+            .await?;
+        // streamers without a baseline in this run are not reported
+        assert_eq!(
+            res,
+            serde_json::json!([{
+                "channel_id": 1,
+                "name": "a",
+                "baseline": 1100,
+                "points": 1300,
+                "net": 200,
+            }])
+        );
+
+        server.abort();
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn top_movers_ordering() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("movers-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        {
+            let (mut analytics, _) = Analytics::new(path)?;
+            for (id, name, points) in [
+                (1, "d", [1000, 1300]),
+                (2, "c", [1000, 1100]),
+                (3, "b", [1000, 1300]),
+                (4, "a", [1000, 800]),
+                (5, "e", [1000, 400]),
+                (6, "f", [1000, 1000]),
+            ] {
+                analytics.insert_streamer(id, name.to_owned())?;
+                analytics.insert_points(id, points[0], PointsInfo::FirstEntry)?;
+                analytics.insert_points(id, points[1], PointsInfo::Watching)?;
+            }
+        }
+
+        let (analytics, _) = Analytics::new(path)?;
+        let router = super::build(
+            pubsub(),
+            Arc::new(AnalyticsWrapper::new(analytics)),
+            None,
+            None,
+        )
+        .0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let top_movers = |limit: usize| {
+            reqwest::Client::new()
+                .get(format!("http://{address}/top-movers"))
+                .query(&[
+                    ("from", (Local::now() - Duration::hours(1)).to_rfc3339()),
+                    ("to", (Local::now() + Duration::hours(1)).to_rfc3339()),
+                    ("limit", limit.to_string()),
+                ])
+                .send()
+        };
+        let names = |items: &serde_json::Value| {
+            items
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| {
+                    (
+                        x["name"].as_str().unwrap().to_owned(),
+                        x["net"].as_i64().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let res: serde_json::Value = top_movers(5).await?.json().await?;
+        // equal gains are ordered by name, unchanged channels are neither
+        assert_eq!(
+            names(&res["gainers"]),
+            vec![
+                ("b".to_owned(), 300),
+                ("d".to_owned(), 300),
+                ("c".to_owned(), 100)
+            ]
+        );
+        assert_eq!(
+            names(&res["losers"]),
+            vec![("e".to_owned(), -600), ("a".to_owned(), -200)]
+        );
+
+        let res: serde_json::Value = top_movers(1).await?.json().await?;
+        assert_eq!(names(&res["gainers"]), vec![("b".to_owned(), 300)]);
+        assert_eq!(names(&res["losers"]), vec![("e".to_owned(), -600)]);
+
+        server.abort();
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn equity_curve_in_order() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("equity-curve-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let (mut analytics, _) = Analytics::new(path)?;
+        for balance in [1500, 1700, 1600] {
+            analytics.insert_equity_snapshot(balance)?;
+        }
+
+        let router = super::build(
+            pubsub(),
+            Arc::new(AnalyticsWrapper::new(analytics)),
+            None,
+            None,
+        )
+        .0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let equity = |from: chrono::DateTime<Local>, to: chrono::DateTime<Local>| {
+            reqwest::Client::new()
+                .get(format!("http://{address}/equity"))
+                .query(&[("from", from.to_rfc3339()), ("to", to.to_rfc3339())])
+                .send()
+        };
+        let res: serde_json::Value = equity(
+            Local::now() - Duration::hours(1),
+            Local::now() + Duration::hours(1),
+        )
+        .await?
+        .json()
+        .await?;
+        let snapshots = res.as_array().unwrap();
+        assert_eq!(
+            snapshots
+                .iter()
+                .map(|x| x["balance"].as_i64().unwrap())
+                .collect::<Vec<_>>(),
+            vec![1500, 1700, 1600]
+        );
+        assert!(snapshots[0]["created_at"].is_string());
+
+        let res: serde_json::Value = equity(
+            Local::now() - Duration::hours(3),
+            Local::now() - Duration::hours(2),
+        )
+        .await?
+        .json()
+        .await?;
+        assert_eq!(res, serde_json::json!([]));
+
+        server.abort();
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn optimize_populated_database() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("optimize-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        fill(path, 100, true, [1000, 1100])?;
+
+        let (analytics, _) = Analytics::new(path)?;
+        let router = super::build(
+            pubsub(),
+            Arc::new(AnalyticsWrapper::new(analytics)),
+            None,
+            None,
+        )
+        .0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let res = reqwest::Client::new()
+            .post(format!("http://{address}/optimize"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+        let res: serde_json::Value = res.json().await?;
+        assert!(res["size_after"].as_i64().unwrap() > 0);
+        assert_eq!(
+            res["reclaimed"].as_i64(),
+            Some(res["size_before"].as_i64().unwrap() - res["size_after"].as_i64().unwrap())
+        );
+
+        server.abort();
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reset_one_streamer() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("reset-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        fill_channel(path, 1, "a", 100, true, [1000, 1100])?;
+        fill_channel(path, 2, "b", 200, false, [1000, 800])?;
+
+        let state = pubsub();
+        for (id, name) in [("1", "a"), ("2", "b")] {
+            state.write().await.streamers.insert(
+                UserId::from_static(id),
+                common::types::StreamerState::new(true, name.to_owned()),
+            );
+        }
+        let (analytics, _) = Analytics::new(path)?;
+        let analytics = Arc::new(AnalyticsWrapper::new(analytics));
+        let router = super::build(state, analytics.clone(), None, None).0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::new();
+        let res = client
+            .delete(format!("http://{address}/streamers/A"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let res: serde_json::Value = client
+            .delete(format!("http://{address}/streamers/A"))
+            .query(&[("confirm", "true")])
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(res["points"], 2);
+        assert_eq!(res["predictions"], 1);
+        assert_eq!(res["watch_events"], 1);
+
+        let from = Local::now() - Duration::hours(1);
+        let to = Local::now() + Duration::hours(1);
+        let timeline = analytics
+            .execute(|analytics| analytics.timeline(from, to, &[1, 2]))
+            .await?;
+        assert!(timeline.iter().all(|x| x.point.channel_id == 2));
+        assert_eq!(timeline.len(), 2);
+        let predictions = analytics
+            .execute(|analytics| analytics.predictions_export(from, to, &[]))
+            .await?;
+        assert_eq!(predictions.len(), 1);
+        assert_eq!(predictions[0].channel_id, 2);
+        // the name mapping is kept
+        assert_eq!(predictions[0].channel_name.as_deref(), Some("b"));
+        let summary = analytics
+            .execute(|analytics| analytics.watch_summary(from, to))
+            .await?;
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].channel_id, 2);
+
+        server.abort();
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+}
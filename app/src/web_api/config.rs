@@ -1,22 +1,33 @@
+use std::{collections::HashMap, time::Instant};
+
 use axum::{
     extract::{Path, State},
-    response::IntoResponse,
     routing::{delete, get, post},
     Json, Router,
 };
-use common::config::{Config, ConfigType, Normalize, StreamerConfig};
+use common::{
+    config::{Config, ConfigType, Normalize, StreamerConfig},
+    twitch::ws,
+    types::StreamerState,
+};
+use eyre::Context;
 use http::StatusCode;
 use indexmap::IndexMap;
 use serde::Deserialize;
 use thiserror::Error;
+use tracing::{info, warn};
 use twitch_api::types::UserId;
 use utoipa::ToSchema;
 
-use crate::{make_paths, pubsub::PubSub, sub_error};
+use crate::{
+    make_paths,
+    pubsub::{ConfigDrift, PubSub},
+    sub_error,
+};
 
 use super::{
-    ApiError, ApiState, ConfigTypeRef, RouterBuild, StreamerConfigRef, StreamerConfigRefWrapper,
-    WebApiError,
+    error_response, save_config, ApiError, ApiState, ConfigTypeRef, RouterBuild, StreamerConfigRef,
+    StreamerConfigRefWrapper, WebApiError,
 };
 
 pub fn build(state: ApiState) -> RouterBuild {
@@ -25,11 +36,19 @@ pub fn build(state: ApiState) -> RouterBuild {
         .route("/presets/", post(add_update_preset))
         .route("/presets/:name", delete(remove_preset))
         .route("/streamer/:name", post(update_streamer_config))
+        .route("/apply-preset", post(apply_preset))
         .route("/watch_priority", get(get_watch_priority))
         .route("/watch_priority/", post(update_watch_priority))
+        .route("/watch_only", get(get_watch_only))
+        .route("/consistency", get(consistency))
         .with_state(state);
 
-    let schemas = vec![AddUpdatePreset::schema()];
+    let schemas = vec![
+        AddUpdatePreset::schema(),
+        ApplyPreset::schema(),
+        ApplyPresetTarget::schema(),
+        ConfigDrift::schema(),
+    ];
 
     let paths = make_paths!(
         __path_get_presets,
@@ -37,7 +56,10 @@ pub fn build(state: ApiState) -> RouterBuild {
         __path_remove_preset,
         __path_get_watch_priority,
         __path_update_watch_priority,
-        __path_update_streamer_config
+        __path_get_watch_only,
+        __path_update_streamer_config,
+        __path_apply_preset,
+        __path_consistency
     );
 
     (routes, schemas, paths)
@@ -58,15 +80,17 @@ pub enum ConfigError {
 impl WebApiError for ConfigError {
     fn make_response(&self) -> axum::response::Response {
         use ConfigError::*;
-        let status_code = match self {
-            PresetConfigNameEqualsStreamerName | PresetConfigDoesNotExist => {
-                StatusCode::BAD_REQUEST
-            }
-            InvalidConfig(_) => StatusCode::BAD_REQUEST,
-            PresetConfigInUse(_) => StatusCode::BAD_REQUEST,
+        let (status_code, code) = match self {
+            PresetConfigNameEqualsStreamerName => (
+                StatusCode::BAD_REQUEST,
+                "preset_config_name_equals_streamer_name",
+            ),
+            PresetConfigDoesNotExist => (StatusCode::BAD_REQUEST, "preset_config_does_not_exist"),
+            InvalidConfig(_) => (StatusCode::BAD_REQUEST, "invalid_config"),
+            PresetConfigInUse(_) => (StatusCode::BAD_REQUEST, "preset_config_in_use"),
         };
 
-        (status_code, self.to_string()).into_response()
+        error_response(status_code, code, self)
     }
 }
 
@@ -126,7 +150,7 @@ async fn add_update_preset(
     }
 
     let mut preset_normalized = preset.config.clone();
-    preset_normalized.prediction.strategy.normalize();
+    preset_normalized.prediction.normalize();
     match writer.configs.get_mut(&preset.name) {
         Some(c) => c.0.write().unwrap().config = preset_normalized,
         None => {
@@ -147,7 +171,7 @@ async fn add_update_preset(
         None => writer.config.presets = Some(IndexMap::from([(preset.name, preset.config)])),
     }
 
-    writer.save_config("Update preset").await?;
+    save_config(&data, writer, "Update preset").await?;
     Ok(())
 }
 
@@ -181,7 +205,7 @@ async fn remove_preset(
 
     writer.configs.remove(&name);
     writer.config.presets.as_mut().unwrap().shift_remove(&name);
-    writer.save_config("Remove preset").await?;
+    save_config(&data, writer, "Remove preset").await?;
     Ok(())
 }
 
@@ -224,10 +248,21 @@ async fn update_watch_priority(
     }
 
     writer.config.watch_priority = Some(priority);
-    writer.save_config("Update watch priority").await?;
+    save_config(&data, writer, "Update watch priority").await?;
     Ok(())
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/config/watch_only",
+    responses(
+        (status = 200, description = "If betting is disabled for all streamers", body = bool),
+    )
+)]
+async fn get_watch_only(State(data): State<ApiState>) -> Json<bool> {
+    Json(data.read().await.watch_only)
+}
+
 #[utoipa::path(
     post,
     path = "/api/config/streamer/{channel_name}",
@@ -252,12 +287,221 @@ async fn update_streamer_config(
         None => return Err(ApiError::StreamerDoesNotExist),
     };
 
-    let config = writer.insert_config(&payload, &channel_name)?;
+    let name = writer.streamers[&id].info.channel_name.clone();
+    let config = writer.insert_config(&payload, &name)?;
     writer.streamers.get_mut(&id).unwrap().config = config;
-    *writer.config.streamers.get_mut(&channel_name).unwrap() = payload;
+    let key = writer.config_key(&name).unwrap_or(name);
+    writer.config.streamers.insert(key, payload);
+
+    save_config(&data, writer, "Update streamer config").await?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ApplyPreset {
+    /// Name of the preset to apply
+    preset: String,
+    streamers: ApplyPresetTarget,
+}
+
+#[derive(Deserialize, ToSchema)]
+enum ApplyPresetTarget {
+    /// Every streamer
+    All,
+    /// Streamers with these channel names
+    Names(Vec<String>),
+    /// Streamers currently in this game category
+    Game(String),
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/config/apply-preset",
+    responses(
+        (status = 200, description = "Names of the streamers the preset was applied to", body = Vec<String>),
+        (status = 400, description = "Preset does not exist"),
+        (status = 404, description = "Could not find a streamer")
+    ),
+    request_body = ApplyPreset
+)]
+async fn apply_preset(
+    State(data): State<ApiState>,
+    Json(payload): Json<ApplyPreset>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    let mut writer = data.write().await;
+
+    if !writer
+        .config
+        .presets
+        .as_ref()
+        .is_some_and(|p| p.contains_key(&payload.preset))
+    {
+        return sub_error!(ConfigError::PresetConfigDoesNotExist);
+    }
+
+    let names = match payload.streamers {
+        ApplyPresetTarget::All => writer
+            .streamers
+            .values()
+            .map(|s| s.info.channel_name.clone())
+            .collect(),
+        ApplyPresetTarget::Names(names) => {
+            let mut canonical = Vec::new();
+            for name in &names {
+                match writer.get_by_name(name) {
+                    Some(s) => canonical.push(s.info.channel_name.clone()),
+                    None => return Err(ApiError::StreamerDoesNotExist),
+                }
+            }
+            canonical
+        }
+        ApplyPresetTarget::Game(game) => writer
+            .streamers
+            .values()
+            .filter(|s| s.info.game.as_ref().is_some_and(|g| g.name == game))
+            .map(|s| s.info.channel_name.clone())
+            .collect::<Vec<_>>(),
+    };
+
+    let preset = ConfigType::Preset(payload.preset);
+    for name in &names {
+        let config = writer.insert_config(&preset, name)?;
+        writer.get_by_name_mut(name).unwrap().config = config;
+        let key = writer.config_key(name).unwrap_or(name.clone());
+        writer.config.streamers.insert(key, preset.clone());
+    }
+
+    save_config(&data, writer, "Apply preset").await?;
+    Ok(Json(names))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/config/consistency",
+    responses(
+        (status = 200, description = "Streamers that differ between the config and the streamers being mined", body = ConfigDrift),
+    )
+)]
+async fn consistency(State(data): State<ApiState>) -> Json<ConfigDrift> {
+    Json(data.read().await.config_drift())
+}
+
+/// Log streamers that differ between the config and the ones being mined. Streamers no longer in the config stop
+/// being mined, and the ones missing are looked up on twitch without holding the lock and then mined
+pub async fn reconcile(pubsub: &ApiState) -> Result<(), ApiError> {
+    let (missing, gql) = {
+        let mut writer = pubsub.write().await;
+        let drift = writer.config_drift();
+        if drift.is_empty() {
+            return Ok(());
+        }
+        warn!(
+            "Streamers out of sync with the config, not in the config: {:?}, not mined: {:?}",
+            drift.orphaned, drift.missing
+        );
+
+        for name in &drift.orphaned {
+            let id = match writer.get_id_by_name(name) {
+                Some(s) => UserId::from(s.to_owned()),
+                None => continue,
+            };
+            writer.streamers.remove(&id);
+            writer.configs.remove(name);
+            ws::remove_streamer(
+                &writer.ws_tx,
+                id.as_str()
+                    .parse()
+                    .context("Parse streamer id")
+                    .map_err(ApiError::internal_error)?,
+            )
+            .await
+            .map_err(ApiError::internal_error)?;
+            info!("Stopped mining {name}, it is no longer in the config");
+        }
+        (drift.missing, writer.gql.clone())
+    };
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let names = missing.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+    let metadata = gql
+        .streamer_metadata(&names)
+        .await
+        .map_err(ApiError::twitch_api_error)?;
+    let mut found = Vec::new();
+    for (name, metadata) in names.into_iter().zip(metadata) {
+        let (id, info) = match metadata {
+            Some(s) => s,
+            None => {
+                warn!("{name} is in the config but does not exist on twitch");
+                continue;
+            }
+        };
+        let points = gql
+            .get_channel_points(&[name])
+            .await
+            .map_err(ApiError::twitch_api_error)?[0]
+            .0;
+        let predictions = gql
+            .channel_points_context(&[name])
+            .await
+            .map_err(ApiError::twitch_api_error)?
+            .swap_remove(0);
+        found.push((name, id, info, points, predictions));
+    }
 
-    writer.save_config("Update streamer config").await?;
+    let mut writer = pubsub.write().await;
+    for (name, id, info, points, predictions) in found {
+        // the config may have been edited again while twitch was asked
+        let config = match writer.config.streamers.get(name) {
+            Some(c) if writer.get_by_name(name).is_none() => c.clone(),
+            _ => continue,
+        };
+        let config = match writer.insert_config(&config, name) {
+            Ok(c) => c,
+            Err(err) => {
+                warn!("Could not mine {name}, {err:?}");
+                continue;
+            }
+        };
 
+        let channel_id = id
+            .as_str()
+            .parse()
+            .context("Parse streamer id")
+            .map_err(ApiError::internal_error)?;
+        let channel_name = info.channel_name.clone();
+        let max_predictions = writer.max_predictions();
+        writer.streamers.insert(
+            id,
+            StreamerState {
+                config,
+                info,
+                predictions: predictions
+                    .into_iter()
+                    .take(max_predictions)
+                    .map(|x| (x.0.id.clone(), x))
+                    .collect::<HashMap<_, _>>(),
+                points,
+                last_points_refresh: Instant::now(),
+                ..Default::default()
+            },
+        );
+        ws::add_streamer(&writer.ws_tx, channel_id)
+            .await
+            .map_err(ApiError::internal_error)?;
+        _ = writer
+            .analytics_tx
+            .send_async(Box::new(move |analytics| {
+                analytics
+                    .insert_streamer(channel_id as i32, channel_name.clone())
+                    .map(|_| ())
+            }))
+            .await;
+        info!("Started mining {name}, it was added to the config");
+    }
     Ok(())
 }
 
@@ -300,3 +544,228 @@ impl PubSub {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, sync::Arc};
+
+    use axum::{extract::State, Json};
+    use common::{
+        config::{ConfigType, StreamerConfig},
+        testing::{container, TestContainer},
+        twitch::{gql, ws::Request},
+        types::{ConfigTypeRef, StreamerConfigRef, StreamerConfigRefWrapper, StreamerState},
+    };
+    use eyre::Result;
+    use flume::unbounded;
+    use indexmap::IndexMap;
+    use rstest::rstest;
+    use tokio::sync::RwLock;
+    use twitch_api::{
+        pubsub::{video_playback::VideoPlaybackById, Topics},
+        types::UserId,
+    };
+
+    use super::{
+        apply_preset, consistency, reconcile, update_watch_priority, ApplyPreset, ApplyPresetTarget,
+    };
+    use crate::{pubsub::PubSub, web_api::ApiState};
+
+    fn state(config_path: &str) -> ApiState {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.config_path = config_path.to_owned();
+        for (id, name) in [("1", "a"), ("2", "b"), ("3", "c")] {
+            pubsub.streamers.insert(
+                UserId::from_static(id),
+                StreamerState::new(true, name.to_owned()),
+            );
+            pubsub.config.streamers.insert(
+                name.to_owned(),
+                ConfigType::Specific(StreamerConfig::default()),
+            );
+        }
+        pubsub.config.presets = Some(IndexMap::from([(
+            "preset".to_owned(),
+            StreamerConfig::default(),
+        )]));
+        pubsub.configs.insert(
+            "preset".to_owned(),
+            StreamerConfigRefWrapper::new(StreamerConfigRef {
+                _type: ConfigTypeRef::Preset("preset".to_owned()),
+                config: StreamerConfig::default(),
+            }),
+        );
+        Arc::new(RwLock::new(pubsub))
+    }
+
+    async fn presets_in_use(state: &ApiState) -> Vec<String> {
+        let reader = state.read().await;
+        let mut names = reader
+            .streamers
+            .values()
+            .filter(|s| {
+                s.config.0.read().unwrap()._type == ConfigTypeRef::Preset("preset".to_owned())
+            })
+            .map(|s| s.info.channel_name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn apply_preset_to_streamers() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("apply-preset-{}.yaml", std::process::id()));
+        let state = state(path.to_str().unwrap());
+
+        let request = |preset: &str, names: &[&str]| {
+            Json(ApplyPreset {
+                preset: preset.to_owned(),
+                streamers: ApplyPresetTarget::Names(names.iter().map(|x| x.to_string()).collect()),
+            })
+        };
+
+        // nothing is applied if the preset or any streamer is missing
+        assert!(
+            apply_preset(State(state.clone()), request("missing", &["a"]))
+                .await
+                .is_err()
+        );
+        assert!(
+            apply_preset(State(state.clone()), request("preset", &["a", "d"]))
+                .await
+                .is_err()
+        );
+        assert!(presets_in_use(&state).await.is_empty());
+
+        let applied = apply_preset(State(state.clone()), request("preset", &["a", "b"]))
+            .await
+            .map_err(|_| eyre::eyre!("Applying preset"))?;
+        assert_eq!(applied.0, vec!["a", "b"]);
+        assert_eq!(presets_in_use(&state).await, vec!["a", "b"]);
+
+        let saved = std::fs::read_to_string(&path)?;
+        let saved: common::config::Config = serde_yaml::from_str(&saved)?;
+        for (name, preset) in [("a", true), ("b", true), ("c", false)] {
+            assert_eq!(
+                matches!(&saved.streamers[name], ConfigType::Preset(p) if p == "preset"),
+                preset
+            );
+        }
+
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(std::time::Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn config_drift_is_repaired(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{base_url}/streamer_metadata"))
+            .json(&serde_json::json!({ "3": ["c", { "id": "3", "stream": null }] }))
+            .send()
+            .await?;
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("3", ("c", 700, None::<String>))]))
+            .send()
+            .await?;
+
+        let path = std::env::temp_dir().join(format!("config-drift-{}.yaml", std::process::id()));
+        let (ws_tx, ws_rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.config_path = path.to_str().unwrap().to_owned();
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        // b is mined without a config entry, c has one but is not mined
+        for (id, name) in [("1", "a"), ("2", "b")] {
+            pubsub.streamers.insert(
+                UserId::from_static(id),
+                StreamerState::new(true, name.to_owned()),
+            );
+        }
+        for name in ["a", "c"] {
+            pubsub.config.streamers.insert(
+                name.to_owned(),
+                ConfigType::Specific(StreamerConfig::default()),
+            );
+        }
+        let state: ApiState = Arc::new(RwLock::new(pubsub));
+
+        let drift = consistency(State(state.clone())).await.0;
+        assert_eq!(drift.orphaned, vec!["b"]);
+        assert_eq!(drift.missing, vec!["c"]);
+
+        // any config edit reconciles
+        update_watch_priority(State(state.clone()), Json(vec!["a".to_owned()]))
+            .await
+            .map_err(|_| eyre::eyre!("Updating watch priority"))?;
+        assert!(consistency(State(state.clone())).await.0.is_empty());
+        let reader = state.read().await;
+        assert!(!reader.streamers.contains_key(&UserId::from_static("2")));
+        let c = &reader.streamers[&UserId::from_static("3")];
+        assert_eq!(c.info.channel_name, "c");
+        assert_eq!(c.points, 700);
+        assert!(reader.configs.contains_key("c"));
+        assert_eq!(
+            ws_rx.drain().collect::<Vec<_>>(),
+            vec![
+                Request::UnListen(Topics::VideoPlaybackById(VideoPlaybackById {
+                    channel_id: 2
+                })),
+                Request::Listen(Topics::VideoPlaybackById(VideoPlaybackById {
+                    channel_id: 3
+                })),
+            ]
+        );
+
+        _ = std::fs::remove_file(path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reconcile_releases_lock_for_twitch() -> Result<()> {
+        // twitch only answers once the test saw the lock free
+        let (asked_tx, asked_rx) = tokio::sync::oneshot::channel::<()>();
+        let (answer_tx, answer_rx) = tokio::sync::oneshot::channel::<()>();
+        let handler = Arc::new(std::sync::Mutex::new(Some((asked_tx, answer_rx))));
+        let router = axum::Router::new().route(
+            "/gql",
+            axum::routing::post(move || {
+                let (asked_tx, answer_rx) = handler.lock().unwrap().take().unwrap();
+                async move {
+                    _ = asked_tx.send(());
+                    _ = answer_rx.await;
+                    http::StatusCode::INTERNAL_SERVER_ERROR
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let mut pubsub = PubSub::empty(unbounded().0);
+        pubsub.gql = gql::Client::new(String::new(), format!("http://{address}/gql"));
+        pubsub.config.streamers.insert(
+            "c".to_owned(),
+            ConfigType::Specific(StreamerConfig::default()),
+        );
+        let state: ApiState = Arc::new(RwLock::new(pubsub));
+
+        let running = tokio::spawn({
+            let state = state.clone();
+            async move { reconcile(&state).await }
+        });
+        asked_rx.await?;
+        assert!(state.try_write().is_ok());
+        _ = answer_tx.send(());
+        assert!(running.await?.is_err());
+        assert_eq!(consistency(State(state.clone())).await.0.missing, vec!["c"]);
+
+        server.abort();
+        Ok(())
+    }
+}
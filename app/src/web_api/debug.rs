@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::Local;
+use common::{config::Config, twitch::ws::RawMessages};
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use twitch_api::types::UserId;
+use utoipa::ToSchema;
+
+use crate::{make_paths, pubsub::PubSub};
+
+use super::{ApiError, ApiState, RouterBuild};
+
+pub fn build(state: ApiState, raw_messages: RawMessages) -> RouterBuild {
+    let routes = Router::new()
+        .route("/dump", post(dump_state))
+        .with_state(state)
+        .route("/pubsub", get(raw_pubsub).with_state(raw_messages));
+
+    let paths = make_paths!(__path_dump_state, __path_raw_pubsub);
+
+    (routes, vec![RawPubsubQuery::schema()], paths)
+}
+
+/// Full application state, including fields skipped by the API. Never contains the access token
+#[derive(Serialize)]
+struct StateDump<'a> {
+    created_at: String,
+    state: &'a PubSub,
+    config: &'a Config,
+    config_path: &'a str,
+    base_url: &'a str,
+    spade_url: Option<&'a str>,
+    /// Requests waiting to be written to the analytics database
+    analytics_queue: usize,
+    /// Commands waiting to be sent to the pubsub connection pool
+    ws_queue: usize,
+    events_subscribers: usize,
+    streamers: HashMap<&'a UserId, StreamerDump>,
+}
+
+#[derive(Serialize)]
+struct StreamerDump {
+    last_points_refresh_secs: u64,
+    live_since_secs: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/debug/dump",
+    responses(
+        (status = 201, description = "Wrote the application state to a file, returns the file path", body = String),
+    )
+)]
+async fn dump_state(State(data): State<ApiState>) -> Result<(StatusCode, Json<String>), ApiError> {
+    let path = data
+        .read()
+        .await
+        .dump(Path::new("."))
+        .await
+        .map_err(ApiError::internal_error)?;
+    Ok((StatusCode::CREATED, Json(path.display().to_string())))
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+struct RawPubsubQuery {
+    /// Number of messages to return, all kept messages by default
+    n: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/debug/pubsub",
+    responses(
+        (status = 200, description = "Recently received raw pubsub messages, oldest first", body = Vec<String>),
+    ),
+    params(RawPubsubQuery)
+)]
+async fn raw_pubsub(
+    State(raw_messages): State<RawMessages>,
+    Query(query): Query<RawPubsubQuery>,
+) -> Json<Vec<String>> {
+    Json(raw_messages.last(query.n.unwrap_or(usize::MAX)))
+}
+
+impl PubSub {
+    /// Write the state to a timestamped JSON file in `dir`
+    pub async fn dump(&self, dir: &Path) -> Result<PathBuf> {
+        let now = Local::now();
+        let dump = StateDump {
+            created_at: now.to_rfc3339(),
+            state: self,
+            config: &self.config,
+            config_path: &self.config_path,
+            base_url: &self.base_url,
+            spade_url: self.spade_url(),
+            analytics_queue: self.analytics_tx.len(),
+            ws_queue: self.ws_tx.len(),
+            events_subscribers: self.events_tx.receiver_count(),
+            streamers: self
+                .streamers
+                .iter()
+                .map(|(id, s)| {
+                    (
+                        id,
+                        StreamerDump {
+                            last_points_refresh_secs: s.last_points_refresh.elapsed().as_secs(),
+                            live_since_secs: s.live_since.map(|x| x.elapsed().as_secs()),
+                        },
+                    )
+                })
+                .collect(),
+        };
+
+        let path = dir.join(format!("dump-{}.json", now.format("%Y%m%d-%H%M%S")));
+        tokio::fs::write(
+            &path,
+            serde_json::to_string_pretty(&dump).context("Serializing state dump")?,
+        )
+        .await
+        .context("Writing state dump")?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{twitch::gql, types::StreamerState};
+    use eyre::Result;
+    use flume::unbounded;
+    use twitch_api::types::UserId;
+
+    use crate::pubsub::PubSub;
+
+    #[tokio::test]
+    async fn dump_contains_internal_state() -> Result<()> {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.gql = gql::Client::new("secret-token".to_owned(), String::new());
+        pubsub.config_path = "config.yaml".to_owned();
+        pubsub.streamers.insert(
+            UserId::from_static("1"),
+            StreamerState::new(true, "a".to_owned()),
+        );
+
+        let dir = std::env::temp_dir();
+        let path = pubsub.dump(&dir).await?;
+        let text = tokio::fs::read_to_string(&path).await?;
+        tokio::fs::remove_file(&path).await?;
+
+        assert!(!text.contains("secret-token"));
+        let dump: serde_json::Value = serde_json::from_str(&text)?;
+        for key in [
+            "created_at",
+            "state",
+            "config",
+            "config_path",
+            "spade_url",
+            "analytics_queue",
+            "ws_queue",
+            "streamers",
+        ] {
+            assert!(dump.get(key).is_some(), "missing {key}");
+        }
+        assert_eq!(dump["config_path"], "config.yaml");
+        assert_eq!(dump["state"]["streamers"]["1"]["info"]["channelName"], "a");
+        assert!(dump["streamers"]["1"]["last_points_refresh_secs"].is_u64());
+        Ok(())
+    }
+}
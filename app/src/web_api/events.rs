@@ -0,0 +1,101 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Router,
+};
+use futures_util::{stream, Stream};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{events::AppEvent, make_paths};
+
+use super::{ApiState, RouterBuild};
+
+pub fn build(state: ApiState) -> RouterBuild {
+    let routes = Router::new()
+        .route("/events", get(events))
+        .with_state(state);
+
+    let schemas = vec![AppEvent::schema()];
+
+    let paths = make_paths!(__path_events);
+
+    (routes, schemas, paths)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    responses(
+        (status = 200, description = "Stream of bet_placed, prediction_ended, points_claimed and live_changed server-sent events", body = AppEvent, content_type = "text/event-stream"),
+    )
+)]
+async fn events(
+    State(data): State<ApiState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = data.read().await.events_tx.subscribe();
+
+    // the receiver is dropped with the stream when the client disconnects
+    let stream = stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let sse = Event::default()
+                        .event(event.name())
+                        .json_data(&event)
+                        .unwrap_or_default();
+                    return Some((Ok(sse), rx));
+                }
+                Err(RecvError::Lagged(n)) => debug!("Events client lagged by {n} events"),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use eyre::Result;
+    use flume::unbounded;
+    use tokio::{net::TcpListener, sync::RwLock, time::timeout};
+
+    use crate::{events::AppEvent, pubsub::PubSub};
+
+    #[tokio::test]
+    async fn event_reaches_client() -> Result<()> {
+        let (ws_tx, _) = unbounded();
+        let pubsub = Arc::new(RwLock::new(PubSub::empty(ws_tx)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let router = super::build(pubsub.clone()).0;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let mut res = reqwest::get(format!("http://{address}/events")).await?;
+        assert_eq!(res.headers()["content-type"], "text/event-stream");
+
+        pubsub.read().await.emit(AppEvent::LiveChanged {
+            channel_name: "a".to_owned(),
+            live: true,
+        });
+
+        let chunk = timeout(Duration::from_secs(5), res.chunk())
+            .await??
+            .unwrap();
+        let chunk = String::from_utf8(chunk.to_vec())?;
+        assert!(chunk.contains("event: live_changed"));
+        assert!(chunk.contains(r#"data: {"channel_name":"a","live":true}"#));
+
+        drop(res);
+        server.abort();
+        Ok(())
+    }
+}
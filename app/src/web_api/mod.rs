@@ -1,4 +1,4 @@
-use std::{io::SeekFrom, sync::Arc};
+use std::{io::SeekFrom, net::SocketAddr, path::Path, sync::Arc};
 
 use axum::{
     extract::{Query, State},
@@ -9,18 +9,22 @@ use axum::{
     Json, Router,
 };
 use common::{
-    config::{filters::Filter, strategy::*, PredictionConfig, StreamerConfig},
-    twitch::auth::Token,
+    config::{filters::Filter, strategy::*, NotifyTarget, PredictionConfig, StreamerConfig},
+    twitch::{
+        auth::Token,
+        ws::{RawMessages, WsPoolStatus},
+    },
     types::*,
 };
 use eyre::{Context, Report, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tokio::{
     fs::File,
     io::{AsyncReadExt, AsyncSeekExt, BufReader},
-    sync::RwLock,
+    sync::{watch, RwLock, RwLockWriteGuard},
 };
 use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
+use tracing::warn;
 use twitch_api::{
     pubsub::predictions::Event,
     types::{Timestamp, UserId},
@@ -33,13 +37,18 @@ use utoipa_swagger_ui::SwaggerUi;
 
 use crate::{
     analytics::{Analytics, AnalyticsWrapper},
-    pubsub::PubSub,
+    pubsub::{ClaimStats, PubSub},
 };
 
 mod analytics;
 mod config;
+mod debug;
+mod events;
 mod predictions;
 mod streamer;
+mod token;
+mod watching;
+mod ws;
 
 type ApiState = Arc<RwLock<PubSub>>;
 type RouterBuild = (
@@ -72,23 +81,90 @@ macro_rules! sub_error {
     };
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_api_server(
-    address: String,
+    address: SocketAddr,
     pubsub: ApiState,
     token: Arc<Token>,
-    analytics_db: &str,
+    analytics_db: Option<&str>,
+    compare_analytics_db: Option<&str>,
     log_path: Option<String>,
+    ws_status: watch::Receiver<WsPoolStatus>,
+    raw_messages: RawMessages,
+    dashboard: bool,
 ) -> Result<Serve<Router, Router>> {
+    let (api, openapi) = api_router(
+        pubsub,
+        token,
+        analytics_db,
+        compare_analytics_db,
+        log_path,
+        ws_status,
+        raw_messages,
+    )
+    .await?;
+
+    let mut router = Router::new().nest("/api", api);
+    if dashboard {
+        router = router.merge(SwaggerUi::new("/docs").url("/docs/openapi.json", openapi));
+        if Path::new("dist").is_dir() {
+            router = router.nest_service("/", ServeDir::new("dist"));
+        } else {
+            warn!("Dashboard directory dist does not exist, only serving the API. Use --no-dashboard to silence this warning");
+        }
+    }
+    let router = router
+        .layer(CorsLayer::very_permissive())
+        .layer(TraceLayer::new_for_http());
+
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Bind the API to {address}, is it already in use?"))?;
+    Ok(axum::serve(listener, router))
+}
+
+/// Write the OpenAPI spec of the web API to `path` as JSON, built from an empty state so the miner does not need to run
+pub async fn dump_openapi(path: &str) -> Result<()> {
+    let (ws_tx, _) = flume::unbounded();
+    let (_, openapi) = api_router(
+        Arc::new(RwLock::new(PubSub::empty(ws_tx))),
+        Arc::new(Token::default()),
+        Some(":memory:"),
+        None,
+        None,
+        watch::channel(WsPoolStatus::default()).1,
+        RawMessages::new(0),
+    )
+    .await?;
+    let json = openapi
+        .to_pretty_json()
+        .context("Serializing OpenAPI spec")?;
+    tokio::fs::write(path, json)
+        .await
+        .context("Writing OpenAPI spec")
+}
+
+/// Routes under /api, and the OpenAPI spec documenting them
+async fn api_router(
+    pubsub: ApiState,
+    token: Arc<Token>,
+    analytics_db: Option<&str>,
+    compare_analytics_db: Option<&str>,
+    log_path: Option<String>,
+    ws_status: watch::Receiver<WsPoolStatus>,
+    raw_messages: RawMessages,
+) -> Result<(Router, utoipa::openapi::OpenApi)> {
     #[derive(OpenApi)]
     #[openapi(
         paths(
             app_state,
-            get_logs
+            get_logs,
+            version
         ),
         components(
             schemas(
-                PubSub, StreamerState, StreamerConfigRefWrapper, ConfigTypeRef, StreamerConfig, PredictionConfig, StreamerInfo, Event,
-                Filter, Strategy, UserId, Game, Detailed, Timestamp, DefaultPrediction, DetailedOdds, Points, OddsComparisonType, LogQuery
+                PubSub, ClaimStats, StreamerState, Circuit, Wager, StreamerConfigRefWrapper, ConfigTypeRef, StreamerConfig, NotifyTarget, PredictionConfig, StreamerInfo, Event,
+                Filter, Strategy, UserId, Game, Detailed, Timestamp, DefaultPrediction, DetailedOdds, Points, OddsComparisonType, ProbabilitySource, RangePreference, Composite, WeightedStrategy, CombineRule, TimeDecay, LogQuery, AppStateQuery, Version, ErrorBody
             ),
         ),
         tags(
@@ -103,8 +179,13 @@ pub async fn get_api_server(
     let mut paths = Vec::new();
     let mut schemas = Vec::new();
 
-    let (analytics, tx) = Analytics::new(analytics_db)?;
-    let analytics = Arc::new(AnalyticsWrapper::new(analytics));
+    let (analytics, tx) = match analytics_db {
+        Some(path) => {
+            let (analytics, tx) = Analytics::new(path)?;
+            (Arc::new(AnalyticsWrapper::new(analytics)), tx)
+        }
+        None => (Arc::new(AnalyticsWrapper::empty()), Analytics::disabled()),
+    };
 
     let streamer = streamer::build(pubsub.clone(), token.clone());
     schemas.extend(streamer.1);
@@ -118,8 +199,33 @@ pub async fn get_api_server(
     schemas.extend(config.1);
     paths.extend(config.2);
 
+    let debug = debug::build(pubsub.clone(), raw_messages);
+    paths.extend(debug.2);
+
+    let token = token::build(pubsub.clone());
+    paths.extend(token.2);
+
+    let events = events::build(pubsub.clone());
+    schemas.extend(events.1);
+    paths.extend(events.2);
+
+    let watching = watching::build(pubsub.clone());
+    schemas.extend(watching.1);
+    paths.extend(watching.2);
+
+    let ws = ws::build(ws_status);
+    schemas.extend(ws.1);
+    paths.extend(ws.2);
+
     let analytics = {
-        let analytics = analytics::build(analytics);
+        let compare = match compare_analytics_db {
+            Some(path) => Some(Arc::new(AnalyticsWrapper::new(Analytics::open_read_only(
+                path,
+            )?))),
+            None => None,
+        };
+        let timezone = pubsub.read().await.config.timezone();
+        let analytics = analytics::build(pubsub.clone(), analytics, compare, timezone);
         schemas.extend(analytics.1);
         paths.extend(analytics.2);
         analytics.0
@@ -132,24 +238,32 @@ pub async fn get_api_server(
         components.schemas.insert(s.0.to_owned(), s.1);
     }
 
-    #[allow(unused_mut)]
-    let mut api = Router::new()
+    let api = Router::new()
         .nest("/streamers", streamer.0)
-        .nest("/predictions", predictions.0)
+        .merge(predictions.0)
         .nest("/config", config.0)
         .nest("/analytics", analytics)
+        .nest("/debug", debug.0)
+        .nest("/token", token.0)
+        .nest("/ws", ws.0)
+        .merge(events.0)
+        .merge(watching.0)
         .route("/logs", get(get_logs).with_state(log_path))
+        .route("/version", get(version))
         .route("/", get(app_state).with_state(pubsub.clone()));
 
-    let router = Router::new()
-        .merge(SwaggerUi::new("/docs").url("/docs/openapi.json", openapi))
-        .nest_service("/", ServeDir::new("dist"))
-        .nest("/api", api)
-        .layer(CorsLayer::very_permissive())
-        .layer(TraceLayer::new_for_http());
+    Ok((api, openapi))
+}
 
-    let listener = tokio::net::TcpListener::bind(address).await.unwrap();
-    Ok(axum::serve(listener, router))
+#[derive(Debug, Default, Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
+/// Filters for the streamers in the application state, all streamers are returned by default
+struct AppStateQuery {
+    /// Only streamers that are live, or offline if false
+    live: Option<bool>,
+    /// Only streamers with an active prediction, or without one if false
+    has_prediction: Option<bool>,
+    /// Comma separated list of streamer names
+    names: Option<String>,
 }
 
 #[utoipa::path(
@@ -157,11 +271,37 @@ pub async fn get_api_server(
     path = "/api",
     responses(
         (status = 200, description = "Get the entire application state information", body = PubSub)
-    )
+    ),
+    params(AppStateQuery)
 )]
-async fn app_state(State(data): State<ApiState>) -> Json<PubSub> {
-    let data = data.read().await;
-    Json(data.clone())
+async fn app_state(
+    State(data): State<ApiState>,
+    Query(query): Query<AppStateQuery>,
+) -> Json<PubSub> {
+    let mut state = data.read().await.clone();
+    state.below_min_balance = state.is_below_min_balance();
+    let names = query
+        .names
+        .as_ref()
+        .map(|x| x.split(',').map(str::trim).collect::<Vec<_>>());
+    state.streamers.retain(|_, s| {
+        if query.live.is_some_and(|live| s.info.live != live) {
+            return false;
+        }
+        if query
+            .has_prediction
+            .is_some_and(|p| s.predictions.is_empty() == p)
+        {
+            return false;
+        }
+        if let Some(names) = &names {
+            return names
+                .iter()
+                .any(|x| x.eq_ignore_ascii_case(&s.info.channel_name));
+        }
+        true
+    });
+    Json(state)
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -184,6 +324,30 @@ trait WebApiError: std::fmt::Debug + Send {
     fn make_response(&self) -> axum::response::Response;
 }
 
+/// Body of every error response of the API
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+struct ErrorBody {
+    /// Human readable description of the error
+    error: String,
+    /// Stable identifier of the kind of error, e.g. streamer_does_not_exist
+    code: String,
+}
+
+fn error_response(
+    status_code: StatusCode,
+    code: &str,
+    error: impl std::fmt::Display,
+) -> axum::response::Response {
+    (
+        status_code,
+        Json(ErrorBody {
+            error: error.to_string(),
+            code: code.to_owned(),
+        }),
+    )
+        .into_response()
+}
+
 impl From<chrono::ParseError> for ApiError {
     fn from(value: chrono::ParseError) -> Self {
         ApiError::ParseTimestamp(value.to_string())
@@ -214,66 +378,101 @@ impl ApiError {
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
-        let status_code = match self {
-            ApiError::ParseTimestamp(_) => StatusCode::BAD_REQUEST,
-            ApiError::StreamerDoesNotExist => StatusCode::BAD_REQUEST,
-            ApiError::TwitchAPIError(_) => StatusCode::SERVICE_UNAVAILABLE,
-            ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            ApiError::AnalyticsError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        let (status_code, code) = match self {
+            ApiError::ParseTimestamp(_) => (StatusCode::BAD_REQUEST, "parse_timestamp"),
+            ApiError::StreamerDoesNotExist => (StatusCode::BAD_REQUEST, "streamer_does_not_exist"),
+            ApiError::TwitchAPIError(_) => (StatusCode::SERVICE_UNAVAILABLE, "twitch_api_error"),
+            ApiError::InternalError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "internal_error"),
+            ApiError::AnalyticsError(err) => return err.into_response(),
             ApiError::SubError(s) => return s.make_response(),
         };
 
-        (status_code, self.to_string()).into_response()
+        error_response(status_code, code, self)
     }
 }
 
-impl PubSub {
-    async fn save_config(&mut self, context: &str) -> Result<(), ApiError> {
-        tokio::fs::write(
-            &self.config_path,
-            serde_yaml::to_string(&self.config)
-                .context(format!("Serializing config {context}"))
-                .map_err(ApiError::internal_error)?,
-        )
-        .await
-        .context(format!("Writing config file {context}"))
-        .map_err(ApiError::internal_error)?;
-        Ok(())
+/// Write the config file, then release the lock before reconciling the streamers with it
+async fn save_config(
+    pubsub: &ApiState,
+    writer: RwLockWriteGuard<'_, PubSub>,
+    context: &str,
+) -> Result<(), ApiError> {
+    tokio::fs::write(
+        &writer.config_path,
+        serde_yaml::to_string(&writer.config)
+            .context(format!("Serializing config {context}"))
+            .map_err(ApiError::internal_error)?,
+    )
+    .await
+    .context(format!("Writing config file {context}"))
+    .map_err(ApiError::internal_error)?;
+    drop(writer);
+
+    // the edit was saved either way, a failed repair is retried after the next one
+    if let Err(err) = config::reconcile(pubsub).await {
+        warn!("Could not reconcile streamers with the config {context}: {err}");
     }
+    Ok(())
 }
 
+/// Most lines returned by a single logs request, whatever `per_page` asks for
+const MAX_LOG_LINES: usize = 1_000;
+/// Most bytes read from the end of the log file by a single logs request, whatever `max_bytes` asks for
+const MAX_LOG_BYTES: u64 = 4 * 1024 * 1024;
+
 async fn read_sliced_lines(file: &mut File, log_query: LogQuery) -> Result<Vec<String>> {
+    let per_page = log_query.per_page.clamp(1, MAX_LOG_LINES);
+    let max_bytes = log_query
+        .max_bytes
+        .unwrap_or(MAX_LOG_BYTES)
+        .min(MAX_LOG_BYTES);
     let mut lines = Vec::new();
-    let mut n = log_query.per_page;
+    let mut n = per_page;
     let mut current_page = 0;
     let mut total_lines = 0;
+    let mut total_read = 0;
 
     let mut file = BufReader::new(file);
     file.seek(SeekFrom::End(0)).await?;
 
     let mut prev_buffer: Vec<u8> = Vec::new();
     while current_page <= log_query.page {
-        file.seek(SeekFrom::Current(-1024)).await?;
-        let mut buffer = [0; 1024];
-        let bytes_read = file.read(&mut buffer).await?;
-
-        let mut temp_buffer = buffer[0..bytes_read].to_vec();
+        let position = file.stream_position().await?;
+        if position == 0 {
+            tracing::debug!("Reached start of file, stopping {n}");
+            break;
+        }
+        if total_read >= max_bytes {
+            tracing::debug!("Read {total_read} bytes of logs, stopping {n}");
+            break;
+        }
+        let chunk = position.min(1024).min(max_bytes - total_read);
+        file.seek(SeekFrom::Current(-(chunk as i64))).await?;
+        let mut buffer = vec![0; chunk as usize];
+        file.read_exact(&mut buffer).await?;
+        let bytes_read = buffer.len();
+        total_read += chunk;
+
+        let mut temp_buffer = buffer.clone();
         temp_buffer.append(&mut prev_buffer);
         prev_buffer = temp_buffer;
-        if !buffer[0..bytes_read].contains(&(b'\n')) {
-            file.seek(SeekFrom::Current(-(bytes_read as i64) - 1))
-                .await?;
+        if !buffer.contains(&(b'\n')) && position != chunk {
+            file.seek(SeekFrom::Current(-(bytes_read as i64))).await?;
             continue;
         }
 
-        let raw_lines = prev_buffer
+        let mut raw_lines = prev_buffer
             .split(|x| *x == b'\n')
             .map(|x| x.to_vec())
-            .rev()
             .collect::<Vec<_>>();
+        // the first line continues in the previous chunk, unless this chunk starts the file
+        prev_buffer = if position == chunk {
+            Vec::new()
+        } else {
+            raw_lines.remove(0)
+        };
 
-        let size = raw_lines.len();
-        for (idx, line) in raw_lines.into_iter().enumerate() {
+        for line in raw_lines.into_iter().rev() {
             if n == 0 {
                 break;
             }
@@ -282,26 +481,17 @@ async fn read_sliced_lines(file: &mut File, log_query: LogQuery) -> Result<Vec<S
                 break;
             }
 
-            let line = String::from_utf8(line.to_vec())?;
+            let line = String::from_utf8(line)?;
             if !line.trim().is_empty() {
-                if idx + 1 == size {
-                    prev_buffer = line.as_bytes().to_vec();
-                    break;
-                } else if current_page == log_query.page {
+                if current_page == log_query.page {
                     lines.push(format!("{line}\n"));
                     n -= 1;
                 }
                 total_lines += 1;
-                current_page = total_lines / log_query.per_page;
+                current_page = total_lines / per_page;
             }
         }
-        file.seek(SeekFrom::Current(-(bytes_read as i64) - 1))
-            .await?;
-
-        if file.stream_position().await? == 0 {
-            tracing::debug!("Reached start of file, stopping {n}");
-            break;
-        }
+        file.seek(SeekFrom::Current(-(bytes_read as i64))).await?;
     }
 
     lines.reverse();
@@ -310,8 +500,11 @@ async fn read_sliced_lines(file: &mut File, log_query: LogQuery) -> Result<Vec<S
 
 #[derive(Deserialize, utoipa::ToSchema, utoipa::IntoParams)]
 struct LogQuery {
+    /// Lines per page, at most 1000
     per_page: usize,
     page: usize,
+    /// Stop reading the log file after this many bytes from its end, at most 4 MiB
+    max_bytes: Option<u64>,
 }
 
 #[utoipa::path(
@@ -353,3 +546,273 @@ async fn get_logs(
         .map_err(ApiError::internal_error)?;
     Ok(Html(html))
 }
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+struct Version {
+    version: &'static str,
+    /// Short hash of the git commit the binary was built from
+    commit: &'static str,
+    /// RFC3339 time of the build
+    built_at: String,
+    /// Cargo features the binary was built with
+    features: Vec<&'static str>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/version",
+    responses(
+        (status = 200, description = "Version and build information of the running binary", body = Version),
+    )
+)]
+async fn version() -> Json<Version> {
+    let built_at = env!("BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|x| chrono::DateTime::from_timestamp(x, 0))
+        .map(|x| x.to_rfc3339())
+        .unwrap_or_default();
+    Json(Version {
+        version: env!("CARGO_PKG_VERSION"),
+        commit: env!("GIT_COMMIT"),
+        built_at,
+        features: env!("BUILD_FEATURES")
+            .split(',')
+            .filter(|x| !x.is_empty())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use axum::extract::{Query, State};
+    use common::{twitch::auth::Token, types::StreamerState};
+    use eyre::Result;
+    use flume::unbounded;
+    use http::StatusCode;
+    use tokio::{
+        net::TcpListener,
+        sync::{watch, RwLock},
+    };
+
+    use twitch_api::types::UserId;
+
+    use crate::pubsub::PubSub;
+
+    async fn filtered_state(query: &str) -> Result<Vec<String>> {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.streamers.insert(
+            UserId::from_static("1"),
+            StreamerState::new(true, "a".to_owned()),
+        );
+        pubsub.streamers.insert(
+            UserId::from_static("2"),
+            StreamerState::new(false, "b".to_owned()),
+        );
+        pubsub.streamers.insert(
+            UserId::from_static("3"),
+            StreamerState::new(true, "c".to_owned()),
+        );
+
+        let query = Query::try_from_uri(&format!("http://localhost/api?{query}").parse()?)?;
+        let state = super::app_state(State(Arc::new(RwLock::new(pubsub))), query).await;
+        let mut names = state
+            .0
+            .streamers
+            .into_values()
+            .map(|x| x.info.channel_name)
+            .collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    #[tokio::test]
+    async fn app_state_filters() -> Result<()> {
+        assert_eq!(filtered_state("").await?, ["a", "b", "c"]);
+        assert_eq!(filtered_state("live=true").await?, ["a", "c"]);
+        assert_eq!(filtered_state("live=false").await?, ["b"]);
+        assert_eq!(filtered_state("names=a,b").await?, ["a", "b"]);
+        assert_eq!(filtered_state("live=true&names=a,b").await?, ["a"]);
+        assert!(filtered_state("has_prediction=true").await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn log_lines_are_capped() -> Result<()> {
+        use super::{read_sliced_lines, LogQuery, MAX_LOG_LINES};
+
+        let path = std::env::temp_dir().join(format!("capped-logs-{}.log", std::process::id()));
+        let text = (0..3_000)
+            .map(|x| format!("line {x}\n"))
+            .collect::<String>();
+        tokio::fs::write(&path, text).await?;
+        let read = |per_page: usize, page: usize, max_bytes: Option<u64>| {
+            let path = path.clone();
+            async move {
+                let mut file = tokio::fs::File::open(path).await?;
+                let query = LogQuery {
+                    per_page,
+                    page,
+                    max_bytes,
+                };
+                read_sliced_lines(&mut file, query).await
+            }
+        };
+
+        let lines = read(usize::MAX, 0, None).await?;
+        assert_eq!(lines.len(), MAX_LOG_LINES);
+        assert_eq!(lines[0], "line 2000\n");
+        assert_eq!(lines[MAX_LOG_LINES - 1], "line 2999\n");
+
+        // the first page from the start of the file
+        let lines = read(10, 299, None).await?;
+        assert_eq!(lines.first().unwrap(), "line 0\n");
+        assert_eq!(lines.last().unwrap(), "line 9\n");
+        assert!(read(10, usize::MAX, None).await?.is_empty());
+
+        // only whole lines within the last 100 bytes
+        let lines = read(100, 0, Some(100)).await?;
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines.last().unwrap(), "line 2999\n");
+
+        let html = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            super::get_logs(
+                State(Some(path.display().to_string())),
+                Query::try_from_uri(
+                    &format!(
+                        "http://localhost/api/logs?per_page={}&page=1000000000",
+                        usize::MAX
+                    )
+                    .parse()?,
+                )?,
+            ),
+        )
+        .await?
+        .map_err(|_| eyre::eyre!("Get logs"))?;
+        assert!(html.0.is_empty());
+
+        tokio::fs::remove_file(&path).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn version() {
+        let res = super::version().await.0;
+        assert_eq!(res.version, env!("CARGO_PKG_VERSION"));
+        assert!(!res.commit.is_empty());
+        assert!(!res.built_at.is_empty());
+    }
+
+    #[tokio::test]
+    async fn no_dashboard_serves_api_only() -> Result<()> {
+        let (ws_tx, _) = unbounded();
+        let pubsub = Arc::new(RwLock::new(PubSub::empty(ws_tx)));
+
+        let address = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+        let server = super::get_api_server(
+            address,
+            pubsub,
+            Arc::new(Token::default()),
+            Some(":memory:"),
+            None,
+            None,
+            watch::channel(Default::default()).1,
+            common::twitch::ws::RawMessages::new(0),
+            false,
+        )
+        .await?;
+        let server = tokio::spawn(async move { server.await });
+
+        let res = reqwest::get(format!("http://{address}/")).await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = reqwest::get(format!("http://{address}/docs/")).await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = reqwest::get(format!("http://{address}/api")).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dump_openapi_spec() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("openapi-{}.json", std::process::id()));
+        super::dump_openapi(path.to_str().unwrap()).await?;
+        let text = tokio::fs::read_to_string(&path).await?;
+        tokio::fs::remove_file(&path).await?;
+
+        let spec: serde_json::Value = serde_json::from_str(&text)?;
+        for path in [
+            "/api",
+            "/api/version",
+            "/api/streamers/{streamer}",
+            "/api/predictions/bet/{streamer}",
+            "/api/analytics/timeline",
+            "/api/token/reload",
+        ] {
+            assert!(spec["paths"].get(path).is_some(), "missing {path}");
+        }
+        assert!(spec["components"]["schemas"].get("ErrorBody").is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_error_bodies() -> Result<()> {
+        use axum::response::IntoResponse;
+
+        let (ws_tx, _) = unbounded();
+        let pubsub = Arc::new(RwLock::new(PubSub::empty(ws_tx)));
+        let address = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+        let server = super::get_api_server(
+            address,
+            pubsub,
+            Arc::new(Token::default()),
+            Some(":memory:"),
+            None,
+            None,
+            watch::channel(Default::default()).1,
+            common::twitch::ws::RawMessages::new(0),
+            false,
+        )
+        .await?;
+        let server = tokio::spawn(async move { server.await });
+
+        let res = reqwest::get(format!("http://{address}/api/streamers/missing")).await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = res.json().await?;
+        assert_eq!(
+            body,
+            serde_json::json!({ "error": "Streamer not found", "code": "streamer_not_found" })
+        );
+        server.abort();
+
+        let body = |res: axum::response::Response| async move {
+            assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await?;
+            Ok::<_, eyre::Report>(serde_json::from_slice::<super::ErrorBody>(&bytes)?)
+        };
+        let res =
+            body(super::ApiError::internal_error(eyre::eyre!("disk full")).into_response()).await?;
+        assert_eq!(res.code, "internal_error");
+        assert_eq!(res.error, "Internal server error disk full");
+
+        let res = body(
+            super::ApiError::from(crate::analytics::AnalyticsError::DbInit("locked".into()))
+                .into_response(),
+        )
+        .await?;
+        assert_eq!(res.code, "analytics_error");
+        assert_eq!(res.error, "Could not initialize database: locked");
+
+        let res = super::ApiError::from(crate::analytics::AnalyticsError::Disabled).into_response();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX).await?;
+        let res = serde_json::from_slice::<super::ErrorBody>(&bytes)?;
+        assert_eq!(res.code, "analytics_disabled");
+        Ok(())
+    }
+}
@@ -1,28 +1,40 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc, time::Instant};
 
 use axum::{
     extract::{Path, State},
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post},
     Json, Router,
 };
-use common::twitch::gql;
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use common::twitch::{
+    gql,
+    user_predictions::{BetResult, BetResultType},
+};
+use common::{config::PredictionBlacklist, types::Wager};
 use eyre::{eyre, Context, ContextCompat};
 use flume::Sender;
+use futures_util::{stream, StreamExt};
 use http::StatusCode;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::sync::RwLockWriteGuard;
 use tracing::info;
+use twitch_api::pubsub::predictions::Event;
 use utoipa::ToSchema;
 
 use crate::{
     analytics::{self, model::*, Analytics, AnalyticsError, AnalyticsWrapper, TimelineResult},
+    events::AppEvent,
     pubsub::PubSub,
 };
-use crate::{make_paths, pubsub::prediction_logic, sub_error};
+use crate::{
+    make_paths,
+    pubsub::{bet_debited, prediction_logic},
+    sub_error,
+};
 
-use super::{ApiError, ApiState, RouterBuild, WebApiError};
+use super::{error_response, save_config, ApiError, ApiState, ErrorBody, RouterBuild, WebApiError};
 
 pub fn build(
     state: ApiState,
@@ -30,26 +42,55 @@ pub fn build(
     tx: Sender<analytics::Request>,
 ) -> RouterBuild {
     let routes = Router::new()
-        .route("/live", get(get_live_prediction))
-        .route("/bet/:streamer", post(make_prediction))
+        .nest(
+            "/predictions",
+            Router::new()
+                .route("/live", get(get_live_prediction))
+                .route("/bet/:streamer", post(make_prediction))
+                .route("/pending", get(get_pending_predictions))
+                .route("/pending/:event_id", delete(cancel_pending_prediction))
+                .route("/blacklist", get(get_blacklist).post(update_blacklist))
+                .route("/budget", get(get_daily_budget))
+                .route("/resume", post(resume_betting)),
+        )
+        .route("/bet/batch", post(make_predictions))
         .with_state((state, analytics, tx));
 
     #[allow(unused_mut)]
-    let mut schemas = vec![MakePrediction::schema()];
+    let mut schemas = vec![
+        MakePrediction::schema(),
+        BatchBet::schema(),
+        BatchBetResult::schema(),
+        PendingPrediction::schema(),
+        PredictionBlacklist::schema(),
+        DailyBudget::schema(),
+    ];
 
     schemas.extend(vec![
         Prediction::schema(),
         TimelineResult::schema(),
         Point::schema(),
         Outcomes::schema(),
+        OutcomesHistory::schema(),
+        OutcomesSnapshot::schema(),
         PointsInfo::schema(),
         PredictionBetWrapper::schema(),
         PredictionBet::schema(),
+        BetResult::schema(),
+        BetResultType::schema(),
     ]);
 
     #[allow(unused_mut)]
-    let mut paths = make_paths!(__path_make_prediction);
-    paths.extend(make_paths!(__path_get_live_prediction));
+    let mut paths = make_paths!(__path_make_prediction, __path_make_predictions);
+    paths.extend(make_paths!(
+        __path_get_live_prediction,
+        __path_get_pending_predictions,
+        __path_cancel_pending_prediction,
+        __path_get_blacklist,
+        __path_update_blacklist,
+        __path_get_daily_budget,
+        __path_resume_betting
+    ));
 
     (routes, schemas, paths)
 }
@@ -60,16 +101,26 @@ pub enum PredictionError {
     PredictionNotFound,
     #[error("Outcome does not exist")]
     OutcomeNotFound,
+    #[error("No active prediction with this event ID")]
+    UnknownEvent,
+    #[error("Bet on {event_id} of {streamer} appears more than once in the batch")]
+    DuplicateBet { streamer: String, event_id: String },
+    #[error("Already bet on another outcome of this prediction")]
+    DifferentOutcome,
 }
 
 impl WebApiError for PredictionError {
     fn make_response(&self) -> axum::response::Response {
         use PredictionError::*;
-        let status_code = match self {
-            OutcomeNotFound | PredictionNotFound => StatusCode::BAD_REQUEST,
+        let (status_code, code) = match self {
+            OutcomeNotFound => (StatusCode::BAD_REQUEST, "outcome_not_found"),
+            PredictionNotFound => (StatusCode::BAD_REQUEST, "prediction_not_found"),
+            UnknownEvent => (StatusCode::NOT_FOUND, "unknown_event"),
+            DuplicateBet { .. } => (StatusCode::BAD_REQUEST, "duplicate_bet"),
+            DifferentOutcome => (StatusCode::BAD_REQUEST, "different_outcome"),
         };
 
-        (status_code, self.to_string()).into_response()
+        error_response(status_code, code, self)
     }
 }
 
@@ -105,9 +156,16 @@ async fn make_prediction(
     Path(streamer): Path<String>,
     Json(payload): Json<MakePrediction>,
 ) -> Result<StatusCode, ApiError> {
-    let mut state = data.write().await;
-    let simulate = state.simulate;
+    bet(data, tx, streamer, payload).await
+}
 
+async fn bet(
+    data: ApiState,
+    tx: Sender<analytics::Request>,
+    streamer: String,
+    payload: MakePrediction,
+) -> Result<StatusCode, ApiError> {
+    let mut state = data.write().await;
     let gql = state.gql.clone();
     let s = state.get_by_name(&streamer);
     if s.is_none() {
@@ -116,6 +174,8 @@ async fn make_prediction(
 
     let s_id = state.get_id_by_name(&streamer).unwrap().to_owned();
     let s = state.get_by_name_mut(&streamer).unwrap().clone();
+    let simulate = state.is_simulated(&s).map_err(ApiError::internal_error)?;
+    let timezone = state.config.timezone();
 
     let prediction = s.predictions.get(&payload.event_id);
     if prediction.is_none() {
@@ -126,24 +186,44 @@ async fn make_prediction(
     if !event.outcomes.iter().any(|o| o.id == payload.outcome_id) {
         return sub_error!(PredictionError::OutcomeNotFound);
     }
+    let balance_before = s.points;
     drop(state);
 
-    let update_placed_state = |mut state: RwLockWriteGuard<PubSub>| {
-        state
-            .get_by_name_mut(&streamer)
-            .context("Streamer not found")
-            .unwrap()
-            .predictions
-            .get_mut(&payload.event_id)
-            .unwrap()
-            .1 = true;
-    };
+    let update_placed_state =
+        |mut state: RwLockWriteGuard<PubSub>, outcome_id: &str, points: u32, balance: u32| {
+            let s = state
+                .get_by_name_mut(&streamer)
+                .context("Streamer not found")
+                .unwrap();
+            let debited = !simulate && bet_debited(balance_before, balance, points);
+            s.points = balance;
+            s.last_points_refresh = Instant::now();
+            s.predictions.get_mut(&payload.event_id).unwrap().1 = true;
+            s.record_wager(&payload.event_id, outcome_id, points, debited);
+            if !simulate {
+                state.add_wagered_today(points);
+            }
+            state.emit(AppEvent::BetPlaced {
+                channel_name: streamer.clone(),
+                event_id: payload.event_id.clone(),
+                outcome_id: outcome_id.to_owned(),
+                points,
+            });
+        };
 
     if payload.points.is_some() && *payload.points.as_ref().unwrap() > 0 {
-        place_bet(
+        let points = *payload.points.as_ref().unwrap();
+        // twitch only takes more points on the outcome already bet on
+        if s.wagered
+            .get(&payload.event_id)
+            .is_some_and(|w| w.outcome_id != payload.outcome_id)
+        {
+            return sub_error!(PredictionError::DifferentOutcome);
+        }
+        let balance = place_bet(
             payload.event_id.clone(),
-            payload.outcome_id,
-            *payload.points.as_ref().unwrap(),
+            payload.outcome_id.clone(),
+            points,
             simulate,
             &streamer,
             &gql,
@@ -151,14 +231,14 @@ async fn make_prediction(
             tx,
         )
         .await?;
-        update_placed_state(data.write().await);
+        update_placed_state(data.write().await, &payload.outcome_id, points, balance);
         Ok(StatusCode::CREATED)
     } else {
-        match prediction_logic(&s, &payload.event_id) {
+        match prediction_logic(&s, &payload.event_id, timezone) {
             Ok(Some((o, p))) => {
-                place_bet(
+                let balance = place_bet(
                     payload.event_id.clone(),
-                    o,
+                    o.clone(),
                     p,
                     simulate,
                     &streamer,
@@ -167,7 +247,7 @@ async fn make_prediction(
                     tx,
                 )
                 .await?;
-                update_placed_state(data.write().await);
+                update_placed_state(data.write().await, &o, p, balance);
                 Ok(StatusCode::CREATED)
             }
             Ok(None) => Ok(StatusCode::ACCEPTED),
@@ -176,6 +256,90 @@ async fn make_prediction(
     }
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+struct BatchBet {
+    /// Name of the streamer
+    streamer: String,
+    #[serde(flatten)]
+    bet: MakePrediction,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+struct BatchBetResult {
+    streamer: String,
+    event_id: String,
+    /// Status the single bet endpoint would have responded with
+    status: u16,
+    /// Why the bet failed
+    error: Option<ErrorBody>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/bet/batch",
+    responses(
+        (status = 200, description = "Every bet was placed or declined by the prediction logic, results are in the order of the request", body = Vec<BatchBetResult>),
+        (status = 207, description = "Some bets failed", body = Vec<BatchBetResult>),
+        (status = 400, description = "A bet on the same streamer and event ID appears more than once, no bet was placed"),
+    ),
+    request_body = Vec<BatchBet>
+)]
+async fn make_predictions(
+    State((data, _analytics, tx)): State<(
+        ApiState,
+        Arc<AnalyticsWrapper>,
+        Sender<analytics::Request>,
+    )>,
+    Json(bets): Json<Vec<BatchBet>>,
+) -> Result<(StatusCode, Json<Vec<BatchBetResult>>), ApiError> {
+    let mut seen = HashSet::new();
+    for item in &bets {
+        if !seen.insert((item.streamer.to_lowercase(), item.bet.event_id.as_str())) {
+            return sub_error!(PredictionError::DuplicateBet {
+                streamer: item.streamer.clone(),
+                event_id: item.bet.event_id.clone(),
+            });
+        }
+    }
+
+    let concurrency = data.read().await.gql.concurrency();
+    let results = stream::iter(bets)
+        .map(|item| {
+            let (data, tx) = (data.clone(), tx.clone());
+            async move {
+                let (streamer, event_id) = (item.streamer.clone(), item.bet.event_id.clone());
+                let (status, error) = match bet(data, tx, item.streamer, item.bet).await {
+                    Ok(status) => (status, None),
+                    Err(err) => {
+                        let res = err.into_response();
+                        let status = res.status();
+                        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+                            .await
+                            .ok()
+                            .and_then(|x| serde_json::from_slice(&x).ok());
+                        (status, body)
+                    }
+                };
+                BatchBetResult {
+                    streamer,
+                    event_id,
+                    status: status.as_u16(),
+                    error,
+                }
+            }
+        })
+        .buffered(concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+    let status = if results.iter().all(|x| x.status < 400) {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+    Ok((status, Json(results)))
+}
+
 async fn place_bet(
     event_id: String,
     outcome_id: String,
@@ -185,7 +349,7 @@ async fn place_bet(
     gql: &gql::Client,
     streamer_id: &str,
     tx: Sender<analytics::Request>,
-) -> Result<(), ApiError> {
+) -> Result<u32, ApiError> {
     info!(
         "{}: predicting {}, with points {}",
         streamer_name, event_id, points
@@ -202,6 +366,7 @@ async fn place_bet(
         .get_channel_points(&[streamer_name])
         .await
         .map_err(ApiError::twitch_api_error)?;
+    let balance = channel_points[0].0;
 
     tx.send_async(Box::new(
         move |analytics: &mut Analytics| -> Result<(), AnalyticsError> {
@@ -211,12 +376,12 @@ async fn place_bet(
                 channel_points[0].0 as i32,
                 PointsInfo::Prediction(event_id.clone(), entry_id),
             )?;
-            analytics.place_bet(&event_id, channel_id, &outcome_id, points)
+            analytics.place_bet(&event_id, channel_id, &outcome_id, points, simulate)
         },
     ))
     .await
     .map_err(|_| eyre!("Could not send analytics request"))?;
-    Ok(())
+    Ok(balance)
 }
 
 #[derive(Deserialize, ToSchema, utoipa::IntoParams)]
@@ -243,3 +408,331 @@ async fn get_live_prediction(
         .await?;
     Ok(Json(res))
 }
+
+#[derive(Debug, Serialize, ToSchema)]
+struct PendingPrediction {
+    /// Name of the streamer
+    streamer: String,
+    /// ID of the prediction
+    event_id: String,
+    title: String,
+    /// If a bet was placed, or the attempt was cancelled
+    placed: bool,
+    /// Points wagered by the miner on this event
+    wagered: Option<Wager>,
+    /// Seconds left until the prediction locks, 0 once locked
+    seconds_until_lock: u64,
+    locked: bool,
+}
+
+/// Seconds until a prediction locks, and if it is already locked
+fn lock_countdown(event: &Event, now: DateTime<FixedOffset>) -> (u64, bool) {
+    let remaining = DateTime::parse_from_rfc3339(event.created_at.as_str())
+        .map(|created_at| {
+            (created_at + Duration::seconds(event.prediction_window_seconds) - now).num_seconds()
+        })
+        .unwrap_or(0)
+        .max(0) as u64;
+    let locked = remaining == 0 || event.locked_at.is_some() || event.status == "LOCKED";
+    (if locked { 0 } else { remaining }, locked)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/predictions/pending",
+    responses(
+        (status = 200, description = "Active predictions and their bet status", body = Vec<PendingPrediction>),
+    )
+)]
+async fn get_pending_predictions(
+    State(state): State<(ApiState, Arc<AnalyticsWrapper>, Sender<analytics::Request>)>,
+) -> Json<Vec<PendingPrediction>> {
+    let state = state.0.read().await;
+    let now = Local::now().fixed_offset();
+    let items = state
+        .streamers
+        .values()
+        .flat_map(|s| {
+            s.predictions
+                .iter()
+                .map(move |(event_id, (event, placed))| {
+                    let (seconds_until_lock, locked) = lock_countdown(event, now);
+                    PendingPrediction {
+                        streamer: s.info.channel_name.clone(),
+                        event_id: event_id.clone(),
+                        title: event.title.clone(),
+                        placed: *placed,
+                        wagered: s.wagered.get(event_id).cloned(),
+                        seconds_until_lock,
+                        locked,
+                    }
+                })
+        })
+        .collect();
+    Json(items)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/predictions/pending/{event_id}",
+    responses(
+        (status = 200, description = "Cancelled the bet attempt on the prediction"),
+        (status = 404, description = "Could not find an active prediction with the event ID")
+    ),
+    params(
+        ("event_id" = String, Path, description = "ID of the prediction"),
+    )
+)]
+async fn cancel_pending_prediction(
+    State(state): State<(ApiState, Arc<AnalyticsWrapper>, Sender<analytics::Request>)>,
+    Path(event_id): Path<String>,
+) -> Result<(), ApiError> {
+    if !state.0.write().await.cancel_prediction(&event_id) {
+        return sub_error!(PredictionError::UnknownEvent);
+    }
+    info!("Cancelled bet attempt on {event_id}");
+    Ok(())
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct DailyBudget {
+    /// Configured daily_bet_cap
+    cap: Option<u32>,
+    /// Points bet across all streamers today
+    wagered: u32,
+    /// Points that can still be bet today, without a cap there is no limit
+    remaining: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/predictions/budget",
+    responses(
+        (status = 200, description = "Points bet today and what is left of the daily bet cap", body = DailyBudget),
+    )
+)]
+async fn get_daily_budget(
+    State(state): State<(ApiState, Arc<AnalyticsWrapper>, Sender<analytics::Request>)>,
+) -> Json<DailyBudget> {
+    let state = state.0.read().await;
+    Json(DailyBudget {
+        cap: state.config.daily_bet_cap,
+        wagered: state.wagered_today(),
+        remaining: state.daily_budget(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/predictions/blacklist",
+    responses(
+        (status = 200, description = "Predictions that are never bet on", body = PredictionBlacklist),
+    )
+)]
+async fn get_blacklist(
+    State(state): State<(ApiState, Arc<AnalyticsWrapper>, Sender<analytics::Request>)>,
+) -> Json<PredictionBlacklist> {
+    Json(
+        state
+            .0
+            .read()
+            .await
+            .config
+            .prediction_blacklist
+            .clone()
+            .unwrap_or_default(),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/predictions/blacklist",
+    responses(
+        (status = 200, description = "Replaced the blacklist"),
+    ),
+    request_body = PredictionBlacklist
+)]
+async fn update_blacklist(
+    State(state): State<(ApiState, Arc<AnalyticsWrapper>, Sender<analytics::Request>)>,
+    Json(blacklist): Json<PredictionBlacklist>,
+) -> Result<(), ApiError> {
+    let mut writer = state.0.write().await;
+    writer.config.prediction_blacklist = Some(blacklist);
+    save_config(&state.0, writer, "Update prediction blacklist").await?;
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/predictions/resume",
+    responses(
+        (status = 200, description = "Resumed bets stopped by panic_stop, true if they were stopped", body = bool),
+    )
+)]
+async fn resume_betting(
+    State(state): State<(ApiState, Arc<AnalyticsWrapper>, Sender<analytics::Request>)>,
+) -> Json<bool> {
+    Json(state.0.write().await.resume_betting())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, sync::Arc};
+
+    use chrono::{DateTime, Duration};
+    use common::{
+        testing::{container, TestContainer},
+        twitch::gql,
+        types::StreamerState,
+    };
+    use eyre::Result;
+    use flume::unbounded;
+    use rstest::rstest;
+    use serde_json::json;
+    use tokio::{net::TcpListener, sync::RwLock};
+    use twitch_api::{
+        pubsub::predictions::{Event, Outcome},
+        types::{Timestamp, UserId},
+    };
+
+    use crate::pubsub::PubSub;
+
+    use super::lock_countdown;
+
+    fn event(created_at: &str, window: i64) -> Event {
+        Event {
+            id: "pred-key-1".to_owned(),
+            channel_id: "channel-id-1".to_owned(),
+            created_at: Timestamp::new(created_at.to_owned()).unwrap(),
+            ended_at: None,
+            locked_at: None,
+            outcomes: Vec::new(),
+            prediction_window_seconds: window,
+            status: "ACTIVE".to_owned(),
+            title: "".to_owned(),
+            winning_outcome_id: None,
+        }
+    }
+
+    #[test]
+    fn seconds_until_lock() {
+        let created_at = "2024-06-01T12:00:00+00:00";
+        let now = DateTime::parse_from_rfc3339(created_at).unwrap() + Duration::seconds(45);
+
+        assert_eq!(lock_countdown(&event(created_at, 120), now), (75, false));
+        assert_eq!(lock_countdown(&event(created_at, 30), now), (0, true));
+
+        let mut locked = event(created_at, 120);
+        locked.status = "LOCKED".to_owned();
+        assert_eq!(lock_countdown(&locked, now), (0, true));
+    }
+
+    #[rstest]
+    #[timeout(std::time::Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn batch_bets(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("1", ("a", 5_000, None::<String>))]))
+            .send()
+            .await?;
+
+        let mut event = event(&chrono::Local::now().to_rfc3339(), 600);
+        event.outcomes = ["1", "2"]
+            .into_iter()
+            .map(|id| Outcome {
+                id: id.to_owned(),
+                color: "".to_owned(),
+                title: id.to_owned(),
+                total_points: 1_000,
+                total_users: 2,
+                top_predictors: Vec::new(),
+            })
+            .collect();
+        let mut pubsub = PubSub::empty(unbounded().0);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.simulate = true;
+        let mut s = StreamerState::new(true, "a".to_owned());
+        s.points = 5_000;
+        s.predictions
+            .insert("pred-key-1".to_owned(), (event.clone(), false));
+        s.predictions
+            .insert("pred-key-2".to_owned(), (event, false));
+        pubsub.streamers.insert(UserId::from_static("1"), s);
+        let analytics = pubsub.analytics.clone();
+        let pubsub = Arc::new(RwLock::new(pubsub));
+        let (tx, _rx) = unbounded();
+        let router = super::build(pubsub.clone(), analytics, tx).0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let bet = |streamer: &str, event_id: &str, outcome_id: &str| json!({ "streamer": streamer, "event_id": event_id, "outcome_id": outcome_id, "points": 100 });
+        // a second bet on the same prediction rejects the whole batch
+        let res = client
+            .post(format!("http://{address}/bet/batch"))
+            .json(&[bet("a", "pred-key-1", "1"), bet("A", "pred-key-1", "2")])
+            .send()
+            .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+        let res: serde_json::Value = res.json().await?;
+        assert_eq!(res["code"], "duplicate_bet");
+        assert!(pubsub.read().await.streamers[&UserId::from_static("1")]
+            .wagered
+            .is_empty());
+
+        let res = client
+            .post(format!("http://{address}/bet/batch"))
+            .json(&[
+                bet("a", "pred-key-1", "1"),
+                bet("missing", "pred-key-1", "1"),
+                bet("a", "unknown", "1"),
+                bet("a", "pred-key-2", "3"),
+            ])
+            .send()
+            .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::MULTI_STATUS);
+        let res: Vec<serde_json::Value> = res.json().await?;
+        assert_eq!(
+            res.iter()
+                .map(|x| (x["status"].as_u64().unwrap(), x["error"]["code"].as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (201, None),
+                (400, Some("streamer_does_not_exist")),
+                (400, Some("prediction_not_found")),
+                (400, Some("outcome_not_found")),
+            ]
+        );
+        assert_eq!(res[1]["streamer"], "missing");
+        let wager =
+            pubsub.read().await.streamers[&UserId::from_static("1")].wagered["pred-key-1"].clone();
+        assert_eq!((wager.outcome_id.as_str(), wager.points), ("1", 100));
+
+        let res = client
+            .post(format!("http://{address}/bet/batch"))
+            .json(&[bet("a", "pred-key-1", "1")])
+            .send()
+            .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+        // more points only go on the outcome already bet on
+        let res: Vec<serde_json::Value> = client
+            .post(format!("http://{address}/bet/batch"))
+            .json(&[bet("a", "pred-key-1", "2")])
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(res[0]["error"]["code"], "different_outcome");
+        let wager =
+            pubsub.read().await.streamers[&UserId::from_static("1")].wagered["pred-key-1"].clone();
+        assert_eq!((wager.outcome_id.as_str(), wager.points), ("1", 200));
+
+        server.abort();
+        Ok(())
+    }
+}
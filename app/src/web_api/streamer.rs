@@ -1,9 +1,11 @@
 use std::{collections::HashMap, sync::Arc, time::Instant};
 
+use chrono::Local;
+
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::IntoResponse,
-    routing::{delete, get, put},
+    routing::{delete, get, post, put},
     Extension, Json, Router,
 };
 
@@ -16,19 +18,29 @@ use eyre::Context;
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::{info, warn};
 use twitch_api::{pubsub::predictions::Event, types::UserId};
 use utoipa::ToSchema;
 
-use crate::{make_paths, sub_error};
+use crate::{
+    make_paths,
+    pubsub::{DecisionTrace, FilterCheck, OutcomeOdds},
+    sub_error,
+};
 
-use super::{ApiError, ApiState, RouterBuild, WebApiError};
+use super::{error_response, save_config, ApiError, ApiState, RouterBuild, WebApiError};
 
 pub fn build(state: ApiState, token: Arc<Token>) -> RouterBuild {
     let routes = Router::new()
+        .route("/", get(list_streamers))
         .route("/live", get(live_streamers))
         .route("/mine/:streamer", put(mine_streamer))
         .route("/mine/:streamer/", delete(remove_streamer))
         .route("/:streamer", get(streamer))
+        .route("/:streamer/tags", post(set_tags))
+        .route("/:streamer/points", post(correct_points))
+        .route("/:streamer/refresh", post(refresh_streamer))
+        .route("/:streamer/last-decision", get(last_decision))
         .layer(Extension(token))
         .with_state(state);
 
@@ -36,13 +48,29 @@ pub fn build(state: ApiState, token: Arc<Token>) -> RouterBuild {
         MineStreamer::schema(),
         ConfigType::schema(),
         LiveStreamer::schema(),
+        StreamerResponse::schema(),
+        HumanizeQuery::schema(),
+        ListQuery::schema(),
+        SortBy::schema(),
+        SortOrder::schema(),
+        StreamerList::schema(),
+        StreamerSummary::schema(),
+        PointsCorrection::schema(),
+        DecisionTrace::schema(),
+        FilterCheck::schema(),
+        OutcomeOdds::schema(),
     ];
 
     let paths = make_paths!(
+        __path_list_streamers,
         __path_streamer,
         __path_live_streamers,
         __path_mine_streamer,
-        __path_remove_streamer
+        __path_remove_streamer,
+        __path_set_tags,
+        __path_correct_points,
+        __path_refresh_streamer,
+        __path_last_decision
     );
 
     (routes, schemas, paths)
@@ -52,42 +80,207 @@ pub fn build(state: ApiState, token: Arc<Token>) -> RouterBuild {
 pub enum StreamerError {
     #[error("Streamer is already being mined")]
     StreamerAlreadyMined,
+    #[error("Streamer not found")]
+    StreamerNotFound,
 }
 
 impl WebApiError for StreamerError {
     fn make_response(&self) -> axum::response::Response {
         use StreamerError::*;
-        let status_code = match self {
-            StreamerAlreadyMined => StatusCode::CONFLICT,
+        let (status_code, code) = match self {
+            StreamerAlreadyMined => (StatusCode::CONFLICT, "streamer_already_mined"),
+            StreamerNotFound => (StatusCode::NOT_FOUND, "streamer_not_found"),
         };
 
-        (status_code, self.to_string()).into_response()
+        error_response(status_code, code, self)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema, utoipa::IntoParams)]
+struct HumanizeQuery {
+    /// Also include the points abbreviated for display, e.g. 1.23M
+    #[serde(default)]
+    humanize: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+struct StreamerResponse {
+    #[serde(flatten)]
+    state: StreamerState,
+    /// Points abbreviated for display, only included with humanize=true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    points_humanized: Option<String>,
+}
+
+impl StreamerResponse {
+    fn new(state: StreamerState, humanize: bool) -> Self {
+        Self {
+            points_humanized: humanize.then(|| humanize_points(state.points)),
+            state,
+        }
     }
 }
 
+/// Abbreviate points to at most three significant digits, e.g. 1234567 to 1.23M.
+/// Digits are truncated rather than rounded, so the value is never overstated
+fn humanize_points(points: u32) -> String {
+    const UNITS: [(u32, &str); 3] = [(1_000_000_000, "B"), (1_000_000, "M"), (1_000, "K")];
+    for (size, unit) in UNITS {
+        if points < size {
+            continue;
+        }
+        let whole = points / size;
+        let decimals = match whole {
+            0..=9 => 2,
+            10..=99 => 1,
+            _ => 0,
+        };
+        let fraction = (points % size) as u64 * 10u64.pow(decimals) / size as u64;
+        if fraction == 0 {
+            return format!("{whole}{unit}");
+        }
+        let fraction = format!("{fraction:0width$}", width = decimals as usize);
+        return format!("{whole}.{}{unit}", fraction.trim_end_matches('0'));
+    }
+    points.to_string()
+}
+
 #[utoipa::path(
     get,
     path = "/api/streamers/{streamer}",
     responses(
-        (status = 200, description = "Get the entire application state information", body = [StreamerState]),
+        (status = 200, description = "Get the entire application state information", body = [StreamerResponse]),
         (status = 404, description = "Could not find streamer")
     ),
     params(
-        ("streamer" = String, Path, description = "Name of streamer to get state for")
+        ("streamer" = String, Path, description = "Name of streamer to get state for"),
+        HumanizeQuery
     )
 )]
-async fn streamer(State(data): State<ApiState>, Path(streamer): Path<String>) -> impl IntoResponse {
+async fn streamer(
+    State(data): State<ApiState>,
+    Path(streamer): Path<String>,
+    Query(query): Query<HumanizeQuery>,
+) -> impl IntoResponse {
     let data = data.read().await;
     match data.get_by_name(streamer.as_str()) {
-        Some(s) => Json(s.clone()).into_response(),
-        None => (StatusCode::NOT_FOUND, "Streamer not found").into_response(),
+        Some(s) => Json(StreamerResponse::new(s.clone(), query.humanize)).into_response(),
+        None => error_response(
+            StatusCode::NOT_FOUND,
+            "streamer_not_found",
+            "Streamer not found",
+        ),
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum SortBy {
+    #[default]
+    Name,
+    Points,
+    Live,
+    Predictions,
+    LastLiveAt,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema, utoipa::IntoParams)]
+struct ListQuery {
+    /// Field to sort by, the name if not specified
+    #[serde(default)]
+    sort: SortBy,
+    #[serde(default)]
+    order: SortOrder,
+    /// Maximum number of streamers returned, all if not specified
+    limit: Option<usize>,
+    /// Streamers skipped after sorting
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct StreamerSummary {
+    id: i32,
+    name: String,
+    live: bool,
+    points: u32,
+    /// Number of active predictions
+    predictions: usize,
+    last_live_at: Option<chrono::DateTime<Local>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct StreamerList {
+    /// Number of tracked streamers, before the limit and offset are applied
+    total: usize,
+    streamers: Vec<StreamerSummary>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/streamers",
+    responses(
+        (status = 200, description = "Sorted list of all tracked streamers with their key fields", body = StreamerList),
+        (status = 400, description = "Invalid sort field or order")
+    ),
+    params(ListQuery)
+)]
+async fn list_streamers(
+    State(data): State<ApiState>,
+    Query(query): Query<ListQuery>,
+) -> Json<StreamerList> {
+    let mut streamers = data
+        .read()
+        .await
+        .streamers
+        .iter()
+        .map(|(id, s)| StreamerSummary {
+            id: id.as_str().parse().unwrap(),
+            name: s.info.channel_name.clone(),
+            live: s.info.live,
+            points: s.points,
+            predictions: s.predictions.len(),
+            last_live_at: s.last_live_at,
+        })
+        .collect::<Vec<_>>();
+
+    streamers.sort_by(|a, b| {
+        let ordering = match query.sort {
+            SortBy::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortBy::Points => a.points.cmp(&b.points),
+            SortBy::Live => a.live.cmp(&b.live),
+            SortBy::Predictions => a.predictions.cmp(&b.predictions),
+            SortBy::LastLiveAt => a.last_live_at.cmp(&b.last_live_at),
+        };
+        let ordering = match query.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        // ties in the order of the names, so pages are stable
+        ordering.then_with(|| a.name.cmp(&b.name))
+    });
+
+    let total = streamers.len();
+    let streamers = streamers
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+    Json(StreamerList { total, streamers })
+}
+
 #[derive(Serialize, ToSchema)]
 struct LiveStreamer {
     id: i32,
-    state: StreamerState,
+    state: StreamerResponse,
 }
 
 #[utoipa::path(
@@ -95,9 +288,13 @@ struct LiveStreamer {
     path = "/api/streamers/live",
     responses(
         (status = 200, description = "List of live streamers and their state", body = Vec<LiveStreamer>)
-    )
+    ),
+    params(HumanizeQuery)
 )]
-async fn live_streamers(State(data): State<ApiState>) -> Json<Vec<LiveStreamer>> {
+async fn live_streamers(
+    State(data): State<ApiState>,
+    Query(query): Query<HumanizeQuery>,
+) -> Json<Vec<LiveStreamer>> {
     let data = data.read().await;
     let items = data
         .streamers
@@ -105,7 +302,7 @@ async fn live_streamers(State(data): State<ApiState>) -> Json<Vec<LiveStreamer>>
         .filter(|x| x.1.info.live)
         .map(|x| LiveStreamer {
             id: x.0.as_str().parse().unwrap(),
-            state: x.1.clone(),
+            state: StreamerResponse::new(x.1.clone(), query.humanize),
         })
         .collect::<Vec<_>>();
     Json(items)
@@ -192,10 +389,15 @@ async fn mine_streamer(
                 .collect::<HashMap<_, _>>(),
             points,
             last_points_refresh: Instant::now(),
+            wagered: Default::default(),
+            live_since: None,
+            last_live_at: None,
+            circuit: Default::default(),
+            points_goal_reached: false,
+            tags: Vec::new(),
         },
     );
 
-    writer.save_config("Mine streamer").await?;
     ws::add_streamer(&writer.ws_tx, streamer.0.as_str().parse().unwrap())
         .await
         .context("Add streamer to pubsub")
@@ -222,9 +424,32 @@ async fn mine_streamer(
                 )
             })
             .await?;
+    } else {
+        let last_live_at = writer
+            .analytics
+            .execute(|analytics| analytics.last_live_at(id))
+            .await?;
+        let tags = writer
+            .analytics
+            .execute(|analytics| analytics.tags(id))
+            .await?;
+        if let Some(s) = writer.streamers.get_mut(&streamer.0) {
+            s.last_live_at = last_live_at.and_then(|x| x.and_local_timezone(Local).earliest());
+            s.tags = tags;
+        }
     }
+    writer
+        .analytics
+        .execute(|analytics| {
+            analytics.insert_points(
+                id,
+                points as i32,
+                crate::analytics::model::PointsInfo::SessionBaseline,
+            )
+        })
+        .await?;
 
-    Ok(())
+    save_config(&data, writer, "Mine streamer").await
 }
 
 #[utoipa::path(
@@ -249,13 +474,542 @@ async fn remove_streamer(
         None => return Err(ApiError::StreamerDoesNotExist),
     };
 
-    writer.streamers.remove(&id);
-    writer.config.streamers.shift_remove(&channel_name);
-    writer.configs.remove(&channel_name);
+    if let Some(s) = writer.streamers.remove(&id) {
+        writer.configs.remove(&s.info.channel_name);
+    }
+    if let Some(key) = writer.config_key(&channel_name) {
+        writer.config.streamers.shift_remove(&key);
+    }
 
-    writer.save_config("Remove streamer").await?;
     ws::remove_streamer(&writer.ws_tx, id.as_str().parse().unwrap())
         .await
         .context("Remove streamer from pubsub")?;
+    save_config(&data, writer, "Remove streamer").await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/streamers/{streamer}/tags",
+    responses(
+        (status = 200, description = "Replace the tags of the streamer, returning them trimmed and without duplicates", body = Vec<String>),
+        (status = 400, description = "Streamer does not exist")
+    ),
+    params(
+        ("streamer" = String, Path, description = "Name of streamer to tag")
+    ),
+    request_body = Vec<String>
+)]
+async fn set_tags(
+    State(data): State<ApiState>,
+    Path(streamer): Path<String>,
+    Json(tags): Json<Vec<String>>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    let mut writer = data.write().await;
+    let id = match writer.get_id_by_name(&streamer) {
+        Some(id) => UserId::from(id.to_owned()),
+        None => return Err(ApiError::StreamerDoesNotExist),
+    };
+    let tags = common::remove_duplicates_in_place(
+        tags.into_iter()
+            .map(|x| x.trim().to_owned())
+            .filter(|x| !x.is_empty())
+            .collect(),
+        |a, b| a == b,
+    );
+
+    let channel_id = id
+        .as_str()
+        .parse()
+        .context("Parse streamer id")
+        .map_err(ApiError::internal_error)?;
+    writer
+        .analytics
+        .execute(|analytics| analytics.set_tags(channel_id, &tags))
+        .await?;
+    if let Some(s) = writer.streamers.get_mut(&id) {
+        s.tags = tags.clone();
+    }
+    Ok(Json(tags))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PointsCorrection {
+    /// Balance of the streamer on twitch
+    value: u32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/streamers/{streamer}/points",
+    responses(
+        (status = 200, description = "Set the balance of the streamer, recording it as a manual correction"),
+        (status = 400, description = "Streamer does not exist")
+    ),
+    params(
+        ("streamer" = String, Path, description = "Name of streamer to correct")
+    ),
+    request_body = PointsCorrection
+)]
+async fn correct_points(
+    State(data): State<ApiState>,
+    Path(streamer): Path<String>,
+    Json(correction): Json<PointsCorrection>,
+) -> Result<(), ApiError> {
+    let mut writer = data.write().await;
+    let id = match writer.get_id_by_name(&streamer) {
+        Some(id) => UserId::from(id.to_owned()),
+        None => return Err(ApiError::StreamerDoesNotExist),
+    };
+    let channel_id = id
+        .as_str()
+        .parse()
+        .context("Parse streamer id")
+        .map_err(ApiError::internal_error)?;
+
+    writer
+        .analytics
+        .execute(|analytics| {
+            analytics.insert_points(
+                channel_id,
+                correction.value as i32,
+                crate::analytics::model::PointsInfo::ManualCorrection,
+            )
+        })
+        .await?;
+    if let Some(s) = writer.streamers.get_mut(&id) {
+        info!(
+            "Corrected points of {streamer} from {} to {}",
+            s.points, correction.value
+        );
+        s.points = correction.value;
+        s.last_points_refresh = Instant::now();
+    }
     Ok(())
 }
+
+#[utoipa::path(
+    post,
+    path = "/api/streamers/{streamer}/refresh",
+    responses(
+        (status = 200, description = "Fetch the points and active predictions of the streamer from twitch now, returning the updated state", body = StreamerResponse),
+        (status = 404, description = "Could not find streamer")
+    ),
+    params(
+        ("streamer" = String, Path, description = "Name of streamer to refresh"),
+        HumanizeQuery
+    )
+)]
+async fn refresh_streamer(
+    State(data): State<ApiState>,
+    Path(streamer): Path<String>,
+    Query(query): Query<HumanizeQuery>,
+) -> Result<Json<StreamerResponse>, ApiError> {
+    let (id, channel_name, gql) = {
+        let reader = data.read().await;
+        match reader.get_id_by_name(&streamer) {
+            Some(id) => {
+                let id = UserId::from(id.to_owned());
+                let channel_name = reader.streamers[&id].info.channel_name.clone();
+                (id, channel_name, reader.gql.clone())
+            }
+            None => return sub_error!(StreamerError::StreamerNotFound),
+        }
+    };
+
+    let points = gql
+        .get_channel_points(&[&channel_name])
+        .await
+        .map_err(ApiError::twitch_api_error)?[0]
+        .0;
+    let active_predictions = gql
+        .channel_points_context(&[&channel_name])
+        .await
+        .map_err(ApiError::twitch_api_error)?
+        .remove(0);
+
+    let mut writer = data.write().await;
+    match writer.streamers.get_mut(&id) {
+        Some(s) => {
+            s.points = points;
+            s.last_points_refresh = Instant::now();
+        }
+        None => return sub_error!(StreamerError::StreamerNotFound),
+    }
+    let cap = writer.max_predictions();
+    for (event, bet_placed) in active_predictions {
+        let s = writer.streamers.get_mut(&id).unwrap();
+        match s.predictions.get_mut(&event.id) {
+            Some(p) => {
+                p.0 = event;
+                p.1 |= bet_placed;
+            }
+            // bet on elsewhere, tracked like the predictions found at startup
+            None if bet_placed => {
+                if !s.track_prediction(event, true, cap) {
+                    warn!(
+                        "{channel_name} already has {cap} predictions tracked, not tracking more"
+                    );
+                }
+            }
+            // missed by pubsub, handled like a prediction announced by an onsite notification
+            None => writer
+                .handle_prediction_event(event, id.clone())
+                .await
+                .map_err(ApiError::internal_error)?,
+        }
+    }
+    info!("Refreshed {channel_name}, {points} points");
+
+    let state = writer.streamers[&id].clone();
+    Ok(Json(StreamerResponse::new(state, query.humanize)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/streamers/{streamer}/last-decision",
+    responses(
+        (status = 200, description = "Why the last prediction considered on the streamer was bet on or skipped, null if none was considered yet", body = Option<DecisionTrace>),
+        (status = 404, description = "Could not find streamer")
+    ),
+    params(
+        ("streamer" = String, Path, description = "Name of streamer to get the last decision of")
+    )
+)]
+async fn last_decision(
+    State(data): State<ApiState>,
+    Path(streamer): Path<String>,
+) -> Result<Json<Option<DecisionTrace>>, ApiError> {
+    let reader = data.read().await;
+    match reader.get_id_by_name(&streamer) {
+        Some(id) => Ok(Json(
+            reader.last_decision(&UserId::from(id.to_owned())).cloned(),
+        )),
+        None => sub_error!(StreamerError::StreamerNotFound),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, sync::Arc};
+
+    use common::{
+        testing::{container, TestContainer},
+        twitch::{auth::Token, gql},
+        types::StreamerState,
+    };
+    use eyre::Result;
+    use flume::unbounded;
+    use rstest::rstest;
+    use tokio::{net::TcpListener, sync::RwLock};
+    use twitch_api::{
+        pubsub::predictions::Event,
+        types::{Timestamp, UserId},
+    };
+
+    use crate::pubsub::PubSub;
+
+    #[test]
+    fn humanize_points() {
+        for (points, humanized) in [
+            (0, "0"),
+            (999, "999"),
+            (1_000, "1K"),
+            (1_500, "1.5K"),
+            (12_345, "12.3K"),
+            (999_999, "999K"),
+            (1_234_567, "1.23M"),
+            (1_000_000, "1M"),
+            (4_000_000_000, "4B"),
+        ] {
+            assert_eq!(super::humanize_points(points), humanized);
+        }
+    }
+
+    #[tokio::test]
+    async fn list_sorted_streamers() -> Result<()> {
+        let mut pubsub = PubSub::empty(unbounded().0);
+        for (id, name, points, live) in [
+            ("1", "b", 500, true),
+            ("2", "C", 1_500, false),
+            ("3", "a", 1_000, false),
+        ] {
+            let mut s = StreamerState::new(live, name.to_owned());
+            s.points = points;
+            pubsub.streamers.insert(UserId::from_static(id), s);
+        }
+        let router = super::build(Arc::new(RwLock::new(pubsub)), Arc::new(Token::default())).0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::new();
+        let list = |query: &'static str| {
+            let client = client.clone();
+            async move {
+                let res: serde_json::Value = client
+                    .get(format!("http://{address}/?{query}"))
+                    .send()
+                    .await?
+                    .json()
+                    .await?;
+                let names = res["streamers"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|x| x["name"].as_str().unwrap().to_owned())
+                    .collect::<Vec<_>>();
+                Ok::<_, eyre::Report>((res["total"].as_u64().unwrap(), names))
+            }
+        };
+
+        assert_eq!(
+            list("").await?,
+            (3, vec!["a".into(), "b".into(), "C".into()])
+        );
+        assert_eq!(
+            list("sort=name&order=desc").await?,
+            (3, vec!["C".into(), "b".into(), "a".into()])
+        );
+        assert_eq!(
+            list("sort=points&order=desc").await?,
+            (3, vec!["C".into(), "a".into(), "b".into()])
+        );
+        assert_eq!(
+            list("sort=points&limit=1&offset=1").await?,
+            (3, vec!["a".into()])
+        );
+
+        let res = client
+            .get(format!("http://{address}/?sort=unknown"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        server.abort();
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(std::time::Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn refresh_from_twitch(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let event = Event {
+            id: "refresh-1".to_owned(),
+            channel_id: "86".to_owned(),
+            created_at: Timestamp::new(chrono::Local::now().to_rfc3339())?,
+            ended_at: None,
+            locked_at: None,
+            outcomes: Vec::new(),
+            prediction_window_seconds: 120,
+            status: "ACTIVE".to_owned(),
+            title: "Refreshed".to_owned(),
+            winning_outcome_id: None,
+        };
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&HashMap::from([("86", ("r", 5_000, None::<String>))]))
+            .send()
+            .await?;
+        client
+            .post(format!("{base_url}/active_predictions"))
+            .json(&HashMap::from([("86", ("r", vec![event.clone()]))]))
+            .send()
+            .await?;
+
+        let mut pubsub = PubSub::empty(unbounded().0);
+        pubsub.gql = gql::Client::new(String::new(), format!("{base_url}/gql"));
+        pubsub.watch_only = true;
+        let mut s = StreamerState::new(true, "r".to_owned());
+        s.points = 100;
+        pubsub.streamers.insert(UserId::from_static("86"), s);
+        let pubsub = Arc::new(RwLock::new(pubsub));
+        let router = super::build(pubsub.clone(), Arc::new(Token::default())).0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let res: serde_json::Value = client
+            .post(format!("http://{address}/r/refresh"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(res["points"], 5_000);
+        assert_eq!(res["predictions"]["refresh-1"][0]["title"], "Refreshed");
+
+        let reader = pubsub.read().await;
+        let s = &reader.streamers[&UserId::from_static("86")];
+        assert_eq!(s.points, 5_000);
+        assert_eq!(s.predictions["refresh-1"], (event, false));
+        drop(reader);
+
+        let res = client
+            .post(format!("http://{address}/missing/refresh"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn manual_points_correction() -> Result<()> {
+        use crate::analytics::model::PointsInfo;
+
+        let mut pubsub = PubSub::empty(unbounded().0);
+        let mut s = StreamerState::new(true, "a".to_owned());
+        s.points = 1_000;
+        pubsub.streamers.insert(UserId::from_static("1"), s);
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(1, "a".to_owned()))
+            .await?;
+        let pubsub = Arc::new(RwLock::new(pubsub));
+        let router = super::build(pubsub.clone(), Arc::new(Token::default())).0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let from = chrono::Local::now();
+        let res = reqwest::Client::new()
+            .post(format!("http://{address}/a/points"))
+            .json(&serde_json::json!({ "value": 1_250 }))
+            .send()
+            .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+        let reader = pubsub.read().await;
+        assert_eq!(reader.streamers[&UserId::from_static("1")].points, 1_250);
+        let timeline = reader
+            .analytics
+            .execute(|analytics| analytics.timeline(from, chrono::Local::now(), &[1]))
+            .await?;
+        assert_eq!(timeline.len(), 1);
+        assert_eq!(timeline[0].point.points_value, 1_250);
+        assert_eq!(timeline[0].point.points_info, PointsInfo::ManualCorrection);
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_streamer_tags() -> Result<()> {
+        let mut pubsub = PubSub::empty(unbounded().0);
+        pubsub.streamers.insert(
+            UserId::from_static("1"),
+            StreamerState::new(true, "a".to_owned()),
+        );
+        pubsub
+            .analytics
+            .execute(|analytics| analytics.insert_streamer(1, "a".to_owned()))
+            .await?;
+        let pubsub = Arc::new(RwLock::new(pubsub));
+        let router = super::build(pubsub.clone(), Arc::new(Token::default())).0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::new();
+        let res: Vec<String> = client
+            .post(format!("http://{address}/a/tags"))
+            .json(&[" favorite", "high-variance", "favorite ", ""])
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(res, vec!["favorite", "high-variance"]);
+
+        let res: serde_json::Value = client
+            .get(format!("http://{address}/a"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(
+            res["tags"],
+            serde_json::json!(["favorite", "high-variance"])
+        );
+        let stored = pubsub
+            .read()
+            .await
+            .analytics
+            .execute(|analytics| analytics.tags(1))
+            .await?;
+        assert_eq!(stored, vec!["favorite", "high-variance"]);
+
+        let res = client
+            .post(format!("http://{address}/b/tags"))
+            .json(&["favorite"])
+            .send()
+            .await?;
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn streamer_with_humanized_points() -> Result<()> {
+        let mut pubsub = PubSub::empty(unbounded().0);
+        for (id, name, points) in [("1", "a", 1_234_567), ("2", "b", 950)] {
+            let mut s = StreamerState::new(true, name.to_owned());
+            s.points = points;
+            pubsub.streamers.insert(UserId::from_static(id), s);
+        }
+        let router = super::build(Arc::new(RwLock::new(pubsub)), Arc::new(Token::default())).0;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::new();
+        let res: serde_json::Value = client
+            .get(format!("http://{address}/a"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(res["points"], 1_234_567);
+        assert!(res.get("points_humanized").is_none());
+
+        let res: serde_json::Value = client
+            .get(format!("http://{address}/a?humanize=true"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(res["points"], 1_234_567);
+        assert_eq!(res["points_humanized"], "1.23M");
+        assert_eq!(res["info"]["channelName"], "a");
+
+        let res: serde_json::Value = client
+            .get(format!("http://{address}/live?humanize=true"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        let mut humanized = res
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| {
+                (
+                    x["state"]["points"].as_u64().unwrap(),
+                    x["state"]["points_humanized"].as_str().unwrap().to_owned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        humanized.sort();
+        assert_eq!(
+            humanized,
+            vec![(950, "950".to_owned()), (1_234_567, "1.23M".to_owned())]
+        );
+
+        server.abort();
+        Ok(())
+    }
+}
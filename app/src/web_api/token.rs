@@ -0,0 +1,106 @@
+use axum::{extract::State, routing::post, Router};
+use common::twitch::ws::Request;
+use eyre::{Context, Result};
+use tracing::info;
+
+use crate::{make_paths, pubsub::PubSub};
+
+use super::{ApiError, ApiState, RouterBuild};
+
+pub fn build(state: ApiState) -> RouterBuild {
+    let routes = Router::new()
+        .route("/reload", post(reload_token))
+        .with_state(state);
+
+    let paths = make_paths!(__path_reload_token);
+
+    (routes, vec![], paths)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/token/reload",
+    responses(
+        (status = 200, description = "Reloaded the token file, GQL requests and pubsub connections use the new access token"),
+    )
+)]
+async fn reload_token(State(data): State<ApiState>) -> Result<(), ApiError> {
+    data.read()
+        .await
+        .reload_token()
+        .await
+        .map_err(ApiError::internal_error)
+}
+
+impl PubSub {
+    /// Read the token file again, and switch the GQL client and pubsub connections to its access token.
+    /// The onsite notifications and user predictions connections reconnect with it
+    pub async fn reload_token(&self) -> Result<()> {
+        let token = crate::read_token(&self.token_path, false).await?;
+        self.gql.set_access_token(token.access_token.clone());
+        self.token_tx.send_replace(token.access_token.clone());
+        self.ws_tx
+            .send_async(Request::SetAccessToken(token.access_token))
+            .await
+            .context("Reconnect pubsub with the new token")?;
+        info!("Reloaded access token from {}", self.token_path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use common::twitch::{auth::Token, ws::Request};
+    use eyre::Result;
+    use flume::unbounded;
+    use http::StatusCode;
+    use tokio::{net::TcpListener, sync::RwLock};
+
+    use crate::pubsub::PubSub;
+
+    #[tokio::test]
+    async fn reload_token_file() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("reload-tokens-{}.json", std::process::id()));
+        let token = Token {
+            access_token: "new-token".to_owned(),
+            ..Default::default()
+        };
+        tokio::fs::write(&path, serde_json::to_string(&token)?).await?;
+
+        let (ws_tx, ws_rx) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.token_path = path.display().to_string();
+        let token_rx = pubsub.token_tx.subscribe();
+        let router = super::build(Arc::new(RwLock::new(pubsub))).0;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::new();
+        let res = client
+            .post(format!("http://{address}/reload"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            ws_rx.try_recv()?,
+            Request::SetAccessToken("new-token".to_owned())
+        );
+        assert_eq!(*token_rx.borrow(), "new-token");
+
+        tokio::fs::write(&path, "not a token").await?;
+        let res = client
+            .post(format!("http://{address}/reload"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(ws_rx.is_empty());
+
+        tokio::fs::remove_file(&path).await?;
+        server.abort();
+        Ok(())
+    }
+}
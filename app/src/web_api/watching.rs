@@ -0,0 +1,79 @@
+use axum::{extract::State, routing::get, Json, Router};
+use utoipa::ToSchema;
+
+use crate::{
+    make_paths,
+    pubsub::{WatchReason, WatchedStreamer},
+};
+
+use super::{ApiState, RouterBuild};
+
+pub fn build(state: ApiState) -> RouterBuild {
+    let routes = Router::new()
+        .route("/watching", get(get_watching))
+        .with_state(state);
+
+    let schemas = vec![WatchedStreamer::schema(), WatchReason::schema()];
+    let paths = make_paths!(__path_get_watching);
+
+    (routes, schemas, paths)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/watching",
+    responses(
+        (status = 200, description = "Streamers sent viewership pings in the last watch round, in slot order, and why each was chosen", body = Vec<WatchedStreamer>),
+    )
+)]
+async fn get_watching(State(data): State<ApiState>) -> Json<Vec<WatchedStreamer>> {
+    Json(data.read().await.watched.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use eyre::Result;
+    use flume::unbounded;
+    use serde_json::json;
+    use tokio::{net::TcpListener, sync::RwLock};
+
+    use crate::pubsub::{PubSub, WatchReason, WatchedStreamer};
+
+    #[tokio::test]
+    async fn streak_in_slot_zero() -> Result<()> {
+        let (ws_tx, _) = unbounded();
+        let mut pubsub = PubSub::empty(ws_tx);
+        pubsub.watched = vec![
+            WatchedStreamer {
+                channel_name: "live_now".to_owned(),
+                reason: WatchReason::Streak,
+            },
+            WatchedStreamer {
+                channel_name: "favorite".to_owned(),
+                reason: WatchReason::Priority,
+            },
+        ];
+        let router = super::build(Arc::new(RwLock::new(pubsub))).0;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let res = reqwest::get(format!("http://{address}/watching"))
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
+        assert_eq!(
+            res,
+            json!([
+                { "channel_name": "live_now", "reason": "streak" },
+                { "channel_name": "favorite", "reason": "priority" },
+            ])
+        );
+
+        server.abort();
+        Ok(())
+    }
+}
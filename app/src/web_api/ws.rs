@@ -0,0 +1,44 @@
+use axum::{extract::State, routing::get, Json, Router};
+use common::twitch::ws::{WsConnTopics, WsPoolStatus};
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+use crate::make_paths;
+
+use super::RouterBuild;
+
+pub fn build(status: watch::Receiver<WsPoolStatus>) -> RouterBuild {
+    let routes = Router::new()
+        .route("/status", get(get_ws_status))
+        .route("/topics", get(get_ws_topics))
+        .with_state(status);
+
+    let schemas = vec![WsPoolStatus::schema(), WsConnTopics::schema()];
+    let paths = make_paths!(__path_get_ws_status, __path_get_ws_topics);
+
+    (routes, schemas, paths)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/ws/status",
+    responses(
+        (status = 200, description = "State of the twitch pubsub connections", body = WsPoolStatus),
+    )
+)]
+async fn get_ws_status(State(status): State<watch::Receiver<WsPoolStatus>>) -> Json<WsPoolStatus> {
+    Json(status.borrow().clone())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/ws/topics",
+    responses(
+        (status = 200, description = "Topics listened to on each twitch pubsub connection", body = Vec<WsConnTopics>),
+    )
+)]
+async fn get_ws_topics(
+    State(status): State<watch::Receiver<WsPoolStatus>>,
+) -> Json<Vec<WsConnTopics>> {
+    Json(status.borrow().connection_topics.clone())
+}
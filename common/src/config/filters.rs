@@ -1,9 +1,9 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, Timelike, Utc};
 use eyre::Result;
 use serde::{Deserialize, Serialize};
-use twitch_api::pubsub::predictions::Event;
+use twitch_api::pubsub::predictions::{Event, Outcome};
 
-use crate::types::StreamerState;
+use crate::{time::Tz, types::StreamerState};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
@@ -11,9 +11,29 @@ pub enum Filter {
     TotalUsers(u32),
     DelaySeconds(u32),
     DelayPercentage(f64),
+    /// Only bet on outcomes with one of these titles, compared case-insensitively
+    OutcomeAllowTitles(Vec<String>),
+    /// Never bet on outcomes with one of these colors, e.g. PINK, compared case-insensitively
+    OutcomeExcludeColors(Vec<String>),
+    /// Skip predictions the account has already bet on, e.g. before a restart
+    SkipIfAlreadyBet,
+    /// Only bet from `start_hour` until `end_hour` in the configured timezone, e.g. 22 to 6 wraps past midnight.
+    /// The end hour is excluded, and equal hours cover the whole day
+    TimeWindow {
+        start_hour: u8,
+        end_hour: u8,
+    },
+    /// Only bet while the stream is in one of these categories, compared case-insensitively. Set `game_refresh_seconds`
+    /// to notice a streamer switching categories mid-stream
+    Games(Vec<String>),
 }
 
-pub fn filter_matches(prediction: &Event, filter: &Filter, _: &StreamerState) -> Result<bool> {
+pub fn filter_matches(
+    prediction: &Event,
+    filter: &Filter,
+    streamer: &StreamerState,
+    timezone: Option<Tz>,
+) -> Result<bool> {
     let res = match filter {
         Filter::TotalUsers(t) => {
             prediction.outcomes.iter().fold(0, |a, b| a + b.total_users) as u32 >= *t
@@ -29,6 +49,75 @@ pub fn filter_matches(prediction: &Event, filter: &Filter, _: &StreamerState) ->
             let d = prediction.prediction_window_seconds as f64 * (d / 100.0);
             (chrono::Local::now() - created_at).num_seconds() as f64 >= d
         }
+        // restricts the outcomes to pick from, see [`outcome_allowed`]
+        Filter::OutcomeAllowTitles(_) | Filter::OutcomeExcludeColors(_) => true,
+        Filter::SkipIfAlreadyBet => !streamer
+            .predictions
+            .get(&prediction.id)
+            .is_some_and(|(_, placed)| *placed),
+        Filter::TimeWindow {
+            start_hour,
+            end_hour,
+        } => {
+            let hour = match timezone {
+                Some(tz) => Utc::now().with_timezone(&tz).hour(),
+                None => Local::now().hour(),
+            };
+            in_time_window(*start_hour, *end_hour, hour as u8)
+        }
+        Filter::Games(games) => streamer.info.game.as_ref().is_some_and(|game| {
+            games
+                .iter()
+                .any(|x| x.to_lowercase() == game.name.to_lowercase())
+        }),
     };
     Ok(res)
 }
+
+fn in_time_window(start_hour: u8, end_hour: u8, hour: u8) -> bool {
+    if start_hour <= end_hour {
+        start_hour == end_hour || (start_hour..end_hour).contains(&hour)
+    } else {
+        hour >= start_hour || hour < end_hour
+    }
+}
+
+pub fn outcome_allowed(outcome: &Outcome, filters: &[Filter]) -> bool {
+    filters.iter().all(|f| match f {
+        Filter::OutcomeAllowTitles(titles) => titles
+            .iter()
+            .any(|t| t.to_lowercase() == outcome.title.to_lowercase()),
+        Filter::OutcomeExcludeColors(colors) => !colors
+            .iter()
+            .any(|c| c.to_lowercase() == outcome.color.to_lowercase()),
+        _ => true,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::in_time_window;
+
+    #[rstest]
+    #[case(9, 17, 9, true)]
+    #[case(9, 17, 12, true)]
+    #[case(9, 17, 17, false)]
+    #[case(9, 17, 3, false)]
+    #[case(22, 6, 23, true)]
+    #[case(22, 6, 0, true)]
+    #[case(22, 6, 5, true)]
+    #[case(22, 6, 6, false)]
+    #[case(22, 6, 12, false)]
+    #[case(22, 6, 21, false)]
+    #[case(8, 8, 3, true)]
+    fn time_window(
+        #[case] start_hour: u8,
+        #[case] end_hour: u8,
+        #[case] hour: u8,
+        #[case] inside: bool,
+    ) {
+        assert_eq!(in_time_window(start_hour, end_hour, hour), inside);
+    }
+}
@@ -1,9 +1,12 @@
+use std::collections::HashMap;
+
 use eyre::{eyre, Result};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
 use self::{filters::Filter, strategy::Strategy};
+use crate::twitch::proxy::Proxy;
 
 pub mod filters;
 pub mod strategy;
@@ -14,6 +17,158 @@ pub struct Config {
     pub streamers: IndexMap<String, ConfigType>,
     pub presets: Option<IndexMap<String, StreamerConfig>>,
     pub watch_streak: Option<bool>,
+    /// Rounds of roughly 10 seconds a stream that just went live is watched with priority for its watch streak bonus,
+    /// unless the bonus is earned earlier
+    pub watch_streak_rounds: Option<u32>,
+    pub claim_mode: Option<ClaimMode>,
+    /// Seconds without messages on a live stream before listening to it again
+    pub pubsub_silence_timeout: Option<u64>,
+    /// Seconds a stream may be offline before its prediction and raid topics are unlistened, so short drops keep them subscribed
+    pub pubsub_unlisten_grace: Option<u64>,
+    /// Seconds between checks of the category of live streams, so Games filters react when a streamer switches games
+    /// mid-stream. Disabled if not specified, as every check is a GQL request
+    pub game_refresh_seconds: Option<u64>,
+    /// Watch live channels with an active drop campaign before the others, and claim drops once they have been watched
+    /// long enough
+    pub drops: Option<bool>,
+    /// Also listen to the user's onsite notifications, which sometimes announce predictions and raids before the channel's own topics
+    pub onsite_notifications: Option<bool>,
+    /// Maximum number of operations in a single batched GQL request
+    pub gql_chunk_size: Option<usize>,
+    /// Number of batched GQL requests sent at the same time
+    pub gql_concurrency: Option<usize>,
+    /// Log startup progress every time this many streamers are resolved
+    pub startup_progress_every: Option<usize>,
+    /// Persisted query hashes by GQL operation name, e.g. MakePrediction, to patch hashes rotated by twitch
+    pub gql_query_hashes: Option<HashMap<String, String>>,
+    /// Consecutive failed pubsub connection attempts before the connection is reported as degraded
+    pub pubsub_degraded_after: Option<u32>,
+    /// Maximum number of pubsub connections, further topics wait until a connection has room
+    pub pubsub_max_connections: Option<usize>,
+    /// Maximum number of topics on a single pubsub connection, only change this if twitch changes its limit
+    pub pubsub_topics_per_connection: Option<usize>,
+    /// Number of recently received raw pubsub messages kept for debugging
+    pub pubsub_raw_messages: Option<usize>,
+    /// Consecutive GQL failures of a streamer before its GQL interactions are paused
+    pub circuit_breaker_failures: Option<u32>,
+    /// Seconds GQL interactions with a failing streamer are paused for
+    pub circuit_breaker_cooldown: Option<u64>,
+    /// Seconds to wait for a connection to twitch when discovering the spade url or sending viewership
+    pub http_connect_timeout: Option<u64>,
+    /// Seconds a spade url discovery or viewership request may take before it is abandoned
+    pub http_timeout: Option<u64>,
+    /// Times a failed spade url discovery or viewership request is retried
+    pub http_retries: Option<u32>,
+    /// Check the balance after placing a bet, and treat the bet as not placed if it was not debited
+    pub confirm_bets: Option<bool>,
+    /// Only bet on predictions that started after the miner was launched
+    pub skip_predictions_at_startup: Option<bool>,
+    /// Maximum number of predictions tracked per streamer, new predictions are dropped with a warning once reached
+    pub max_predictions_per_streamer: Option<usize>,
+    /// Predictions that are never bet on, updated at runtime through the API
+    pub prediction_blacklist: Option<PredictionBlacklist>,
+    /// Pause all bets while the total balance across streamers is below this value
+    pub global_min_balance: Option<u32>,
+    /// Stop all bets once the total balance drops too fast, until they are resumed through the API
+    pub panic_stop: Option<PanicStop>,
+    /// Maximum points bet across all streamers per day in the configured timezone, bets are made smaller to fit
+    /// and skipped once it is reached
+    pub daily_bet_cap: Option<u32>,
+    /// Randomly move every bet up or down by up to this percent, so wagers are not an exact share of the balance.
+    /// A bet is never raised above the max_value of the strategy's points or above the balance, and is rounded again
+    /// with the strategy's bet_rounding
+    pub points_jitter_percent: Option<f64>,
+    /// Balance to save towards on every streamer, a points_goal_reached event is published once it is reached
+    pub points_goal: Option<u32>,
+    /// Only place real bets on these channels, bets on any other channel are simulated
+    pub bet_allowlist: Option<Vec<String>>,
+    /// Log a warning and publish a large_bet event whenever a single bet is above this many points
+    pub alert_on_bet_above: Option<u32>,
+    /// Webhook events are posted to, streamers with their own notify target use that instead
+    pub notify: Option<NotifyTarget>,
+    /// Record points, predictions and watch time in the analytics database, enabled if not specified
+    pub analytics: Option<bool>,
+    /// Vacuum and analyze the analytics database once a month, keeping it compact and queries fast
+    pub optimize_analytics: Option<bool>,
+    /// Maximum number of writes waiting for the analytics database. Once full, point updates and watch events are dropped
+    /// with a warning, while predictions and bets wait for room
+    pub analytics_queue_capacity: Option<usize>,
+    /// Seconds between snapshots of the total balance across streamers, recorded for the equity curve. Disabled if not
+    /// specified
+    pub equity_snapshot_seconds: Option<u64>,
+    /// IANA timezone name, e.g. Europe/Berlin, used for day boundaries and timeline ranges without an offset. Defaults to the server's local time
+    pub timezone: Option<String>,
+    /// Proxy for all traffic to twitch, e.g. socks5://127.0.0.1:1080. Supports http://, https:// and socks5://
+    pub proxy: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ClaimMode {
+    /// Claim each bonus with its own request as soon as it is found
+    #[default]
+    Immediate,
+    /// Claim all bonuses found across streamers in a single request
+    Batched,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub struct PredictionBlacklist {
+    /// Case-insensitive keywords matched against the prediction title
+    #[serde(default)]
+    pub title_keywords: Vec<String>,
+    #[serde(default)]
+    pub event_ids: Vec<String>,
+}
+
+impl PredictionBlacklist {
+    pub fn matches(&self, event_id: &str, title: &str) -> bool {
+        let title = title.to_lowercase();
+        self.event_ids.iter().any(|x| x == event_id)
+            || self
+                .title_keywords
+                .iter()
+                .any(|x| title.contains(&x.to_lowercase()))
+    }
+}
+
+/// Loss of the total balance across streamers within a rolling window that stops all bets. Stops once either
+/// limit is exceeded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub struct PanicStop {
+    /// Seconds the loss is measured over, from the highest total balance within them
+    pub window_seconds: u64,
+    pub max_loss_points: Option<u64>,
+    /// Percent of the highest total balance within the window, between 0 and 100
+    pub max_loss_percent: Option<f64>,
+}
+
+impl PanicStop {
+    pub fn exceeded(&self, peak: u64, loss: u64) -> bool {
+        self.max_loss_points.is_some_and(|max| loss > max)
+            || self
+                .max_loss_percent
+                .is_some_and(|max| peak > 0 && loss as f64 / peak as f64 * 100.0 > max)
+    }
+}
+
+/// Webhook that events are posted to as JSON, e.g. a discord channel's webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub struct NotifyTarget {
+    pub webhook_url: String,
+    /// Names of the events to post, e.g. bet_placed or prediction_ended. All events if not specified
+    pub events: Option<Vec<String>>,
+}
+
+impl NotifyTarget {
+    pub fn wants(&self, event_name: &str) -> bool {
+        match &self.events {
+            Some(events) => events.iter().any(|x| x == event_name),
+            None => true,
+        }
+    }
 }
 
 pub trait Normalize {
@@ -24,12 +179,38 @@ pub trait Normalize {
 #[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
 pub struct StreamerConfig {
     pub follow_raid: bool,
+    /// Place bets on predictions, enabled if not specified
+    pub betting: Option<bool>,
+    /// Only bet once the stream has been live for this many seconds
+    pub min_live_seconds: Option<u64>,
+    /// Simulate bets on this streamer instead of placing them, the --simulate argument is used if not specified
+    pub simulate: Option<bool>,
+    /// Balance to save towards on this streamer, overrides the global points_goal
+    pub points_goal: Option<u32>,
+    /// Where this streamer's events are posted, overrides the global notify target
+    pub notify: Option<Box<NotifyTarget>>,
     #[validate(nested)]
     pub prediction: PredictionConfig,
 }
 
 impl StreamerConfig {
     pub fn validate(&self) -> Result<()> {
+        for filter in &self.prediction.filters {
+            if let Filter::TimeWindow {
+                start_hour,
+                end_hour,
+            } = filter
+            {
+                if *start_hour > 23 || *end_hour > 23 {
+                    return Err(eyre!(
+                        "TimeWindow hours must be between 0 and 23, got {start_hour} to {end_hour}"
+                    ));
+                }
+            }
+        }
+        if let Some(fallback) = &self.prediction.fallback {
+            fallback.validate()?;
+        }
         Ok(self.prediction.validate()?)
     }
 }
@@ -42,6 +223,17 @@ pub struct PredictionConfig {
     pub strategy: Strategy,
     #[validate(length(min = 0))]
     pub filters: Vec<Filter>,
+    /// Consulted when the strategy declines to bet, e.g. a conservative bet on the favorite
+    pub fallback: Option<Box<Strategy>>,
+}
+
+impl Normalize for PredictionConfig {
+    fn normalize(&mut self) {
+        self.strategy.normalize();
+        if let Some(fallback) = self.fallback.as_mut() {
+            fallback.normalize();
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +245,35 @@ pub enum ConfigType {
 
 impl Config {
     pub fn parse_and_validate(&mut self) -> Result<()> {
+        if let Some(tz) = &self.timezone {
+            tz.parse::<chrono_tz::Tz>()
+                .map_err(|_| eyre!("Unknown timezone {tz}"))?;
+        }
+        if let Some(proxy) = &self.proxy {
+            proxy.parse::<Proxy>()?;
+        }
+        if let Some(jitter) = self.points_jitter_percent {
+            if !(0.0..=100.0).contains(&jitter) {
+                return Err(eyre!(
+                    "points_jitter_percent must be between 0 and 100, got {jitter}"
+                ));
+            }
+        }
+        if let Some(panic_stop) = &self.panic_stop {
+            if panic_stop.max_loss_points.is_none() && panic_stop.max_loss_percent.is_none() {
+                return Err(eyre!(
+                    "panic_stop needs max_loss_points or max_loss_percent"
+                ));
+            }
+            if let Some(percent) = panic_stop.max_loss_percent {
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(eyre!(
+                        "panic_stop max_loss_percent must be between 0 and 100, got {percent}"
+                    ));
+                }
+            }
+        }
+
         for (_, c) in &mut self.streamers {
             match c {
                 ConfigType::Preset(s_name) => {
@@ -70,7 +291,7 @@ impl Config {
                 }
                 ConfigType::Specific(s) => {
                     s.validate()?;
-                    s.prediction.strategy.normalize();
+                    s.prediction.normalize();
                 }
             }
         }
@@ -81,9 +302,14 @@ impl Config {
                     return Err(eyre!("Preset {key} already in use as a streamer. Preset names cannot be the same as a streamer mentioned in the config"));
                 }
 
-                c.prediction.strategy.normalize();
+                c.prediction.normalize();
             }
         }
         Ok(())
     }
+
+    /// The configured timezone, or `None` for the server's local time
+    pub fn timezone(&self) -> Option<chrono_tz::Tz> {
+        self.timezone.as_ref().and_then(|x| x.parse().ok())
+    }
 }
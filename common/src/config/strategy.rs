@@ -8,6 +8,80 @@ use super::Normalize;
 #[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
 pub enum Strategy {
     Detailed(Detailed),
+    /// Evaluate several strategies and combine their bets
+    Composite(Composite),
+    /// Bet more the closer the prediction is to locking, when the odds have settled
+    TimeDecay(TimeDecay),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Validate)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+#[validate(nested)]
+pub struct Composite {
+    /// Weights are relative to each other and must sum to 100
+    #[validate(length(min = 1), custom(function = "validate_weights"), nested)]
+    pub strategies: Vec<WeightedStrategy>,
+    /// How the bets of the strategies are combined into one
+    #[serde(default)]
+    pub combine: CombineRule,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Validate)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+#[validate(nested)]
+pub struct WeightedStrategy {
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub weight: f64,
+    #[validate(nested)]
+    pub strategy: Detailed,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub enum CombineRule {
+    /// Every strategy has to bet on the same outcome, the bet is the weighted average
+    #[default]
+    Agreement,
+    /// The outcome with the most weight behind it wins, the bet is the weighted average of its backers
+    Average,
+    /// Bet what the highest weighted strategy that wants to bet suggests
+    HighestWeight,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, Validate)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+#[validate(schema(function = "validate_time_decay"))]
+pub struct TimeDecay {
+    /// Picks the outcome and the full stake, which is scaled by how much of the prediction window has passed
+    #[validate(nested)]
+    pub strategy: Detailed,
+    /// Share of the prediction window (0 - 100) that has to pass before anything is bet
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub start_percentage: f64,
+    /// Share of the prediction window (0 - 100) from which the full stake is bet
+    #[serde(default = "defaults::_time_decay_full_default")]
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub full_percentage: f64,
+}
+
+fn validate_time_decay(s: &TimeDecay) -> Result<(), validator::ValidationError> {
+    if s.start_percentage > s.full_percentage {
+        Err(validator::ValidationError::new(
+            "start_percentage must not be above full_percentage",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_weights(strategies: &[WeightedStrategy]) -> Result<(), validator::ValidationError> {
+    let sum = strategies.iter().map(|x| x.weight).sum::<f64>();
+    if (sum - 100.0).abs() > 0.01 {
+        Err(validator::ValidationError::new("weights must sum to 100"))
+    } else {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Validate)]
@@ -18,6 +92,69 @@ pub struct Detailed {
     pub detailed: Option<Vec<DetailedOdds>>,
     #[validate(nested)]
     pub default: DefaultPrediction,
+    /// Place additional bets on the same outcome when a detailed odds rule is satisfied after the first bet
+    #[serde(default)]
+    pub chase_odds: bool,
+    /// How the probability of each outcome is derived
+    #[serde(default)]
+    #[validate(custom(function = "validate_probability_source"))]
+    pub probability_source: ProbabilitySource,
+    /// Round bets to the nearest multiple of this value, 0 or 1 to disable
+    #[serde(default)]
+    pub bet_rounding: u32,
+    /// Never bet on outcomes nobody has wagered on, and skip predictions with no points wagered at all
+    #[serde(default)]
+    pub skip_empty_outcomes: bool,
+    /// Never bet on outcomes backed by fewer users than this, however good their odds look
+    #[serde(default)]
+    pub min_outcome_users: Option<u64>,
+    /// Only consider this many outcomes with the largest pools, so the thin outcomes of large predictions are never bet on
+    #[serde(default)]
+    pub max_outcomes: Option<usize>,
+    /// Lowest probability (0 - 100) an outcome needs before the default odds config bets on it. Unlike the
+    /// min_percentage to max_percentage band, it has no upper bound, so in predictions with many outcomes it keeps
+    /// bets off long shots without also ruling out favorites
+    #[serde(default)]
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub min_confidence: Option<f64>,
+    /// Which outcome the default odds config bets on when several are inside the min_percentage to max_percentage
+    /// band. The first one is used if not specified
+    #[serde(default)]
+    pub prefer: Option<RangePreference>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub enum RangePreference {
+    /// The most likely outcome inside the band
+    HighestInRange,
+    /// The least likely outcome inside the band, for the best payout
+    LowestInRange,
+    /// The outcome closest to the middle of the band
+    ClosestToMidpoint,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub enum ProbabilitySource {
+    /// Share of the points wagered on the outcome
+    #[default]
+    Points,
+    /// Share of the users who bet on the outcome
+    Users,
+    /// Mix of both, the value is the weight given to the users share (0 - 100)
+    Blend(f64),
+}
+
+fn validate_probability_source(
+    source: &ProbabilitySource,
+) -> Result<(), validator::ValidationError> {
+    match source {
+        ProbabilitySource::Blend(x) if !(0.0..=100.0).contains(x) => {
+            Err(validator::ValidationError::new("range"))
+        }
+        _ => Ok(()),
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Validate)]
@@ -68,6 +205,7 @@ pub struct Points {
 mod defaults {
     pub const fn _detailed_low_threshold_default() -> f64 { 40.0 }
     pub const fn _detailed_high_threshold_default() -> f64 { 60.0 }
+    pub const fn _time_decay_full_default() -> f64 { 90.0 }
 }
 
 impl<'v_a> ::validator::ValidateNested<'v_a> for Strategy {
@@ -102,6 +240,12 @@ impl Validate for Strategy {
             Strategy::Detailed(t) => {
                 ::validator::ValidationErrors::merge(result, "detailed", t.validate())
             }
+            Strategy::Composite(t) => {
+                ::validator::ValidationErrors::merge(result, "composite", t.validate())
+            }
+            Strategy::TimeDecay(t) => {
+                ::validator::ValidationErrors::merge(result, "timeDecay", t.validate())
+            }
         }
     }
 }
@@ -109,6 +253,12 @@ impl Validate for Strategy {
 impl Normalize for Detailed {
     fn normalize(&mut self) {
         self.default.normalize();
+        if let ProbabilitySource::Blend(x) = &mut self.probability_source {
+            *x /= 100.0;
+        }
+        if let Some(x) = self.min_confidence.as_mut() {
+            *x /= 100.0;
+        }
 
         if let Some(h) = self.detailed.as_mut() {
             h.iter_mut().for_each(|x| {
@@ -120,6 +270,27 @@ impl Normalize for Detailed {
     }
 }
 
+impl Normalize for Composite {
+    fn normalize(&mut self) {
+        // weights become fractions of their sum, so rounding in the config does not matter
+        let sum = self.strategies.iter().map(|x| x.weight).sum::<f64>();
+        self.strategies.iter_mut().for_each(|x| {
+            if sum > 0.0 {
+                x.weight /= sum;
+            }
+            x.strategy.normalize();
+        });
+    }
+}
+
+impl Normalize for TimeDecay {
+    fn normalize(&mut self) {
+        self.strategy.normalize();
+        self.start_percentage /= 100.0;
+        self.full_percentage /= 100.0;
+    }
+}
+
 impl Normalize for DefaultPrediction {
     fn normalize(&mut self) {
         self.max_percentage /= 100.0;
@@ -143,12 +314,129 @@ impl Points {
     }
 }
 
+impl Detailed {
+    /// Round a bet to the nearest multiple of `bet_rounding`, never exceeding the balance.
+    /// A bet is at least one multiple if the balance allows it, otherwise 0
+    pub fn round_bet(&self, value: u32, balance: u32) -> u32 {
+        let step = self.bet_rounding;
+        if step <= 1 {
+            return value;
+        }
+
+        let rounded = (value + step / 2) / step * step;
+        if rounded > balance {
+            value / step * step
+        } else if rounded == 0 && value > 0 && step <= balance {
+            step
+        } else {
+            rounded
+        }
+    }
+
+    /// Largest bet any of the odds configs allows, None if one of them has no max_value
+    pub fn max_bet(&self) -> Option<u32> {
+        std::iter::once(&self.default.points)
+            .chain(self.detailed.iter().flatten().map(|x| &x.points))
+            .try_fold(0, |max, x| (x.max_value > 0).then(|| max.max(x.max_value)))
+    }
+}
+
+impl RangePreference {
+    /// Index of the preferred candidate, given as outcome index and probability. Ties go to the earlier outcome
+    pub fn pick(&self, candidates: &[(usize, f64)], band: &DefaultPrediction) -> Option<usize> {
+        let midpoint = (band.min_percentage + band.max_percentage) / 2.0;
+        let better = |a: f64, b: f64| match self {
+            RangePreference::HighestInRange => b > a,
+            RangePreference::LowestInRange => b < a,
+            RangePreference::ClosestToMidpoint => (b - midpoint).abs() < (a - midpoint).abs(),
+        };
+        candidates
+            .iter()
+            .copied()
+            .reduce(|a, b| if better(a.1, b.1) { b } else { a })
+            .map(|x| x.0)
+    }
+}
+
+impl TimeDecay {
+    /// Share of the full stake to bet once `elapsed` (0 - 1) of the prediction window has passed, growing linearly
+    /// from nothing at `start_percentage` to all of it at `full_percentage`
+    pub fn stake_share(&self, elapsed: f64) -> f64 {
+        if elapsed < self.start_percentage {
+            0.0
+        } else if elapsed >= self.full_percentage {
+            1.0
+        } else {
+            (elapsed - self.start_percentage) / (self.full_percentage - self.start_percentage)
+        }
+    }
+}
+
+impl ProbabilitySource {
+    /// Probability of an outcome given its share of the points and users
+    pub fn probability(&self, points_share: f64, users_share: f64) -> f64 {
+        match self {
+            ProbabilitySource::Points => points_share,
+            ProbabilitySource::Users => users_share,
+            ProbabilitySource::Blend(ratio) => points_share * (1.0 - ratio) + users_share * ratio,
+        }
+    }
+}
+
 impl Normalize for Points {
     fn normalize(&mut self) {
         self.percent /= 100.0;
     }
 }
 
+impl Strategy {
+    pub fn chase_odds(&self) -> bool {
+        match self {
+            Strategy::Detailed(s) => s.chase_odds,
+            Strategy::Composite(s) => s.strategies.iter().any(|x| x.strategy.chase_odds),
+            // the stake grows as the window passes, so the first bet is topped up
+            Strategy::TimeDecay(_) => true,
+        }
+    }
+
+    /// How outcome odds are computed, the first strategy's for Composite
+    pub fn probability_source(&self) -> ProbabilitySource {
+        match self {
+            Strategy::Detailed(s) => s.probability_source,
+            Strategy::Composite(s) => s
+                .strategies
+                .first()
+                .map(|x| x.strategy.probability_source)
+                .unwrap_or_default(),
+            Strategy::TimeDecay(s) => s.strategy.probability_source,
+        }
+    }
+
+    /// Largest bet the strategy places at once, None if it is only bounded by the balance
+    pub fn max_bet(&self) -> Option<u32> {
+        match self {
+            Strategy::Detailed(s) => s.max_bet(),
+            Strategy::Composite(s) => s
+                .strategies
+                .iter()
+                .try_fold(0, |max, x| Some(max.max(x.strategy.max_bet()?))),
+            Strategy::TimeDecay(s) => s.strategy.max_bet(),
+        }
+    }
+
+    /// Round a bet with `bet_rounding`, the first strategy's for Composite
+    pub fn round_bet(&self, value: u32, balance: u32) -> u32 {
+        match self {
+            Strategy::Detailed(s) => s.round_bet(value, balance),
+            Strategy::Composite(s) => s
+                .strategies
+                .first()
+                .map_or(value, |x| x.strategy.round_bet(value, balance)),
+            Strategy::TimeDecay(s) => s.strategy.round_bet(value, balance),
+        }
+    }
+}
+
 impl Default for Strategy {
     fn default() -> Self {
         Self::Detailed(Default::default())
@@ -159,6 +447,8 @@ impl Normalize for Strategy {
     fn normalize(&mut self) {
         match self {
             Strategy::Detailed(s) => s.normalize(),
+            Strategy::Composite(s) => s.normalize(),
+            Strategy::TimeDecay(s) => s.normalize(),
         }
     }
 }
@@ -1,4 +1,5 @@
 pub mod config;
+pub mod time;
 pub mod twitch;
 pub mod types;
 
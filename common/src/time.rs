@@ -0,0 +1,99 @@
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+pub use chrono_tz::Tz;
+use eyre::{eyre, Result};
+
+/// Start of the day containing `at`, in the given zone or the server's local time
+pub fn day_start(at: DateTime<Utc>, zone: Option<Tz>) -> DateTime<Utc> {
+    match zone {
+        Some(tz) => midnight(at.with_timezone(&tz)),
+        None => midnight(at.with_timezone(&Local)),
+    }
+}
+
+fn midnight<T: TimeZone>(at: DateTime<T>) -> DateTime<Utc> {
+    let tz = at.timezone();
+    let naive = at.date_naive().and_hms_opt(0, 0, 0).unwrap();
+    // a daylight saving change can skip midnight, the day then starts an hour later
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(naive + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|x| x.with_timezone(&Utc))
+        .unwrap_or(at.with_timezone(&Utc))
+}
+
+/// Parse an RFC3339 timestamp, or a date (time) without an offset interpreted in the given zone
+pub fn parse_in_zone(value: &str, zone: Option<Tz>) -> Result<DateTime<Utc>> {
+    if let Ok(at) = DateTime::<FixedOffset>::parse_from_rfc3339(value) {
+        return Ok(at.with_timezone(&Utc));
+    }
+
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|x| x.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .map_err(|_| eyre!("Could not parse timestamp {value}"))?;
+    let at = match zone {
+        Some(tz) => tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|x| x.with_timezone(&Utc)),
+        None => Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|x| x.with_timezone(&Utc)),
+    };
+    at.ok_or(eyre!("{value} does not exist in the configured timezone"))
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    use super::{day_start, parse_in_zone};
+
+    #[test]
+    fn day_resets_at_zone_midnight() {
+        let tz: Tz = "Asia/Kolkata".parse().unwrap();
+
+        // 20:00 UTC is already the next day in India (UTC+5:30)
+        let at = Utc.with_ymd_and_hms(2024, 6, 1, 20, 0, 0).unwrap();
+        assert_eq!(
+            day_start(at, Some(tz)),
+            Utc.with_ymd_and_hms(2024, 6, 1, 18, 30, 0).unwrap()
+        );
+
+        // just before midnight in India, still the previous day
+        let at = Utc.with_ymd_and_hms(2024, 6, 1, 18, 29, 59).unwrap();
+        assert_eq!(
+            day_start(at, Some(tz)),
+            Utc.with_ymd_and_hms(2024, 5, 31, 18, 30, 0).unwrap()
+        );
+
+        let utc: Tz = "UTC".parse().unwrap();
+        assert_eq!(
+            day_start(at, Some(utc)),
+            Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn range_without_offset_uses_zone() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        assert_eq!(
+            parse_in_zone("2024-06-01", Some(tz)).unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 1, 4, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_in_zone("2024-06-01T10:00:00", Some(tz)).unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 1, 14, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_in_zone("2024-06-01T10:00:00+00:00", Some(tz)).unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap()
+        );
+    }
+}
@@ -1,6 +1,10 @@
+use std::{future::Future, time::Duration};
+
 use base64::{engine::general_purpose::URL_SAFE, Engine};
 use eyre::{eyre, Result};
 use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::debug;
 use twitch_api::types::UserId;
 
 use crate::{
@@ -8,10 +12,90 @@ use crate::{
     types::{MinuteWatched, StreamerInfo},
 };
 
-use super::{CHROME_USER_AGENT, CLIENT_ID};
+use super::{proxy::Proxy, CHROME_USER_AGENT, CLIENT_ID};
+
+pub const CONNECT_TIMEOUT_SECS: u64 = 5;
+pub const REQUEST_TIMEOUT_SECS: u64 = 15;
+pub const REQUEST_RETRIES: u32 = 2;
+
+#[cfg(feature = "testing")]
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+#[cfg(not(feature = "testing"))]
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Timeouts and retries for the spade url discovery and viewership requests
+#[derive(Debug, Clone)]
+pub struct RequestOptions {
+    pub connect_timeout: Duration,
+    /// Timeout of the whole request, including reading the response
+    pub timeout: Duration,
+    /// Attempts made after the first one fails
+    pub retries: u32,
+    pub proxy: Option<Proxy>,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(CONNECT_TIMEOUT_SECS),
+            timeout: Duration::from_secs(REQUEST_TIMEOUT_SECS),
+            retries: REQUEST_RETRIES,
+            proxy: None,
+        }
+    }
+}
+
+impl RequestOptions {
+    fn client(&self) -> Result<reqwest::Client> {
+        let builder = match &self.proxy {
+            Some(proxy) => proxy.client_builder()?,
+            None => reqwest::Client::builder(),
+        };
+        Ok(builder
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .build()?)
+    }
+
+    async fn retry<T, F, Fut>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f().await {
+                Ok(x) => return Ok(x),
+                Err(err) if attempt < self.retries => {
+                    attempt += 1;
+                    debug!(
+                        "Request failed, attempt {attempt} of {}: {err}",
+                        self.retries
+                    );
+                    sleep(RETRY_DELAY).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+pub async fn get_spade_url(
+    streamer: &str,
+    base_url: &str,
+    options: &RequestOptions,
+) -> Result<String> {
+    let client = options.client()?;
+    options
+        .retry(|| spade_url_attempt(&client, streamer, base_url))
+        .await
+}
 
-pub async fn get_spade_url(streamer: &str, base_url: &str) -> Result<String> {
-    let client = reqwest::Client::new();
+async fn spade_url_attempt(
+    client: &reqwest::Client,
+    streamer: &str,
+    base_url: &str,
+) -> Result<String> {
     let page_text = client
         .get(&format!("{base_url}/{streamer}"))
         .header("User-Agent", CHROME_USER_AGENT)
@@ -21,6 +105,7 @@ pub async fn get_spade_url(streamer: &str, base_url: &str) -> Result<String> {
         .await?;
 
     async fn inner(
+        client: &reqwest::Client,
         text: &str,
         uri: &str,
         #[cfg(feature = "testing")] base_url: &str,
@@ -32,7 +117,6 @@ pub async fn get_spade_url(streamer: &str, base_url: &str) -> Result<String> {
                     let prefix = format!("{base_url}/");
                     #[cfg(not(feature = "testing"))]
                     let prefix = "";
-                    let client = reqwest::Client::new();
                     let text = client
                         .get(&format!("{prefix}{uri}{pattern_js}.js"))
                         .header("User-Agent", CHROME_USER_AGENT)
@@ -55,6 +139,7 @@ pub async fn get_spade_url(streamer: &str, base_url: &str) -> Result<String> {
     }
 
     match inner(
+        client,
         &page_text,
         #[cfg(feature = "testing")]
         "config/settings.",
@@ -68,6 +153,7 @@ pub async fn get_spade_url(streamer: &str, base_url: &str) -> Result<String> {
         Ok(s) => Ok(s),
         Err(_) => {
             inner(
+                client,
                 &page_text,
                 "https://assets.twitch.tv/config/settings.",
                 #[cfg(feature = "testing")]
@@ -92,6 +178,7 @@ pub async fn set_viewership(
     channel_id: UserId,
     info: StreamerInfo,
     spade_url: &str,
+    options: &RequestOptions,
 ) -> Result<()> {
     let watch_event = SetViewership {
         event: "minute-watched".to_owned(),
@@ -100,19 +187,60 @@ pub async fn set_viewership(
 
     let body = serde_json::to_string(&[watch_event])?;
 
-    let client = reqwest::Client::new();
-    let res = client
-        .post(spade_url)
-        .header("Client-Id", CLIENT_ID)
-        .header("User-Agent", CHROME_USER_AGENT)
-        .header("X-Device-Id", DEVICE_ID)
-        .form(&[("data", &URL_SAFE.encode(body))])
-        .send()
-        .await?;
+    let data = URL_SAFE.encode(body);
+    let client = options.client()?;
+    options
+        .retry(|| async {
+            let res = client
+                .post(spade_url)
+                .header("Client-Id", CLIENT_ID)
+                .header("User-Agent", CHROME_USER_AGENT)
+                .header("X-Device-Id", DEVICE_ID)
+                .form(&[("data", &data)])
+                .send()
+                .await?;
 
-    if !res.status().is_success() {
-        return Err(eyre!("Failed to set viewership"));
-    }
+            if !res.status().is_success() {
+                return Err(eyre!("Failed to set viewership: {}", res.status()));
+            }
+            Ok(())
+        })
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use eyre::Result;
+    use tokio::net::TcpListener;
+
+    use super::RequestOptions;
 
-    Ok(())
+    #[tokio::test]
+    async fn stalled_request_times_out() -> Result<()> {
+        // accepts connections but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let options = RequestOptions {
+            connect_timeout: Duration::from_millis(100),
+            timeout: Duration::from_millis(100),
+            retries: 1,
+            proxy: None,
+        };
+        let start = Instant::now();
+        let res = super::get_spade_url("a", &format!("http://{address}"), &options).await;
+        assert!(res.is_err());
+        assert!(start.elapsed() < Duration::from_secs(3));
+
+        server.abort();
+        Ok(())
+    }
 }
@@ -1,11 +1,21 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
 use eyre::{eyre, Result};
+use futures_util::{stream, StreamExt};
 use rand::distributions::{Alphanumeric, DistString};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use strum_macros::EnumDiscriminants;
-use twitch_api::{pubsub, types::UserId};
+use tracing::{error, warn};
+use twitch_api::{
+    pubsub,
+    types::{Timestamp, UserId},
+};
 
-use super::{CLIENT_ID, DEVICE_ID, USER_AGENT};
+use super::{proxy::Proxy, CLIENT_ID, DEVICE_ID, USER_AGENT};
 use crate::{
     twitch::traverse_json,
     types::{Game, StreamerInfo},
@@ -37,8 +47,22 @@ impl TryFrom<GqlPlaceHolder> for GqlRequest {
             (ChannelPointsContext, Variables::StreamMetadata(content)) => {
                 Variables::ChannelPointsContext(content)
             }
+            // untagged variables match the channel login first, the count is always one
+            (ChannelPointsPredictionContext, Variables::StreamMetadata(content)) => {
+                Variables::ChannelPointsPredictionContext(self::ChannelPointsPredictionContext {
+                    count: 1,
+                    channel_login: content.channel_login,
+                })
+            }
             (
-                MakePrediction | ClaimCommunityPoints | ChannelPointsPredictionContext | JoinRaid,
+                MakePrediction
+                | ClaimCommunityPoints
+                | ChannelPointsPredictionContext
+                | JoinRaid
+                | AvailableDrops
+                | Inventory
+                | ClaimDropRewards
+                | CoreActionsCurrentUser,
                 content,
             ) => content,
             (operation_name, _) => {
@@ -67,27 +91,243 @@ pub enum Variables {
     ClaimCommunityPoints(ClaimCommunityPoints),
     ChannelPointsPredictionContext(ChannelPointsPredictionContext),
     JoinRaid(JoinRaid),
+    #[strum_discriminants(serde(rename = "DropsHighlightService_AvailableDrops"))]
+    AvailableDrops(AvailableDrops),
+    Inventory(Inventory),
+    #[strum_discriminants(serde(rename = "DropsPage_ClaimDropRewards"))]
+    ClaimDropRewards(ClaimDropRewards),
+    // has no variables, so it has to come last to not match every other operation
+    CoreActionsCurrentUser(CoreActionsCurrentUser),
 }
 
-#[derive(Debug, Clone, Default)]
+impl OperationName {
+    /// Name of the operation as sent to twitch
+    pub fn name(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|x| x.as_str().map(str::to_owned))
+            .unwrap_or_else(|| format!("{self:?}"))
+    }
+}
+
+/// Default number of operations sent in a single batched GQL request
+pub const CHUNK_SIZE: usize = 30;
+/// Default number of batched GQL requests in flight at once
+pub const CONCURRENCY: usize = 4;
+/// Default number of streamers resolved between progress logs at startup
+pub const PROGRESS_EVERY: usize = 50;
+
+#[derive(Debug, Clone)]
 pub struct Client {
-    access_token: String,
+    /// Shared by all clones, so a reloaded token is picked up everywhere
+    access_token: Arc<RwLock<String>>,
     url: String,
+    http: reqwest::Client,
+    chunk_size: usize,
+    concurrency: usize,
+    /// Persisted query hashes by operation name, replacing the compiled in ones
+    query_hashes: Arc<HashMap<String, String>>,
+    progress: Option<Progress>,
+}
+
+/// Called with (what is resolved, resolved so far, total) while a batched request is in progress
+pub type ProgressCallback = Arc<dyn Fn(&str, usize, usize) + Send + Sync>;
+
+#[derive(Clone)]
+struct Progress {
+    every: usize,
+    callback: ProgressCallback,
+}
+
+impl std::fmt::Debug for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Progress")
+            .field("every", &self.every)
+            .finish()
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new(String::new(), String::new())
+    }
 }
 
 impl Client {
     pub fn new(access_token: String, url: String) -> Client {
-        Client { access_token, url }
+        Client {
+            access_token: Arc::new(RwLock::new(access_token)),
+            url,
+            http: reqwest::Client::new(),
+            chunk_size: CHUNK_SIZE,
+            concurrency: CONCURRENCY,
+            query_hashes: Default::default(),
+            progress: None,
+        }
+    }
+
+    /// Send all requests through the proxy
+    pub fn with_proxy(mut self, proxy: &Proxy) -> Result<Client> {
+        self.http = proxy.client_builder()?.build()?;
+        Ok(self)
+    }
+
+    /// Use a new access token for all following requests, of this client and all its clones
+    pub fn set_access_token(&self, access_token: String) {
+        *self.access_token.write().unwrap() = access_token;
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Client {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Number of requests sent at the same time
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Report progress every `every` resolved items of a batched request, and once it is done
+    pub fn with_progress(mut self, every: usize, callback: ProgressCallback) -> Client {
+        self.progress = Some(Progress {
+            every: every.max(1),
+            callback,
+        });
+        self
+    }
+
+    fn report_progress(&self, what: &str, before: usize, done: usize, total: usize) {
+        if let Some(progress) = &self.progress {
+            if done / progress.every > before / progress.every || done == total {
+                (progress.callback)(what, done, total);
+            }
+        }
+    }
+
+    pub fn with_query_hashes(mut self, query_hashes: HashMap<String, String>) -> Client {
+        self.query_hashes = Arc::new(query_hashes);
+        self
+    }
+
+    /// Use the configured persisted query hash of the operation, if there is one
+    fn override_hash(&self, operation_name: &str, extensions: &mut serde_json::Value) {
+        if let Some(hash) = self.query_hashes.get(operation_name) {
+            extensions["persistedQuery"]["sha256Hash"] = json!(hash);
+        }
+    }
+
+    fn request(&self, mut req: GqlRequest) -> GqlRequest {
+        self.override_hash(&req.operation_name.name(), &mut req.extensions);
+        req
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Client {
+        self.chunk_size = chunk_size.max(1);
+        self
     }
 
     fn gql_req(&self) -> reqwest::RequestBuilder {
-        let client = reqwest::Client::new();
-        client
+        self.http
             .post(&self.url)
             .header("Client-Id", CLIENT_ID)
             .header("User-Agent", USER_AGENT)
             .header("X-Device-Id", DEVICE_ID)
-            .header("Authorization", &format!("OAuth {}", self.access_token))
+            .header(
+                "Authorization",
+                &format!("OAuth {}", self.access_token.read().unwrap()),
+            )
+    }
+
+    /// Send the requests in chunks, results are returned in the order of the requests
+    async fn gql_batch(
+        &self,
+        reqs: &[GqlRequest],
+        what: &str,
+        error: &str,
+    ) -> Result<Vec<serde_json::Value>> {
+        let mut requests = Vec::new();
+        for (idx, chunk) in reqs.chunks(self.chunk_size).enumerate() {
+            requests.push(self.gql_chunk(idx, chunk, error));
+        }
+        let mut responses = stream::iter(requests).buffer_unordered(self.concurrency);
+
+        let mut chunks = Vec::new();
+        let mut done = 0;
+        while let Some((idx, res)) = responses.next().await {
+            let items = res?;
+            let before = done;
+            done += items.len();
+            self.report_progress(what, before, done, reqs.len());
+            chunks.push((idx, items));
+        }
+
+        chunks.sort_by_key(|x| x.0);
+        Ok(chunks.into_iter().flat_map(|x| x.1).collect())
+    }
+
+    /// Like `gql_batch`, but a failed chunk only fails the requests in it
+    async fn gql_batch_each(
+        &self,
+        reqs: &[GqlRequest],
+        what: &str,
+        error: &str,
+    ) -> Vec<Result<serde_json::Value>> {
+        let mut requests = Vec::new();
+        for (idx, chunk) in reqs.chunks(self.chunk_size).enumerate() {
+            requests.push(self.gql_chunk(idx, chunk, error));
+        }
+        let mut responses = stream::iter(requests).buffer_unordered(self.concurrency);
+
+        let mut chunks = Vec::new();
+        let mut done = 0;
+        while let Some((idx, res)) = responses.next().await {
+            let len = reqs[idx * self.chunk_size..].len().min(self.chunk_size);
+            let items = match res {
+                Ok(items) if items.len() == len => items.into_iter().map(Ok).collect(),
+                Ok(_) => (0..len)
+                    .map(|_| Err(eyre!("{error}, expected {len} results")))
+                    .collect(),
+                Err(err) => (0..len).map(|_| Err(eyre!("{err:#}"))).collect::<Vec<_>>(),
+            };
+            let before = done;
+            done += len;
+            self.report_progress(what, before, done, reqs.len());
+            chunks.push((idx, items));
+        }
+
+        chunks.sort_by_key(|x| x.0);
+        chunks.into_iter().flat_map(|x| x.1).collect()
+    }
+
+    /// Send a single chunk, returns the index of the chunk along with the results
+    async fn gql_chunk(
+        &self,
+        idx: usize,
+        chunk: &[GqlRequest],
+        error: &str,
+    ) -> (usize, Result<Vec<serde_json::Value>>) {
+        (idx, self.send_chunk(chunk, error).await)
+    }
+
+    async fn send_chunk(
+        &self,
+        chunk: &[GqlRequest],
+        error: &str,
+    ) -> Result<Vec<serde_json::Value>> {
+        let res = self.gql_req().json(chunk).send().await?;
+        if !res.status().is_success() {
+            return Err(eyre!("{error}"));
+        }
+
+        match res.json().await? {
+            serde_json::Value::Array(arr) => {
+                for (req, item) in chunk.iter().zip(&arr) {
+                    check_persisted_query(&req.operation_name.name(), item)?;
+                }
+                Ok(arr)
+            }
+            _ => Err(eyre!("{error}, expected array as response")),
+        }
     }
 
     pub async fn streamer_metadata(
@@ -96,15 +336,12 @@ impl Client {
     ) -> Result<Vec<Option<(UserId, StreamerInfo)>>> {
         let users = channels
             .iter()
-            .map(|user| GqlRequest::stream_metadata(user))
+            .map(|user| self.request(GqlRequest::stream_metadata(user)))
             .collect::<Vec<_>>();
 
-        let items: serde_json::Value = self.gql_req().json(&users).send().await?.json().await?;
-        if !items.is_array() {
-            return Err(eyre!("Failed to get streamer metadata"));
-        }
-
-        let items = items.as_array().unwrap().clone();
+        let items = self
+            .gql_batch(&users, "streamers", "Failed to get streamer metadata")
+            .await?;
         let items = items
             .into_iter()
             .zip(channels)
@@ -136,7 +373,7 @@ impl Client {
             return Ok(());
         }
 
-        let pred = GqlRequest::make_prediction(event_id, outcome_id, points);
+        let pred = self.request(GqlRequest::make_prediction(event_id, outcome_id, points));
         let res = self.gql_req().json(&pred).send().await?;
 
         if !res.status().is_success() {
@@ -144,8 +381,15 @@ impl Client {
         }
 
         let mut res = res.json().await?;
+        check_persisted_query("MakePrediction", &res)?;
         let res = traverse_json(&mut res, ".data.makePrediction.error").unwrap();
         if !res.is_null() {
+            if let Some(cooldown) = PredictionCooldown::from_error(res) {
+                return Err(cooldown.into());
+            }
+            if let Some(subscriber_only) = SubscriberOnly::from_error(res) {
+                return Err(subscriber_only.into());
+            }
             return Err(eyre!("Failed to make prediction: {:#?}", res));
         }
         Ok(())
@@ -158,58 +402,60 @@ impl Client {
     ) -> Result<Vec<(u32, Option<String>)>> {
         let reqs = channel_names
             .iter()
-            .map(|name| GqlRequest::channel_points_context(name))
+            .map(|name| self.request(GqlRequest::channel_points_context(name)))
             .collect::<Vec<_>>();
 
-        let res = self.gql_req().json(&reqs).send().await?;
-        if !res.status().is_success() {
-            return Err(eyre!("Failed to get channel points"));
-        }
-
-        let json: serde_json::Value = res.json().await?;
-        if !json.is_array() {
-            return Err(eyre!(
-                "Failed to get channel points, expected array as response"
-            ));
-        }
-
-        let arr = json.as_array().unwrap().clone();
-        let items = arr
-            .into_iter()
-            .map(|mut result| {
+        let arr = self
+            .gql_batch(&reqs, "channel points", "Failed to get channel points")
+            .await?;
+        arr.into_iter()
+            .zip(channel_names)
+            .map(|(mut result, name)| {
+                // deleted or banned channels have no community
                 let balance = traverse_json(
                     &mut result,
                     ".data.community.channel.self.communityPoints.balance",
                 )
-                .unwrap()
-                .as_u64()
-                .unwrap() as u32;
+                .and_then(|x| x.as_u64())
+                .ok_or(eyre!("No channel points for {name}"))? as u32;
                 let available_claim = traverse_json(
                     &mut result,
                     ".data.community.channel.self.communityPoints.availableClaim.id",
                 )
                 .map(|x| x.as_str().unwrap().to_owned());
 
-                (balance, available_claim)
+                Ok((balance, available_claim))
             })
-            .collect();
+            .collect()
+    }
 
-        Ok(items)
+    /// Send an empty request, succeeding on any response from the GQL server regardless of the token
+    pub async fn ping(&self) -> Result<()> {
+        self.gql_req().json(&json!([])).send().await?;
+        Ok(())
     }
 
     /// (UserID, UserName)
     pub async fn get_user_id(&self) -> Result<(String, String)> {
-        let mut data = self.gql_req()
+        let mut extensions = json!({
+            "persistedQuery": {
+                "version": 1,
+                "sha256Hash": "6b5b63a013cf66a995d61f71a508ab5c8e4473350c5d4136f846ba65e8101e95"
+            }
+        });
+        self.override_hash("CoreActionsCurrentUser", &mut extensions);
+        let mut data = self
+            .gql_req()
             .json(&json!({
                 "operationName": "CoreActionsCurrentUser",
                 "variables": {},
-                "extensions": {
-                    "persistedQuery": {
-                        "version": 1,
-                        "sha256Hash": "6b5b63a013cf66a995d61f71a508ab5c8e4473350c5d4136f846ba65e8101e95"
-                    }
-                }
-            })).send().await?.json().await?;
+                "extensions": extensions
+            }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        check_persisted_query("CoreActionsCurrentUser", &data)?;
 
         let user_id = traverse_json(&mut data, ".data.currentUser.id")
             .map(|x| x.as_str().unwrap().to_owned())
@@ -222,7 +468,7 @@ impl Client {
     }
 
     pub async fn claim_points(&self, channel_id: &str, claim_id: &str) -> Result<u32> {
-        let claim = GqlRequest::claim_community_points(claim_id, channel_id);
+        let claim = self.request(GqlRequest::claim_community_points(claim_id, channel_id));
         let res = self.gql_req().json(&claim).send().await?;
 
         if !res.status().is_success() {
@@ -230,6 +476,7 @@ impl Client {
         }
 
         let mut res = res.json().await?;
+        check_persisted_query("ClaimCommunityPoints", &res)?;
         let current_points = traverse_json(&mut res, ".data.claimCommunityPoints.currentPoints")
             .unwrap()
             .as_u64()
@@ -238,48 +485,63 @@ impl Client {
         Ok(current_points as u32)
     }
 
+    /// Claim multiple bonuses in batched requests, takes (Channel ID, Claim ID). A failed chunk only fails the
+    /// claims in it, results are in the order of the claims
+    pub async fn claim_points_batched(&self, claims: &[(&str, &str)]) -> Vec<Result<u32>> {
+        let reqs = claims
+            .iter()
+            .map(|(channel_id, claim_id)| {
+                self.request(GqlRequest::claim_community_points(claim_id, channel_id))
+            })
+            .collect::<Vec<_>>();
+        self.gql_batch_each(&reqs, "claims", "Failed to claim points")
+            .await
+            .into_iter()
+            .map(|x| {
+                x.and_then(|mut x| {
+                    traverse_json(&mut x, ".data.claimCommunityPoints.currentPoints")
+                        .and_then(|x| x.as_u64())
+                        .map(|x| x as u32)
+                        .ok_or(eyre!("Failed to claim points"))
+                })
+            })
+            .collect()
+    }
+
     pub async fn channel_points_context(
         &self,
         channel_names: &[&str],
     ) -> Result<Vec<Vec<(pubsub::predictions::Event, bool)>>> {
         let request = channel_names
             .iter()
-            .map(|x| GqlRequest::channel_points_prediction_context(x))
+            .map(|x| self.request(GqlRequest::channel_points_prediction_context(x)))
             .collect::<Vec<_>>();
-        let res = self.gql_req().json(&request).send().await?;
-        if !res.status().is_success() {
-            return Err(eyre!("Failed to claim points"));
-        }
-
-        let res: Vec<serde_json::Value> = res.json().await?;
+        let res = self
+            .gql_batch(
+                &request,
+                "predictions",
+                "Failed to get channel points context",
+            )
+            .await?;
         let active_predictions = res
             .into_iter()
-            .filter_map(|mut x| {
+            .zip(channel_names)
+            .map(|(mut x, channel_name)| {
                 let channel_id = traverse_json(&mut x, ".data.community.channel.id")
-                    .unwrap()
-                    .clone();
+                    .and_then(|x| x.as_str())
+                    .unwrap_or_default()
+                    .to_owned();
                 let mut v = traverse_json(&mut x, ".data.community.channel.activePredictionEvents")
                     .unwrap()
                     .clone();
                 super::camel_to_snake_case_json(&mut v);
 
-                for item in v.as_array_mut().unwrap() {
-                    item.as_object_mut()
-                        .unwrap()
-                        .insert("channel_id".to_owned(), channel_id.clone());
-                    for outcome in traverse_json(item, ".outcomes")
-                        .unwrap()
-                        .as_array_mut()
-                        .unwrap()
-                    {
-                        let x = outcome.as_object_mut().unwrap();
-                        *x.get_mut("top_predictors").unwrap() =
-                            serde_json::Value::Array(Vec::new());
-                    }
-                }
-
-                match serde_json::from_value::<Vec<pubsub::predictions::Event>>(v) {
+                match serde_json::from_value::<Vec<ContextEvent>>(v) {
                     Ok(s) => {
+                        let s = s
+                            .into_iter()
+                            .map(|x| x.into_event(&channel_id))
+                            .collect::<Vec<_>>();
                         match traverse_json(
                             &mut x,
                             ".data.community.channel.self.recentPredictions",
@@ -306,12 +568,15 @@ impl Client {
                                         (x, bet_placed)
                                     })
                                     .collect();
-                                Some(items)
+                                items
                             }
-                            None => Some(s.into_iter().map(|x| (x, false)).collect()),
+                            None => s.into_iter().map(|x| (x, false)).collect(),
                         }
                     }
-                    Err(_) => None,
+                    Err(err) => {
+                        warn!("Dropping active predictions of {channel_name}, could not parse them: {err}");
+                        Vec::new()
+                    }
                 }
             })
             .collect::<Vec<_>>();
@@ -319,14 +584,177 @@ impl Client {
     }
 
     pub async fn join_raid(&self, raid_id: &str) -> Result<()> {
-        let claim = GqlRequest::join_raid(raid_id);
+        let claim = self.request(GqlRequest::join_raid(raid_id));
         let res = self.gql_req().json(&claim).send().await?;
 
         if !res.status().is_success() {
             return Err(eyre!("Failed to join raid"));
         }
+        if let Ok(res) = res.json().await {
+            check_persisted_query("JoinRaid", &res)?;
+        }
         Ok(())
     }
+
+    /// If each channel has a drop campaign that can be progressed by watching it, takes channel IDs
+    pub async fn available_drops(&self, channel_ids: &[&str]) -> Result<Vec<bool>> {
+        let reqs = channel_ids
+            .iter()
+            .map(|x| self.request(GqlRequest::available_drops(x)))
+            .collect::<Vec<_>>();
+        let res = self
+            .gql_batch(&reqs, "drop campaigns", "Failed to get drop campaigns")
+            .await?;
+        Ok(res
+            .into_iter()
+            .map(|mut x| {
+                traverse_json(&mut x, ".data.channel.viewerDropCampaigns")
+                    .and_then(|x| x.as_array())
+                    .is_some_and(|x| !x.is_empty())
+            })
+            .collect())
+    }
+
+    /// Drops of the campaigns in progress that have enough watch time and are not claimed yet
+    pub async fn claimable_drops(&self) -> Result<Vec<Drop>> {
+        let req = self.request(GqlRequest::inventory());
+        let res = self.gql_req().json(&req).send().await?;
+        if !res.status().is_success() {
+            return Err(eyre!("Failed to get drops inventory"));
+        }
+
+        let mut res = res.json().await?;
+        check_persisted_query("Inventory", &res)?;
+        let campaigns = match traverse_json(
+            &mut res,
+            ".data.currentUser.inventory.dropCampaignsInProgress",
+        ) {
+            Some(serde_json::Value::Null) | None => return Ok(Vec::new()),
+            Some(x) => serde_json::from_value::<Vec<DropCampaign>>(x.clone())?,
+        };
+        Ok(campaigns
+            .into_iter()
+            .flat_map(|x| x.time_based_drops)
+            .filter_map(|x| match x.progress {
+                Some(DropProgress {
+                    current_minutes_watched,
+                    is_claimed: false,
+                    drop_instance_id: Some(id),
+                }) if current_minutes_watched >= x.required_minutes_watched => Some(Drop {
+                    drop_instance_id: id,
+                    name: x.name,
+                }),
+                _ => None,
+            })
+            .collect())
+    }
+
+    pub async fn claim_drop(&self, drop_instance_id: &str) -> Result<()> {
+        let req = self.request(GqlRequest::claim_drop_rewards(drop_instance_id));
+        let res = self.gql_req().json(&req).send().await?;
+        if !res.status().is_success() {
+            return Err(eyre!("Failed to claim drop"));
+        }
+
+        let mut res = res.json().await?;
+        check_persisted_query("DropsPage_ClaimDropRewards", &res)?;
+        match traverse_json(&mut res, ".data.claimDropRewards.status") {
+            Some(serde_json::Value::String(_)) => Ok(()),
+            _ => Err(eyre!("Failed to claim drop {drop_instance_id}")),
+        }
+    }
+}
+
+/// Twitch no longer accepts the persisted query hash of an operation, it has to be updated
+#[derive(Debug)]
+pub struct PersistedQueryNotFound {
+    pub operation_name: String,
+}
+
+impl std::fmt::Display for PersistedQueryNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Persisted query hash of {} is stale, set the current hash under gql_query_hashes in the config or update the miner",
+            self.operation_name
+        )
+    }
+}
+
+impl std::error::Error for PersistedQueryNotFound {}
+
+/// Twitch rejected a bet because the account has to wait before predicting again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PredictionCooldown {
+    /// Seconds left on the cooldown, if twitch said
+    pub seconds: Option<u64>,
+}
+
+impl PredictionCooldown {
+    fn from_error(error: &serde_json::Value) -> Option<Self> {
+        let code = error["code"].as_str()?;
+        if !(code.contains("COOLDOWN") || code.contains("RATE_LIMIT")) {
+            return None;
+        }
+        Some(Self {
+            seconds: error["cooldownSeconds"]
+                .as_u64()
+                .or_else(|| error["retryAfterSeconds"].as_u64()),
+        })
+    }
+}
+
+impl std::fmt::Display for PredictionCooldown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.seconds {
+            Some(seconds) => write!(f, "Prediction cooldown, {seconds}s left"),
+            None => write!(f, "Prediction cooldown"),
+        }
+    }
+}
+
+impl std::error::Error for PredictionCooldown {}
+
+/// Twitch rejected a bet because the prediction is restricted to subscribers of the channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriberOnly {
+    pub code: String,
+}
+
+impl SubscriberOnly {
+    fn from_error(error: &serde_json::Value) -> Option<Self> {
+        let code = error["code"].as_str()?;
+        if !(code.contains("SUBSCRIBER") || code.contains("SUB_ONLY")) {
+            return None;
+        }
+        Some(Self {
+            code: code.to_owned(),
+        })
+    }
+}
+
+impl std::fmt::Display for SubscriberOnly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Prediction is restricted to subscribers, {}", self.code)
+    }
+}
+
+impl std::error::Error for SubscriberOnly {}
+
+fn check_persisted_query(operation_name: &str, res: &serde_json::Value) -> Result<()> {
+    let not_found = res["errors"].as_array().is_some_and(|errors| {
+        errors
+            .iter()
+            .any(|x| x["message"] == "PersistedQueryNotFound")
+    });
+    if not_found {
+        let err = PersistedQueryNotFound {
+            operation_name: operation_name.to_owned(),
+        };
+        error!("{err}");
+        return Err(err.into());
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -360,52 +788,167 @@ pub struct ChanelLogin {
     pub channel_login: String,
 }
 
+/// Active prediction as returned by the channel points context, fields that are not needed are
+/// ignored so additions by twitch do not break parsing
+#[derive(Debug, Deserialize)]
+struct ContextEvent {
+    id: String,
+    created_at: Timestamp,
+    #[serde(default)]
+    ended_at: Option<Timestamp>,
+    #[serde(default)]
+    locked_at: Option<Timestamp>,
+    outcomes: Vec<ContextOutcome>,
+    prediction_window_seconds: i64,
+    status: String,
+    title: String,
+    #[serde(default)]
+    winning_outcome_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContextOutcome {
+    id: String,
+    #[serde(default)]
+    color: String,
+    title: String,
+    total_points: i64,
+    total_users: i64,
+}
+
+impl ContextEvent {
+    fn into_event(self, channel_id: &str) -> pubsub::predictions::Event {
+        pubsub::predictions::Event {
+            id: self.id,
+            channel_id: channel_id.to_owned(),
+            created_at: self.created_at,
+            ended_at: self.ended_at,
+            locked_at: self.locked_at,
+            outcomes: self
+                .outcomes
+                .into_iter()
+                .map(|x| pubsub::predictions::Outcome {
+                    id: x.id,
+                    color: x.color,
+                    title: x.title,
+                    total_points: x.total_points,
+                    total_users: x.total_users,
+                    top_predictors: Vec::new(),
+                })
+                .collect(),
+            prediction_window_seconds: self.prediction_window_seconds,
+            status: self.status,
+            title: self.title,
+            winning_outcome_id: self.winning_outcome_id,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MakePrediction {
     #[serde(rename = "input")]
-    input: MakePredictionInput,
+    pub input: MakePredictionInput,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MakePredictionInput {
     #[serde(rename = "eventID")]
-    event_id: String,
+    pub event_id: String,
     #[serde(rename = "outcomeID")]
-    outcome_id: String,
-    points: u32,
+    pub outcome_id: String,
+    pub points: u32,
     #[serde(rename = "transactionID")]
     transaction_id: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaimCommunityPoints {
-    input: ClaimCommunityPointsInput,
+    pub input: ClaimCommunityPointsInput,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaimCommunityPointsInput {
     #[serde(rename = "claimID")]
-    claim_id: String,
+    pub claim_id: String,
     #[serde(rename = "channelID")]
-    channel_id: String,
+    pub channel_id: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelPointsPredictionContext {
     count: u8,
     #[serde(rename = "channelLogin")]
-    channel_login: String,
+    pub channel_login: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JoinRaid {
-    input: JoinRaidInput,
+    pub input: JoinRaidInput,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JoinRaidInput {
     #[serde(rename = "raidID")]
-    raid_id: String,
+    pub raid_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvailableDrops {
+    #[serde(rename = "channelID")]
+    pub channel_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Inventory {
+    #[serde(rename = "fetchRewardCampaigns")]
+    fetch_reward_campaigns: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimDropRewards {
+    pub input: ClaimDropRewardsInput,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimDropRewardsInput {
+    #[serde(rename = "dropInstanceID")]
+    pub drop_instance_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoreActionsCurrentUser {}
+
+/// Drop that has been watched long enough to be claimed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Drop {
+    pub drop_instance_id: String,
+    pub name: String,
+}
+
+/// Drop campaign in the inventory, fields that are not needed are ignored
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DropCampaign {
+    #[serde(default)]
+    time_based_drops: Vec<TimeBasedDrop>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TimeBasedDrop {
+    name: String,
+    required_minutes_watched: u32,
+    #[serde(rename = "self")]
+    progress: Option<DropProgress>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DropProgress {
+    current_minutes_watched: u32,
+    is_claimed: bool,
+    #[serde(rename = "dropInstanceID")]
+    drop_instance_id: Option<String>,
 }
 
 impl GqlRequest {
@@ -509,4 +1052,313 @@ impl GqlRequest {
             }),
         }
     }
+
+    fn available_drops(channel_id: &str) -> Self {
+        Self {
+            operation_name: OperationName::AvailableDrops,
+            extensions: json!({
+                "persistedQuery": {
+                    "version": 1,
+                    "sha256Hash": "9a62a09bce5b53e26e64a671e530bc599cb6aab1e5ba3cbd5d85966d3940716f",
+                }
+            }),
+            variables: Variables::AvailableDrops(AvailableDrops {
+                channel_id: channel_id.to_owned(),
+            }),
+        }
+    }
+
+    fn inventory() -> Self {
+        Self {
+            operation_name: OperationName::Inventory,
+            extensions: json!({
+                "persistedQuery": {
+                    "version": 1,
+                    "sha256Hash": "37fea486d6179047c41d0f549088a4c3a7dd60c05c70956a1490262f532dccd9",
+                }
+            }),
+            variables: Variables::Inventory(Inventory {
+                fetch_reward_campaigns: false,
+            }),
+        }
+    }
+
+    fn claim_drop_rewards(drop_instance_id: &str) -> Self {
+        Self {
+            operation_name: OperationName::ClaimDropRewards,
+            extensions: json!({
+                "persistedQuery": {
+                    "version": 1,
+                    "sha256Hash": "a455deea71bdc9015b78eb49f4acfbce8baa7ccbedd28e549bb025bd0f751930",
+                }
+            }),
+            variables: Variables::ClaimDropRewards(ClaimDropRewards {
+                input: ClaimDropRewardsInput {
+                    drop_instance_id: drop_instance_id.to_owned(),
+                },
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use eyre::Result;
+    use rstest::rstest;
+
+    use super::{Client, PersistedQueryNotFound, PredictionCooldown};
+    use crate::testing::{container, TestContainer};
+
+    /// Answer a single request with `response`, returning the request head and body
+    async fn capture_request(
+        response: &'static str,
+    ) -> Result<(String, tokio::task::JoinHandle<Result<(String, String)>>)> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/gql", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await?;
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            let (head, body) = loop {
+                let n = socket.read(&mut buf).await?;
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|x| {
+                            x.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|x| x.parse::<usize>())
+                        })
+                        .unwrap_or(Ok(0))?;
+                    if body.len() >= length {
+                        break (head.to_owned(), body.to_owned());
+                    }
+                }
+            };
+            socket
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{response}",
+                        response.len()
+                    )
+                    .as_bytes(),
+                )
+                .await?;
+            Ok((head, body))
+        });
+        Ok((url, server))
+    }
+
+    #[tokio::test]
+    async fn stale_query_hash_is_reported() -> Result<()> {
+        let (url, server) =
+            capture_request(r#"{"errors":[{"message":"PersistedQueryNotFound"}],"extensions":{}}"#)
+                .await?;
+
+        let client = Client::new(String::new(), url);
+        let err = client
+            .make_prediction(10, "event", "outcome", false)
+            .await
+            .unwrap_err();
+        server.await??;
+
+        let err = err.downcast_ref::<PersistedQueryNotFound>().unwrap();
+        assert_eq!(err.operation_name, "MakePrediction");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn prediction_cooldown_is_reported() -> Result<()> {
+        let (url, server) = capture_request(
+            r#"{"data":{"makePrediction":{"error":{"code":"USER_PREDICTION_COOLDOWN","cooldownSeconds":90}}},"extensions":{}}"#,
+        )
+        .await?;
+
+        let client = Client::new(String::new(), url);
+        let err = client
+            .make_prediction(10, "event", "outcome", false)
+            .await
+            .unwrap_err();
+        server.await??;
+
+        assert_eq!(
+            err.downcast_ref::<PredictionCooldown>(),
+            Some(&PredictionCooldown { seconds: Some(90) })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn prediction_with_unknown_fields() -> Result<()> {
+        let (url, server) = capture_request(
+            r#"[{"data":{"community":{"channel":{"id":"1","activePredictionEvents":[{"id":"event-1","createdAt":"2024-06-01T10:00:00Z","createdBy":{"__typename":"User","id":"2"},"endedAt":null,"lockedAt":null,"outcomes":[{"id":"o1","color":"BLUE","title":"Yes","totalPoints":100,"totalUsers":2,"topPredictors":[{"user":{"id":"3"},"points":50}],"badge":{"version":"blue-1"}},{"id":"o2","color":"PINK","title":"No","totalPoints":50,"totalUsers":1,"topPredictors":[]}],"predictionWindowSeconds":120,"status":"ACTIVE","title":"Win?","winningOutcome":null,"__typename":"Event"}],"self":{"recentPredictions":[{"event":{"id":"event-1"}}]}}}}}]"#,
+        )
+        .await?;
+
+        let client = Client::new(String::new(), url);
+        let res = client.channel_points_context(&["a"]).await?;
+        server.await??;
+
+        assert_eq!(res.len(), 1);
+        let (event, bet_placed) = &res[0][0];
+        assert!(bet_placed);
+        assert_eq!(event.id, "event-1");
+        assert_eq!(event.channel_id, "1");
+        assert_eq!(event.outcomes.len(), 2);
+        assert_eq!(event.outcomes[0].total_points, 100);
+        assert!(event.outcomes[0].top_predictors.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn overridden_query_hash_is_sent() -> Result<()> {
+        let (url, server) =
+            capture_request(r#"{"data":{"makePrediction":{"error":null}}}"#).await?;
+
+        let client = Client::new(String::new(), url).with_query_hashes(HashMap::from([(
+            "MakePrediction".to_owned(),
+            "rotated-hash".to_owned(),
+        )]));
+        client
+            .make_prediction(10, "event", "outcome", false)
+            .await?;
+
+        let (_, body) = server.await??;
+        let body: serde_json::Value = serde_json::from_str(&body)?;
+        assert_eq!(body["operationName"], "MakePrediction");
+        assert_eq!(
+            body["extensions"]["persistedQuery"]["sha256Hash"],
+            "rotated-hash"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reloaded_token_is_used() -> Result<()> {
+        let (url, server) =
+            capture_request(r#"{"data":{"makePrediction":{"error":null}}}"#).await?;
+
+        let client = Client::new("old-token".to_owned(), url);
+        let clone = client.clone();
+        client.set_access_token("new-token".to_owned());
+        clone.make_prediction(10, "event", "outcome", false).await?;
+
+        let (head, _) = server.await??;
+        let head = head.to_lowercase();
+        assert!(head.contains("authorization: oauth new-token"));
+        assert!(!head.contains("old-token"));
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn chunked_requests_keep_order(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let client = reqwest::Client::new();
+        let channel_points = (0..100)
+            .map(|i| (i.to_string(), (format!("c{i}"), i, None::<String>)))
+            .collect::<HashMap<_, _>>();
+        client
+            .post(format!("{base_url}/channel_points"))
+            .json(&channel_points)
+            .send()
+            .await?;
+        client
+            .delete(format!("{base_url}/gql_requests"))
+            .send()
+            .await?;
+
+        let names = (0..100).map(|i| format!("c{i}")).collect::<Vec<_>>();
+        let names = names.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+        let gql = Client::new(String::new(), format!("{base_url}/gql")).with_chunk_size(30);
+        let res = gql.get_channel_points(&names).await?;
+
+        assert_eq!(
+            res.into_iter().map(|x| x.0).collect::<Vec<_>>(),
+            (0..100).collect::<Vec<_>>()
+        );
+        let requests: u32 = client
+            .get(format!("{base_url}/gql_requests"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(requests, 4);
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn progress_is_reported(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let channel_points = (0..340)
+            .map(|i| (i.to_string(), (format!("c{i}"), i, None::<String>)))
+            .collect::<HashMap<_, _>>();
+        reqwest::Client::new()
+            .post(format!("{base_url}/channel_points"))
+            .json(&channel_points)
+            .send()
+            .await?;
+
+        let names = (0..340).map(|i| format!("c{i}")).collect::<Vec<_>>();
+        let names = names.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let gql = Client::new(String::new(), format!("{base_url}/gql"))
+            .with_chunk_size(30)
+            .with_concurrency(3)
+            .with_progress(100, {
+                let reports = reports.clone();
+                Arc::new(move |what, done, total| {
+                    assert_eq!(what, "channel points");
+                    reports.lock().unwrap().push((done, total));
+                })
+            });
+        let res = gql.get_channel_points(&names).await?;
+        assert_eq!(
+            res.into_iter().map(|x| x.0).collect::<Vec<_>>(),
+            (0..340).collect::<Vec<_>>()
+        );
+
+        // once for each 100 resolved, and once when done
+        let reports = reports.lock().unwrap().clone();
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(|x| x.1 == 340));
+        assert!(reports.windows(2).all(|x| x[0].0 < x[1].0));
+        assert_eq!(reports.last(), Some(&(340, 340)));
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn join_raid(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let base_url = format!("http://localhost:{}", container.port);
+
+        let gql = Client::new(String::new(), format!("{base_url}/gql"));
+        gql.join_raid("raid-join-1").await?;
+
+        let joined: Vec<String> = reqwest::get(format!("{base_url}/raids_joined"))
+            .await?
+            .json()
+            .await?;
+        assert!(joined.contains(&"raid-join-1".to_owned()));
+        Ok(())
+    }
 }
@@ -1,6 +1,9 @@
 pub mod api;
 pub mod auth;
 pub mod gql;
+pub mod onsite;
+pub mod proxy;
+pub mod user_predictions;
 pub mod ws;
 
 const CLIENT_ID: &str = "ue6666qo983tsx6so1t0vnawi233wa";
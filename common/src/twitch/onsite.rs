@@ -0,0 +1,185 @@
+use eyre::{Context, Result};
+use flume::Sender;
+use serde::Deserialize;
+use tokio::sync::watch;
+
+use super::{proxy::Proxy, ws};
+
+/// Onsite notifications topic of a user, not modelled by `twitch_api`
+pub fn topic(user_id: &str) -> String {
+    format!("onsite-notifications.{user_id}")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OnsiteNotificationsReply {
+    CreateNotification {
+        notification: Notification,
+    },
+    /// Notifications being read, deleted or summarized
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Notification {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub notification_type: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub actions: Vec<NotificationAction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct NotificationAction {
+    #[serde(default)]
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotificationKind {
+    Prediction,
+    Raid,
+    Other,
+}
+
+impl Notification {
+    pub fn kind(&self) -> NotificationKind {
+        let notification_type = self.notification_type.to_lowercase();
+        if notification_type.contains("prediction") {
+            NotificationKind::Prediction
+        } else if notification_type.contains("raid") {
+            NotificationKind::Raid
+        } else {
+            NotificationKind::Other
+        }
+    }
+
+    /// Login of the channel the notification links to
+    pub fn channel_login(&self) -> Option<String> {
+        self.actions.iter().find_map(|x| {
+            let url = reqwest::Url::parse(&x.url).ok()?;
+            if !url.host_str()?.ends_with("twitch.tv") {
+                return None;
+            }
+            url.path_segments()?
+                .next()
+                .filter(|x| !x.is_empty())
+                .map(|x| x.to_lowercase())
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct Frame {
+    #[serde(rename = "type")]
+    frame_type: String,
+    data: Option<FrameData>,
+}
+
+#[derive(Deserialize)]
+struct FrameData {
+    topic: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    #[serde(rename = "type")]
+    reply_type: String,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct CreateNotification {
+    notification: Notification,
+}
+
+/// Parse a raw pubsub message, `None` if it is not an onsite notification
+pub fn parse(raw: &str) -> Option<Result<OnsiteNotificationsReply>> {
+    let frame: Frame = serde_json::from_str(raw).ok()?;
+    let data = frame.data?;
+    if frame.frame_type != "MESSAGE" || !data.topic.starts_with("onsite-notifications.") {
+        return None;
+    }
+    Some(parse_reply(&data.message).context("Parse onsite notification"))
+}
+
+fn parse_reply(message: &str) -> Result<OnsiteNotificationsReply> {
+    let reply: Reply = serde_json::from_str(message)?;
+    match reply.reply_type.as_str() {
+        "create-notification" => {
+            let data: CreateNotification = serde_json::from_value(reply.data)?;
+            Ok(OnsiteNotificationsReply::CreateNotification {
+                notification: data.notification,
+            })
+        }
+        _ => Ok(OnsiteNotificationsReply::Other),
+    }
+}
+
+/// Listen to the onsite notifications of the user on a connection of its own, reconnecting
+/// whenever it drops. Returns once the receiver is dropped
+pub async fn listen(
+    url: String,
+    access_token: watch::Receiver<String>,
+    user_id: String,
+    proxy: Option<Proxy>,
+    tx: Sender<Notification>,
+) {
+    ws::listen_topic(
+        "onsite notifications",
+        ws::TopicConnection {
+            url,
+            access_token,
+            topic: topic(&user_id),
+            proxy,
+        },
+        tx,
+        |m| {
+            parse(m).map(|reply| {
+                reply.map(|reply| match reply {
+                    OnsiteNotificationsReply::CreateNotification { notification } => {
+                        Some(notification)
+                    }
+                    OnsiteNotificationsReply::Other => None,
+                })
+            })
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, NotificationKind, OnsiteNotificationsReply};
+
+    /// Onsite notification of a prediction starting on channel a
+    const PREDICTION_CREATED: &str = r#"{"type":"MESSAGE","data":{"topic":"onsite-notifications.1234","message":"{\"type\":\"create-notification\",\"data\":{\"notification\":{\"id\":\"notif-1\",\"user_id\":\"1234\",\"type\":\"prediction_created\",\"body\":\"A prediction started on a's channel\",\"body_md\":\"\",\"render_style\":\"DEFAULT\",\"thumbnail_url\":\"\",\"actions\":[{\"id\":\"click\",\"type\":\"click\",\"url\":\"https://www.twitch.tv/A?referrer=onsite\",\"modal_id\":\"\",\"body\":\"\",\"label\":\"\"}],\"create_at\":\"2024-06-01T10:00:00Z\",\"updated_at\":\"2024-06-01T10:00:00Z\",\"read\":false,\"display_type\":\"VIEWER\",\"category\":\"Recommended\"},\"persistent\":true,\"toast\":true}}"}}"#;
+
+    #[test]
+    fn parse_prediction_notification() {
+        let reply = parse(PREDICTION_CREATED).unwrap().unwrap();
+        let notification = match reply {
+            OnsiteNotificationsReply::CreateNotification { notification } => notification,
+            OnsiteNotificationsReply::Other => panic!("Expected a notification"),
+        };
+        assert_eq!(notification.id, "notif-1");
+        assert_eq!(notification.kind(), NotificationKind::Prediction);
+        assert_eq!(notification.channel_login().as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn other_messages_are_ignored() {
+        let read = r#"{"type":"MESSAGE","data":{"topic":"onsite-notifications.1234","message":"{\"type\":\"read-notifications\",\"data\":{\"notification_ids\":[\"notif-1\"]}}"}}"#;
+        assert_eq!(
+            parse(read).unwrap().unwrap(),
+            OnsiteNotificationsReply::Other
+        );
+
+        let raid = r#"{"type":"MESSAGE","data":{"topic":"raid.1","message":"{}"}}"#;
+        assert!(parse(raid).is_none());
+        assert!(parse(r#"{"type":"PONG"}"#).is_none());
+    }
+}
@@ -0,0 +1,183 @@
+use eyre::{Context, Result};
+use flume::Sender;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use super::{proxy::Proxy, ws};
+
+/// Predictions topic of a user, not modelled by `twitch_api`
+pub fn topic(user_id: &str) -> String {
+    format!("predictions-user-v1.{user_id}")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PredictionsUserReply {
+    /// A bet of the user landed, sent again with the new total when it is added to
+    PredictionMade {
+        prediction: UserPrediction,
+    },
+    /// A prediction the user bet on ended
+    PredictionResult {
+        prediction: UserPrediction,
+    },
+    Other,
+}
+
+/// The user's bet on a prediction, as twitch recorded it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UserPrediction {
+    pub event_id: String,
+    pub outcome_id: String,
+    pub channel_id: String,
+    /// Total points bet on the prediction
+    pub points: u32,
+    pub result: Option<BetResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub struct BetResult {
+    #[serde(rename = "type")]
+    pub result_type: BetResultType,
+    /// Points paid out, including the bet itself
+    #[serde(default)]
+    pub points_won: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub enum BetResultType {
+    Win,
+    Lose,
+    /// The prediction was cancelled and the bet returned
+    Refund,
+}
+
+#[derive(Deserialize)]
+struct Frame {
+    #[serde(rename = "type")]
+    frame_type: String,
+    data: Option<FrameData>,
+}
+
+#[derive(Deserialize)]
+struct FrameData {
+    topic: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    #[serde(rename = "type")]
+    reply_type: String,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct PredictionData {
+    prediction: UserPrediction,
+}
+
+/// Parse a raw pubsub message, `None` if it is not on the user's predictions topic
+pub fn parse(raw: &str) -> Option<Result<PredictionsUserReply>> {
+    let frame: Frame = serde_json::from_str(raw).ok()?;
+    let data = frame.data?;
+    if frame.frame_type != "MESSAGE" || !data.topic.starts_with("predictions-user-v1.") {
+        return None;
+    }
+    Some(parse_reply(&data.message).context("Parse user prediction"))
+}
+
+fn parse_reply(message: &str) -> Result<PredictionsUserReply> {
+    let reply: Reply = serde_json::from_str(message)?;
+    match reply.reply_type.as_str() {
+        "prediction-made" => {
+            let data: PredictionData = serde_json::from_value(reply.data)?;
+            Ok(PredictionsUserReply::PredictionMade {
+                prediction: data.prediction,
+            })
+        }
+        "prediction-result" => {
+            let data: PredictionData = serde_json::from_value(reply.data)?;
+            Ok(PredictionsUserReply::PredictionResult {
+                prediction: data.prediction,
+            })
+        }
+        _ => Ok(PredictionsUserReply::Other),
+    }
+}
+
+/// Listen to the user's predictions on a connection of its own, reconnecting whenever it drops. Returns once the
+/// receiver is dropped
+pub async fn listen(
+    url: String,
+    access_token: watch::Receiver<String>,
+    user_id: String,
+    proxy: Option<Proxy>,
+    tx: Sender<PredictionsUserReply>,
+) {
+    ws::listen_topic(
+        "user predictions",
+        ws::TopicConnection {
+            url,
+            access_token,
+            topic: topic(&user_id),
+            proxy,
+        },
+        tx,
+        |m| {
+            parse(m).map(|reply| {
+                reply.map(|reply| match reply {
+                    PredictionsUserReply::Other => None,
+                    reply => Some(reply),
+                })
+            })
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, BetResult, BetResultType, PredictionsUserReply, UserPrediction};
+
+    /// Bet of 100 points on outcome-1 of event-1 on channel 1 landing
+    const PREDICTION_MADE: &str = r#"{"type":"MESSAGE","data":{"topic":"predictions-user-v1.1234","message":"{\"type\":\"prediction-made\",\"data\":{\"timestamp\":\"2024-06-01T10:00:05Z\",\"prediction\":{\"id\":\"bet-1\",\"event_id\":\"event-1\",\"outcome_id\":\"outcome-1\",\"channel_id\":\"1\",\"points\":100,\"predicted_at\":\"2024-06-01T10:00:05Z\",\"updated_at\":\"2024-06-01T10:00:05Z\",\"user_id\":\"1234\",\"result\":null,\"user_display_name\":null}}}"}}"#;
+
+    #[test]
+    fn parse_prediction_made() {
+        assert_eq!(
+            parse(PREDICTION_MADE).unwrap().unwrap(),
+            PredictionsUserReply::PredictionMade {
+                prediction: UserPrediction {
+                    event_id: "event-1".to_owned(),
+                    outcome_id: "outcome-1".to_owned(),
+                    channel_id: "1".to_owned(),
+                    points: 100,
+                    result: None,
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn parse_prediction_result() {
+        let won = r#"{"type":"MESSAGE","data":{"topic":"predictions-user-v1.1234","message":"{\"type\":\"prediction-result\",\"data\":{\"timestamp\":\"2024-06-01T10:10:00Z\",\"prediction\":{\"id\":\"bet-1\",\"event_id\":\"event-1\",\"outcome_id\":\"outcome-1\",\"channel_id\":\"1\",\"points\":100,\"result\":{\"type\":\"WIN\",\"points_won\":250,\"is_acknowledged\":false}}}}"}}"#;
+        let prediction = match parse(won).unwrap().unwrap() {
+            PredictionsUserReply::PredictionResult { prediction } => prediction,
+            reply => panic!("Expected a result, got {reply:?}"),
+        };
+        assert_eq!(
+            prediction.result,
+            Some(BetResult {
+                result_type: BetResultType::Win,
+                points_won: Some(250),
+            })
+        );
+
+        let other = r#"{"type":"MESSAGE","data":{"topic":"raid.1","message":"{}"}}"#;
+        assert!(parse(other).is_none());
+    }
+}
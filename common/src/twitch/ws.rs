@@ -1,46 +1,170 @@
 use std::{
+    collections::{HashMap, VecDeque},
     sync::Arc,
     time::{Duration, Instant},
 };
 
-use eyre::{Context, Report, Result};
+use eyre::{eyre, Context, Report, Result};
 use flume::{Receiver, Sender};
 use futures_util::{
     stream::{SplitSink, SplitStream},
     SinkExt, StreamExt,
 };
 use rand::distributions::{Alphanumeric, DistString};
+use serde::Serialize;
 use serde_json::json;
 use tokio::{
     net::TcpStream,
     spawn,
-    sync::Mutex,
+    sync::{watch, Mutex},
     task::JoinHandle,
     time::{sleep, timeout},
 };
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
-use tracing::{debug, info, trace, warn};
+use tokio_tungstenite::{
+    client_async_tls, connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream,
+};
+use tracing::{debug, error, info, trace, warn};
 use twitch_api::pubsub::{
     listen_command, unlisten_command,
     video_playback::{VideoPlaybackById, VideoPlaybackReply},
     Response, TopicData, Topics,
 };
 
+use super::proxy::Proxy;
+
 type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
+pub const PUBSUB_URL: &str = "wss://pubsub-edge.twitch.tv/v1";
+
+/// Default time without messages on a live stream before its topic is listened to again
+pub const SILENCE_TIMEOUT_SECS: u64 = 300;
+/// Default number of consecutive failed connection attempts before the pool is considered degraded
+pub const DEGRADED_AFTER: u32 = 5;
+/// Default number of recently received raw messages that are kept
+pub const RAW_MESSAGES: usize = 200;
+/// Default maximum number of topics on a single connection, the limit imposed by twitch
+pub const TOPICS_PER_CONNECTION: usize = 50;
+/// Default time a stream may be offline before its prediction and raid topics are unlistened
+pub const UNLISTEN_GRACE_SECS: u64 = 60;
+
+/// Time without messages before a single topic connection is pinged
+const TOPIC_PING_INTERVAL: Duration = Duration::from_secs(60);
+const TOPIC_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[cfg(feature = "testing")]
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+#[cfg(not(feature = "testing"))]
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
 pub struct WsPool {
     connections: Vec<WsConn>,
     rx: Receiver<Request>,
     tx: Sender<TopicData>,
     access_token: String,
+    options: WsPoolOptions,
+    // topics waiting for capacity when the connection limit is reached
+    queued: VecDeque<Topics>,
+    status: watch::Sender<WsPoolStatus>,
     #[cfg(feature = "testing")]
     base_url: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct WsPoolOptions {
+    /// Time without messages on a live stream before its topic is listened to again
+    pub silence_timeout: Duration,
+    /// Consecutive failed connection attempts before the pool is considered degraded
+    pub degraded_after: u32,
+    /// Maximum number of open connections, topics are queued once all of them are full
+    pub max_connections: Option<usize>,
+    /// Maximum number of topics listened to on a single connection
+    pub topics_per_connection: usize,
+    /// Recently received raw messages, shared with the caller
+    pub raw_messages: RawMessages,
+    /// Proxy the connections are tunneled through
+    pub proxy: Option<Proxy>,
+}
+
+impl Default for WsPoolOptions {
+    fn default() -> Self {
+        Self {
+            silence_timeout: Duration::from_secs(SILENCE_TIMEOUT_SECS),
+            degraded_after: DEGRADED_AFTER,
+            max_connections: None,
+            topics_per_connection: TOPICS_PER_CONNECTION,
+            raw_messages: RawMessages::new(RAW_MESSAGES),
+            proxy: None,
+        }
+    }
+}
+
+/// Ring buffer of the raw text of received messages, to inspect messages that failed to parse
+#[derive(Debug, Clone)]
+pub struct RawMessages {
+    capacity: usize,
+    messages: Arc<std::sync::Mutex<VecDeque<String>>>,
+}
+
+impl RawMessages {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            messages: Arc::new(std::sync::Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    fn push(&self, message: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() == self.capacity {
+            messages.pop_front();
+        }
+        messages.push_back(message.to_owned());
+    }
+
+    /// The last `n` messages, oldest first
+    pub fn last(&self, n: usize) -> Vec<String> {
+        let messages = self.messages.lock().unwrap();
+        messages
+            .iter()
+            .skip(messages.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub struct WsPoolStatus {
+    /// Connecting to twitch pubsub failed too many times in a row
+    pub degraded: bool,
+    /// Consecutive failed connection attempts
+    pub failed_reconnects: u32,
+    /// Number of open connections
+    pub connections: usize,
+    /// Topics waiting for a free connection
+    pub queued_topics: usize,
+    /// Topics listened to, per connection
+    #[serde(skip)]
+    pub connection_topics: Vec<WsConnTopics>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub struct WsConnTopics {
+    #[cfg_attr(feature = "web_api", schema(value_type = Vec<String>))]
+    pub topics: Vec<Topics>,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Request {
     Listen(Topics),
     UnListen(Topics),
+    /// Reconnect all connections with a new access token, keeping their topics
+    SetAccessToken(String),
 }
 
 struct WsConn {
@@ -57,6 +181,8 @@ struct WsConnState {
     stream_state: WsStreamState,
     // string of command nonce's
     retry_commands: Vec<String>,
+    // channel ID of live streams, with the time of their last message
+    live_topics: HashMap<u32, Instant>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -76,25 +202,31 @@ impl Drop for WsPool {
 impl WsPool {
     pub async fn start(
         access_token: &str,
+        options: WsPoolOptions,
         #[cfg(feature = "testing")] base_url: String,
     ) -> (
         JoinHandle<()>,
         Sender<Request>,
         (Sender<TopicData>, Receiver<TopicData>),
+        watch::Receiver<WsPoolStatus>,
     ) {
         let (req_tx, req_rx) = flume::unbounded();
         let (res_tx, res_rx) = flume::unbounded();
+        let (status_tx, status_rx) = watch::channel(WsPoolStatus::default());
 
         let pool = spawn(WsPool::run(WsPool {
             connections: vec![],
             rx: req_rx,
             tx: res_tx.clone(),
             access_token: access_token.to_owned(),
+            options,
+            queued: VecDeque::new(),
+            status: status_tx,
             #[cfg(feature = "testing")]
             base_url,
         }));
 
-        (pool, req_tx, (res_tx, res_rx))
+        (pool, req_tx, (res_tx, res_rx), status_rx)
     }
 
     async fn run(mut self) {
@@ -123,7 +255,7 @@ impl WsPool {
                         .map(|x| x.topics.clone())
                         .flatten()
                         .find(|x| x.0.eq(&topic));
-                    if topic_already_exists.is_none() {
+                    if topic_already_exists.is_none() && !self.queued.contains(&topic) {
                         self.listen_command(topic).await
                     } else {
                         debug!("Got request to add existing topic {topic:#?}");
@@ -131,6 +263,7 @@ impl WsPool {
                 }
                 Ok(Ok(Request::UnListen(topic))) => {
                     debug!("Got request to remove topic {topic:#?}");
+                    self.queued.retain(|x| x.ne(&topic));
                     let mut conn = None;
                     self.connections = self
                         .connections
@@ -168,6 +301,17 @@ impl WsPool {
                             .await;
                     }
                 }
+                Ok(Ok(Request::SetAccessToken(access_token))) => {
+                    info!(
+                        "Access token changed, reconnecting {} connections",
+                        self.connections.len()
+                    );
+                    self.access_token = access_token;
+                    for conn in std::mem::take(&mut self.connections) {
+                        let conn = self.reconnect(conn).await;
+                        self.connections.push(conn);
+                    }
+                }
                 Ok(Err(_)) => break,
                 Err(_) => {}
             }
@@ -204,8 +348,35 @@ impl WsPool {
                     }
                 }
 
-                if !state.retry_commands.is_empty() {
-                    for nonce in state.retry_commands {
+                for (channel_id, last_message) in state.live_topics {
+                    if last_message.elapsed() < self.options.silence_timeout {
+                        continue;
+                    }
+
+                    let topic = Topics::VideoPlaybackById(VideoPlaybackById { channel_id });
+                    conn.state
+                        .lock()
+                        .await
+                        .live_topics
+                        .insert(channel_id, Instant::now());
+                    if let Some(idx) = conn.topics.iter().position(|x| x.0.eq(&topic)) {
+                        warn!("No messages on live stream {channel_id}, listening again");
+                        _ = conn.unlisten_topic(&topic).await;
+                        match conn.listen_topic(&topic).await {
+                            Ok(nonce) => conn.topics[idx].1 = nonce,
+                            Err(err) => {
+                                warn!("Failed to listen to topic {:#?}", err);
+                                conn = self.reconnect(conn).await;
+                            }
+                        }
+                    }
+                }
+
+                // read the retries off the connection itself, a reconnect above replaces the
+                // connection and re-listens every topic under new nonces
+                let retry_commands = { conn.state.lock().await.retry_commands.clone() };
+                if !retry_commands.is_empty() {
+                    for nonce in retry_commands {
                         let mut topic = None;
                         conn.topics = conn
                             .topics
@@ -238,26 +409,84 @@ impl WsPool {
                     .collect();
                 self.connections.push(conn);
             }
+
+            while !self.queued.is_empty() && self.has_capacity() {
+                let topic = self.queued.pop_front().unwrap();
+                debug!("Listening to queued topic {topic:#?}");
+                self.listen_command(topic).await;
+            }
+
+            let (connections, queued_topics) = (self.connections.len(), self.queued.len());
+            let connection_topics = self
+                .connections
+                .iter()
+                .map(|x| WsConnTopics {
+                    topics: x.topics.iter().map(|(t, _)| t.clone()).collect(),
+                })
+                .collect::<Vec<_>>();
+            self.status.send_if_modified(|status| {
+                let changed = status.connections != connections
+                    || status.queued_topics != queued_topics
+                    || status.connection_topics != connection_topics;
+                status.connections = connections;
+                status.queued_topics = queued_topics;
+                status.connection_topics = connection_topics;
+                changed
+            });
         }
     }
 
+    fn connection_result(&self, success: bool) {
+        self.status.send_if_modified(|status| {
+            if success {
+                let changed = status.failed_reconnects != 0;
+                status.failed_reconnects = 0;
+                status.degraded = false;
+                return changed;
+            }
+
+            status.failed_reconnects += 1;
+            if status.failed_reconnects == self.options.degraded_after {
+                error!(
+                    "Could not connect to twitch pubsub after {} attempts",
+                    status.failed_reconnects
+                );
+            }
+            status.degraded = status.failed_reconnects >= self.options.degraded_after;
+            true
+        });
+    }
+
+    fn has_room(&self, conn: &WsConn) -> bool {
+        conn.topics.len() < self.options.topics_per_connection
+    }
+
+    fn has_capacity(&self) -> bool {
+        self.connections.iter().any(|x| self.has_room(x))
+            || match self.options.max_connections {
+                Some(max) => self.connections.len() < max,
+                None => true,
+            }
+    }
+
     async fn listen_command(&mut self, topic: Topics) {
-        if self
-            .connections
-            .iter()
-            .filter(|x| x.topics.len() < 50)
-            .count()
-            == 0
-        {
+        if !self.has_capacity() {
+            warn!("Connection limit reached, queueing topic {topic:#?}");
+            self.queued.push_back(topic);
+            return;
+        }
+
+        if self.connections.iter().filter(|x| self.has_room(x)).count() == 0 {
             self.retry_add_connection().await;
         }
 
         let mut conn = None;
+        let limit = self.options.topics_per_connection;
         self.connections = self
             .connections
             .drain(..)
             .filter_map(|x| {
-                if x.topics.len() < 50 && conn.is_none() {
+                if x.topics.len() < limit && conn.is_none() {
                     conn = Some(x);
                     None
                 } else {
@@ -287,12 +516,14 @@ impl WsPool {
         loop {
             match self.add_connection().await {
                 Ok(conn) => {
+                    self.connection_result(true);
                     self.connections.push(conn);
                     break;
                 }
                 Err(err) => {
                     warn!("Failed to add connection {err:#?}");
-                    sleep(Duration::from_secs(1)).await;
+                    self.connection_result(false);
+                    sleep(RETRY_DELAY).await;
                 }
             }
         }
@@ -308,6 +539,7 @@ impl WsPool {
             last_update: Instant::now(),
             stream_state: WsStreamState::Open,
             retry_commands: Vec::new(),
+            live_topics: HashMap::new(),
         }));
 
         writer
@@ -315,7 +547,12 @@ impl WsPool {
             .await?;
 
         let conn = WsConn {
-            reader: spawn(ws_reader(state.clone(), self.tx.clone(), reader)),
+            reader: spawn(ws_reader(
+                state.clone(),
+                self.tx.clone(),
+                self.options.raw_messages.clone(),
+                reader,
+            )),
             writer,
             topics: Vec::new(),
             state,
@@ -331,6 +568,11 @@ impl WsPool {
             mut conn: WsConn,
         ) -> Result<WsConn, (WsConn, Report)> {
             debug!("Reconnecting ws with {} topics", conn.topics.len());
+            // topics pending a retry are still in conn.topics, and are issued again below
+            let pending_retries = std::mem::take(&mut conn.state.lock().await.retry_commands);
+            if !pending_retries.is_empty() {
+                debug!("Re-issuing {} pending retries", pending_retries.len());
+            }
             if !conn.reader.is_finished() {
                 _ = conn.writer.close().await;
                 conn.reader.abort();
@@ -342,10 +584,14 @@ impl WsPool {
 
             let mut added_connection = 'outer: loop {
                 match pool.add_connection().await {
-                    Ok(c) => break 'outer c,
+                    Ok(c) => {
+                        pool.connection_result(true);
+                        break 'outer c;
+                    }
                     Err(err) => {
                         warn!("Failed to add connection {err:#?}");
-                        sleep(Duration::from_secs(1)).await;
+                        pool.connection_result(false);
+                        sleep(RETRY_DELAY).await;
                     }
                 }
             };
@@ -379,7 +625,7 @@ impl WsPool {
                 Err((failed_conn, err)) => {
                     conn = failed_conn;
                     warn!("Failed to reconnect {err:#?}");
-                    sleep(Duration::from_secs(1)).await;
+                    sleep(RETRY_DELAY).await;
                 }
             }
         }
@@ -388,15 +634,136 @@ impl WsPool {
     async fn connect_twitch_ws(
         &self,
     ) -> Result<(SplitSink<WsStream, Message>, SplitStream<WsStream>)> {
-        let (socket, _) = connect_async(
-            #[cfg(feature = "testing")]
-            &format!("{}/pubsub", self.base_url),
-            #[cfg(not(feature = "testing"))]
-            "wss://pubsub-edge.twitch.tv/v1",
-        )
-        .await?;
+        #[cfg(feature = "testing")]
+        let url = format!("{}/pubsub", self.base_url);
+        #[cfg(not(feature = "testing"))]
+        let url = PUBSUB_URL.to_owned();
+
+        Ok(connect(url, self.options.proxy.as_ref()).await?.split())
+    }
+}
+
+/// Open a websocket connection to pubsub, tunneled through the proxy if there is one
+pub(super) async fn connect(url: String, proxy: Option<&Proxy>) -> Result<WsStream> {
+    let (socket, _) = match proxy {
+        Some(proxy) => {
+            let uri = reqwest::Url::parse(&url)?;
+            let stream = proxy
+                .connect(
+                    uri.host_str().unwrap_or_default(),
+                    uri.port_or_known_default().unwrap_or(443),
+                )
+                .await
+                .context("Connect to pubsub through proxy")?;
+            client_async_tls(url, stream).await?
+        }
+        None => connect_async(url).await?,
+    };
+    Ok(socket)
+}
+
+/// Open a pubsub connection and close it again, to check pubsub can be reached
+pub async fn probe(url: &str, proxy: Option<&Proxy>) -> Result<()> {
+    let mut socket = connect(url.to_owned(), proxy).await?;
+    _ = socket.close(None).await;
+    Ok(())
+}
+
+/// Resolves once a new access token is sent, never if the sender is gone
+async fn token_changed(access_token: &mut watch::Receiver<String>) {
+    if access_token.changed().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Connection to pubsub for a single topic that `twitch_api` does not model
+pub struct TopicConnection {
+    pub url: String,
+    /// Read on every reconnect, a new token reconnects right away
+    pub access_token: watch::Receiver<String>,
+    pub topic: String,
+    pub proxy: Option<Proxy>,
+}
+
+/// Listen to a topic on a connection of its own, reconnecting whenever it drops. `parse` turns a raw message into an
+/// item to send, it returns `None` for messages that are not on the topic and `Ok(None)` for ones that are ignored.
+/// Returns once the receiver is dropped
+pub async fn listen_topic<T>(
+    name: &str,
+    mut conn: TopicConnection,
+    tx: Sender<T>,
+    parse: impl Fn(&str) -> Option<Result<Option<T>>>,
+) {
+    loop {
+        match listen_topic_once(name, &mut conn, &tx, &parse).await {
+            Ok(()) => return,
+            Err(err) => warn!("Connection for {name} closed: {err:#}"),
+        }
+        sleep(TOPIC_RECONNECT_DELAY).await;
+    }
+}
+
+async fn listen_topic_once<T>(
+    name: &str,
+    conn: &mut TopicConnection,
+    tx: &Sender<T>,
+    parse: &impl Fn(&str) -> Option<Result<Option<T>>>,
+) -> Result<()> {
+    let access_token = conn.access_token.borrow_and_update().clone();
+    let (mut writer, mut reader) = connect(conn.url.clone(), conn.proxy.as_ref())
+        .await
+        .context("Connecting to twitch pubsub")?
+        .split();
+    let nonce = Alphanumeric.sample_string(&mut rand::thread_rng(), 30);
+    let listen = json!({
+        "type": "LISTEN",
+        "nonce": nonce,
+        "data": {
+            "topics": [conn.topic],
+            "auth_token": access_token,
+        }
+    });
+    writer.send(Message::Text(listen.to_string())).await?;
+    info!("Listening to {name}");
+
+    let mut awaiting_pong = false;
+    loop {
+        let next = tokio::select! {
+            next = timeout(TOPIC_PING_INTERVAL, reader.next()) => next,
+            _ = token_changed(&mut conn.access_token) => {
+                return Err(eyre!("Access token changed"));
+            }
+        };
+        let msg = match next {
+            Ok(Some(msg)) => msg?,
+            Ok(None) => return Err(eyre!("Connection closed by twitch")),
+            Err(_) if awaiting_pong => return Err(eyre!("Twitch pubsub did not respond to ping")),
+            Err(_) => {
+                writer
+                    .send(Message::Text(json!({"type": "PING"}).to_string()))
+                    .await?;
+                awaiting_pong = true;
+                continue;
+            }
+        };
+        awaiting_pong = false;
 
-        Ok(socket.split())
+        if let Message::Text(m) = msg {
+            trace!("Got {name} message {m}");
+            match parse(&m) {
+                Some(Ok(Some(item))) => {
+                    if tx.send_async(item).await.is_err() {
+                        return Ok(());
+                    }
+                }
+                Some(Ok(None)) => {}
+                Some(Err(err)) => warn!("{err:#}\nmessage {m}"),
+                None if m.contains("\"RECONNECT\"") => {
+                    return Err(eyre!("Twitch requested reconnect"))
+                }
+                None => debug!("{name} response {m}"),
+            }
+        }
     }
 }
 
@@ -451,11 +818,13 @@ impl WsConn {
 async fn ws_reader(
     state: Arc<Mutex<WsConnState>>,
     tx: Sender<TopicData>,
+    raw_messages: RawMessages,
     mut stream: SplitStream<WsStream>,
 ) -> Result<()> {
     while let Some(Ok(msg)) = stream.next().await {
         if let Message::Text(m) = msg {
             trace!("Got message {m}");
+            raw_messages.push(&m);
             match Response::parse(&m) {
                 Ok(r) => match r {
                     Response::Response(data) => {
@@ -474,7 +843,15 @@ async fn ws_reader(
                         }
                     }
                     Response::Message { data } => {
-                        if let TopicData::VideoPlaybackById { topic: _, reply } = &data {
+                        if let TopicData::VideoPlaybackById { topic, reply } = &data {
+                            {
+                                let mut state = state.lock().await;
+                                if let VideoPlaybackReply::StreamDown { .. } = reply.as_ref() {
+                                    state.live_topics.remove(&topic.channel_id);
+                                } else {
+                                    state.live_topics.insert(topic.channel_id, Instant::now());
+                                }
+                            }
                             match reply.as_ref() {
                                 VideoPlaybackReply::StreamUp {
                                     server_time: _,
@@ -518,8 +895,12 @@ mod test {
     #[tokio::test(flavor = "multi_thread")]
     async fn listen(#[future] container: TestContainer) -> Result<()> {
         let container = container.await;
-        let (pool, tx, (_, rx)) =
-            WsPool::start("test", format!("ws://localhost:{}", container.port)).await;
+        let (pool, tx, (_, rx), _) = WsPool::start(
+            "test",
+            WsPoolOptions::default(),
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
 
         let topic = VideoPlaybackById { channel_id: 1 };
         _ = tx
@@ -555,6 +936,116 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn raw_messages(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        reqwest::Client::new()
+            .post(format!(
+                "http://localhost:{}/pubsub/test_mode",
+                container.port
+            ))
+            .json(&json!("Listen"))
+            .send()
+            .await?;
+
+        let raw_messages = RawMessages::new(2);
+        let (pool, tx, (_, rx), _) = WsPool::start(
+            "test",
+            WsPoolOptions {
+                raw_messages: raw_messages.clone(),
+                ..Default::default()
+            },
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
+
+        let topic = VideoPlaybackById { channel_id: 1 };
+        _ = tx
+            .send_async(Request::Listen(Topics::VideoPlaybackById(topic.clone())))
+            .await;
+        rx.recv_async().await?;
+
+        // the command response and the stream up message, the oldest message was dropped
+        let messages = raw_messages.last(10);
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| Response::parse(m).is_ok()));
+        assert_eq!(raw_messages.last(1), messages[1..]);
+
+        pool.abort();
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn listen_through_proxy(#[future] container: TestContainer) -> Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let container = container.await;
+        reqwest::Client::new()
+            .post(format!(
+                "http://localhost:{}/pubsub/test_mode",
+                container.port
+            ))
+            .json(&json!("Listen"))
+            .send()
+            .await?;
+
+        // forwards CONNECT tunnels, recording their targets
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let proxy = format!("http://{}", listener.local_addr()?).parse()?;
+        let tunnels = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let proxy_tunnels = tunnels.clone();
+        let proxy_server = tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut head = Vec::new();
+                let mut byte = [0u8; 1];
+                while !head.ends_with(b"\r\n\r\n") {
+                    socket.read_exact(&mut byte).await.unwrap();
+                    head.push(byte[0]);
+                }
+                let head = String::from_utf8(head).unwrap();
+                let target = head.split_whitespace().nth(1).unwrap().to_owned();
+                proxy_tunnels.lock().unwrap().push(target.clone());
+
+                let mut upstream = TcpStream::connect(target).await.unwrap();
+                socket
+                    .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                    .await
+                    .unwrap();
+                tokio::spawn(async move {
+                    _ = tokio::io::copy_bidirectional(&mut socket, &mut upstream).await;
+                });
+            }
+        });
+
+        let (pool, tx, (_, rx), _) = WsPool::start(
+            "test",
+            WsPoolOptions {
+                proxy: Some(proxy),
+                ..Default::default()
+            },
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
+
+        let topic = VideoPlaybackById { channel_id: 1 };
+        _ = tx
+            .send_async(Request::Listen(Topics::VideoPlaybackById(topic.clone())))
+            .await;
+        rx.recv_async().await?;
+        assert_eq!(
+            *tunnels.lock().unwrap(),
+            vec![format!("localhost:{}", container.port)]
+        );
+
+        pool.abort();
+        proxy_server.abort();
+        Ok(())
+    }
+
     #[rstest]
     #[timeout(Duration::from_secs(5))]
     #[tokio::test(flavor = "multi_thread")]
@@ -569,8 +1060,12 @@ mod test {
             .send()
             .await?;
 
-        let (pool, tx, (_, _)) =
-            WsPool::start("test", format!("ws://localhost:{}", container.port)).await;
+        let (pool, tx, (_, _), _) = WsPool::start(
+            "test",
+            WsPoolOptions::default(),
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
 
         let topic = VideoPlaybackById { channel_id: 1 };
         _ = tx
@@ -618,8 +1113,12 @@ mod test {
             .send()
             .await?;
 
-        let (pool, tx, (_, _)) =
-            WsPool::start("test", format!("ws://localhost:{}", container.port)).await;
+        let (pool, tx, (_, _), _) = WsPool::start(
+            "test",
+            WsPoolOptions::default(),
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
 
         let topic = VideoPlaybackById { channel_id: 1 };
         _ = tx
@@ -646,6 +1145,64 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reconnect_with_pending_retry(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let pubsub_uri = format!("http://localhost:{}/pubsub", container.port);
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{pubsub_uri}/test_mode"))
+            .json(&json!("RetryReconnect"))
+            .send()
+            .await?;
+
+        let (pool, tx, (_, _rx), status) = WsPool::start(
+            "test",
+            WsPoolOptions::default(),
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
+
+        let topic = |channel_id| Topics::VideoPlaybackById(VideoPlaybackById { channel_id });
+        for channel_id in [1, 2] {
+            _ = tx.send_async(Request::Listen(topic(channel_id))).await;
+        }
+
+        loop {
+            let mut mock: serde_json::Value = client
+                .get(format!("{pubsub_uri}/test_stats"))
+                .send()
+                .await?
+                .json()
+                .await?;
+
+            let mut subscribed = traverse_json(&mut mock, ".RetryReconnect.subscribed")
+                .and_then(|x| serde_json::from_value::<Vec<u32>>(x.clone()).ok())
+                .unwrap_or_default();
+            subscribed.sort();
+            if subscribed == vec![1, 2] {
+                break;
+            } else {
+                sleep(Duration::from_millis(1)).await;
+            }
+        }
+
+        let mut topics = status
+            .borrow()
+            .connection_topics
+            .iter()
+            .flat_map(|c| c.topics.clone())
+            .collect::<Vec<_>>();
+        topics.sort_by_key(|x| format!("{x:?}"));
+        assert_eq!(topics, vec![topic(1), topic(2)]);
+
+        pool.abort();
+        Ok(())
+    }
+
     #[rstest]
     #[timeout(Duration::from_secs(5))]
     #[tokio::test(flavor = "multi_thread")]
@@ -660,8 +1217,12 @@ mod test {
             .send()
             .await?;
 
-        let (pool, tx, (_, rx)) =
-            WsPool::start("test", format!("ws://localhost:{}", container.port)).await;
+        let (pool, tx, (_, rx), _) = WsPool::start(
+            "test",
+            WsPoolOptions::default(),
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
 
         for i in 0..50 {
             let topic = VideoPlaybackById { channel_id: i };
@@ -730,4 +1291,356 @@ mod test {
         pool.abort();
         Ok(())
     }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn silence_relisten(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let pubsub_uri = format!("http://localhost:{}/pubsub", container.port);
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{pubsub_uri}/test_mode"))
+            .json(&json!("Silence"))
+            .send()
+            .await?;
+
+        let (pool, tx, (_, rx), _) = WsPool::start(
+            "test",
+            WsPoolOptions {
+                silence_timeout: Duration::from_millis(200),
+                ..Default::default()
+            },
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
+
+        let topic = VideoPlaybackById { channel_id: 1 };
+        _ = tx
+            .send_async(Request::Listen(Topics::VideoPlaybackById(topic.clone())))
+            .await;
+
+        // the stream goes live, then the topic stays silent
+        let res = rx.recv_async().await?;
+        assert_eq!(
+            res,
+            TopicData::VideoPlaybackById {
+                topic,
+                reply: Box::new(VideoPlaybackReply::StreamUp {
+                    server_time: 0.0,
+                    play_delay: 0
+                })
+            }
+        );
+
+        loop {
+            let mut mock: serde_json::Value = client
+                .get(format!("{pubsub_uri}/test_stats"))
+                .send()
+                .await?
+                .json()
+                .await?;
+
+            let listen_count = traverse_json(&mut mock, ".Silence.count");
+            if listen_count.unwrap().as_i64().unwrap() >= 2 {
+                break;
+            } else {
+                sleep(Duration::from_millis(1)).await;
+            }
+        }
+
+        pool.abort();
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn degraded_status(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let pubsub_uri = format!("http://localhost:{}/pubsub", container.port);
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{pubsub_uri}/test_mode"))
+            .json(&json!("Refuse"))
+            .send()
+            .await?;
+
+        let (pool, _tx, (_, _), mut status) = WsPool::start(
+            "test",
+            WsPoolOptions {
+                degraded_after: 3,
+                ..Default::default()
+            },
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
+
+        let degraded = status.wait_for(|x| x.degraded).await?.clone();
+        assert!(degraded.failed_reconnects >= 3);
+        assert_eq!(degraded.connections, 0);
+
+        client
+            .post(format!("{pubsub_uri}/test_mode"))
+            .json(&json!("Listen"))
+            .send()
+            .await?;
+
+        let restored = status
+            .wait_for(|x| !x.degraded && x.connections == 1)
+            .await?
+            .clone();
+        assert_eq!(restored.failed_reconnects, 0);
+
+        pool.abort();
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn topics_per_connection(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let pubsub_uri = format!("http://localhost:{}/pubsub", container.port);
+
+        reqwest::Client::new()
+            .post(format!("{pubsub_uri}/test_mode"))
+            .json(&json!("Listen"))
+            .send()
+            .await?;
+
+        let (pool, tx, (_, _rx), mut status) = WsPool::start(
+            "test",
+            WsPoolOptions::default(),
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
+
+        let topic = |channel_id| Topics::VideoPlaybackById(VideoPlaybackById { channel_id });
+        for channel_id in 200..252 {
+            _ = tx.send_async(Request::Listen(topic(channel_id))).await;
+        }
+
+        let status = status
+            .wait_for(|x| {
+                x.connection_topics
+                    .iter()
+                    .map(|c| c.topics.len())
+                    .sum::<usize>()
+                    == 52
+            })
+            .await?
+            .clone();
+        let mut connections = status
+            .connection_topics
+            .into_iter()
+            .map(|c| c.topics)
+            .collect::<Vec<_>>();
+        connections.sort_by_key(|x| x.len());
+        assert_eq!(
+            connections,
+            vec![
+                (250..252).map(topic).collect::<Vec<_>>(),
+                (200..250).map(topic).collect::<Vec<_>>(),
+            ]
+        );
+
+        pool.abort();
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn topic_limit_override(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        reqwest::Client::new()
+            .post(format!(
+                "http://localhost:{}/pubsub/test_mode",
+                container.port
+            ))
+            .json(&json!("Listen"))
+            .send()
+            .await?;
+
+        let (pool, tx, (_, _rx), mut status) = WsPool::start(
+            "test",
+            WsPoolOptions {
+                topics_per_connection: 5,
+                ..Default::default()
+            },
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
+
+        let topic = |channel_id| Topics::VideoPlaybackById(VideoPlaybackById { channel_id });
+        for channel_id in 0..6 {
+            _ = tx.send_async(Request::Listen(topic(channel_id))).await;
+        }
+
+        let status = status
+            .wait_for(|x| {
+                x.connection_topics
+                    .iter()
+                    .map(|c| c.topics.len())
+                    .sum::<usize>()
+                    == 6
+            })
+            .await?
+            .clone();
+        let mut connections = status
+            .connection_topics
+            .into_iter()
+            .map(|c| c.topics)
+            .collect::<Vec<_>>();
+        connections.sort_by_key(|x| x.len());
+        assert_eq!(
+            connections,
+            vec![vec![topic(5)], (0..5).map(topic).collect::<Vec<_>>(),]
+        );
+
+        pool.abort();
+        Ok(())
+    }
+
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn max_connections(#[future] container: TestContainer) -> Result<()> {
+        let container = container.await;
+        let pubsub_uri = format!("http://localhost:{}/pubsub", container.port);
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{pubsub_uri}/test_mode"))
+            .json(&json!("ScaleConnections"))
+            .send()
+            .await?;
+        let stats = || async {
+            let mut mock: serde_json::Value = client
+                .get(format!("{pubsub_uri}/test_stats"))
+                .send()
+                .await?
+                .json()
+                .await?;
+            let topics = traverse_json(&mut mock, ".ScaleConnections.topics")
+                .unwrap()
+                .as_i64()
+                .unwrap();
+            let sockets = traverse_json(&mut mock, ".ScaleConnections.sockets")
+                .unwrap()
+                .as_i64()
+                .unwrap();
+            Ok::<_, Report>((topics, sockets))
+        };
+        let (base_topics, base_sockets) = stats().await?;
+
+        let (pool, tx, (_, _), mut status) = WsPool::start(
+            "test",
+            WsPoolOptions {
+                max_connections: Some(1),
+                ..Default::default()
+            },
+            format!("ws://localhost:{}", container.port),
+        )
+        .await;
+
+        for channel_id in 100..155 {
+            _ = tx
+                .send_async(Request::Listen(Topics::VideoPlaybackById(
+                    VideoPlaybackById { channel_id },
+                )))
+                .await;
+        }
+
+        status
+            .wait_for(|x| {
+                assert!(x.connections <= 1);
+                x.queued_topics == 5 && x.connections == 1
+            })
+            .await?;
+        loop {
+            let (topics, sockets) = stats().await?;
+            assert_eq!(sockets, base_sockets + 1);
+            if topics == base_topics + 50 {
+                break;
+            }
+            sleep(Duration::from_millis(1)).await;
+        }
+
+        // freed capacity is used by the queued topics
+        for channel_id in 100..103 {
+            _ = tx
+                .send_async(Request::UnListen(Topics::VideoPlaybackById(
+                    VideoPlaybackById { channel_id },
+                )))
+                .await;
+        }
+
+        status
+            .wait_for(|x| {
+                assert!(x.connections <= 1);
+                x.queued_topics == 2
+            })
+            .await?;
+        // three topics were removed and three queued topics took their place
+        loop {
+            let (topics, sockets) = stats().await?;
+            assert_eq!(sockets, base_sockets + 1);
+            if topics == base_topics + 50 {
+                break;
+            }
+            sleep(Duration::from_millis(1)).await;
+        }
+
+        pool.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn topic_reconnects_with_new_token() -> Result<()> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("ws://{}", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let (mut tokens, mut sockets) = (Vec::new(), Vec::new());
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await?;
+                let mut socket = tokio_tungstenite::accept_async(stream).await?;
+                if let Some(Message::Text(listen)) = socket.next().await.transpose()? {
+                    let listen: serde_json::Value = serde_json::from_str(&listen)?;
+                    tokens.push(listen["data"]["auth_token"].as_str().unwrap().to_owned());
+                }
+                // kept open, so only the token change ends the connection
+                sockets.push(socket);
+            }
+            Ok::<_, eyre::Report>(tokens)
+        });
+
+        let (token_tx, access_token) = watch::channel("old-token".to_owned());
+        let mut conn = TopicConnection {
+            url,
+            access_token,
+            topic: "topic.1".to_owned(),
+            proxy: None,
+        };
+        let (tx, _rx) = flume::unbounded::<()>();
+        let parse = |_: &str| None;
+
+        let first = listen_topic_once("test", &mut conn, &tx, &parse);
+        let (res, _) = tokio::join!(first, async {
+            sleep(Duration::from_millis(100)).await;
+            token_tx.send_replace("new-token".to_owned());
+        });
+        assert_eq!(res.unwrap_err().to_string(), "Access token changed");
+        _ = timeout(
+            Duration::from_millis(100),
+            listen_topic_once("test", &mut conn, &tx, &parse),
+        )
+        .await;
+
+        assert_eq!(server.await??, vec!["old-token", "new-token"]);
+        Ok(())
+    }
 }
@@ -1,5 +1,6 @@
 use std::{collections::HashMap, sync::Arc, time::Instant};
 
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize, Serializer};
 use twitch_api::{pubsub::predictions::Event, types::UserId};
 
@@ -14,6 +15,19 @@ pub struct StreamerState {
     pub points: u32,
     #[serde(skip)]
     pub last_points_refresh: Instant,
+    /// Cumulative bets placed per event ID
+    pub wagered: HashMap<String, Wager>,
+    /// When the stream was seen going live, unknown for streams already live at startup
+    #[serde(skip)]
+    pub live_since: Option<Instant>,
+    /// Last time the stream was seen going live or offline
+    pub last_live_at: Option<DateTime<Local>>,
+    /// Consecutive GQL failures, interactions are paused while the circuit is open
+    pub circuit: Circuit,
+    /// Whether the balance is at or above the points goal
+    pub points_goal_reached: bool,
+    /// Freeform tags to group channels in analytics
+    pub tags: Vec<String>,
 }
 
 impl Default for StreamerState {
@@ -24,6 +38,12 @@ impl Default for StreamerState {
             config: Default::default(),
             points: Default::default(),
             last_points_refresh: Instant::now(),
+            wagered: Default::default(),
+            live_since: None,
+            last_live_at: None,
+            circuit: Default::default(),
+            points_goal_reached: false,
+            tags: Vec::new(),
         }
     }
 }
@@ -39,6 +59,95 @@ impl StreamerState {
             ..Default::default()
         }
     }
+
+    /// Track a new prediction, evicting ended ones first. Returns false without tracking it if `cap` predictions are
+    /// tracked already
+    pub fn track_prediction(&mut self, event: Event, placed: bool, cap: usize) -> bool {
+        self.predictions.retain(|_, (e, _)| e.ended_at.is_none());
+        if self.predictions.len() >= cap {
+            return false;
+        }
+        self.predictions.insert(event.id.clone(), (event, placed));
+        true
+    }
+
+    /// Add a bet to the running total for an event, returns the new total. `debited` if the balance already
+    /// reflects the bet. Twitch only takes more points on the outcome already bet on, so a bet on another
+    /// outcome is not recorded and returns `None`
+    pub fn record_wager(
+        &mut self,
+        event_id: &str,
+        outcome_id: &str,
+        points: u32,
+        debited: bool,
+    ) -> Option<u32> {
+        let wager = self
+            .wagered
+            .entry(event_id.to_owned())
+            .or_insert_with(|| Wager {
+                outcome_id: outcome_id.to_owned(),
+                points: 0,
+                confirmed: false,
+                debited: 0,
+            });
+        if wager.outcome_id != outcome_id {
+            return None;
+        }
+        wager.points += points;
+        if debited {
+            wager.debited += points;
+        }
+        Some(wager.points)
+    }
+}
+
+/// Default number of predictions tracked per streamer, further predictions are dropped
+pub const MAX_PREDICTIONS: usize = 50;
+/// Default number of consecutive GQL failures of a streamer before its circuit opens
+pub const CIRCUIT_FAILURES: u32 = 5;
+/// Default seconds GQL interactions with a streamer are paused once its circuit opens
+pub const CIRCUIT_COOLDOWN_SECS: u64 = 1800;
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub struct Circuit {
+    /// Consecutive failed GQL requests
+    pub failures: u32,
+    /// GQL interactions are skipped until this time
+    pub open_until: Option<DateTime<Local>>,
+}
+
+impl Circuit {
+    pub fn is_open(&self) -> bool {
+        self.open_until.is_some_and(|x| Local::now() < x)
+    }
+
+    /// Record a failed request, returns true if it opened the circuit
+    pub fn failure(&mut self, threshold: u32, cooldown: chrono::Duration) -> bool {
+        self.failures += 1;
+        if self.failures < threshold || self.is_open() {
+            return false;
+        }
+        self.open_until = Some(Local::now() + cooldown);
+        true
+    }
+
+    /// Record a successful request, closing the circuit
+    pub fn success(&mut self) {
+        self.failures = 0;
+        self.open_until = None;
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[cfg_attr(feature = "web_api", derive(utoipa::ToSchema))]
+pub struct Wager {
+    pub outcome_id: String,
+    pub points: u32,
+    /// Twitch reported the bet on the user's predictions topic, so the points are its total
+    pub confirmed: bool,
+    /// Points of the wager already taken out of the balance, simulated bets never are
+    pub debited: u32,
 }
 
 #[derive(Debug, Default, Clone, Serialize)]
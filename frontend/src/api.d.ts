@@ -20,6 +20,118 @@ export interface paths {
         patch?: never;
         trace?: never;
     };
+    "/api/analytics/compare": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["compare_summary"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/analytics/equity": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["equity"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/analytics/optimize": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post: operations["optimize"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/analytics/predictions": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["predictions_export"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/analytics/session": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["session"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/analytics/streamers/{name}": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post?: never;
+        delete: operations["reset_streamer"];
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/analytics/summary": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["summary"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
     "/api/analytics/timeline": {
         parameters: {
             query?: never;
@@ -36,6 +148,86 @@ export interface paths {
         patch?: never;
         trace?: never;
     };
+    "/api/analytics/top-movers": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["top_movers"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/analytics/watch": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["watch_summary"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/bet/batch": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post: operations["make_predictions"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/config/apply-preset": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post: operations["apply_preset"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/config/consistency": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["consistency"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
     "/api/config/presets": {
         parameters: {
             query?: never;
@@ -100,6 +292,22 @@ export interface paths {
         patch?: never;
         trace?: never;
     };
+    "/api/config/watch_only": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["get_watch_only"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
     "/api/config/watch_priority": {
         parameters: {
             query?: never;
@@ -132,6 +340,54 @@ export interface paths {
         patch?: never;
         trace?: never;
     };
+    "/api/debug/dump": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post: operations["dump_state"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/debug/pubsub": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["raw_pubsub"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/events": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["events"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
     "/api/logs": {
         parameters: {
             query?: never;
@@ -164,14 +420,30 @@ export interface paths {
         patch?: never;
         trace?: never;
     };
-    "/api/predictions/live": {
+    "/api/predictions/blacklist": {
         parameters: {
             query?: never;
             header?: never;
             path?: never;
             cookie?: never;
         };
-        get: operations["get_live_prediction"];
+        get?: never;
+        put?: never;
+        post: operations["update_blacklist"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/predictions/budget": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["get_daily_budget"];
         put?: never;
         post?: never;
         delete?: never;
@@ -180,14 +452,14 @@ export interface paths {
         patch?: never;
         trace?: never;
     };
-    "/api/streamers/live": {
+    "/api/predictions/live": {
         parameters: {
             query?: never;
             header?: never;
             path?: never;
             cookie?: never;
         };
-        get: operations["live_streamers"];
+        get: operations["get_live_prediction"];
         put?: never;
         post?: never;
         delete?: never;
@@ -196,15 +468,15 @@ export interface paths {
         patch?: never;
         trace?: never;
     };
-    "/api/streamers/mine/{channel_name}": {
+    "/api/predictions/pending": {
         parameters: {
             query?: never;
             header?: never;
             path?: never;
             cookie?: never;
         };
-        get?: never;
-        put: operations["mine_streamer"];
+        get: operations["get_pending_predictions"];
+        put?: never;
         post?: never;
         delete?: never;
         options?: never;
@@ -212,7 +484,7 @@ export interface paths {
         patch?: never;
         trace?: never;
     };
-    "/api/streamers/mine/{channel_name}/": {
+    "/api/predictions/pending/{event_id}": {
         parameters: {
             query?: never;
             header?: never;
@@ -222,246 +494,1864 @@ export interface paths {
         get?: never;
         put?: never;
         post?: never;
-        delete: operations["remove_streamer"];
+        delete: operations["cancel_pending_prediction"];
         options?: never;
         head?: never;
         patch?: never;
         trace?: never;
     };
-    "/api/streamers/{streamer}": {
+    "/api/predictions/resume": {
         parameters: {
             query?: never;
             header?: never;
             path?: never;
             cookie?: never;
         };
-        get: operations["streamer"];
+        get?: never;
         put?: never;
-        post?: never;
+        post: operations["resume_betting"];
         delete?: never;
         options?: never;
         head?: never;
         patch?: never;
         trace?: never;
     };
-}
-export type webhooks = Record<string, never>;
+    "/api/streamers": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["list_streamers"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/streamers/live": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["live_streamers"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/streamers/mine/{channel_name}": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put: operations["mine_streamer"];
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/streamers/mine/{channel_name}/": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post?: never;
+        delete: operations["remove_streamer"];
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/streamers/{streamer}": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["streamer"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/streamers/{streamer}/last-decision": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["last_decision"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/streamers/{streamer}/points": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post: operations["correct_points"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/streamers/{streamer}/refresh": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post: operations["refresh_streamer"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/streamers/{streamer}/tags": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post: operations["set_tags"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/token/reload": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post: operations["reload_token"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/version": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["version"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/watching": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["get_watching"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/ws/status": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["get_ws_status"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/api/ws/topics": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["get_ws_topics"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+}
+export type webhooks = Record<string, never>;
 export interface components {
     schemas: {
         AddUpdatePreset: {
             config: components["schemas"]["StreamerConfig"];
             name: string;
         };
-        ConfigType: {
-            Preset: string;
-        } | {
-            Specific: components["schemas"]["StreamerConfig"];
-        };
-        ConfigTypeRef: {
-            Preset: string;
-        } | "Specific";
-        DefaultPrediction: {
-            /** Format: double */
-            max_percentage?: number;
-            /** Format: double */
-            min_percentage?: number;
-            points: components["schemas"]["Points"];
-        };
-        Detailed: {
-            default: components["schemas"]["DefaultPrediction"];
-            detailed?: components["schemas"]["DetailedOdds"][] | null;
-        };
-        DetailedOdds: {
-            _type: components["schemas"]["OddsComparisonType"];
-            /** Format: double */
-            attempt_rate: number;
-            points: components["schemas"]["Points"];
-            /** Format: double */
-            threshold: number;
-        };
-        /** @description Event */
-        Event: {
-            /** @description Channel ID */
-            channel_id: string;
-            created_at: components["schemas"]["Timestamp"];
-            ended_at?: components["schemas"]["Timestamp"] | null;
-            /** @description ID */
-            id: string;
-            locked_at?: components["schemas"]["Timestamp"] | null;
-            /** @description Outcomes */
-            outcomes: components["schemas"]["Outcome"][];
+        AnalyticsSummary: {
             /**
              * Format: int64
-             * @description Prediction window in seconds
+             * @description Predictions a bet was placed on
              */
-            prediction_window_seconds: number;
-            /** @description Status */
-            status: string;
-            /** @description Title */
-            title: string;
-            /** @description Winning outcome ID */
-            winning_outcome_id?: string | null;
-        };
-        Filter: {
-            /** Format: int32 */
-            TotalUsers: number;
+            bets_placed: number;
+            /**
+             * Format: int64
+             * @description Bets placed on the winning outcome
+             */
+            bets_won: number;
+            /**
+             * Format: int64
+             * @description Change in points across all channels, between the first and last entry of each channel
+             */
+            points_change: number;
+            /** Format: int64 */
+            points_wagered: number;
+            /**
+             * Format: int64
+             * @description Predictions started in the range
+             */
+            predictions: number;
+        };
+        AppEvent: {
+            channel_name: string;
+            event_id: string;
+            outcome_id: string;
+            /** Format: int32 */
+            points: number;
+        } | {
+            channel_name: string;
+            event_id: string;
+            winning_outcome_id?: string | null;
+        } | {
+            channel_name: string;
+            /** Format: int32 */
+            points: number;
+        } | {
+            channel_name: string;
+            live: boolean;
+        } | {
+            degraded: boolean;
+            /** Format: int32 */
+            failed_reconnects: number;
+        } | {
+            channel_name: string;
+            event_id: string;
+            outcome_id: string;
+            /** Format: int32 */
+            points: number;
+            /** Format: int32 */
+            threshold: number;
+        } | {
+            channel_name: string;
+            /** Format: int32 */
+            goal: number;
+            /** Format: int32 */
+            points: number;
+        } | {
+            drop_instance_id: string;
+            name: string;
+        } | {
+            /** Format: int64 */
+            loss: number;
+            /** Format: int64 */
+            peak: number;
+            /** Format: int64 */
+            window_seconds: number;
+        };
+        /** @description Filters for the streamers in the application state, all streamers are returned by default */
+        AppStateQuery: {
+            /** @description Only streamers with an active prediction, or without one if false */
+            has_prediction?: boolean | null;
+            /** @description Only streamers that are live, or offline if false */
+            live?: boolean | null;
+            /** @description Comma separated list of streamer names */
+            names?: string | null;
+        };
+        ApplyPreset: {
+            /** @description Name of the preset to apply */
+            preset: string;
+            streamers: components["schemas"]["ApplyPresetTarget"];
+        };
+        ApplyPresetTarget: "All" | {
+            /** @description Streamers with these channel names */
+            Names: string[];
+        } | {
+            /** @description Streamers currently in this game category */
+            Game: string;
+        };
+        BatchBet: components["schemas"]["MakePrediction"] & {
+            /** @description Name of the streamer */
+            streamer: string;
+        };
+        BatchBetResult: {
+            error?: components["schemas"]["ErrorBody"] | null;
+            event_id: string;
+            /**
+             * Format: int32
+             * @description Status the single bet endpoint would have responded with
+             */
+            status: number;
+            streamer: string;
+        };
+        BetResult: {
+            /**
+             * Format: int32
+             * @description Points paid out, including the bet itself
+             */
+            points_won?: number | null;
+            type: components["schemas"]["BetResultType"];
+        };
+        /** @enum {string} */
+        BetResultType: "WIN" | "LOSE" | "REFUND";
+        Circuit: {
+            /**
+             * Format: int32
+             * @description Consecutive failed GQL requests
+             */
+            failures: number;
+            /**
+             * Format: date-time
+             * @description GQL interactions are skipped until this time
+             */
+            open_until?: string | null;
+        };
+        ClaimStats: {
+            /**
+             * Format: int64
+             * @description Failed claim attempts, including ones that succeeded when retried
+             */
+            failed: number;
+            /**
+             * Format: int64
+             * @description Bonuses claimed
+             */
+            succeeded: number;
+        };
+        /** @enum {string} */
+        CombineRule: "Agreement" | "Average" | "HighestWeight";
+        Comparison: {
+            primary: components["schemas"]["AnalyticsSummary"];
+            secondary: components["schemas"]["AnalyticsSummary"];
+        };
+        Composite: {
+            combine?: components["schemas"]["CombineRule"];
+            /** @description Weights are relative to each other and must sum to 100 */
+            strategies: components["schemas"]["WeightedStrategy"][];
+        };
+        /** @description Streamers that differ between the config and the streamers being mined */
+        ConfigDrift: {
+            /** @description Streamers in the config that are not mined */
+            missing: string[];
+            /** @description Mined streamers without an entry in the config */
+            orphaned: string[];
+        };
+        ConfigType: {
+            Preset: string;
+        } | {
+            Specific: components["schemas"]["StreamerConfig"];
+        };
+        ConfigTypeRef: {
+            Preset: string;
+        } | "Specific";
+        DailyBudget: {
+            /**
+             * Format: int32
+             * @description Configured daily_bet_cap
+             */
+            cap?: number | null;
+            /**
+             * Format: int32
+             * @description Points that can still be bet today, without a cap there is no limit
+             */
+            remaining?: number | null;
+            /**
+             * Format: int32
+             * @description Points bet across all streamers today
+             */
+            wagered: number;
+        };
+        /** @description Why the last prediction considered on a streamer was bet on or skipped */
+        DecisionTrace: {
+            /** Format: date-time */
+            decided_at: string;
+            event_id: string;
+            /** @description Filters in config order, up to the first one the prediction did not pass */
+            filters: components["schemas"]["FilterCheck"][];
+            /** @description Odds of every outcome, as computed by the strategy */
+            odds: components["schemas"]["OutcomeOdds"][];
+            outcome_id?: string | null;
+            /**
+             * Format: int32
+             * @description Final stake, after jitter and the daily bet cap
+             */
+            points?: number | null;
+            /** @description Why nothing was bet */
+            skip_reason?: string | null;
+            /** @description Strategy that picked the bet, `primary` or `fallback` */
+            strategy?: string | null;
+        };
+        DefaultPrediction: {
+            /** Format: double */
+            max_percentage?: number;
+            /** Format: double */
+            min_percentage?: number;
+            points: components["schemas"]["Points"];
+        };
+        Detailed: {
+            /**
+             * Format: int32
+             * @description Round bets to the nearest multiple of this value, 0 or 1 to disable
+             */
+            bet_rounding?: number;
+            /** @description Place additional bets on the same outcome when a detailed odds rule is satisfied after the first bet */
+            chase_odds?: boolean;
+            default: components["schemas"]["DefaultPrediction"];
+            detailed?: components["schemas"]["DetailedOdds"][] | null;
+            /** @description Only consider this many outcomes with the largest pools, so the thin outcomes of large predictions are never bet on */
+            max_outcomes?: number | null;
+            /**
+             * Format: double
+             * @description Lowest probability (0 - 100) an outcome needs before the default odds config bets on it. Unlike the
+             *     min_percentage to max_percentage band, it has no upper bound, so in predictions with many outcomes it keeps
+             *     bets off long shots without also ruling out favorites
+             */
+            min_confidence?: number | null;
+            /**
+             * Format: int64
+             * @description Never bet on outcomes backed by fewer users than this, however good their odds look
+             */
+            min_outcome_users?: number | null;
+            prefer?: components["schemas"]["RangePreference"] | null;
+            probability_source?: components["schemas"]["ProbabilitySource"];
+            /** @description Never bet on outcomes nobody has wagered on, and skip predictions with no points wagered at all */
+            skip_empty_outcomes?: boolean;
+        };
+        DetailedOdds: {
+            _type: components["schemas"]["OddsComparisonType"];
+            /** Format: double */
+            attempt_rate: number;
+            points: components["schemas"]["Points"];
+            /** Format: double */
+            threshold: number;
+        };
+        /** @description Time range, RFC3339 strings. Times without an offset are in the configured timezone */
+        EquityRange: {
+            /** @description GE time */
+            from: string;
+            /** @description LE time */
+            to: string;
+        };
+        EquitySnapshot: {
+            /**
+             * Format: int64
+             * @description Total balance across all streamers
+             */
+            balance: number;
+            /** Format: date-time */
+            created_at: string;
+        };
+        /** @description Body of every error response of the API */
+        ErrorBody: {
+            /** @description Stable identifier of the kind of error, e.g. streamer_does_not_exist */
+            code: string;
+            /** @description Human readable description of the error */
+            error: string;
+        };
+        /** @description Event */
+        Event: {
+            /** @description Channel ID */
+            channel_id: string;
+            created_at: components["schemas"]["Timestamp"];
+            ended_at?: components["schemas"]["Timestamp"] | null;
+            /** @description ID */
+            id: string;
+            locked_at?: components["schemas"]["Timestamp"] | null;
+            /** @description Outcomes */
+            outcomes: components["schemas"]["Outcome"][];
+            /**
+             * Format: int64
+             * @description Prediction window in seconds
+             */
+            prediction_window_seconds: number;
+            /** @description Status */
+            status: string;
+            /** @description Title */
+            title: string;
+            /** @description Winning outcome ID */
+            winning_outcome_id?: string | null;
+        };
+        Filter: {
+            /** Format: int32 */
+            TotalUsers: number;
         } | {
             /** Format: int32 */
             DelaySeconds: number;
         } | {
             /** Format: double */
             DelayPercentage: number;
+        } | {
+            /** @description Only bet on outcomes with one of these titles, compared case-insensitively */
+            OutcomeAllowTitles: string[];
+        } | {
+            /** @description Never bet on outcomes with one of these colors, e.g. PINK, compared case-insensitively */
+            OutcomeExcludeColors: string[];
+        } | "SkipIfAlreadyBet" | {
+            /**
+             * @description Only bet from `start_hour` until `end_hour` in the configured timezone, e.g. 22 to 6 wraps past midnight.
+             *     The end hour is excluded, and equal hours cover the whole day
+             */
+            TimeWindow: {
+                /** Format: int32 */
+                end_hour: number;
+                /** Format: int32 */
+                start_hour: number;
+            };
+        } | {
+            /**
+             * @description Only bet while the stream is in one of these categories, compared case-insensitively. Set `game_refresh_seconds`
+             *     to notice a streamer switching categories mid-stream
+             */
+            Games: string[];
+        };
+        FilterCheck: {
+            filter: components["schemas"]["Filter"];
+            passed: boolean;
         };
         Game: {
             id: string;
             name: string;
         };
+        HumanizeQuery: {
+            /** @description Also include the points abbreviated for display, e.g. 1.23M */
+            humanize?: boolean;
+        };
+        ListQuery: {
+            /** @description Maximum number of streamers returned, all if not specified */
+            limit?: number | null;
+            /** @description Streamers skipped after sorting */
+            offset?: number;
+            order?: components["schemas"]["SortOrder"];
+            sort?: components["schemas"]["SortBy"];
+        };
         LiveStreamer: {
             /** Format: int32 */
             id: number;
-            state: components["schemas"]["StreamerState"];
+            state: components["schemas"]["StreamerResponse"];
+        };
+        LogQuery: {
+            /**
+             * Format: int64
+             * @description Stop reading the log file after this many bytes from its end, at most 4 MiB
+             */
+            max_bytes?: number | null;
+            page: number;
+            /** @description Lines per page, at most 1000 */
+            per_page: number;
+        };
+        MakePrediction: {
+            /** @description ID of the prediction */
+            event_id: string;
+            /** @description The outcome to place the bet on */
+            outcome_id: string;
+            /**
+             * Format: int32
+             * @description If specified, a bet is forcefully placed, otherwise the prediction logic specified in the configuration is used
+             */
+            points?: number | null;
+        };
+        MineStreamer: {
+            config: components["schemas"]["ConfigType"];
+        };
+        /** @description Webhook that events are posted to as JSON, e.g. a discord channel's webhook */
+        NotifyTarget: {
+            /** @description Names of the events to post, e.g. bet_placed or prediction_ended. All events if not specified */
+            events?: string[] | null;
+            webhook_url: string;
+        };
+        /** @enum {string} */
+        OddsComparisonType: "Le" | "Ge";
+        OptimizeResult: {
+            /**
+             * Format: int64
+             * @description Bytes reclaimed by the vacuum
+             */
+            reclaimed: number;
+            /**
+             * Format: int64
+             * @description Database size in bytes after optimizing
+             */
+            size_after: number;
+            /**
+             * Format: int64
+             * @description Database size in bytes before optimizing
+             */
+            size_before: number;
+        };
+        Outcome: {
+            /** @description Display color of the outcome, empty for entries stored before it was recorded */
+            color?: string;
+            id: string;
+            title: string;
+            /** Format: int64 */
+            total_points: number;
+            /** Format: int64 */
+            total_users: number;
+        };
+        OutcomeOdds: {
+            /**
+             * Format: double
+             * @description Between 0 and 1
+             */
+            odds: number;
+            outcome_id: string;
+            title: string;
+        };
+        Outcomes: components["schemas"]["Outcome"][];
+        /** @description Outcomes observed over the prediction window, oldest first */
+        OutcomesHistory: components["schemas"]["OutcomesSnapshot"][];
+        OutcomesSnapshot: {
+            /** Format: date-time */
+            at: string;
+            outcomes: components["schemas"]["Outcome"][];
+        };
+        PendingPrediction: {
+            /** @description ID of the prediction */
+            event_id: string;
+            locked: boolean;
+            /** @description If a bet was placed, or the attempt was cancelled */
+            placed: boolean;
+            /**
+             * Format: int64
+             * @description Seconds left until the prediction locks, 0 once locked
+             */
+            seconds_until_lock: number;
+            /** @description Name of the streamer */
+            streamer: string;
+            title: string;
+            wagered?: components["schemas"]["Wager"] | null;
+        };
+        Point: {
+            /** Format: int32 */
+            channel_id: number;
+            /** Format: date-time */
+            created_at: string;
+            points_info: components["schemas"]["PointsInfo"];
+            /** Format: int32 */
+            points_value: number;
+        };
+        Points: {
+            /** Format: int32 */
+            max_value: number;
+            /** Format: double */
+            percent: number;
+        };
+        PointsCorrection: {
+            /**
+             * Format: int32
+             * @description Balance of the streamer on twitch
+             */
+            value: number;
+        };
+        PointsInfo: "FirstEntry" | "SessionBaseline" | "Watching" | "CommunityPointsClaimed" | {
+            /** @description prediction event id */
+            Prediction: Record<string, never>[];
+        } | "Raid" | "WatchStreak" | "ManualCorrection";
+        PointsMover: {
+            /** Format: int32 */
+            channel_id: number;
+            name?: string | null;
+            /**
+             * Format: int32
+             * @description Last balance in the range minus the first
+             */
+            net: number;
+        };
+        Prediction: {
+            /** Format: int32 */
+            channel_id: number;
+            /** Format: date-time */
+            closed_at?: string | null;
+            /** Format: date-time */
+            created_at: string;
+            outcomes: components["schemas"]["Outcomes"];
+            outcomes_history: components["schemas"]["OutcomesHistory"];
+            placed_bet: components["schemas"]["PredictionBetWrapper"];
+            prediction_id: string;
+            /** Format: int64 */
+            prediction_window: number;
+            title: string;
+            winning_outcome_id?: string | null;
+        };
+        PredictionBet: {
+            outcome_id: string;
+            /** Format: int32 */
+            points: number;
+            result?: components["schemas"]["BetResult"] | null;
+            /** @description Bet was only simulated, no points were wagered */
+            simulated?: boolean;
+        };
+        PredictionBetWrapper: "None" | {
+            Some: components["schemas"]["PredictionBet"];
+        } | "Blacklisted";
+        PredictionBlacklist: {
+            event_ids?: string[];
+            /** @description Case-insensitive keywords matched against the prediction title */
+            title_keywords?: string[];
+        };
+        PredictionConfig: {
+            fallback?: components["schemas"]["Strategy"] | null;
+            filters: components["schemas"]["Filter"][];
+            strategy: components["schemas"]["Strategy"];
+        };
+        /** @description Flat record of a single prediction, for exports */
+        PredictionRecord: {
+            /** @description Outcome bet on, null if no bet was placed */
+            bet_outcome_id?: string | null;
+            /**
+             * Format: int64
+             * @description Points bet, 0 if no bet was placed
+             */
+            bet_points: number;
+            /** @description Bet was only simulated, no points were wagered */
+            bet_simulated: boolean;
+            /** @description Not bet on because the prediction matched the blacklist */
+            blacklisted: boolean;
+            /** Format: int32 */
+            channel_id: number;
+            channel_name?: string | null;
+            /** @description RFC3339 timestamp, null while the prediction is open */
+            closed_at?: string | null;
+            /** @description RFC3339 timestamp */
+            created_at: string;
+            /** @description Final outcomes of the prediction */
+            outcomes: components["schemas"]["Outcome"][];
+            prediction_id: string;
+            /**
+             * Format: int64
+             * @description Seconds bets were accepted for
+             */
+            prediction_window: number;
+            title: string;
+            winning_outcome_id?: string | null;
+            /** @description If the bet won, null if no bet was placed or the prediction has not been resolved */
+            won?: boolean | null;
+        };
+        /** @description Time range and channels, RFC3339 strings. Times without an offset are in the configured timezone */
+        PredictionsRange: {
+            /** @description Comma separated channel IDs, all channels if not specified */
+            channels?: string | null;
+            /** @description GE time */
+            from: string;
+            /** @description LE time */
+            to: string;
+        };
+        ProbabilitySource: "Points" | "Users" | {
+            /**
+             * Format: double
+             * @description Mix of both, the value is the weight given to the users share (0 - 100)
+             */
+            Blend: number;
+        };
+        PubSub: {
+            /** @description Bets are paused as the total balance is below `global_min_balance` */
+            below_min_balance: boolean;
+            claims: components["schemas"]["ClaimStats"];
+            configs: {
+                [key: string]: components["schemas"]["StreamerConfigRefWrapper"] | undefined;
+            };
+            /** @description All bets are stopped as `panic_stop` was triggered, until resumed through the API */
+            panic_stopped: boolean;
+            simulate: boolean;
+            streamers: {
+                [key: string]: components["schemas"]["StreamerState"] | undefined;
+            };
+            user_id: string;
+            user_name: string;
+            watch_only: boolean;
+            watching: components["schemas"]["StreamerState"][];
+        };
+        /** @enum {string} */
+        RangePreference: "HighestInRange" | "LowestInRange" | "ClosestToMidpoint";
+        ResetQuery: {
+            /** @description Must be true, guards against accidentally removing data */
+            confirm?: boolean;
+        };
+        ResetResult: {
+            /** @description Points entries removed */
+            points: number;
+            /** @description Predictions removed */
+            predictions: number;
+            /** @description Viewership pings removed */
+            watch_events: number;
+        };
+        SessionChange: {
+            /**
+             * Format: int32
+             * @description Balance when the miner started
+             */
+            baseline: number;
+            /** Format: int32 */
+            channel_id: number;
+            name?: string | null;
+            /**
+             * Format: int32
+             * @description Change in points since the miner started
+             */
+            net: number;
+            /**
+             * Format: int32
+             * @description Latest known balance
+             */
+            points: number;
+        };
+        /** @enum {string} */
+        SortBy: "name" | "points" | "live" | "predictions" | "last_live_at";
+        /** @enum {string} */
+        SortOrder: "asc" | "desc";
+        Strategy: {
+            detailed: components["schemas"]["Detailed"];
+        } | {
+            composite: components["schemas"]["Composite"];
+        } | {
+            timeDecay: components["schemas"]["TimeDecay"];
+        };
+        StreamerConfig: {
+            /** @description Place bets on predictions, enabled if not specified */
+            betting?: boolean | null;
+            follow_raid: boolean;
+            /**
+             * Format: int64
+             * @description Only bet once the stream has been live for this many seconds
+             */
+            min_live_seconds?: number | null;
+            notify?: components["schemas"]["NotifyTarget"] | null;
+            /**
+             * Format: int32
+             * @description Balance to save towards on this streamer, overrides the global points_goal
+             */
+            points_goal?: number | null;
+            prediction: components["schemas"]["PredictionConfig"];
+            /** @description Simulate bets on this streamer instead of placing them, the --simulate argument is used if not specified */
+            simulate?: boolean | null;
+        };
+        StreamerConfigRefWrapper: {
+            _type: components["schemas"]["ConfigTypeRef"];
+            config: components["schemas"]["StreamerConfig"];
+        };
+        StreamerInfo: {
+            broadcastId?: components["schemas"]["UserId"] | null;
+            channelName: string;
+            game?: components["schemas"]["Game"] | null;
+            live: boolean;
+        };
+        StreamerList: {
+            streamers: components["schemas"]["StreamerSummary"][];
+            /** @description Number of tracked streamers, before the limit and offset are applied */
+            total: number;
+        };
+        StreamerResponse: components["schemas"]["StreamerState"] & {
+            /** @description Points abbreviated for display, only included with humanize=true */
+            points_humanized?: string | null;
+        };
+        StreamerState: {
+            circuit: components["schemas"]["Circuit"];
+            config: components["schemas"]["StreamerConfigRefWrapper"];
+            info: components["schemas"]["StreamerInfo"];
+            /**
+             * Format: date-time
+             * @description Last time the stream was seen going live or offline
+             */
+            last_live_at?: string | null;
+            /** Format: int32 */
+            points: number;
+            /** @description Whether the balance is at or above the points goal */
+            points_goal_reached: boolean;
+            predictions: {
+                [key: string]: (components["schemas"]["Event"] & boolean)[] | undefined;
+            };
+            /** @description Freeform tags to group channels in analytics */
+            tags: string[];
+            /** @description Cumulative bets placed per event ID */
+            wagered: {
+                [key: string]: components["schemas"]["Wager"] | undefined;
+            };
+        };
+        StreamerSummary: {
+            /** Format: int32 */
+            id: number;
+            /** Format: date-time */
+            last_live_at?: string | null;
+            live: boolean;
+            name: string;
+            /** Format: int32 */
+            points: number;
+            /** @description Number of active predictions */
+            predictions: number;
+        };
+        /** @description Time range, RFC3339 strings. Times without an offset are in the configured timezone */
+        SummaryRange: {
+            /** @description GE time */
+            from: string;
+            /** @description Only channels with this tag */
+            tag?: string | null;
+            /** @description LE time */
+            to: string;
+        };
+        TimeDecay: {
+            /**
+             * Format: double
+             * @description Share of the prediction window (0 - 100) from which the full stake is bet
+             */
+            full_percentage?: number;
+            /**
+             * Format: double
+             * @description Share of the prediction window (0 - 100) that has to pass before anything is bet
+             */
+            start_percentage?: number;
+            strategy: components["schemas"]["Detailed"];
+        };
+        /** @description Timeline information, RFC3339 strings. Times without an offset are in the configured timezone */
+        Timeline: {
+            /** @description Channels */
+            channels: number[];
+            /** @description GE time */
+            from: string;
+            /** @description Only channels with this tag, all channels with the tag if no channels are given */
+            tag?: string | null;
+            /** @description LE time */
+            to: string;
+        };
+        TimelineResult: {
+            /** Format: int32 */
+            difference?: number | null;
+            point: components["schemas"]["Point"];
+            prediction?: components["schemas"]["Prediction"] | null;
+        };
+        /** @description RFC3339 timestamp */
+        Timestamp: string;
+        TopMovers: {
+            /** @description Largest gains first */
+            gainers: components["schemas"]["PointsMover"][];
+            /** @description Largest losses first */
+            losers: components["schemas"]["PointsMover"][];
+        };
+        /** @description Time range, RFC3339 strings. Times without an offset are in the configured timezone */
+        TopMoversQuery: {
+            /** @description GE time */
+            from: string;
+            /** @description Number of gainers and of losers, 5 by default */
+            limit?: number | null;
+            /** @description LE time */
+            to: string;
+        };
+        UserId: string;
+        Version: {
+            /** @description RFC3339 time of the build */
+            built_at: string;
+            /** @description Short hash of the git commit the binary was built from */
+            commit: string;
+            /** @description Cargo features the binary was built with */
+            features: string[];
+            version: string;
+        };
+        Wager: {
+            /** @description Twitch reported the bet on the user's predictions topic, so the points are its total */
+            confirmed: boolean;
+            /**
+             * Format: int32
+             * @description Points of the wager already taken out of the balance, simulated bets never are
+             */
+            debited: number;
+            outcome_id: string;
+            /** Format: int32 */
+            points: number;
+        };
+        /** @description Time range, RFC3339 strings. Times without an offset are in the configured timezone */
+        WatchRange: {
+            /** @description GE time */
+            from: string;
+            /** @description LE time */
+            to: string;
+        };
+        /** @enum {string} */
+        WatchReason: "streak" | "drops" | "priority" | "fill";
+        WatchSummary: {
+            /** Format: int32 */
+            channel_id: number;
+            /**
+             * Format: int64
+             * @description Number of viewership pings that failed
+             */
+            failures: number;
+            /**
+             * Format: int64
+             * @description Distinct minutes with at least one successful viewership ping
+             */
+            minutes_watched: number;
+            name?: string | null;
+        };
+        WatchedStreamer: {
+            channel_name: string;
+            reason: components["schemas"]["WatchReason"];
+        };
+        WeightedStrategy: {
+            strategy: components["schemas"]["Detailed"];
+            /** Format: double */
+            weight: number;
+        };
+        WsConnTopics: {
+            topics: string[];
+        };
+        WsPoolStatus: {
+            /** @description Number of open connections */
+            connections: number;
+            /** @description Connecting to twitch pubsub failed too many times in a row */
+            degraded: boolean;
+            /**
+             * Format: int32
+             * @description Consecutive failed connection attempts
+             */
+            failed_reconnects: number;
+            /** @description Topics waiting for a free connection */
+            queued_topics: number;
+        };
+    };
+    responses: never;
+    parameters: never;
+    requestBodies: never;
+    headers: never;
+    pathItems: never;
+}
+export type $defs = Record<string, never>;
+export interface operations {
+    app_state: {
+        parameters: {
+            query?: {
+                /** @description Only streamers that are live, or offline if false */
+                live?: boolean | null;
+                /** @description Only streamers with an active prediction, or without one if false */
+                has_prediction?: boolean | null;
+                /** @description Comma separated list of streamer names */
+                names?: string | null;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Get the entire application state information */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["PubSub"];
+                };
+            };
+        };
+    };
+    compare_summary: {
+        parameters: {
+            query: {
+                /** @description GE time */
+                from: string;
+                /** @description LE time */
+                to: string;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Summaries of both analytics databases in the specified range */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["Comparison"];
+                };
+            };
+            /** @description No comparison analytics database was loaded */
+            404: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    equity: {
+        parameters: {
+            query: {
+                /** @description GE time */
+                from: string;
+                /** @description LE time */
+                to: string;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Snapshots of the total balance across streamers in the specified range, oldest first */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["EquitySnapshot"][];
+                };
+            };
+            /** @description Invalid timestamp */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    optimize: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Vacuum and analyze the analytics database, returning the reclaimed size */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["OptimizeResult"];
+                };
+            };
+        };
+    };
+    predictions_export: {
+        parameters: {
+            query: {
+                /** @description GE time */
+                from: string;
+                /** @description LE time */
+                to: string;
+                /** @description Comma separated channel IDs, all channels if not specified */
+                channels?: string | null;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Flat records of the predictions started in the specified range, oldest first */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["PredictionRecord"][];
+                };
+            };
+            /** @description Invalid timestamp or channel IDs */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    session: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Change in points per channel since the miner started */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["SessionChange"][];
+                };
+            };
+        };
+    };
+    reset_streamer: {
+        parameters: {
+            query?: {
+                /** @description Must be true, guards against accidentally removing data */
+                confirm?: boolean;
+            };
+            header?: never;
+            path: {
+                /** @description Name of streamer */
+                name: string;
+            };
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Remove all points, predictions and viewership pings of the streamer, keeping its name */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["ResetResult"];
+                };
+            };
+            /** @description Streamer does not exist, or the reset was not confirmed */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    summary: {
+        parameters: {
+            query: {
+                /** @description GE time */
+                from: string;
+                /** @description LE time */
+                to: string;
+                /** @description Only channels with this tag */
+                tag?: string | null;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Predictions, bets and change in points in the specified range */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["AnalyticsSummary"];
+                };
+            };
+            /** @description Invalid timestamp */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    points_timeline: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["Timeline"];
+            };
+        };
+        responses: {
+            /** @description Timeline of point information in the specified range */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["TimelineResult"][];
+                };
+            };
+        };
+    };
+    top_movers: {
+        parameters: {
+            query: {
+                /** @description GE time */
+                from: string;
+                /** @description LE time */
+                to: string;
+                /** @description Number of gainers and of losers, 5 by default */
+                limit?: number | null;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Channels with the largest net gains and losses in points over the range */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["TopMovers"];
+                };
+            };
+            /** @description Invalid timestamp */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    watch_summary: {
+        parameters: {
+            query: {
+                /** @description GE time */
+                from: string;
+                /** @description LE time */
+                to: string;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Minutes watched and failed viewership pings per channel in the specified range */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["WatchSummary"][];
+                };
+            };
+        };
+    };
+    make_predictions: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["BatchBet"][];
+            };
+        };
+        responses: {
+            /** @description Every bet was placed or declined by the prediction logic, results are in the order of the request */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["BatchBetResult"][];
+                };
+            };
+            /** @description Some bets failed */
+            207: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["BatchBetResult"][];
+                };
+            };
+            /** @description A bet on the same streamer and event ID appears more than once, no bet was placed */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    apply_preset: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["ApplyPreset"];
+            };
+        };
+        responses: {
+            /** @description Names of the streamers the preset was applied to */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": string[];
+                };
+            };
+            /** @description Preset does not exist */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+            /** @description Could not find a streamer */
+            404: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    consistency: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Streamers that differ between the config and the streamers being mined */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["ConfigDrift"];
+                };
+            };
+        };
+    };
+    get_presets: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Get all preset configurations */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": {
+                        [key: string]: components["schemas"]["StreamerConfig"] | undefined;
+                    }[];
+                };
+            };
+        };
+    };
+    add_update_preset: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["AddUpdatePreset"];
+            };
+        };
+        responses: {
+            /** @description Successfully created a preset configuration */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    remove_preset: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path: {
+                /** @description Name of the preset to delete */
+                name: string;
+            };
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Successfully removed the preset configuration */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    update_streamer_config: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path: {
+                /** @description Name of streamer whose config to update */
+                channel_name: string;
+            };
+            cookie?: never;
+        };
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["ConfigType"];
+            };
+        };
+        responses: {
+            /** @description Successfully updated streamer config */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+            /** @description Could not find streamer */
+            404: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    get_watch_only: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description If betting is disabled for all streamers */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "text/plain": boolean;
+                };
+            };
+        };
+    };
+    get_watch_priority: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Successfully removed the preset configuration */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": string[];
+                };
+            };
+        };
+    };
+    update_watch_priority: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody: {
+            content: {
+                "application/json": string[];
+            };
         };
-        LogQuery: {
-            page: number;
-            per_page: number;
+        responses: {
+            /** @description Successfully created a preset configuration */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
         };
-        MakePrediction: {
-            /** @description ID of the prediction */
-            event_id: string;
-            /** @description The outcome to place the bet on */
-            outcome_id: string;
-            /**
-             * Format: int32
-             * @description If specified, a bet is forcefully placed, otherwise the prediction logic specified in the configuration is used
-             */
-            points?: number | null;
+    };
+    dump_state: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
         };
-        MineStreamer: {
-            config: components["schemas"]["ConfigType"];
+        requestBody?: never;
+        responses: {
+            /** @description Wrote the application state to a file, returns the file path */
+            201: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "text/plain": string;
+                };
+            };
         };
-        /** @enum {string} */
-        OddsComparisonType: "Le" | "Ge";
-        Outcome: {
-            id: string;
-            title: string;
-            /** Format: int64 */
-            total_points: number;
-            /** Format: int64 */
-            total_users: number;
+    };
+    raw_pubsub: {
+        parameters: {
+            query?: {
+                /** @description Number of messages to return, all kept messages by default */
+                n?: number | null;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
         };
-        Outcomes: components["schemas"]["Outcome"][];
-        Point: {
-            /** Format: int32 */
-            channel_id: number;
-            /** Format: date-time */
-            created_at: string;
-            points_info: components["schemas"]["PointsInfo"];
-            /** Format: int32 */
-            points_value: number;
+        requestBody?: never;
+        responses: {
+            /** @description Recently received raw pubsub messages, oldest first */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": string[];
+                };
+            };
         };
-        Points: {
-            /** Format: int32 */
-            max_value: number;
-            /** Format: double */
-            percent: number;
+    };
+    events: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
         };
-        PointsInfo: "FirstEntry" | "Watching" | "CommunityPointsClaimed" | {
-            /** @description prediction event id */
-            Prediction: Record<string, never>[];
+        requestBody?: never;
+        responses: {
+            /** @description Stream of bet_placed, prediction_ended, points_claimed and live_changed server-sent events */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "text/event-stream": components["schemas"]["AppEvent"];
+                };
+            };
         };
-        Prediction: {
-            /** Format: int32 */
-            channel_id: number;
-            /** Format: date-time */
-            closed_at?: string | null;
-            /** Format: date-time */
-            created_at: string;
-            outcomes: components["schemas"]["Outcomes"];
-            placed_bet: components["schemas"]["PredictionBetWrapper"];
-            prediction_id: string;
-            /** Format: int64 */
-            prediction_window: number;
-            title: string;
-            winning_outcome_id?: string | null;
+    };
+    get_logs: {
+        parameters: {
+            query: {
+                /** @description Lines per page, at most 1000 */
+                per_page: number;
+                page: number;
+                /** @description Stop reading the log file after this many bytes from its end, at most 4 MiB */
+                max_bytes?: number | null;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
         };
-        PredictionBet: {
-            outcome_id: string;
-            /** Format: int32 */
-            points: number;
+        requestBody?: never;
+        responses: {
+            /** @description Get last logs as rendered html */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "text/html": string;
+                };
+            };
         };
-        PredictionBetWrapper: "None" | {
-            Some: components["schemas"]["PredictionBet"];
+    };
+    make_prediction: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path: {
+                /** @description Name of streamer to get state for */
+                streamer: string;
+            };
+            cookie?: never;
         };
-        PredictionConfig: {
-            filters: components["schemas"]["Filter"][];
-            strategy: components["schemas"]["Strategy"];
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["MakePrediction"];
+            };
         };
-        PubSub: {
-            configs: {
-                [key: string]: components["schemas"]["StreamerConfigRefWrapper"] | undefined;
+        responses: {
+            /** @description Placed a bet */
+            201: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["Points"];
+                };
             };
-            simulate: boolean;
-            streamers: {
-                [key: string]: components["schemas"]["StreamerState"] | undefined;
+            /** @description Did not place a bet, but no error occurred */
+            202: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+            /** @description Could not find streamer or event ID */
+            404: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
             };
-            user_id: string;
-            user_name: string;
-            watching: components["schemas"]["StreamerState"][];
         };
-        Strategy: {
-            detailed: components["schemas"]["Detailed"];
+    };
+    update_blacklist: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
         };
-        StreamerConfig: {
-            follow_raid: boolean;
-            prediction: components["schemas"]["PredictionConfig"];
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["PredictionBlacklist"];
+            };
         };
-        StreamerConfigRefWrapper: {
-            _type: components["schemas"]["ConfigTypeRef"];
-            config: components["schemas"]["StreamerConfig"];
+        responses: {
+            /** @description Replaced the blacklist */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
         };
-        StreamerInfo: {
-            broadcastId?: components["schemas"]["UserId"] | null;
-            channelName: string;
-            game?: components["schemas"]["Game"] | null;
-            live: boolean;
+    };
+    get_daily_budget: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
         };
-        StreamerState: {
-            config: components["schemas"]["StreamerConfigRefWrapper"];
-            info: components["schemas"]["StreamerInfo"];
-            /** Format: int32 */
-            points: number;
-            predictions: {
-                [key: string]: (components["schemas"]["Event"] & boolean)[] | undefined;
+        requestBody?: never;
+        responses: {
+            /** @description Points bet today and what is left of the daily bet cap */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["DailyBudget"];
+                };
+            };
+        };
+    };
+    get_live_prediction: {
+        parameters: {
+            query: {
+                prediction_id: string;
+                channel_id: number;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Get live prediction */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["Prediction"] | null;
+                };
             };
         };
-        /** @description Timeline information, RFC3339 strings */
-        Timeline: {
-            /** @description Channels */
-            channels: number[];
-            /** @description GE time */
-            from: string;
-            /** @description LE time */
-            to: string;
-        };
-        TimelineResult: {
-            /** Format: int32 */
-            difference?: number | null;
-            point: components["schemas"]["Point"];
-            prediction?: components["schemas"]["Prediction"] | null;
-        };
-        /** @description RFC3339 timestamp */
-        Timestamp: string;
-        UserId: string;
     };
-    responses: never;
-    parameters: never;
-    requestBodies: never;
-    headers: never;
-    pathItems: never;
-}
-export type $defs = Record<string, never>;
-export interface operations {
-    app_state: {
+    get_pending_predictions: {
         parameters: {
             query?: never;
             header?: never;
@@ -470,128 +2360,201 @@ export interface operations {
         };
         requestBody?: never;
         responses: {
-            /** @description Get the entire application state information */
+            /** @description Active predictions and their bet status */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content: {
-                    "application/json": components["schemas"]["PubSub"];
+                    "application/json": components["schemas"]["PendingPrediction"][];
                 };
             };
         };
     };
-    points_timeline: {
+    cancel_pending_prediction: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path: {
+                /** @description ID of the prediction */
+                event_id: string;
+            };
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description Cancelled the bet attempt on the prediction */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+            /** @description Could not find an active prediction with the event ID */
+            404: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    resume_betting: {
         parameters: {
             query?: never;
             header?: never;
             path?: never;
             cookie?: never;
         };
-        requestBody: {
-            content: {
-                "application/json": components["schemas"]["Timeline"];
+        requestBody?: never;
+        responses: {
+            /** @description Resumed bets stopped by panic_stop, true if they were stopped */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "text/plain": boolean;
+                };
             };
         };
+    };
+    list_streamers: {
+        parameters: {
+            query?: {
+                /** @description Field to sort by, the name if not specified */
+                sort?: components["schemas"]["SortBy"];
+                order?: components["schemas"]["SortOrder"];
+                /** @description Maximum number of streamers returned, all if not specified */
+                limit?: number | null;
+                /** @description Streamers skipped after sorting */
+                offset?: number;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
         responses: {
-            /** @description Timeline of point information in the specified range */
+            /** @description Sorted list of all tracked streamers with their key fields */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content: {
-                    "application/json": components["schemas"]["TimelineResult"][];
+                    "application/json": components["schemas"]["StreamerList"];
+                };
+            };
+            /** @description Invalid sort field or order */
+            400: {
+                headers: {
+                    [name: string]: unknown;
                 };
+                content?: never;
             };
         };
     };
-    get_presets: {
+    live_streamers: {
         parameters: {
-            query?: never;
+            query?: {
+                /** @description Also include the points abbreviated for display, e.g. 1.23M */
+                humanize?: boolean;
+            };
             header?: never;
             path?: never;
             cookie?: never;
         };
         requestBody?: never;
         responses: {
-            /** @description Get all preset configurations */
+            /** @description List of live streamers and their state */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content: {
-                    "application/json": {
-                        [key: string]: components["schemas"]["StreamerConfig"] | undefined;
-                    }[];
+                    "application/json": components["schemas"]["LiveStreamer"][];
                 };
             };
         };
     };
-    add_update_preset: {
+    mine_streamer: {
         parameters: {
             query?: never;
             header?: never;
-            path?: never;
+            path: {
+                /** @description Name of streamer to watch */
+                channel_name: string;
+            };
             cookie?: never;
         };
         requestBody: {
             content: {
-                "application/json": components["schemas"]["AddUpdatePreset"];
+                "application/json": components["schemas"]["MineStreamer"];
             };
         };
         responses: {
-            /** @description Successfully created a preset configuration */
+            /** @description Add streamer to mine */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
-                content?: never;
+                content: {
+                    "application/json": unknown;
+                };
             };
         };
     };
-    remove_preset: {
+    remove_streamer: {
         parameters: {
             query?: never;
             header?: never;
             path: {
-                /** @description Name of the preset to delete */
-                name: string;
+                /** @description Name of streamer to delete */
+                channel_name: string;
             };
             cookie?: never;
         };
         requestBody?: never;
         responses: {
-            /** @description Successfully removed the preset configuration */
+            /** @description Successfully removed streamer from the mine list */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content?: never;
             };
+            /** @description Could not find streamer */
+            404: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
         };
     };
-    update_streamer_config: {
+    streamer: {
         parameters: {
-            query?: never;
+            query?: {
+                /** @description Also include the points abbreviated for display, e.g. 1.23M */
+                humanize?: boolean;
+            };
             header?: never;
             path: {
-                /** @description Name of streamer whose config to update */
-                channel_name: string;
+                /** @description Name of streamer to get state for */
+                streamer: string;
             };
             cookie?: never;
         };
-        requestBody: {
-            content: {
-                "application/json": components["schemas"]["ConfigType"];
-            };
-        };
+        requestBody?: never;
         responses: {
-            /** @description Successfully updated streamer config */
+            /** @description Get the entire application state information */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
-                content?: never;
+                content: {
+                    "application/json": components["schemas"]["StreamerResponse"][];
+                };
             };
             /** @description Could not find streamer */
             404: {
@@ -602,105 +2565,128 @@ export interface operations {
             };
         };
     };
-    get_watch_priority: {
+    last_decision: {
         parameters: {
             query?: never;
             header?: never;
-            path?: never;
+            path: {
+                /** @description Name of streamer to get the last decision of */
+                streamer: string;
+            };
             cookie?: never;
         };
         requestBody?: never;
         responses: {
-            /** @description Successfully removed the preset configuration */
+            /** @description Why the last prediction considered on the streamer was bet on or skipped, null if none was considered yet */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content: {
-                    "application/json": string[];
+                    "application/json": components["schemas"]["DecisionTrace"] | null;
+                };
+            };
+            /** @description Could not find streamer */
+            404: {
+                headers: {
+                    [name: string]: unknown;
                 };
+                content?: never;
             };
         };
     };
-    update_watch_priority: {
+    correct_points: {
         parameters: {
             query?: never;
             header?: never;
-            path?: never;
+            path: {
+                /** @description Name of streamer to correct */
+                streamer: string;
+            };
             cookie?: never;
         };
         requestBody: {
             content: {
-                "application/json": string[];
+                "application/json": components["schemas"]["PointsCorrection"];
             };
         };
         responses: {
-            /** @description Successfully created a preset configuration */
+            /** @description Set the balance of the streamer, recording it as a manual correction */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content?: never;
             };
+            /** @description Streamer does not exist */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
         };
     };
-    get_logs: {
+    refresh_streamer: {
         parameters: {
-            query: {
-                per_page: number;
-                page: number;
+            query?: {
+                /** @description Also include the points abbreviated for display, e.g. 1.23M */
+                humanize?: boolean;
             };
             header?: never;
-            path?: never;
+            path: {
+                /** @description Name of streamer to refresh */
+                streamer: string;
+            };
             cookie?: never;
         };
         requestBody?: never;
         responses: {
-            /** @description Get last logs as rendered html */
+            /** @description Fetch the points and active predictions of the streamer from twitch now, returning the updated state */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content: {
-                    "text/html": string;
+                    "application/json": components["schemas"]["StreamerResponse"];
+                };
+            };
+            /** @description Could not find streamer */
+            404: {
+                headers: {
+                    [name: string]: unknown;
                 };
+                content?: never;
             };
         };
     };
-    make_prediction: {
+    set_tags: {
         parameters: {
             query?: never;
             header?: never;
             path: {
-                /** @description Name of streamer to get state for */
+                /** @description Name of streamer to tag */
                 streamer: string;
             };
             cookie?: never;
         };
         requestBody: {
             content: {
-                "application/json": components["schemas"]["MakePrediction"];
+                "application/json": string[];
             };
         };
         responses: {
-            /** @description Placed a bet */
-            201: {
+            /** @description Replace the tags of the streamer, returning them trimmed and without duplicates */
+            200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content: {
-                    "application/json": components["schemas"]["Points"];
-                };
-            };
-            /** @description Did not place a bet, but no error occurred */
-            202: {
-                headers: {
-                    [name: string]: unknown;
+                    "application/json": string[];
                 };
-                content?: never;
             };
-            /** @description Could not find streamer or event ID */
-            404: {
+            /** @description Streamer does not exist */
+            400: {
                 headers: {
                     [name: string]: unknown;
                 };
@@ -708,30 +2694,25 @@ export interface operations {
             };
         };
     };
-    get_live_prediction: {
+    reload_token: {
         parameters: {
-            query: {
-                prediction_id: string;
-                channel_id: number;
-            };
+            query?: never;
             header?: never;
             path?: never;
             cookie?: never;
         };
         requestBody?: never;
         responses: {
-            /** @description Get live prediction */
+            /** @description Reloaded the token file, GQL requests and pubsub connections use the new access token */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
-                content: {
-                    "application/json": components["schemas"]["Prediction"] | null;
-                };
+                content?: never;
             };
         };
     };
-    live_streamers: {
+    version: {
         parameters: {
             query?: never;
             header?: never;
@@ -740,99 +2721,74 @@ export interface operations {
         };
         requestBody?: never;
         responses: {
-            /** @description List of live streamers and their state */
+            /** @description Version and build information of the running binary */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content: {
-                    "application/json": components["schemas"]["LiveStreamer"][];
+                    "application/json": components["schemas"]["Version"];
                 };
             };
         };
     };
-    mine_streamer: {
+    get_watching: {
         parameters: {
             query?: never;
             header?: never;
-            path: {
-                /** @description Name of streamer to watch */
-                channel_name: string;
-            };
+            path?: never;
             cookie?: never;
         };
-        requestBody: {
-            content: {
-                "application/json": components["schemas"]["MineStreamer"];
-            };
-        };
+        requestBody?: never;
         responses: {
-            /** @description Add streamer to mine */
+            /** @description Streamers sent viewership pings in the last watch round, in slot order, and why each was chosen */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content: {
-                    "application/json": unknown;
+                    "application/json": components["schemas"]["WatchedStreamer"][];
                 };
             };
         };
     };
-    remove_streamer: {
+    get_ws_status: {
         parameters: {
             query?: never;
             header?: never;
-            path: {
-                /** @description Name of streamer to delete */
-                channel_name: string;
-            };
+            path?: never;
             cookie?: never;
         };
         requestBody?: never;
         responses: {
-            /** @description Successfully removed streamer from the mine list */
+            /** @description State of the twitch pubsub connections */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
-                content?: never;
-            };
-            /** @description Could not find streamer */
-            404: {
-                headers: {
-                    [name: string]: unknown;
+                content: {
+                    "application/json": components["schemas"]["WsPoolStatus"];
                 };
-                content?: never;
             };
         };
     };
-    streamer: {
+    get_ws_topics: {
         parameters: {
             query?: never;
             header?: never;
-            path: {
-                /** @description Name of streamer to get state for */
-                streamer: string;
-            };
+            path?: never;
             cookie?: never;
         };
         requestBody?: never;
         responses: {
-            /** @description Get the entire application state information */
+            /** @description Topics listened to on each twitch pubsub connection */
             200: {
                 headers: {
                     [name: string]: unknown;
                 };
                 content: {
-                    "application/json": components["schemas"]["StreamerState"][];
-                };
-            };
-            /** @description Could not find streamer */
-            404: {
-                headers: {
-                    [name: string]: unknown;
+                    "application/json": components["schemas"]["WsConnTopics"][];
                 };
-                content?: never;
             };
         };
     };
@@ -21,17 +21,50 @@ use tracing::{debug, trace, warn};
 use tracing_subscriber::EnvFilter;
 use twitch_api::{
     pubsub::{
-        video_playback::VideoPlaybackReply, Request, Response, TopicData, Topics, TwitchResponse,
+        predictions::Event, video_playback::VideoPlaybackReply, Request, Response, TopicData,
+        Topics, TwitchResponse,
     },
     types::UserId,
 };
 
+/// Channel login, balance and available claim ID
+type ChannelPoints = HashMap<UserId, (String, u32, Option<String>)>;
+/// Channel login and active predictions
+type ActivePredictions = HashMap<UserId, (String, Vec<Event>)>;
+
 #[derive(Default)]
 struct AppState {
     streamer_metadata: HashMap<UserId, (String, gql::User)>,
     ws_test_mode: WsTest,
     test_stats: HashMap<String, serde_json::Value>,
     watching: Vec<UserId>,
+    channel_points: ChannelPoints,
+    gql_requests: u32,
+    /// Bets accepted, the balance is never debited
+    predictions_made: u32,
+    active_predictions: ActivePredictions,
+    /// Event IDs of the accepted bets
+    predicted_events: Vec<String>,
+    /// Raid IDs joined
+    raids_joined: Vec<String>,
+    /// Requests with a claim that fail before claims succeed again
+    claim_failures: u32,
+    drops: Drops,
+    /// Drop instance IDs claimed
+    drops_claimed: Vec<String>,
+    /// Access token, user ID and login of the logged in user
+    current_user: Option<(String, String, String)>,
+}
+
+#[derive(Default, Deserialize)]
+struct Drops {
+    /// Channel IDs with an active drop campaign
+    channels: Vec<String>,
+    /// Drop instance ID and name of drops watched long enough to claim
+    claimable: Vec<(String, String)>,
+    /// Drop instance IDs whose claim fails
+    #[serde(default)]
+    failing: Vec<String>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Deserialize)]
@@ -40,7 +73,10 @@ enum WsTest {
     Listen,
     Reconnect,
     RetryCommand,
+    RetryReconnect,
     ScaleConnections,
+    Silence,
+    Refuse,
 }
 
 #[tokio::main]
@@ -63,6 +99,12 @@ async fn main() -> Result<()> {
     let router = Router::new()
         .route("/gql", post(gql_handler))
         .route("/streamer_metadata", post(set_streamer_metadata))
+        .route("/channel_points", post(set_channel_points))
+        .route("/gql_requests", get(get_gql_requests).delete(clear_gql_requests))
+        .route(
+            "/predictions_made",
+            get(get_predictions_made).delete(clear_predictions_made),
+        )
         .route(
             "/base/:streamer",
             get(|| async { "config/settings.12345.js" }),
@@ -71,6 +113,12 @@ async fn main() -> Result<()> {
             "/base/config/settings.12345.js",
             get(|| async { r#""spade_url":"/spade""# }),
         )
+        .route("/claim_failures", post(set_claim_failures))
+        .route("/active_predictions", post(set_active_predictions))
+        .route("/raids_joined", get(get_raids_joined))
+        .route("/drops", post(set_drops))
+        .route("/drops_claimed", get(get_drops_claimed))
+        .route("/current_user", post(set_current_user))
         .route("/watching", get(get_watching).delete(clear_watching))
         .route("/spade", post(spade_handler))
         .nest("/pubsub", pubsub_router)
@@ -87,19 +135,37 @@ async fn main() -> Result<()> {
 
 async fn gql_handler(
     State(state): State<Arc<Mutex<AppState>>>,
+    headers: http::HeaderMap,
     Json(body): Json<vec_or_one::VecOrOne<GqlRequest>>,
 ) -> impl IntoResponse {
+    let token = headers
+        .get("Authorization")
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("OAuth "))
+        .unwrap_or_default()
+        .to_owned();
     let mut state = state.lock().await;
-    match body {
+    state.gql_requests += 1;
+    let has_claim = match &body {
+        vec_or_one::VecOrOne::Vec(items) => items
+            .iter()
+            .any(|x| matches!(x.variables, Variables::ClaimCommunityPoints(_))),
+        vec_or_one::VecOrOne::One(item) => matches!(item.variables, Variables::ClaimCommunityPoints(_)),
+    };
+    if has_claim && state.claim_failures > 0 {
+        state.claim_failures -= 1;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Ok(match body {
         vec_or_one::VecOrOne::Vec(items) => {
             let mut results = Vec::new();
             for i in items {
-                results.push(state.gql_req(i).await);
+                results.push(state.gql_req(i, &token).await);
             }
             Json(serde_json::Value::Array(results))
         }
-        vec_or_one::VecOrOne::One(item) => Json(state.gql_req(item).await),
-    }
+        vec_or_one::VecOrOne::One(item) => Json(state.gql_req(item, &token).await),
+    })
 }
 
 async fn pubsub_test_mode(
@@ -109,7 +175,10 @@ async fn pubsub_test_mode(
     let mut state = state.lock().await;
     match &body {
         WsTest::Listen => {}
-        WsTest::RetryCommand | WsTest::Reconnect => {
+        WsTest::RetryCommand
+        | WsTest::Reconnect
+        | WsTest::Silence
+        | WsTest::Refuse => {
             state
                 .test_stats
                 .entry(format!("{:?}", body))
@@ -121,6 +190,12 @@ async fn pubsub_test_mode(
                 .entry(format!("{:?}", body))
                 .or_insert(serde_json::json!({ "sockets": 0, "topics": 0 }));
         }
+        WsTest::RetryReconnect => {
+            state
+                .test_stats
+                .entry(format!("{:?}", body))
+                .or_insert(serde_json::json!({ "count": 0, "subscribed": [] }));
+        }
     }
     state.ws_test_mode = body;
     StatusCode::ACCEPTED
@@ -133,8 +208,17 @@ async fn pubsub_test_stats(
 }
 
 impl AppState {
-    async fn gql_req(&mut self, item: GqlRequest) -> serde_json::Value {
+    async fn gql_req(&mut self, item: GqlRequest, token: &str) -> serde_json::Value {
         match item.variables {
+            Variables::CoreActionsCurrentUser(_) => match &self.current_user {
+                Some((t, id, login)) if t == token => serde_json::json!({
+                    "data": { "currentUser": { "id": id, "login": login } }
+                }),
+                _ => serde_json::json!({
+                    "errors": [{ "message": "unauthorized" }],
+                    "data": { "currentUser": null }
+                }),
+            },
             Variables::StreamMetadata(s) => match self.get_by_name(&s.channel_login) {
                 Some((_, u)) => serde_json::json!({
                     "data": {
@@ -147,11 +231,159 @@ impl AppState {
                     }
                 }),
             },
-            Variables::MakePrediction(_) => todo!(),
-            Variables::ChannelPointsContext(_) => todo!(),
-            Variables::ClaimCommunityPoints(_) => todo!(),
-            Variables::ChannelPointsPredictionContext(_) => todo!(),
-            Variables::JoinRaid(_) => todo!(),
+            Variables::MakePrediction(p) => {
+                self.predictions_made += 1;
+                self.predicted_events.push(p.input.event_id);
+                serde_json::json!({
+                    "data": {
+                        "makePrediction": {
+                            "error": null
+                        }
+                    }
+                })
+            }
+            Variables::ChannelPointsContext(s) => {
+                let (balance, claim) = self
+                    .channel_points
+                    .values()
+                    .find(|x| x.0.eq(&s.channel_login))
+                    .map(|x| (x.1, x.2.clone()))
+                    .unwrap_or_default();
+                serde_json::json!({
+                    "data": {
+                        "community": {
+                            "channel": {
+                                "self": {
+                                    "communityPoints": {
+                                        "balance": balance,
+                                        "availableClaim": claim.map(|id| serde_json::json!({ "id": id }))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                })
+            }
+            Variables::ClaimCommunityPoints(c) => {
+                let entry = self
+                    .channel_points
+                    .get_mut(&UserId::from(c.input.channel_id))
+                    .unwrap();
+                if entry.2.as_ref() == Some(&c.input.claim_id) {
+                    entry.1 += 50;
+                    entry.2 = None;
+                }
+                serde_json::json!({
+                    "data": {
+                        "claimCommunityPoints": {
+                            "currentPoints": entry.1
+                        }
+                    }
+                })
+            }
+            Variables::ChannelPointsPredictionContext(c) => {
+                let (channel_id, events) = self
+                    .active_predictions
+                    .iter()
+                    .find(|x| x.1 .0.eq(&c.channel_login))
+                    .map(|x| (x.0.as_str().to_owned(), x.1 .1.clone()))
+                    .unwrap_or_default();
+                let recent = events
+                    .iter()
+                    .filter(|x| self.predicted_events.contains(&x.id))
+                    .map(|x| serde_json::json!({ "event": { "id": x.id } }))
+                    .collect::<Vec<_>>();
+                serde_json::json!({
+                    "data": {
+                        "community": {
+                            "channel": {
+                                "id": channel_id,
+                                "activePredictionEvents": events,
+                                "self": {
+                                    "recentPredictions": recent
+                                }
+                            }
+                        }
+                    }
+                })
+            }
+            Variables::JoinRaid(r) => {
+                self.raids_joined.push(r.input.raid_id.clone());
+                serde_json::json!({
+                    "data": {
+                        "joinRaid": {
+                            "raidID": r.input.raid_id
+                        }
+                    }
+                })
+            }
+            Variables::AvailableDrops(c) => {
+                let campaigns = if self.drops.channels.contains(&c.channel_id) {
+                    serde_json::json!([{ "id": format!("campaign-{}", c.channel_id) }])
+                } else {
+                    serde_json::json!([])
+                };
+                serde_json::json!({
+                    "data": {
+                        "channel": {
+                            "id": c.channel_id,
+                            "viewerDropCampaigns": campaigns
+                        }
+                    }
+                })
+            }
+            Variables::Inventory(_) => {
+                let mut drops = self
+                    .drops
+                    .claimable
+                    .iter()
+                    .map(|(id, name)| serde_json::json!({
+                        "name": name,
+                        "requiredMinutesWatched": 60,
+                        "self": {
+                            "currentMinutesWatched": 60,
+                            "isClaimed": self.drops_claimed.contains(id),
+                            "dropInstanceID": id
+                        }
+                    }))
+                    .collect::<Vec<_>>();
+                // still being watched, so never claimable
+                drops.push(serde_json::json!({
+                    "name": "unfinished",
+                    "requiredMinutesWatched": 120,
+                    "self": {
+                        "currentMinutesWatched": 30,
+                        "isClaimed": false,
+                        "dropInstanceID": null
+                    }
+                }));
+                serde_json::json!({
+                    "data": {
+                        "currentUser": {
+                            "inventory": {
+                                "dropCampaignsInProgress": [{ "timeBasedDrops": drops }]
+                            }
+                        }
+                    }
+                })
+            }
+            Variables::ClaimDropRewards(c) => {
+                if self.drops.failing.contains(&c.input.drop_instance_id) {
+                    return serde_json::json!({
+                        "data": {
+                            "claimDropRewards": null
+                        }
+                    });
+                }
+                self.drops_claimed.push(c.input.drop_instance_id);
+                serde_json::json!({
+                    "data": {
+                        "claimDropRewards": {
+                            "status": "ELIGIBLE_FOR_ALL"
+                        }
+                    }
+                })
+            }
         }
     }
 
@@ -160,6 +392,15 @@ impl AppState {
     }
 }
 
+async fn set_current_user(
+    State(state): State<Arc<Mutex<AppState>>>,
+    Json(body): Json<(String, String, String)>,
+) -> impl IntoResponse {
+    let mut state = state.lock().await;
+    state.current_user = Some(body);
+    StatusCode::ACCEPTED
+}
+
 async fn set_streamer_metadata(
     State(state): State<Arc<Mutex<AppState>>>,
     Json(body): Json<HashMap<UserId, (String, gql::User)>>,
@@ -169,6 +410,69 @@ async fn set_streamer_metadata(
     StatusCode::ACCEPTED
 }
 
+async fn set_channel_points(
+    State(state): State<Arc<Mutex<AppState>>>,
+    Json(body): Json<ChannelPoints>,
+) -> impl IntoResponse {
+    let mut state = state.lock().await;
+    state.channel_points = body;
+    StatusCode::ACCEPTED
+}
+
+async fn set_active_predictions(
+    State(state): State<Arc<Mutex<AppState>>>,
+    Json(body): Json<ActivePredictions>,
+) -> StatusCode {
+    let mut state = state.lock().await;
+    state.active_predictions = body;
+    state.predicted_events.clear();
+    StatusCode::ACCEPTED
+}
+
+async fn get_raids_joined(State(state): State<Arc<Mutex<AppState>>>) -> Json<Vec<String>> {
+    Json(state.lock().await.raids_joined.clone())
+}
+
+async fn set_drops(
+    State(state): State<Arc<Mutex<AppState>>>,
+    Json(body): Json<Drops>,
+) -> StatusCode {
+    let mut state = state.lock().await;
+    state.drops = body;
+    state.drops_claimed.clear();
+    StatusCode::ACCEPTED
+}
+
+async fn get_drops_claimed(State(state): State<Arc<Mutex<AppState>>>) -> Json<Vec<String>> {
+    Json(state.lock().await.drops_claimed.clone())
+}
+
+async fn set_claim_failures(
+    State(state): State<Arc<Mutex<AppState>>>,
+    Json(body): Json<u32>,
+) -> StatusCode {
+    state.lock().await.claim_failures = body;
+    StatusCode::ACCEPTED
+}
+
+async fn get_gql_requests(State(state): State<Arc<Mutex<AppState>>>) -> Json<u32> {
+    Json(state.lock().await.gql_requests)
+}
+
+async fn clear_gql_requests(State(state): State<Arc<Mutex<AppState>>>) -> StatusCode {
+    state.lock().await.gql_requests = 0;
+    StatusCode::OK
+}
+
+async fn get_predictions_made(State(state): State<Arc<Mutex<AppState>>>) -> Json<u32> {
+    Json(state.lock().await.predictions_made)
+}
+
+async fn clear_predictions_made(State(state): State<Arc<Mutex<AppState>>>) -> StatusCode {
+    state.lock().await.predictions_made = 0;
+    StatusCode::OK
+}
+
 #[derive(Deserialize)]
 struct SpadeData {
     data: String
@@ -226,6 +530,13 @@ async fn ws_handler(
 ) -> AxumResponse {
     {
         let mut state = state.lock().await;
+        if let WsTest::Refuse = state.ws_test_mode {
+            let field =
+                traverse_json(state.test_stats.get_mut("Refuse").unwrap(), ".count").unwrap();
+            *field = serde_json::Value::Number((field.as_i64().unwrap() + 1).into());
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+
         if let WsTest::ScaleConnections = state.ws_test_mode {
             let field = traverse_json(
                 state.test_stats.get_mut("ScaleConnections").unwrap(),
@@ -281,6 +592,7 @@ async fn wrapper_handle_socket(socket: WebSocket, state: Arc<Mutex<AppState>>) {
 async fn handle_socket(mut socket: WebSocket, state: Arc<Mutex<AppState>>) -> Result<()> {
     let test_mode = { state.lock().await.ws_test_mode.clone() };
     debug!("connected, test_mode={test_mode:?}");
+    let mut subscribed = Vec::new();
 
     while let Some(Ok(msg)) = socket.recv().await {
         if let Message::Text(msg) = msg {
@@ -341,6 +653,39 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<Mutex<AppState>>) -> Re
                                 success_msg!(socket, nonce);
                             }
                         }
+                        WsTest::RetryReconnect => {
+                            let mut state = state.lock().await;
+                            let stats = state.test_stats.get_mut("RetryReconnect").unwrap();
+
+                            let field = traverse_json(stats, ".count").unwrap();
+                            *field =
+                                serde_json::Value::Number((field.as_i64().unwrap() + 1).into());
+
+                            // fail the first listen, and ask for a reconnect while it is pending
+                            if field == 1 {
+                                socket
+                                    .send(Message::Text(serde_json::to_string(
+                                        &Response::Response(TwitchResponse {
+                                            error: Some("retrying mode".to_owned()),
+                                            nonce,
+                                        }),
+                                    )?))
+                                    .await?;
+                                socket
+                                    .send(Message::Text(serde_json::to_string(&Response::Reconnect)?))
+                                    .await?;
+                            } else {
+                                success_msg!(socket, nonce);
+                                if let Topics::VideoPlaybackById(data) = &data.topics[0] {
+                                    subscribed.push(data.channel_id);
+                                    if let serde_json::Value::Array(all) =
+                                        traverse_json(stats, ".subscribed").unwrap()
+                                    {
+                                        all.push(data.channel_id.into());
+                                    }
+                                }
+                            }
+                        }
                         WsTest::ScaleConnections => {
                             let mut state = state.lock().await;
 
@@ -353,6 +698,35 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<Mutex<AppState>>) -> Re
                                 serde_json::Value::Number((field.as_i64().unwrap() + 1).into());
                             trace!("{field:#?}");
                         }
+                        WsTest::Silence => {
+                            success_msg!(socket, nonce);
+
+                            let mut state = state.lock().await;
+                            let field = traverse_json(
+                                state.test_stats.get_mut("Silence").unwrap(),
+                                ".count",
+                            )
+                            .unwrap();
+                            *field =
+                                serde_json::Value::Number((field.as_i64().unwrap() + 1).into());
+
+                            // go live once, then withhold all further messages
+                            if field == 1 {
+                                if let Topics::VideoPlaybackById(data) = &data.topics[0] {
+                                    send_msg!(
+                                        socket,
+                                        TopicData::VideoPlaybackById {
+                                            topic: data.clone(),
+                                            reply: Box::new(VideoPlaybackReply::StreamUp {
+                                                server_time: 0.0,
+                                                play_delay: 0,
+                                            }),
+                                        }
+                                    );
+                                }
+                            }
+                        }
+                        WsTest::Refuse => {}
                     },
                     Request::UnListen { data, nonce } => match test_mode {
                         WsTest::Listen => {
@@ -371,7 +745,8 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<Mutex<AppState>>) -> Re
                             }
                         }
                         WsTest::Reconnect => {}
-                        WsTest::RetryCommand => {}
+                        WsTest::RetryCommand | WsTest::RetryReconnect => {}
+                        WsTest::Silence | WsTest::Refuse => {}
                         WsTest::ScaleConnections => {
                             let mut state = state.lock().await;
 
@@ -398,6 +773,19 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<Mutex<AppState>>) -> Re
         }
     }
     debug!("disconnected");
+
+    // topics on a closed socket are no longer subscribed
+    if let WsTest::RetryReconnect = test_mode {
+        let mut state = state.lock().await;
+        let stats = state.test_stats.get_mut("RetryReconnect").unwrap();
+        if let serde_json::Value::Array(all) = traverse_json(stats, ".subscribed").unwrap() {
+            for channel_id in subscribed {
+                if let Some(idx) = all.iter().position(|x| *x == channel_id) {
+                    all.remove(idx);
+                }
+            }
+        }
+    }
     Ok(())
 }
 